@@ -1,18 +1,46 @@
-use crate::startup_config::ProviderConfig;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
 use structopt::StructOpt;
 
+use crate::execution::{fetch_manifest, parse_checksum, StagedPackage, BACKUP_DIR};
+use crate::startup_config::ProviderConfig;
+
 #[derive(StructOpt, Clone, Debug)]
 #[structopt(rename_all = "kebab-case")]
 pub enum ExeUnitsConfig {
     List,
-    // TODO: Install command - could download ExeUnit and add to descriptor file.
-    // TODO: Update command - could update ExeUnit.
+    /// Download and install ExeUnit package
+    Install {
+        /// URL or path of the package (tar.gz, tar.bz2, tar.xz, tar or zip archive)
+        location: String,
+        /// Expected checksum of the package, e.g. sha3:<hex digest>
+        #[structopt(long)]
+        checksum: String,
+        /// Overwrite installed ExeUnits
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Update installed ExeUnit to the latest version listed in manifest.
+    /// Running provider needs to be restarted to use the new version.
+    Update {
+        name: String,
+        /// URL or path of the manifest (JSON list of name, version, url and checksum)
+        #[structopt(long)]
+        manifest: String,
+    },
 }
 
 impl ExeUnitsConfig {
-    pub fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
+    pub async fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
         match self {
             ExeUnitsConfig::List => list(config),
+            ExeUnitsConfig::Install {
+                location,
+                checksum,
+                force,
+            } => install(config, location, checksum, force).await,
+            ExeUnitsConfig::Update { name, manifest } => update(config, name, manifest).await,
         }
     }
 }
@@ -33,3 +61,113 @@ fn list(config: ProviderConfig) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+async fn install(
+    config: ProviderConfig,
+    location: String,
+    checksum: String,
+    force: bool,
+) -> anyhow::Result<()> {
+    let checksum = parse_checksum(&checksum)?;
+    let plugins_dir = plugins_dir(&config)?;
+    std::fs::create_dir_all(&plugins_dir)?;
+
+    let package = StagedPackage::fetch(&location, checksum, &plugins_dir).await?;
+    if !force {
+        let registry = config.registry()?;
+        if let Some(desc) = package
+            .descriptors
+            .iter()
+            .find(|desc| registry.find_exeunit(&desc.name).is_ok())
+        {
+            anyhow::bail!(
+                "ExeUnit [{}] is already installed. Use --force to overwrite it.",
+                desc.name
+            );
+        }
+    }
+    let descriptors = package.descriptors.clone();
+    package.install(&plugins_dir, force)?;
+
+    if config.json {
+        println!("{}", serde_json::to_string_pretty(&descriptors)?);
+    } else {
+        for desc in descriptors {
+            println!("Installed ExeUnit [{}] version {}", desc.name, desc.version);
+        }
+    }
+    Ok(())
+}
+
+async fn update(config: ProviderConfig, name: String, manifest: String) -> anyhow::Result<()> {
+    let plugins_dir = plugins_dir(&config)?;
+    let mut registry = config.registry()?;
+    let installed = registry.find_exeunit(&name)?;
+
+    let latest = fetch_manifest(&manifest)
+        .await?
+        .into_iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| anyhow!("ExeUnit [{}] not found in manifest {}", name, manifest))?;
+    if latest.version <= installed.version {
+        println!(
+            "ExeUnit [{}] is already up to date ({}).",
+            name, installed.version
+        );
+        return Ok(());
+    }
+
+    let checksum = parse_checksum(&latest.checksum)?;
+    let package = StagedPackage::fetch(&latest.url, checksum, &plugins_dir).await?;
+    if !package.descriptors.iter().any(|desc| desc.name == name) {
+        anyhow::bail!("Package {} doesn't contain ExeUnit [{}]", latest.url, name);
+    }
+    let backup_dir = plugins_dir
+        .join(BACKUP_DIR)
+        .join(format!("{}-{}", name, installed.version));
+    let backup = package.update(&plugins_dir, &backup_dir)?;
+
+    let reloaded = registry
+        .reload(&config.exe_unit_path)
+        .and_then(|_| Ok(registry.find_exeunit(&name)?))
+        .and_then(|desc| Ok(desc.validate().map(|_| desc)?));
+    match reloaded {
+        Ok(desc) if desc.version == latest.version => {
+            println!(
+                "Updated ExeUnit [{}] {} -> {}. Previous version is kept in {}",
+                name,
+                installed.version,
+                desc.version,
+                backup_dir.display()
+            );
+            Ok(())
+        }
+        result => {
+            backup.restore()?;
+            let reason = match result {
+                Ok(desc) => format!("version {} installed instead", desc.version),
+                Err(e) => e.to_string(),
+            };
+            anyhow::bail!(
+                "Failed to update ExeUnit [{}]: {}. Rolled back to {}",
+                name,
+                reason,
+                installed.version
+            )
+        }
+    }
+}
+
+/// Directory with ExeUnit descriptors
+fn plugins_dir(config: &ProviderConfig) -> anyhow::Result<PathBuf> {
+    config
+        .exe_unit_path
+        .parent()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid ExeUnit descriptors path: {}",
+                config.exe_unit_path.display()
+            )
+        })
+}
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use dialoguer::{Input, Select};
 use structopt::StructOpt;
 
-use crate::market::{Preset, PresetManager};
+use crate::market::{Preset, PresetManager, PresetsBundle};
 use crate::startup_config::{PresetNoInteractive, ProviderConfig, UpdateNames};
 
 #[derive(StructOpt, Clone, Debug)]
@@ -36,6 +37,28 @@ pub enum PresetsConfig {
     Activate { name: String },
     /// Deactivate a preset
     Deactivate { name: String },
+    /// Create a copy of a preset under a new name
+    Clone {
+        source: String,
+        new_name: String,
+        /// Replace existing preset named `new_name`
+        #[structopt(long)]
+        overwrite: bool,
+    },
+    /// Export presets to a JSON bundle
+    Export {
+        path: PathBuf,
+        /// Presets to export (all by default)
+        #[structopt(long)]
+        name: Vec<String>,
+    },
+    /// Import presets from a JSON bundle
+    Import {
+        path: PathBuf,
+        /// Replace existing presets with matching names
+        #[structopt(long)]
+        overwrite: bool,
+    },
 }
 
 impl PresetsConfig {
@@ -70,6 +93,13 @@ impl PresetsConfig {
             }
             PresetsConfig::Activate { name } => activate_preset(config, name),
             PresetsConfig::Deactivate { name } => deactivate_preset(config, name),
+            PresetsConfig::Clone {
+                source,
+                new_name,
+                overwrite,
+            } => clone_preset(config, source, new_name, overwrite),
+            PresetsConfig::Export { path, name } => export_presets(config, path, name),
+            PresetsConfig::Import { path, overwrite } => import_presets(config, path, overwrite),
         }
     }
 }
@@ -215,6 +245,7 @@ pub fn create(config: ProviderConfig, params: PresetNoInteractive) -> anyhow::Re
         .ok_or(anyhow!("Preset name is required."))?;
     preset.exeunit_name = params.exe_unit.ok_or(anyhow!("ExeUnit is required."))?;
     preset.pricing_model = params.pricing.unwrap_or("linear".to_string());
+    preset.priority = params.priority.unwrap_or_default();
 
     let registry = config.registry()?;
 
@@ -281,6 +312,83 @@ fn deactivate_preset(config: ProviderConfig, name: String) -> anyhow::Result<()>
     presets.save_to_file(&config.presets_file)
 }
 
+fn clone_preset(
+    config: ProviderConfig,
+    source: String,
+    new_name: String,
+    overwrite: bool,
+) -> anyhow::Result<()> {
+    let mut presets = PresetManager::load_or_create(&config.presets_file)?;
+    let preset = presets.clone_preset(&source, &new_name, overwrite)?;
+    presets.save_to_file(&config.presets_file)?;
+
+    if config.json {
+        println!("{}", serde_json::to_string_pretty(&preset)?);
+    } else {
+        let registry = config.registry()?;
+        println!();
+        println!("Preset created:");
+        println!("{}", preset.display(&registry));
+    }
+    Ok(())
+}
+
+fn export_presets(config: ProviderConfig, path: PathBuf, names: Vec<String>) -> anyhow::Result<()> {
+    let presets = PresetManager::load_or_create(&config.presets_file)?;
+    let bundle = presets.export(&names)?;
+    bundle.save_to_file(&path)?;
+
+    if config.json {
+        println!("{}", serde_json::to_string_pretty(&bundle)?);
+    } else {
+        println!(
+            "Exported {} preset(s) to {}",
+            bundle.presets.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn import_presets(config: ProviderConfig, path: PathBuf, overwrite: bool) -> anyhow::Result<()> {
+    let bundle = PresetsBundle::load_from_file(&path)?;
+    let registry = config.registry()?;
+
+    // Prices can't be validated without the ExeUnit, the rest is rejected when invalid.
+    for preset in bundle.presets.iter() {
+        if registry.find_exeunit(&preset.exeunit_name).is_err() {
+            log::warn!(
+                "Preset [{}] references unknown ExeUnit [{}]. Importing anyway.",
+                preset.name,
+                preset.exeunit_name
+            );
+            continue;
+        }
+        validate_preset(&config, preset)
+            .with_context(|| format!("Invalid preset [{}] in {}", preset.name, path.display()))?;
+    }
+
+    let mut presets = PresetManager::load_or_create(&config.presets_file)?;
+    let (imported, skipped) = presets.import(bundle, overwrite);
+    presets.save_to_file(&config.presets_file)?;
+
+    if config.json {
+        println!(
+            "{}",
+            serde_json::json!({ "imported": imported, "skipped": skipped })
+        );
+    } else {
+        println!("Imported presets: {}", imported.join(", "));
+        if !skipped.is_empty() {
+            println!(
+                "Skipped existing presets (use --overwrite to replace): {}",
+                skipped.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
 fn update_presets(
     config: &ProviderConfig,
     names: UpdateNames,
@@ -311,6 +419,9 @@ fn update_presets(
             if let Some(new_pricing_model) = params.pricing {
                 preset.pricing_model = new_pricing_model;
             }
+            if let Some(new_priority) = params.priority {
+                preset.priority = new_priority;
+            }
             let exe_unit_desc = registry.find_exeunit(&preset.exeunit_name)?;
 
             for (name, price) in params.price.iter() {
@@ -345,12 +456,23 @@ fn remove_preset(config: ProviderConfig, name: String) -> anyhow::Result<()> {
 fn validate_preset(config: &ProviderConfig, preset: &Preset) -> anyhow::Result<()> {
     // Validate ExeUnit existence and pricing model.
     let registry = config.registry()?;
-    registry.find_exeunit(&preset.exeunit_name)?;
+    let exe_unit_desc = registry.find_exeunit(&preset.exeunit_name)?;
 
     if !(preset.pricing_model == "linear") {
         bail!("Not supported pricing model.")
     }
 
+    // Prices may be left over after changing ExeUnit, so all of them are checked.
+    for (name, price) in preset.usage_coeffs.iter() {
+        exe_unit_desc.resolve_coefficient(name)?;
+        if !(*price >= 0.) {
+            bail!("Price for [{}] can't be negative: {}", name, price);
+        }
+    }
+    if !(preset.initial_price >= 0.) {
+        bail!("Initial price can't be negative: {}", preset.initial_price);
+    }
+
     Ok(())
 }
 
@@ -2,27 +2,39 @@ use crate::startup_config::NodeConfig;
 use std::path::Path;
 use ya_client::model::NodeId;
 
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{fs, io};
 use ya_utils_path::SwapSave;
 
 pub(crate) const GLOBALS_JSON: &'static str = "globals.json";
 pub(crate) const DEFAULT_SUBNET: &'static str = "public-beta";
 
-fn default_subnet() -> Option<String> {
-    Some(DEFAULT_SUBNET.into())
+fn default_subnet() -> Vec<String> {
+    vec![DEFAULT_SUBNET.into()]
+}
+
+/// Subnets are stored as a comma-separated string, which is readable by
+/// older versions and accepted back by `--subnet`.
+fn serialize_subnet<S: Serializer>(subnet: &Vec<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    if subnet.is_empty() {
+        serializer.serialize_none()
+    } else {
+        serializer.serialize_str(&subnet.join(","))
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize, derive_more::Display)]
 #[display(
     fmt = "{}{}{}",
     "node_name.as_ref().map(|nn| format!(\"Node name: {}\", nn)).unwrap_or(\"\".into())",
-    "subnet.as_ref().map(|s| format!(\"\nSubnet: {}\", s)).unwrap_or(\"\".into())",
+    "if subnet.is_empty() { \"\".into() } else { format!(\"\nSubnet: {}\", subnet.join(\", \")) }",
     "account.as_ref().map(|a| format!(\"\nAccount: {}\", a)).unwrap_or(\"\".into())"
 )]
 pub struct GlobalsState {
     pub node_name: Option<String>,
-    pub subnet: Option<String>,
+    /// Subnets the node participates in. Empty list means no subnet filter.
+    #[serde(serialize_with = "serialize_subnet")]
+    pub subnet: Vec<String>,
     pub account: Option<NodeId>,
 }
 
@@ -49,17 +61,37 @@ impl<'de> Deserialize<'de> for GlobalsState {
             }
         }
 
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        pub enum Subnet {
+            Joined(String),
+            List(Vec<String>),
+        }
+
+        impl Subnet {
+            pub fn into_vec(self) -> Vec<String> {
+                match self {
+                    Subnet::Joined(subnet) => subnet
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect(),
+                    Subnet::List(subnet) => subnet,
+                }
+            }
+        }
+
         #[derive(Deserialize)]
         pub struct GenericGlobalsState {
             pub node_name: Option<String>,
-            pub subnet: Option<String>,
+            pub subnet: Option<Subnet>,
             pub account: Option<Account>,
         }
 
         let s = GenericGlobalsState::deserialize(deserializer)?;
         Ok(GlobalsState {
             node_name: s.node_name,
-            subnet: s.subnet,
+            subnet: s.subnet.map(Subnet::into_vec).unwrap_or_default(),
             account: s.account.map(|a| a.address()),
         })
     }
@@ -73,7 +105,15 @@ impl GlobalsState {
                 fs::OpenOptions::new().read(true).open(path)?,
             ))?)
         } else {
-            Ok(Self::default())
+            Ok(Self::initial())
+        }
+    }
+
+    /// State of a node, which has never stored its globals.
+    fn initial() -> Self {
+        GlobalsState {
+            subnet: default_subnet(),
+            ..Default::default()
         }
     }
 
@@ -85,7 +125,7 @@ impl GlobalsState {
                 std::fs::create_dir_all(parent)?;
             }
             std::fs::File::create(&path)?;
-            let state = Self::default();
+            let state = Self::initial();
             state.save(path)?;
             Ok(state)
         }
@@ -95,10 +135,8 @@ impl GlobalsState {
         if node_config.node_name.is_some() {
             self.node_name = node_config.node_name;
         }
-        if node_config.subnet.is_some() {
+        if !node_config.subnet.is_empty() {
             self.subnet = node_config.subnet;
-        } else {
-            self.subnet = default_subnet();
         }
         if node_config.account.account.is_some() {
             self.account = node_config.account.account;
@@ -114,6 +152,7 @@ impl GlobalsState {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::startup_config::ReceiverAccount;
 
     const GLOBALS_JSON_ALPHA_3: &str = r#"
 {
@@ -140,9 +179,9 @@ mod test {
         let g4: GlobalsState = serde_json::from_str(GLOBALS_JSON_ALPHA_4).unwrap();
         assert_eq!(g3.node_name, Some("amusing-crate".into()));
         assert_eq!(g3.node_name, g4.node_name);
-        assert_eq!(g3.subnet, Some("community.3".into()));
-        assert_eq!(g4.subnet, Some("community.4".into()));
-        g3.subnet = Some("community.4".into());
+        assert_eq!(g3.subnet, vec!["community.3"]);
+        assert_eq!(g4.subnet, vec!["community.4"]);
+        g3.subnet = vec!["community.4".into()];
         assert_eq!(
             serde_json::to_string(&g3).unwrap(),
             serde_json::to_string(&g4).unwrap()
@@ -166,7 +205,7 @@ mod test {
         .unwrap();
 
         assert_eq!(g.node_name, Some("amusing-crate".into()));
-        assert_eq!(g.subnet, Some("community.3".into()));
+        assert_eq!(g.subnet, vec!["community.3"]);
         assert!(g.account.is_none())
     }
 
@@ -184,7 +223,64 @@ mod test {
         .unwrap();
 
         assert_eq!(g.node_name, Some("amusing-crate".into()));
-        assert_eq!(g.subnet, Some("community.4".into()));
+        assert_eq!(g.subnet, vec!["community.4"]);
         assert!(g.account.is_none())
     }
+
+    #[test]
+    fn multiple_subnets() {
+        let g: GlobalsState = serde_json::from_str(
+            r#"
+    {
+      "node_name": "amusing-crate",
+      "subnet": "staging, partner"
+    }
+    "#,
+        )
+        .unwrap();
+        assert_eq!(g.subnet, vec!["staging", "partner"]);
+
+        let json = serde_json::to_value(&g).unwrap();
+        assert_eq!(json["subnet"], "staging,partner");
+
+        let g: GlobalsState =
+            serde_json::from_str(r#"{ "subnet": ["staging", "partner"] }"#).unwrap();
+        assert_eq!(g.subnet, vec!["staging", "partner"]);
+
+        let g: GlobalsState = serde_json::from_str(r#"{ "subnet": null }"#).unwrap();
+        assert!(g.subnet.is_empty());
+        assert!(serde_json::to_value(&g).unwrap()["subnet"].is_null());
+    }
+
+    #[test]
+    fn empty_subnet_means_no_filter() {
+        let dir = tempdir::TempDir::new("globals").unwrap();
+        let path = dir.path().join(GLOBALS_JSON);
+        let node_config = |subnet: Vec<&str>| NodeConfig {
+            node_name: None,
+            subnet: subnet.into_iter().map(String::from).collect(),
+            account: ReceiverAccount {
+                account: None,
+                networks: vec![],
+            },
+        };
+
+        assert_eq!(
+            GlobalsState::load(&path).unwrap().subnet,
+            vec![DEFAULT_SUBNET]
+        );
+        let mut state = GlobalsState::load_or_create(&path).unwrap();
+        assert_eq!(state.subnet, vec![DEFAULT_SUBNET]);
+
+        state.subnet = vec![];
+        state.save(&path).unwrap();
+        let mut state = GlobalsState::load_or_create(&path).unwrap();
+        state.update_and_save(node_config(vec![]), &path).unwrap();
+        assert!(GlobalsState::load(&path).unwrap().subnet.is_empty());
+
+        state
+            .update_and_save(node_config(vec!["staging"]), &path)
+            .unwrap();
+        assert_eq!(GlobalsState::load(&path).unwrap().subnet, vec!["staging"]);
+    }
 }
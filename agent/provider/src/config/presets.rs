@@ -24,6 +24,13 @@ pub struct Presets {
     pub presets: HashMap<String, Preset>,
 }
 
+/// Portable set of presets, which can be moved between nodes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct PresetsBundle {
+    pub presets: Vec<Preset>,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "ver")]
 enum PresetsFile {
@@ -103,6 +110,27 @@ impl Presets {
     }
 }
 
+impl PresetsBundle {
+    pub fn load_from_file(path: &Path) -> anyhow::Result<PresetsBundle> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Can't read presets bundle {}: {}", path.display(), e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| anyhow!("Invalid presets bundle {}: {}", path.display(), e))
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|error| anyhow!("Failed to serialize presets bundle: {}", error))?;
+        std::fs::write(path, json).map_err(|error| {
+            anyhow!(
+                "Failed to save presets bundle to {}, error: {}.",
+                path.display(),
+                error
+            )
+        })
+    }
+}
+
 impl Default for Presets {
     fn default() -> Self {
         Presets {
@@ -159,6 +187,7 @@ impl From<PresetV0> for Preset {
                     _ => None,
                 })
                 .collect(),
+            priority: 0,
         }
     }
 }
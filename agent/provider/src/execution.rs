@@ -3,10 +3,12 @@ pub use task_runner::{
     TaskRunner, TaskRunnerConfig, TerminateActivity, UpdateActivity,
 };
 
+pub use self::package::{fetch_manifest, parse_checksum, StagedPackage, BACKUP_DIR};
 pub use self::registry::Configuration;
 pub use self::registry::{ExeUnitDesc, ExeUnitsRegistry};
 
 mod exeunit_instance;
+mod package;
 mod registry;
 mod task;
 mod task_runner;
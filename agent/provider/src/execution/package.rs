@@ -0,0 +1,374 @@
+//! Installation of ExeUnit packages.
+//!
+//! Package is an archive with ExeUnit descriptors (`*.json`) placed next to
+//! the binaries they point to. It's unpacked into the plugins directory,
+//! where `ExeUnitsRegistry` picks up the descriptors.
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures::TryStreamExt;
+use semver::Version;
+use serde::Deserialize;
+use url::Url;
+
+use ya_client_model::activity::TransferArgs;
+use ya_transfer::error::Error as TransferError;
+use ya_transfer::{
+    DirTransferProvider, FileTransferProvider, HttpTransferProvider, TransferContext, TransferData,
+    TransferHash, TransferProvider, TransferUrl,
+};
+
+use super::registry::ExeUnitDesc;
+
+const ARCHIVE_FORMATS: &[&str] = &["tar.gz", "tar.bz2", "tar.xz", "tar", "zip"];
+const STAGING_PREFIX: &str = ".staging-";
+/// Directory within the plugins directory, keeping ExeUnits replaced by updates
+pub const BACKUP_DIR: &str = ".backup";
+
+type Provider = Box<dyn TransferProvider<TransferData, TransferError>>;
+
+/// Parses checksum in `<algorithm>:<hex digest>` format, e.g. `sha3:1f2e...`.
+pub fn parse_checksum(checksum: &str) -> Result<TransferHash> {
+    let (alg, val) = checksum.split_once(':').ok_or_else(|| {
+        anyhow!(
+            "Invalid checksum [{}], expected <algorithm>:<hex>",
+            checksum
+        )
+    })?;
+    Ok(TransferHash {
+        alg: alg.to_lowercase(),
+        val: hex::decode(val.trim_start_matches("0x"))
+            .with_context(|| format!("Invalid checksum [{}]", checksum))?,
+    })
+}
+
+fn archive_format(file_name: &str) -> Result<&'static str> {
+    let file_name = file_name.to_lowercase();
+    ARCHIVE_FORMATS
+        .iter()
+        .find(|format| file_name.ends_with(&format!(".{}", format)))
+        .copied()
+        .ok_or_else(|| anyhow!("Unsupported ExeUnit package format: {}", file_name))
+}
+
+/// Latest package of an ExeUnit, as listed in a remote manifest.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub version: Version,
+    /// URL of the package
+    pub url: String,
+    /// Package checksum, in `parse_checksum` format
+    pub checksum: String,
+}
+
+/// Downloads the manifest (JSON list of `ManifestEntry`) from `location`.
+pub async fn fetch_manifest(location: &str) -> Result<Vec<ManifestEntry>> {
+    let url = package_url(location)?;
+    let provider = source_provider(&url)?;
+    let ctx = TransferContext::default();
+    provider.prepare_source(&url.url, &ctx).await?;
+    let data = provider
+        .source(&url.url, &ctx)
+        .try_fold(Vec::new(), |mut data, chunk| {
+            data.extend_from_slice(chunk.as_ref());
+            futures::future::ok(data)
+        })
+        .await
+        .with_context(|| format!("Failed to fetch ExeUnits manifest from {}", location))?;
+    serde_json::from_slice(&data)
+        .with_context(|| format!("Can't deserialize ExeUnits manifest from {}", location))
+}
+
+fn source_provider(url: &TransferUrl) -> Result<Provider> {
+    Ok(match url.url.scheme() {
+        "http" | "https" => Box::new(HttpTransferProvider::default()),
+        "file" => Box::new(FileTransferProvider::default()),
+        scheme => bail!("Unsupported URL scheme: {}", scheme),
+    })
+}
+
+fn package_url(location: &str) -> Result<TransferUrl> {
+    let path = Path::new(location);
+    if path.exists() {
+        let path = path.canonicalize()?;
+        let url = Url::from_file_path(&path)
+            .map_err(|_| anyhow!("Invalid package path: {}", path.display()))?;
+        return Ok(TransferUrl { hash: None, url });
+    }
+    Ok(TransferUrl::parse(location, "file")?)
+}
+
+/// Package unpacked within the plugins directory, but not installed yet.
+/// Removed when dropped.
+pub struct StagedPackage {
+    dir: PathBuf,
+    pub descriptors: Vec<ExeUnitDesc>,
+}
+
+impl StagedPackage {
+    /// Downloads the package from `location` (URL or path), verifies its
+    /// `checksum` and unpacks it.
+    pub async fn fetch(
+        location: &str,
+        checksum: TransferHash,
+        plugins_dir: &Path,
+    ) -> Result<StagedPackage> {
+        let mut src_url = package_url(location)?;
+        src_url.hash = Some(checksum);
+        let format = archive_format(&src_url.file_name()?)?;
+        let src = source_provider(&src_url)?;
+
+        let dir = plugins_dir.join(format!("{}{}", STAGING_PREFIX, std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let mut package = StagedPackage {
+            dir,
+            descriptors: Vec::new(),
+        };
+        let dst_url = TransferUrl {
+            hash: None,
+            url: Url::from_directory_path(&package.dir)
+                .map_err(|_| anyhow!("Invalid plugins path: {}", plugins_dir.display()))?,
+        };
+        let dst: Provider = Box::new(DirTransferProvider::default());
+        let ctx = TransferContext::from(TransferArgs {
+            format: Some(format.to_string()),
+            ..Default::default()
+        });
+
+        log::info!("Downloading ExeUnit package from {}", src_url.url);
+        ya_transfer::transfer_with(src, &src_url, dst, &dst_url, &ctx)
+            .await
+            .with_context(|| format!("Failed to fetch ExeUnit package from {}", location))?;
+
+        package.descriptors = package
+            .descriptor_files()?
+            .iter()
+            .map(|path| read_descriptors(path))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        if package.descriptors.is_empty() {
+            bail!("No ExeUnit descriptors found in package {}", location);
+        }
+        Ok(package)
+    }
+
+    fn descriptor_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Package contents along with their destination in `plugins_dir`.
+    fn entries(&self, plugins_dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        Ok(fs::read_dir(&self.dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.path(), plugins_dir.join(entry.file_name())))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?)
+    }
+
+    /// Moves package contents to `plugins_dir`. Fails without changing anything,
+    /// when some of them already exist, unless `force` is set.
+    pub fn install(self, plugins_dir: &Path, force: bool) -> Result<()> {
+        if !force {
+            let entries = self.entries(plugins_dir)?;
+            if let Some((_, target)) = entries.iter().find(|(_, target)| target.exists()) {
+                bail!(
+                    "[{}] already exists. Use --force to overwrite it.",
+                    target.display()
+                );
+            }
+        }
+        // Replaced files are removed along with the staging directory
+        self.swap(plugins_dir, &self.dir.join(BACKUP_DIR))?;
+        Ok(())
+    }
+
+    /// Moves package contents to `plugins_dir`, keeping replaced files in `backup_dir`.
+    pub fn update(self, plugins_dir: &Path, backup_dir: &Path) -> Result<Backup> {
+        self.swap(plugins_dir, backup_dir)
+    }
+
+    fn swap(&self, plugins_dir: &Path, backup_dir: &Path) -> Result<Backup> {
+        let entries = self.entries(plugins_dir)?;
+        fs::create_dir_all(backup_dir)?;
+
+        let mut backup = Backup::default();
+        let result = entries.iter().try_for_each(|(source, target)| {
+            if target.exists() {
+                let replaced = backup_dir.join(target.file_name().unwrap_or_default());
+                remove_path(&replaced)?;
+                fs::rename(target, &replaced)?;
+                backup.replaced.push((replaced, target.clone()));
+            }
+            fs::rename(source, target)
+                .with_context(|| format!("Failed to install [{}]", target.display()))?;
+            backup.installed.push(target.clone());
+            Ok::<_, anyhow::Error>(())
+        });
+
+        if let Err(e) = result {
+            if let Err(restore_error) = backup.restore() {
+                log::error!(
+                    "Failed to restore replaced ExeUnit files: {}",
+                    restore_error
+                );
+            }
+            return Err(e);
+        }
+        Ok(backup)
+    }
+}
+
+/// Files replaced by a package.
+#[derive(Default)]
+pub struct Backup {
+    installed: Vec<PathBuf>,
+    /// Backup location along with the original one
+    replaced: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Backup {
+    /// Removes installed package and brings back the files it replaced.
+    pub fn restore(self) -> Result<()> {
+        for path in self.installed.iter() {
+            remove_path(path)?;
+        }
+        for (backup, original) in self.replaced.iter() {
+            fs::rename(backup, original)
+                .with_context(|| format!("Failed to restore [{}]", original.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    }
+}
+
+impl Drop for StagedPackage {
+    fn drop(&mut self) {
+        if self.dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                log::warn!("Failed to remove [{}]: {}", self.dir.display(), e);
+            }
+        }
+    }
+}
+
+fn read_descriptors(path: &Path) -> Result<Vec<ExeUnitDesc>> {
+    let reader = BufReader::new(File::open(path)?);
+    serde_json::from_reader(reader).with_context(|| {
+        format!(
+            "Can't deserialize ExeUnits descriptors from file {}",
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staged(plugins: &Path, files: &[&str]) -> StagedPackage {
+        let dir = plugins.join(format!("{}test", STAGING_PREFIX));
+        fs::create_dir_all(dir.join("ya-runtime-test")).unwrap();
+        for file in files {
+            fs::write(dir.join(file), "new").unwrap();
+        }
+        StagedPackage {
+            dir,
+            descriptors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let hash = parse_checksum("SHA3:0x0a0b").unwrap();
+        assert_eq!(hash.alg, "sha3");
+        assert_eq!(hash.val, vec![0x0a, 0x0b]);
+        assert!(parse_checksum("0a0b").is_err());
+        assert!(parse_checksum("sha3:xyz").is_err());
+    }
+
+    #[test]
+    fn test_archive_format() {
+        assert_eq!(archive_format("ya-runtime-vm.tar.gz").unwrap(), "tar.gz");
+        assert_eq!(archive_format("RUNTIME.ZIP").unwrap(), "zip");
+        assert_eq!(archive_format("runtime.tar").unwrap(), "tar");
+        assert!(archive_format("runtime.exe").is_err());
+    }
+
+    #[test]
+    fn test_install_refuses_to_overwrite() {
+        let plugins = tempdir::TempDir::new("plugins").unwrap();
+        let plugins = plugins.path();
+        fs::write(plugins.join("ya-runtime-test.json"), "old").unwrap();
+
+        let package = staged(plugins, &["ya-runtime-test.json"]);
+        let staging = package.dir.clone();
+        assert!(package.install(plugins, false).is_err());
+        assert_eq!(
+            fs::read_to_string(plugins.join("ya-runtime-test.json")).unwrap(),
+            "old"
+        );
+        assert!(!plugins.join("ya-runtime-test").exists());
+        assert!(!staging.exists());
+
+        staged(plugins, &["ya-runtime-test.json"])
+            .install(plugins, true)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(plugins.join("ya-runtime-test.json")).unwrap(),
+            "new"
+        );
+        assert!(plugins.join("ya-runtime-test").is_dir());
+        assert!(!staging.exists());
+    }
+
+    #[test]
+    fn test_update_keeps_backup() {
+        let plugins = tempdir::TempDir::new("plugins").unwrap();
+        let plugins = plugins.path();
+        let backup_dir = plugins.join(BACKUP_DIR).join("test-0.1.0");
+        fs::write(plugins.join("ya-runtime-test.json"), "old").unwrap();
+
+        let backup = staged(plugins, &["ya-runtime-test.json"])
+            .update(plugins, &backup_dir)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(plugins.join("ya-runtime-test.json")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(backup_dir.join("ya-runtime-test.json")).unwrap(),
+            "old"
+        );
+
+        backup.restore().unwrap();
+        assert_eq!(
+            fs::read_to_string(plugins.join("ya-runtime-test.json")).unwrap(),
+            "old"
+        );
+        assert!(!plugins.join("ya-runtime-test").exists());
+    }
+}
@@ -101,22 +101,34 @@ impl ExeUnitDesc {
         Ok(desc)
     }
 
+    /// Resolves metric given either by property or by counter name.
     pub fn resolve_coefficient(&self, coefficient_name: &str) -> Result<String> {
-        self.config
-            .as_ref()
-            .and_then(|config| {
-                if config.counters.contains_key(coefficient_name) {
-                    return Some(coefficient_name.to_string());
+        let counters: HashMap<String, CounterDefinition> = self.coefficients().collect();
+        if counters.contains_key(coefficient_name) {
+            return Ok(coefficient_name.to_string());
+        }
+        counters
+            .iter()
+            .find_map(|(prop_name, definition)| {
+                if definition.name.eq_ignore_ascii_case(&coefficient_name) {
+                    Some(prop_name.clone())
+                } else {
+                    None
                 }
-                config.counters.iter().find_map(|(prop_name, definition)| {
-                    if definition.name.eq_ignore_ascii_case(&coefficient_name) {
-                        Some(prop_name.into())
-                    } else {
-                        None
-                    }
-                })
             })
-            .ok_or_else(|| anyhow!("invalid coefficient name = {}", coefficient_name))
+            .ok_or_else(|| {
+                let mut valid = counters
+                    .iter()
+                    .map(|(prop_name, definition)| format!("{} ({})", definition.name, prop_name))
+                    .collect::<Vec<_>>();
+                valid.sort();
+                anyhow!(
+                    "Unknown metric [{}] for ExeUnit [{}]. Valid metrics: {}",
+                    coefficient_name,
+                    self.name,
+                    valid.join(", ")
+                )
+            })
     }
 
     pub fn coefficient_name(&self, propery_name: &str) -> Option<String> {
@@ -272,6 +284,15 @@ impl ExeUnitsRegistry {
         Ok(())
     }
 
+    /// Replaces registered ExeUnits with ones described in files matching `pattern`.
+    /// Registry is left unchanged on failure.
+    pub fn reload(&mut self, pattern: &Path) -> Result<()> {
+        let mut registry = ExeUnitsRegistry::new();
+        registry.register_from_file_pattern(pattern)?;
+        self.descriptors = registry.descriptors;
+        Ok(())
+    }
+
     pub fn find_exeunit(&self, name: &str) -> Result<ExeUnitDesc> {
         Ok(self
             .descriptors
@@ -518,6 +539,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_coefficient() {
+        let desc: ExeUnitDesc = serde_json::from_value(serde_json::json!({
+            "name": "wasm",
+            "version": "0.1.0",
+            "supervisor-path": "wasm.exe",
+            "config": {
+                "counters": {
+                    "golem.usage.cpu_sec": {
+                        "name": "cpu",
+                        "description": "CPU",
+                        "price": true
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            desc.resolve_coefficient("CPU").unwrap(),
+            "golem.usage.cpu_sec"
+        );
+        assert_eq!(
+            desc.resolve_coefficient("golem.usage.cpu_sec").unwrap(),
+            "golem.usage.cpu_sec"
+        );
+        let error = desc.resolve_coefficient("cpus").unwrap_err().to_string();
+        assert!(error.contains("cpu (golem.usage.cpu_sec)"));
+        assert!(desc.resolve_coefficient("duration").is_err());
+
+        // ExeUnits without configuration use default counters.
+        let desc = ExeUnitDesc {
+            config: None,
+            ..desc
+        };
+        assert_eq!(
+            desc.resolve_coefficient("duration").unwrap(),
+            "golem.usage.duration_sec"
+        );
+    }
+
+    #[test]
+    fn test_reload_registry() {
+        let mut registry = ExeUnitsRegistry::new();
+        registry
+            .register_exeunits_from_file(&resources_directory().join("example-exeunits.json"))
+            .unwrap();
+
+        assert!(registry
+            .reload(&resources_directory().join("missing-exeunits.json"))
+            .is_err());
+        assert!(registry.find_exeunit("dummy").is_ok());
+
+        registry
+            .reload(&resources_directory().join("exeunits.json"))
+            .unwrap();
+        assert!(registry.find_exeunit("dummy").is_err());
+        assert!(registry.find_exeunit("vm").is_ok());
+    }
+
     #[test]
     fn test_fill_registry_from_local_exe_unit_descriptor() {
         let exe_units_descriptor = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
@@ -17,7 +17,8 @@ use structopt::StructOpt;
 use ya_agreement_utils::{AgreementView, OfferTemplate};
 use ya_client::activity::ActivityProviderApi;
 use ya_client::model::activity::provider_event::ProviderEventType;
-use ya_client::model::activity::{ActivityState, ProviderEvent, State, StatePair};
+use ya_client::model::activity::{ProviderEvent, State, StatePair};
+use ya_core_model::activity::TerminationReason;
 use ya_std_utils::LogErr;
 use ya_utils_actix::actix_handler::ResultTypeGetter;
 use ya_utils_actix::actix_signal::{Signal, SignalSlot};
@@ -98,7 +99,7 @@ pub struct DestroyActivity {
 pub struct TerminateActivity {
     pub activity_id: String,
     pub agreement_id: String,
-    pub reason: String,
+    pub reason: TerminationReason,
     pub message: String,
 }
 
@@ -326,8 +327,8 @@ impl TaskRunner {
                         activity_id
                     );
 
-                    let reason = "execution aborted";
-                    let msg = format!("exit code {:?}", exit_status.code());
+                    let reason = TerminationReason::ExeUnitCrashed;
+                    let msg = format!("execution aborted, exit code {:?}", exit_status.code());
                     set_activity_terminated(api, &activity_id, reason, msg, state_retry_interval)
                         .await;
                 }
@@ -337,8 +338,9 @@ impl TaskRunner {
                         error, exeunit_name, activity_id
                     );
 
-                    let reason = "execution error";
-                    set_activity_terminated(api, &activity_id, reason, error, state_retry_interval)
+                    let reason = TerminationReason::ExeUnitCrashed;
+                    let msg = format!("execution error: {}", error);
+                    set_activity_terminated(api, &activity_id, reason, msg, state_retry_interval)
                         .await;
                 }
                 _ => (),
@@ -512,15 +514,11 @@ fn exe_unit_name_from(agreement: &AgreementView) -> Result<String> {
 async fn set_activity_terminated(
     api: Arc<ActivityProviderApi>,
     activity_id: &str,
-    reason: impl ToString,
+    reason: TerminationReason,
     message: impl ToString,
     retry_interval: Duration,
 ) {
-    let state = ActivityState {
-        state: State::Terminated.into(),
-        reason: Some(reason.to_string()),
-        error_message: Some(message.to_string()),
-    };
+    let state = reason.to_state(Some(message.to_string()));
 
     // Potentially infinite loop. This is done intentionally.
     // Possible fail reasons:
@@ -713,8 +711,8 @@ impl Handler<CreateActivity> for TaskRunner {
                     set_activity_terminated(
                         api,
                         &activity_id,
-                        "creation failed",
-                        &error,
+                        TerminationReason::ExeUnitCrashed,
+                        format!("creation failed: {}", error),
                         state_retry_interval,
                     )
                     .await;
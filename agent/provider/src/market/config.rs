@@ -22,4 +22,16 @@ pub struct MarketConfig {
     pub process_market_events_timeout: std::time::Duration,
     #[structopt(skip)]
     pub keystore: Keystore,
+    /// Maximum number of presets offered at once. When there are more
+    /// active presets, the ones with the highest priority are offered.
+    #[structopt(long, env)]
+    pub max_offered_presets: Option<usize>,
+    /// Connect to the market without publishing offers. Offers
+    /// of other Providers within the subnet are logged instead.
+    #[structopt(long)]
+    pub observe_only: bool,
+    /// In observe-only mode, publish Offers as probes, which reject
+    /// all Proposals, to see Demands of Requestors too.
+    #[structopt(long, requires = "observe-only")]
+    pub observe_demands: bool,
 }
@@ -1,8 +1,9 @@
 pub mod config;
 pub mod negotiator;
+pub mod observer;
 pub mod presets;
 pub mod provider_market;
 pub mod termination_reason;
 
-pub use presets::{Preset, PresetManager, Presets};
+pub use presets::{Preset, PresetManager, Presets, PresetsBundle};
 pub use provider_market::{CreateOffer, ProviderMarket};
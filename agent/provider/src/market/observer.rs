@@ -0,0 +1,152 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use ya_client::model::market::{NewDemand, NewOffer, Proposal};
+use ya_client::model::NodeId;
+
+const SUBNET_PROPERTY: &str = "golem.node.debug.subnet";
+const RUNTIME_PROPERTY: &str = "golem.runtime.name";
+const EXPIRATION_PROPERTY: &str = "golem.srv.comp.expiration";
+
+/// Probe subscriptions are renewed with this period, before they expire.
+/// Offers not proposed again within a whole period are no longer observed.
+pub const PROBE_REFRESH: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Offer of another Provider seen on the market.
+#[derive(Clone, Debug)]
+pub struct ObservedOffer {
+    pub proposal_id: String,
+    pub issuer_id: NodeId,
+    pub runtime: Option<String>,
+    pub expiration: Option<DateTime<Utc>>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Demand of a Requestor, which matched one of withheld Offers.
+#[derive(Clone, Debug)]
+pub struct ObservedDemand {
+    pub proposal_id: String,
+    pub issuer_id: NodeId,
+    pub first_seen: DateTime<Utc>,
+}
+
+/// Local view of the market in observe-only mode.
+///
+/// Offers of other Providers are matched against a probe Demand, which accepts
+/// any runtime within the subnet. Demands are not broadcast on the network,
+/// so they are seen only when withheld Offers are published as probes, which
+/// reject all Proposals.
+#[derive(Clone, Debug, Default)]
+pub struct ObservedMarket {
+    /// Offers, which would be published, keyed by preset name.
+    pub withheld: HashMap<String, NewOffer>,
+    /// Keyed by the issuer and properties, which don't change between probes.
+    pub offers: HashMap<String, ObservedOffer>,
+    pub demands: HashMap<String, ObservedDemand>,
+}
+
+impl ObservedMarket {
+    pub fn withhold(&mut self, preset: &str, offer: NewOffer) {
+        self.withheld.insert(preset.to_string(), offer);
+    }
+
+    /// Returns the offer, if it was not observed before.
+    pub fn observe(&mut self, proposal: &Proposal) -> Option<&ObservedOffer> {
+        let now = Utc::now();
+        let key = observation_key(proposal);
+        if let Some(offer) = self.offers.get_mut(&key) {
+            offer.proposal_id = proposal.proposal_id.clone();
+            offer.last_seen = now;
+            return None;
+        }
+
+        let offer = ObservedOffer {
+            proposal_id: proposal.proposal_id.clone(),
+            issuer_id: proposal.issuer_id,
+            runtime: pointer_str(&proposal.properties, RUNTIME_PROPERTY),
+            expiration: pointer(&proposal.properties, EXPIRATION_PROPERTY)
+                .and_then(|value| value.as_i64())
+                .map(|millis| Utc.timestamp_millis(millis)),
+            first_seen: now,
+            last_seen: now,
+        };
+        Some(self.offers.entry(key).or_insert(offer))
+    }
+
+    /// Returns `true` if the demand was not observed before.
+    pub fn observe_demand(&mut self, proposal: &Proposal) -> bool {
+        let key = observation_key(proposal);
+        if self.demands.contains_key(&key) {
+            return false;
+        }
+
+        let demand = ObservedDemand {
+            proposal_id: proposal.proposal_id.clone(),
+            issuer_id: proposal.issuer_id,
+            first_seen: Utc::now(),
+        };
+        self.demands.insert(key, demand);
+        true
+    }
+
+    /// Removes expired offers and the ones not seen since `seen_since`.
+    /// Returns the number of removed offers.
+    pub fn evict(&mut self, seen_since: DateTime<Utc>) -> usize {
+        let now = Utc::now();
+        let before = self.offers.len();
+        self.offers.retain(|_, offer| {
+            offer.last_seen >= seen_since && offer.expiration.map_or(true, |exp| exp > now)
+        });
+        before - self.offers.len()
+    }
+}
+
+/// Identifies the same Offer or Demand proposed to subsequent probes.
+fn observation_key(proposal: &Proposal) -> String {
+    let mut hasher = DefaultHasher::new();
+    proposal.properties.to_string().hash(&mut hasher);
+    proposal.constraints.hash(&mut hasher);
+    format!("{}-{:016x}", proposal.issuer_id, hasher.finish())
+}
+
+/// Subnets of the Offer, which would be published.
+pub fn offer_subnet(offer: &NewOffer) -> Vec<String> {
+    match pointer(&offer.properties, SUBNET_PROPERTY) {
+        Some(serde_json::Value::String(subnet)) => vec![subnet.clone()],
+        Some(serde_json::Value::Array(subnets)) => subnets
+            .iter()
+            .filter_map(|subnet| subnet.as_str().map(ToString::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Demand matching all Offers within any of `subnet`.
+pub fn probe_demand(subnet: Vec<String>) -> NewDemand {
+    let expiration = Utc::now() + Duration::hours(1);
+    let mut properties = serde_json::json!({
+        "golem.srv.comp.expiration": expiration.timestamp_millis(),
+    });
+    match subnet.len() {
+        0 => (),
+        1 => properties[SUBNET_PROPERTY] = serde_json::json!(subnet[0]),
+        _ => properties[SUBNET_PROPERTY] = serde_json::json!(subnet),
+    }
+    NewDemand::new(properties, format!("({}=*)", RUNTIME_PROPERTY))
+}
+
+/// Properties can be either flat or nested.
+fn pointer<'a>(properties: &'a serde_json::Value, name: &str) -> Option<&'a serde_json::Value> {
+    properties
+        .get(name)
+        .or_else(|| properties.pointer(&format!("/{}", name.replace('.', "/"))))
+}
+
+fn pointer_str(properties: &serde_json::Value, name: &str) -> Option<String> {
+    pointer(properties, name)
+        .and_then(|value| value.as_str())
+        .map(ToString::to_string)
+}
@@ -8,7 +8,7 @@ use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use tokio::sync::watch;
 
-pub use crate::config::presets::Presets;
+pub use crate::config::presets::{Presets, PresetsBundle};
 use crate::events::Event;
 use crate::execution::ExeUnitsRegistry;
 use crate::startup_config::FileMonitor;
@@ -22,6 +22,9 @@ pub struct Preset {
     pub pricing_model: String,
     pub initial_price: f64,
     pub usage_coeffs: HashMap<String, f64>,
+    /// Presets with higher priority are offered first, when capacity is limited.
+    #[serde(default)]
+    pub priority: i32,
 }
 
 impl Preset {
@@ -37,6 +40,21 @@ impl Preset {
     }
 }
 
+/// Orders presets by priority (descending, ties broken by name) and splits them
+/// into the ones to be offered and the ones withheld due to `capacity` limit.
+pub fn prioritize(mut presets: Vec<Preset>, capacity: Option<usize>) -> (Vec<Preset>, Vec<Preset>) {
+    presets.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    let withheld = match capacity {
+        Some(capacity) if capacity < presets.len() => presets.split_off(capacity),
+        _ => Vec::new(),
+    };
+    (presets, withheld)
+}
+
 /// Responsible for presets management.
 pub struct PresetManager {
     pub(crate) state: Arc<Mutex<Presets>>,
@@ -118,6 +136,59 @@ impl PresetManager {
         Ok(())
     }
 
+    /// Adds presets from a bundle. Presets with already existing names are
+    /// replaced when `overwrite` is set and skipped otherwise.
+    /// Returns names of imported and skipped presets.
+    pub fn import(&mut self, bundle: PresetsBundle, overwrite: bool) -> (Vec<String>, Vec<String>) {
+        let mut state = self.state.lock().unwrap();
+        let mut imported = Vec::new();
+        let mut skipped = Vec::new();
+
+        for preset in bundle.presets {
+            if state.presets.contains_key(&preset.name) && !overwrite {
+                skipped.push(preset.name);
+                continue;
+            }
+            imported.push(preset.name.clone());
+            state.presets.insert(preset.name.clone(), preset);
+        }
+        (imported, skipped)
+    }
+
+    /// Copies `source` preset under `new_name`.
+    pub fn clone_preset(
+        &mut self,
+        source: &str,
+        new_name: &str,
+        overwrite: bool,
+    ) -> Result<Preset> {
+        let mut state = self.state.lock().unwrap();
+        let mut preset = state
+            .presets
+            .get(source)
+            .cloned()
+            .ok_or(anyhow!("Source preset [{}] doesn't exists.", source))?;
+        if state.presets.contains_key(new_name) && !overwrite {
+            return Err(anyhow!(
+                "Preset name [{}] already exists. Use --overwrite to replace it.",
+                new_name
+            ));
+        }
+
+        preset.name = new_name.to_string();
+        state.presets.insert(preset.name.clone(), preset.clone());
+        Ok(preset)
+    }
+
+    pub fn export(&self, names: &[String]) -> Result<PresetsBundle> {
+        let presets = if names.is_empty() {
+            self.list()
+        } else {
+            self.list_matching(&names.to_vec())?
+        };
+        Ok(PresetsBundle { presets })
+    }
+
     pub fn get(&self, name: &str) -> Result<Preset> {
         let state = self.state.lock().unwrap();
         match state.presets.get(name) {
@@ -225,6 +296,7 @@ impl Default for Preset {
             exeunit_name: "wasmtime".to_string(),
             pricing_model: "linear".to_string(),
             usage_coeffs,
+            priority: 0,
         }
     }
 }
@@ -235,6 +307,7 @@ impl PartialEq for Preset {
             && self.exeunit_name == other.exeunit_name
             && self.pricing_model == other.pricing_model
             && self.usage_coeffs == other.usage_coeffs
+            && self.priority == other.priority
     }
 }
 
@@ -272,6 +345,13 @@ fn display_preset(
         preset.pricing_model,
         width = align
     )?;
+    write!(
+        f,
+        "{:width$}{}\n",
+        "Priority:",
+        preset.priority,
+        width = align
+    )?;
     write!(f, "{}\n", "Coefficients:")?;
 
     let exe_unit = registry.find_exeunit(&preset.exeunit_name).ok();
@@ -292,3 +372,96 @@ fn display_preset(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, priority: i32) -> Preset {
+        Preset {
+            name: name.to_string(),
+            priority,
+            ..Preset::default()
+        }
+    }
+
+    fn names(presets: &[Preset]) -> Vec<&str> {
+        presets.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_capacity_cap_withholds_lower_priority() {
+        let presets = vec![preset("cheap", 1), preset("premium", 10)];
+
+        let (offered, withheld) = prioritize(presets.clone(), Some(1));
+        assert_eq!(names(&offered), vec!["premium"]);
+        assert_eq!(names(&withheld), vec!["cheap"]);
+
+        let (offered, withheld) = prioritize(presets, None);
+        assert_eq!(names(&offered), vec!["premium", "cheap"]);
+        assert!(withheld.is_empty());
+    }
+
+    #[test]
+    fn test_priority_ties_are_deterministic() {
+        let presets = vec![
+            preset("c", 5),
+            preset("a", 5),
+            preset("b", 5),
+            preset("d", 7),
+        ];
+        let (offered, withheld) = prioritize(presets, Some(3));
+        assert_eq!(names(&offered), vec!["d", "a", "b"]);
+        assert_eq!(names(&withheld), vec!["c"]);
+    }
+
+    #[test]
+    fn test_import_resolves_conflicts() {
+        let mut manager = PresetManager::default();
+        let mut changed = Preset::default();
+        changed.initial_price = 1.0;
+        changed.priority = 3;
+        let bundle = PresetsBundle {
+            presets: vec![changed, preset("gpu", 1)],
+        };
+
+        let (imported, skipped) = manager.import(bundle.clone(), false);
+        assert_eq!(imported, vec!["gpu"]);
+        assert_eq!(skipped, vec!["default"]);
+        assert_eq!(manager.get("default").unwrap().priority, 0);
+
+        let (imported, skipped) = manager.import(bundle, true);
+        assert_eq!(imported, vec!["default", "gpu"]);
+        assert!(skipped.is_empty());
+        assert_eq!(manager.get("default").unwrap().priority, 3);
+        assert_eq!(manager.active(), vec!["default"]);
+    }
+
+    #[test]
+    fn test_clone_preset() {
+        let mut manager = PresetManager::default();
+        manager.add_preset(preset("gpu", 2)).unwrap();
+
+        let cloned = manager.clone_preset("gpu", "gpu-cheap", false).unwrap();
+        assert_eq!(cloned.name, "gpu-cheap");
+        assert_eq!(cloned.priority, 2);
+        assert_eq!(manager.get("gpu-cheap").unwrap(), cloned);
+        assert_eq!(manager.active(), vec!["default"]);
+
+        assert!(manager.clone_preset("missing", "other", false).is_err());
+        assert!(manager.clone_preset("gpu", "default", false).is_err());
+        assert_eq!(manager.get("default").unwrap().priority, 0);
+
+        manager.clone_preset("gpu", "default", true).unwrap();
+        assert_eq!(manager.get("default").unwrap().priority, 2);
+    }
+
+    #[test]
+    fn test_priority_defaults_to_zero() {
+        let preset: Preset = serde_json::from_str(
+            r#"{"name":"x","exeunit-name":"wasmtime","pricing-model":"linear","initial-price":0.0,"usage-coeffs":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(preset.priority, 0);
+    }
+}
@@ -2,7 +2,7 @@ use actix::prelude::*;
 use actix::AsyncContext;
 use anyhow::{anyhow, Error, Result};
 use backoff::backoff::Backoff;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use derive_more::Display;
 use futures::prelude::*;
 use futures_util::FutureExt;
@@ -13,11 +13,12 @@ use std::sync::Arc;
 use tokio::time::timeout;
 
 use ya_agreement_utils::{AgreementView, OfferDefinition};
-use ya_client::market::MarketProviderApi;
+use ya_client::market::{MarketProviderApi, MarketRequestorApi};
 use ya_client::model::market::agreement_event::AgreementEventType;
 use ya_client::model::market::proposal::State;
 use ya_client::model::market::{
     agreement_event::AgreementTerminator, Agreement, NewOffer, Proposal, ProviderEvent, Reason,
+    RequestorEvent,
 };
 use ya_client::model::NodeId;
 use ya_std_utils::LogErr;
@@ -28,6 +29,7 @@ use ya_utils_actix::{
 
 use super::negotiator::factory;
 use super::negotiator::{AgreementResponse, AgreementResult, NegotiatorAddr, ProposalResponse};
+use super::observer::{offer_subnet, probe_demand, ObservedMarket, PROBE_REFRESH};
 use super::Preset;
 use crate::display::EnableDisplay;
 use crate::market::config::MarketConfig;
@@ -55,6 +57,17 @@ pub struct Shutdown;
 #[rtype(result = "Result<()>")]
 pub struct Unsubscribe(pub OfferKind);
 
+/// Returns the market view collected in observe-only mode.
+#[derive(Message)]
+#[rtype(result = "Result<MarketObservation>")]
+pub struct GetMarketObservation;
+
+#[derive(Clone, Debug)]
+pub struct MarketObservation {
+    pub published_offers: usize,
+    pub market: ObservedMarket,
+}
+
 pub enum OfferKind {
     Any,
     WithPresets(Vec<String>),
@@ -82,6 +95,33 @@ struct Subscription {
     offer: NewOffer,
 }
 
+/// Sent when the Offer created by negotiator is ready for publication.
+#[derive(Message)]
+#[rtype(result = "Result<()>")]
+struct PublishOffer {
+    offer: NewOffer,
+    preset: Preset,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<()>")]
+struct ObserveProposal(Proposal);
+
+#[derive(Message)]
+#[rtype(result = "Result<()>")]
+struct ObserveDemand(Proposal);
+
+#[derive(Message)]
+#[rtype(result = "Result<()>")]
+struct ProbeSubscribed(String);
+
+#[derive(Message)]
+#[rtype(result = "Result<()>")]
+struct ProbeOfferSubscribed {
+    preset: String,
+    id: String,
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<()>")]
 struct AgreementFinalized {
@@ -100,6 +140,12 @@ struct OnAgreementTerminated {
 // ProviderMarket declaration
 // =========================================== //
 
+const PROBE_HANDLE: &str = "observe-offers";
+
+fn probe_offer_handle(preset: &str) -> String {
+    format!("observe-demands-{}", preset)
+}
+
 pub struct SubscriptionProposal {
     pub subscription_id: String,
     pub proposal: Proposal,
@@ -116,6 +162,14 @@ pub struct ProviderMarket {
     postponed_demands: Vec<SubscriptionProposal>,
     config: Arc<MarketConfig>,
 
+    /// Used in observe-only mode to see Offers of other Providers.
+    observer: Option<Arc<MarketRequestorApi>>,
+    observed: ObservedMarket,
+    probe_subscription: Option<String>,
+    probe_since: Option<DateTime<Utc>>,
+    /// Subscriptions of withheld Offers published as probes, keyed by preset name.
+    probe_offers: HashMap<String, String>,
+
     /// External actors can listen on this signal.
     pub agreement_signed_signal: SignalSlot<NewAgreement>,
     pub agreement_terminated_signal: SignalSlot<CloseAgreement>,
@@ -144,12 +198,23 @@ impl ProviderMarket {
             config: Arc::new(config),
             subscriptions: HashMap::new(),
             postponed_demands: Vec::new(),
+            observer: None,
+            observed: Default::default(),
+            probe_subscription: None,
+            probe_since: None,
+            probe_offers: HashMap::new(),
             agreement_signed_signal: SignalSlot::<NewAgreement>::new(),
             agreement_terminated_signal: SignalSlot::<CloseAgreement>::new(),
             handles: HashMap::new(),
         };
     }
 
+    /// Api used to observe the market, when offer publication is disabled.
+    pub fn with_observer(mut self, api: MarketRequestorApi) -> Self {
+        self.observer = Some(Arc::new(api));
+        self
+    }
+
     fn async_context(&self, ctx: &mut Context<Self>) -> AsyncCtx {
         AsyncCtx {
             config: self.config.clone(),
@@ -179,6 +244,159 @@ impl ProviderMarket {
     // Market internals - proposals and agreements reactions
     // =========================================== //
 
+    fn on_publish_offer(
+        &mut self,
+        msg: PublishOffer,
+        ctx: &mut Context<Self>,
+    ) -> ResponseFuture<Result<()>> {
+        if !self.config.observe_only {
+            let preset_name = msg.preset.name.clone();
+            return subscribe(ctx.address(), self.api.clone(), msg.offer, msg.preset)
+                .map(move |result| {
+                    result.log_err_msg(&format!(
+                        "Can't subscribe new offer for preset [{}]",
+                        preset_name,
+                    ))
+                })
+                .boxed_local();
+        }
+
+        log::info!(
+            "Observe-only mode: offer for preset [{}] not published.",
+            msg.preset.name
+        );
+
+        if let Some(api) = self.observer.clone() {
+            if !self.handles.contains_key(PROBE_HANDLE) {
+                let subnet = offer_subnet(&msg.offer);
+                let interval = self.config.negotiation_events_interval;
+                let handle = ctx
+                    .spawn(observe_offers(ctx.address(), api, subnet, interval).into_actor(self));
+                self.handles.insert(PROBE_HANDLE.to_string(), handle);
+            }
+        }
+
+        if self.config.observe_demands {
+            // Probe always publishes the latest Offer of the preset.
+            let handle_name = probe_offer_handle(&msg.preset.name);
+            if let Some(handle) = self.handles.remove(&handle_name) {
+                ctx.cancel_future(handle);
+            }
+            let interval = self.config.negotiation_events_interval;
+            let probe = observe_demands(
+                ctx.address(),
+                self.api.clone(),
+                msg.preset.name.clone(),
+                msg.offer.clone(),
+                interval,
+            );
+            let handle = ctx.spawn(probe.into_actor(self));
+            self.handles.insert(handle_name, handle);
+        }
+
+        self.observed.withhold(&msg.preset.name, msg.offer);
+        future::ok(()).boxed_local()
+    }
+
+    fn on_observe_proposal(&mut self, msg: ObserveProposal, _: &mut Context<Self>) -> Result<()> {
+        if let Some(offer) = self.observed.observe(&msg.0) {
+            log::info!(
+                "Observed offer [{}] from [{}]. Runtime [{}].",
+                offer.proposal_id,
+                offer.issuer_id,
+                offer.runtime.as_deref().unwrap_or("unknown"),
+            );
+        }
+        Ok(())
+    }
+
+    fn on_observe_demand(&mut self, msg: ObserveDemand, _: &mut Context<Self>) -> Result<()> {
+        if self.observed.observe_demand(&msg.0) {
+            log::info!(
+                "Observed demand [{}] from Requestor [{}].",
+                msg.0.proposal_id,
+                msg.0.issuer_id,
+            );
+        }
+        Ok(())
+    }
+
+    fn on_probe_subscribed(&mut self, msg: ProbeSubscribed, ctx: &mut Context<Self>) -> Result<()> {
+        log::info!("Observing the market. Probe demand [{}].", msg.0);
+        // Offers of the previous probe, which weren't proposed again, are gone.
+        if let Some(since) = self.probe_since.replace(Utc::now()) {
+            let evicted = self.observed.evict(since);
+            if evicted > 0 {
+                log::info!("{} observed offers expired or were unsubscribed.", evicted);
+            }
+        }
+
+        let previous = self.probe_subscription.replace(msg.0);
+        if let Some((api, id)) = self.observer.clone().zip(previous) {
+            ctx.spawn(
+                async move {
+                    api.unsubscribe(&id)
+                        .await
+                        .map_err(|e| log::debug!("Failed to unsubscribe probe demand. {}", e))
+                        .ok();
+                }
+                .into_actor(self),
+            );
+        }
+        Ok(())
+    }
+
+    fn on_probe_offer_subscribed(
+        &mut self,
+        msg: ProbeOfferSubscribed,
+        ctx: &mut Context<Self>,
+    ) -> Result<()> {
+        let previous = match self.handles.contains_key(&probe_offer_handle(&msg.preset)) {
+            true => {
+                log::info!(
+                    "Observing demands. Probe offer [{}] for preset [{}].",
+                    msg.id,
+                    msg.preset
+                );
+                self.probe_offers.insert(msg.preset, msg.id)
+            }
+            // Preset was unsubscribed in the meantime.
+            false => Some(msg.id),
+        };
+
+        if let Some(id) = previous {
+            let api = self.api.clone();
+            ctx.spawn(
+                async move {
+                    api.unsubscribe(&id)
+                        .await
+                        .map_err(|e| log::debug!("Failed to unsubscribe probe offer. {}", e))
+                        .ok();
+                }
+                .into_actor(self),
+            );
+        }
+        Ok(())
+    }
+
+    /// Stops probes of the given presets. Returns their subscriptions.
+    fn take_probe_offers(&mut self, kind: &OfferKind, ctx: &mut Context<Self>) -> Vec<String> {
+        let presets = match kind {
+            OfferKind::Any => self.probe_offers.keys().cloned().collect(),
+            OfferKind::WithPresets(presets) => presets.clone(),
+            OfferKind::WithIds(_) => Vec::new(),
+        };
+        presets
+            .into_iter()
+            .filter_map(|preset| {
+                if let Some(handle) = self.handles.remove(&probe_offer_handle(&preset)) {
+                    ctx.cancel_future(handle);
+                }
+                self.probe_offers.remove(&preset)
+            })
+            .collect()
+    }
+
     fn on_agreement_approved(&mut self, msg: NewAgreement, _ctx: &mut Context<Self>) -> Result<()> {
         log::info!("Got approved agreement [{}].", msg.agreement.agreement_id,);
         // At this moment we only forward agreement to outside world.
@@ -587,10 +805,13 @@ impl Actor for ProviderMarket {
         let actx = self.async_context(ctx);
 
         // Note: There will be no collision with subscription ids stored normally here.
-        self.handles.insert(
-            "collect-agreement-events".to_string(),
-            ctx.spawn(collect_agreement_events(actx).into_actor(self)),
-        );
+        // Without published offers there are no agreements to collect.
+        if !self.config.observe_only {
+            self.handles.insert(
+                "collect-agreement-events".to_string(),
+                ctx.spawn(collect_agreement_events(actx).into_actor(self)),
+            );
+        }
 
         self.negotiator = factory::create_negotiator(ctx.address(), &self.config);
     }
@@ -605,7 +826,15 @@ impl Handler<Shutdown> for ProviderMarket {
         }
 
         let market = ctx.address();
+        let probe = self.observer.clone().zip(self.probe_subscription.take());
         async move {
+            if let Some((api, id)) = probe {
+                log::info!("Unsubscribing probe demand: {}", id);
+                api.unsubscribe(&id)
+                    .await
+                    .map_err(|e| log::warn!("Failed to unsubscribe probe demand. {}", e))
+                    .ok();
+            }
             Ok(market
                 .send(Unsubscribe(OfferKind::Any))
                 .await?
@@ -674,14 +903,12 @@ impl Handler<CreateOffer> for ProviderMarket {
 
             log::info!("Subscribing to events... [{}]", msg.preset.name);
 
-            let preset_name = msg.preset.name.clone();
-            subscribe(ctx.market, ctx.api, offer, msg.preset)
-                .await
-                .log_err_msg(&format!(
-                    "Can't subscribe new offer for preset [{}]",
-                    preset_name,
-                ))?;
-            Ok(())
+            ctx.market
+                .send(PublishOffer {
+                    offer,
+                    preset: msg.preset,
+                })
+                .await?
         }
         .boxed_local()
     }
@@ -864,7 +1091,8 @@ impl Handler<Unsubscribe> for ProviderMarket {
     type Result = ResponseFuture<Result<(), Error>>;
 
     fn handle(&mut self, msg: Unsubscribe, ctx: &mut Context<Self>) -> Self::Result {
-        let subscriptions = match msg.0 {
+        let probes = self.take_probe_offers(&msg.0, ctx);
+        let mut subscriptions = match msg.0 {
             OfferKind::Any => {
                 log::info!("Unsubscribing all active offers");
                 std::mem::replace(&mut self.subscriptions, HashMap::new())
@@ -900,16 +1128,134 @@ impl Handler<Unsubscribe> for ProviderMarket {
             .for_each(|handle| {
                 ctx.cancel_future(handle);
             });
+        subscriptions.extend(probes);
 
         unsubscribe_all(self.api.clone(), subscriptions).boxed_local()
     }
 }
 
 forward_actix_handler!(ProviderMarket, Subscription, on_subscription);
+forward_actix_handler!(ProviderMarket, ObserveProposal, on_observe_proposal);
+forward_actix_handler!(ProviderMarket, ObserveDemand, on_observe_demand);
+forward_actix_handler!(ProviderMarket, ProbeSubscribed, on_probe_subscribed);
+forward_actix_handler!(
+    ProviderMarket,
+    ProbeOfferSubscribed,
+    on_probe_offer_subscribed
+);
 forward_actix_handler!(ProviderMarket, NewAgreement, on_agreement_approved);
 actix_signal_handler!(ProviderMarket, CloseAgreement, agreement_terminated_signal);
 actix_signal_handler!(ProviderMarket, NewAgreement, agreement_signed_signal);
 
+impl Handler<PublishOffer> for ProviderMarket {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: PublishOffer, ctx: &mut Context<Self>) -> Self::Result {
+        self.on_publish_offer(msg, ctx)
+    }
+}
+
+impl Handler<GetMarketObservation> for ProviderMarket {
+    type Result = Result<MarketObservation>;
+
+    fn handle(&mut self, _: GetMarketObservation, _: &mut Context<Self>) -> Self::Result {
+        Ok(MarketObservation {
+            published_offers: self.subscriptions.len(),
+            market: self.observed.clone(),
+        })
+    }
+}
+
+/// Subscribes a probe Demand and forwards matching Offers to the market actor.
+/// The probe is renewed periodically and when its events can't be queried.
+async fn observe_offers(
+    market: Addr<ProviderMarket>,
+    api: Arc<MarketRequestorApi>,
+    subnet: Vec<String>,
+    interval: f32,
+) {
+    let interval_duration = std::time::Duration::from_secs_f32(interval);
+    loop {
+        let id = loop {
+            match api.subscribe(&probe_demand(subnet.clone())).await {
+                Ok(id) => break id,
+                Err(e) => log::warn!("Can't subscribe probe demand. Error: {}", e),
+            }
+            tokio::time::sleep(interval_duration).await;
+        };
+        market.do_send(ProbeSubscribed(id.clone()));
+
+        let refresh = std::time::Instant::now() + PROBE_REFRESH;
+        while std::time::Instant::now() < refresh {
+            match api.collect(&id, Some(interval), Some(100)).await {
+                Ok(events) => events.into_iter().for_each(|event| match event {
+                    RequestorEvent::ProposalEvent { proposal, .. } => {
+                        market.do_send(ObserveProposal(proposal))
+                    }
+                    event => log::trace!("Observe-only mode. Got: {:?}", event),
+                }),
+                Err(e) => {
+                    log::warn!("Can't query probe demand events. Renewing it. Error: {}", e);
+                    tokio::time::sleep(interval_duration).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Publishes withheld Offer as a probe, which rejects all Proposals, and forwards
+/// Demands proposed to it to the market actor. The probe is renewed like the probe Demand.
+async fn observe_demands(
+    market: Addr<ProviderMarket>,
+    api: Arc<MarketProviderApi>,
+    preset: String,
+    offer: NewOffer,
+    interval: f32,
+) {
+    let interval_duration = std::time::Duration::from_secs_f32(interval);
+    let reason = Some(Reason::new(
+        "Provider is only observing the market.".to_string(),
+    ));
+    loop {
+        let id = loop {
+            match api.subscribe(&offer).await {
+                Ok(id) => break id,
+                Err(e) => log::warn!("Can't subscribe probe offer [{}]. Error: {}", preset, e),
+            }
+            tokio::time::sleep(interval_duration).await;
+        };
+        market.do_send(ProbeOfferSubscribed {
+            preset: preset.clone(),
+            id: id.clone(),
+        });
+
+        let refresh = std::time::Instant::now() + PROBE_REFRESH;
+        while std::time::Instant::now() < refresh {
+            let events = match api.collect(&id, Some(interval), Some(100)).await {
+                Ok(events) => events,
+                Err(e) => {
+                    log::warn!("Can't query probe offer events. Renewing it. Error: {}", e);
+                    tokio::time::sleep(interval_duration).await;
+                    break;
+                }
+            };
+            for event in events {
+                match event {
+                    ProviderEvent::ProposalEvent { proposal, .. } => {
+                        api.reject_proposal(&id, &proposal.proposal_id, &reason)
+                            .await
+                            .map_err(|e| log::debug!("Can't reject probe proposal. {}", e))
+                            .ok();
+                        market.do_send(ObserveDemand(proposal));
+                    }
+                    event => log::trace!("Observe-only mode. Got: {:?}", event),
+                }
+            }
+        }
+    }
+}
+
 fn get_backoff() -> backoff::ExponentialBackoff {
     // TODO: We could have config for Market actor to be able to set at least initial interval.
     let mut backoff = backoff::ExponentialBackoff::default();
@@ -947,3 +1293,120 @@ impl From<AgreementClosed> for AgreementFinalized {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+    use ya_client::web::WebClient;
+
+    fn proposal(id: &str, runtime: &str) -> Proposal {
+        serde_json::from_value(serde_json::json!({
+            "properties": { "golem.runtime.name": runtime },
+            "constraints": "()",
+            "proposalId": id,
+            "issuerId": "0x99402605903da83901151b0871ebeae9296ef66b",
+            "state": "Initial",
+            "timestamp": "2022-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_observe_only_publishes_no_offers() {
+        let config = MarketConfig::from_iter_safe(&["", "--observe-only"]).unwrap();
+        // Not connected: publishing an offer would fail.
+        let api = WebClient::builder().build().interface().unwrap();
+        let market = ProviderMarket::new(api, config).start();
+
+        let preset = Preset::default();
+        let offer = NewOffer::new(
+            serde_json::json!({ "golem.node.debug.subnet": "public" }),
+            "()".to_string(),
+        );
+        market
+            .send(PublishOffer {
+                offer,
+                preset: preset.clone(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        for (id, runtime) in [
+            ("proposal-1", "vm"),
+            ("proposal-2", "wasmtime"),
+            ("proposal-1", "vm"),
+        ] {
+            market
+                .send(ObserveProposal(proposal(id, runtime)))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        market
+            .send(ObserveDemand(proposal("demand-1", "vm")))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let observation = market.send(GetMarketObservation).await.unwrap().unwrap();
+        assert_eq!(observation.published_offers, 0);
+        assert!(observation.market.withheld.contains_key(&preset.name));
+        assert_eq!(observation.market.offers.len(), 2);
+        let offer = observation
+            .market
+            .offers
+            .values()
+            .find(|offer| offer.proposal_id == "proposal-1")
+            .unwrap();
+        assert_eq!(offer.runtime.as_deref(), Some("vm"));
+        assert_eq!(observation.market.demands.len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_offers_not_proposed_to_renewed_probe_are_evicted() {
+        let config = MarketConfig::from_iter_safe(&["", "--observe-only"]).unwrap();
+        let api = WebClient::builder().build().interface().unwrap();
+        let market = ProviderMarket::new(api, config).start();
+        let pause = || tokio::time::sleep(std::time::Duration::from_millis(10));
+
+        market
+            .send(ProbeSubscribed("probe-1".into()))
+            .await
+            .unwrap()
+            .unwrap();
+        pause().await;
+        for (id, runtime) in [("proposal-1", "vm"), ("proposal-2", "wasmtime")] {
+            market
+                .send(ObserveProposal(proposal(id, runtime)))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        pause().await;
+        market
+            .send(ProbeSubscribed("probe-2".into()))
+            .await
+            .unwrap()
+            .unwrap();
+        pause().await;
+        // Only the first Offer is still on the market.
+        market
+            .send(ObserveProposal(proposal("proposal-3", "vm")))
+            .await
+            .unwrap()
+            .unwrap();
+        pause().await;
+        market
+            .send(ProbeSubscribed("probe-3".into()))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let observation = market.send(GetMarketObservation).await.unwrap().unwrap();
+        assert_eq!(observation.market.offers.len(), 1);
+        let offer = observation.market.offers.values().next().unwrap();
+        assert_eq!(offer.proposal_id, "proposal-3");
+    }
+}
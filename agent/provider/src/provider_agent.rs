@@ -10,7 +10,7 @@ use tokio_stream::wrappers::WatchStream;
 
 use ya_agreement_utils::agreement::TypedArrayPointer;
 use ya_agreement_utils::*;
-use ya_client::cli::ProviderApi;
+use ya_client::cli::{ProviderApi, RequestorApi};
 use ya_core_model::payment::local::NetworkName;
 use ya_file_logging::{start_logger, LoggerHandle};
 use ya_manifest_utils::Keystore;
@@ -23,7 +23,7 @@ use crate::execution::{
 };
 use crate::hardware;
 use crate::market::provider_market::{OfferKind, Shutdown as MarketShutdown, Unsubscribe};
-use crate::market::{CreateOffer, Preset, PresetManager, ProviderMarket};
+use crate::market::{presets, CreateOffer, Preset, PresetManager, ProviderMarket};
 use crate::payments::{AccountView, LinearPricingOffer, Payments, PricingOffer};
 use crate::startup_config::{
     FileMonitor, FileMonitorConfig, NodeConfig, ProviderConfig, RunConfig,
@@ -75,6 +75,7 @@ pub struct ProviderAgent {
     log_handler: LoggerHandle,
     networks: Vec<NetworkName>,
     keystore_monitor: FileMonitor,
+    max_offered_presets: Option<usize>,
 }
 
 impl ProviderAgent {
@@ -171,7 +172,16 @@ impl ProviderAgent {
         hardware.spawn_monitor(&config.hardware_file)?;
         let keystore_monitor = spawn_keystore_monitor(&config.trusted_keys_file, keystore)?;
 
-        let market = ProviderMarket::new(api.market, args.market).start();
+        let max_offered_presets = args.market.max_offered_presets;
+        let market = match args.market.observe_only {
+            true => {
+                log::info!("Running in observe-only mode. Offers won't be published.");
+                let observer = RequestorApi::try_from(&args.api)?.market;
+                ProviderMarket::new(api.market, args.market).with_observer(observer)
+            }
+            false => ProviderMarket::new(api.market, args.market),
+        }
+        .start();
         let payments = Payments::new(api.activity.clone(), api.payment, args.payment).start();
         let runner = TaskRunner::new(api.activity, args.runner, registry, data_dir)?.start();
         let task_manager =
@@ -188,6 +198,7 @@ impl ProviderAgent {
             log_handler,
             networks,
             keystore_monitor,
+            max_offered_presets,
         })
     }
 
@@ -220,7 +231,7 @@ impl ProviderAgent {
 
             let (initial_price, prices) = get_prices(pricing_model.as_ref(), &preset, &offer)?;
             offer.set_property("golem.com.usage.vector", get_usage_vector_value(&prices));
-            offer.add_constraints(Self::build_constraints(subnet.clone())?);
+            offer.add_constraints(Self::build_constraints(subnet)?);
 
             let com_info = pricing_model.build(&accounts, initial_price, prices)?;
             let name = preset.exeunit_name.clone();
@@ -251,11 +262,22 @@ impl ProviderAgent {
         Ok(())
     }
 
-    fn build_constraints(subnet: Option<String>) -> anyhow::Result<String> {
+    fn build_constraints(subnet: &[String]) -> anyhow::Result<String> {
         let mut cnts =
             constraints!["golem.srv.comp.expiration" > chrono::Utc::now().timestamp_millis(),];
-        if let Some(subnet) = subnet {
-            cnts = cnts.and(constraints!["golem.node.debug.subnet" == subnet,]);
+        match subnet {
+            [] => (),
+            [subnet] => {
+                cnts = cnts.and(constraints!["golem.node.debug.subnet" == subnet.as_str(),])
+            }
+            subnets => {
+                // Demand matching any of the subnets is accepted.
+                let any = subnets
+                    .iter()
+                    .map(|subnet| constraints!["golem.node.debug.subnet" == subnet.as_str(),])
+                    .collect();
+                cnts = cnts.and(Constraints::new_clause(ClauseOperator::Or, any));
+            }
         }
         Ok(cnts.to_string())
     }
@@ -263,7 +285,8 @@ impl ProviderAgent {
     fn create_node_info(&self) -> NodeInfo {
         let globals = self.globals.get_state();
 
-        if let Some(subnet) = &globals.subnet {
+        if !globals.subnet.is_empty() {
+            let subnet = globals.subnet.join(", ");
             log::info!("Using subnet: {}", yansi::Color::Fixed(184).paint(subnet));
         }
 
@@ -419,6 +442,8 @@ impl Handler<Initialize> for ProviderAgent {
         let market = self.market.clone();
         let agent = ctx.address();
         let preset_state = self.presets.state.clone();
+        // Under capacity limit, any change can affect which presets are offered.
+        let capped = self.max_offered_presets.is_some();
 
         let rx = futures::stream::select_all(vec![
             WatchStream::new(self.hardware.event_receiver()),
@@ -457,6 +482,18 @@ impl Handler<Initialize> for ProviderAgent {
                             *state = presets;
                         }
 
+                        if capped {
+                            let _ = market
+                                .send(Unsubscribe(OfferKind::Any))
+                                .map_err(|e| log::error!("Cannot unsubscribe offers: {}", e))
+                                .await;
+                            let _ = agent
+                                .send(CreateOffers(OfferKind::Any))
+                                .map_err(|e| log::error!("Cannot create offers: {}", e))
+                                .await;
+                            return;
+                        }
+
                         let mut to_unsub = updated;
                         to_unsub.extend(removed);
 
@@ -522,18 +559,31 @@ impl Handler<CreateOffers> for ProviderAgent {
             Err(e) => return Box::pin(async { Err(e) }),
         };
         let inf_node_info = InfNodeInfo::from(self.hardware.capped());
-        let preset_names = match msg.0 {
-            OfferKind::Any => self.presets.active(),
-            OfferKind::WithPresets(names) => names,
+        let (preset_names, capacity) = match msg.0 {
+            OfferKind::Any => (self.presets.active(), self.max_offered_presets),
+            OfferKind::WithPresets(names) => (names, None),
             OfferKind::WithIds(_) => {
                 log::warn!("ProviderAgent shouldn't create Offers using OfferKind::WithIds");
-                vec![]
+                (vec![], None)
             }
         };
 
-        let presets = self.presets.list_matching(&preset_names);
+        let presets = match self.presets.list_matching(&preset_names) {
+            Ok(presets) => presets,
+            Err(e) => return Box::pin(async { Err(e) }),
+        };
+        let (presets, withheld) = presets::prioritize(presets, capacity);
+        if !withheld.is_empty() {
+            log::info!(
+                "Offering {} preset(s) out of {} due to capacity limit. Withheld: {:?}",
+                presets.len(),
+                presets.len() + withheld.len(),
+                withheld.iter().map(|p| &p.name).collect::<Vec<_>>()
+            );
+        }
+
         async move {
-            Self::create_offers(presets?, node_info, inf_node_info, runner, market, accounts).await
+            Self::create_offers(presets, node_info, inf_node_info, runner, market, accounts).await
         }
         .boxed_local()
     }
@@ -127,10 +127,12 @@ pub struct NodeConfig {
     /// Your human readable identity in the network.
     #[structopt(long, env = "NODE_NAME", hide_env_values = true)]
     pub node_name: Option<String>,
-    /// Subnetwork identifier. You can set this value to filter nodes
+    /// Subnetwork identifiers. You can set this value to filter nodes
     /// with other identifiers than selected. Useful for test purposes.
-    #[structopt(long, env = "SUBNET")]
-    pub subnet: Option<String>,
+    /// Accepts repeated flags or a comma-separated list; nodes matching
+    /// any of the subnets are accepted.
+    #[structopt(long, env = "SUBNET", use_delimiter = true)]
+    pub subnet: Vec<String>,
 
     #[structopt(flatten)]
     pub account: ReceiverAccount,
@@ -165,6 +167,9 @@ pub struct PresetNoInteractive {
     pub pricing: Option<String>,
     #[structopt(long, parse(try_from_str = parse_key_val))]
     pub price: Vec<(String, f64)>,
+    /// Presets with higher priority are offered first, when capacity is limited
+    #[structopt(long)]
+    pub priority: Option<i32>,
 }
 
 #[derive(StructOpt, Clone, Debug)]
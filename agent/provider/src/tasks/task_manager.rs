@@ -6,6 +6,7 @@ use chrono::Utc;
 use futures::future::TryFutureExt;
 use std::collections::HashMap;
 
+use ya_core_model::activity::TerminationReason;
 use ya_std_utils::LogErr;
 use ya_utils_actix::actix_handler::ResultTypeGetter;
 use ya_utils_actix::actix_signal::Subscribe;
@@ -392,7 +393,7 @@ impl Handler<CreateActivity> for TaskManager {
                     .send(TerminateActivity {
                         activity_id: msg.activity_id.clone(),
                         agreement_id: msg.agreement_id.clone(),
-                        reason: "Only single Activity allowed".to_string(),
+                        reason: TerminationReason::ProviderCapacity,
                         message: "Can't create 2 simultaneous Activities.".to_string(),
                     })
                     .await?;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS activity_usage_snapshot_activity_id_idx;
+
+DROP TABLE "activity_usage_snapshot";
@@ -0,0 +1,9 @@
+CREATE TABLE "activity_usage_snapshot"(
+	"id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	"activity_id" INTEGER NOT NULL,
+	"vector_json" TEXT,
+	"snapshot_date" DATETIME NOT NULL,
+    FOREIGN KEY("activity_id") REFERENCES "activity" ("id")
+);
+
+CREATE INDEX IF NOT EXISTS activity_usage_snapshot_activity_id_idx ON activity_usage_snapshot (activity_id, snapshot_date);
@@ -18,7 +18,7 @@ pub fn web_scope(db: &DbExecutor, tracker: TrackerRef) -> Scope {
 
 /// Common operations for both sides: Provider and Requestor
 mod common {
-    use actix_web::{web, HttpResponse, Responder};
+    use actix_web::{web, HttpRequest, HttpResponse, Responder};
     use futures::prelude::*;
 
     use ya_core_model::{activity, NodeId, Role};
@@ -27,6 +27,7 @@ mod common {
     use ya_service_bus::{timeout::IntoTimeoutFuture, RpcEndpoint};
 
     use crate::common::*;
+    use crate::content::Encoding;
     use crate::tracker::TrackingEvent;
     use crate::TrackerRef;
     use actix_web::http::header;
@@ -37,6 +38,7 @@ mod common {
             .service(get_events)
             .service(get_activity_state_web)
             .service(get_activity_usage_web)
+            .service(get_activity_usage_history_web)
     }
 
     // TODO this endpoint needs authorization via Identity, otherwise is vulnerable for attacks.
@@ -51,7 +53,9 @@ mod common {
         path: web::Path<PathActivity>,
         query: web::Query<QueryTimeout>,
         id: Identity,
+        request: HttpRequest,
     ) -> impl Responder {
+        let encoding = Encoding::negotiate(&request);
         log::debug!("get_activity_state_web");
 
         // check if caller is the Provider
@@ -62,7 +66,7 @@ mod common {
             log::trace!("get_activity_state_web: I'm the provider");
             return get_persisted_state(&db, &path.activity_id)
                 .await
-                .map(web::Json);
+                .and_then(|value| encoding.respond(&value));
         }
 
         log::trace!("get_activity_state_web: Not provider, maybe requestor?");
@@ -76,7 +80,7 @@ mod common {
         let state = get_persisted_state(&db, &path.activity_id).await?;
         if !state.alive() {
             log::trace!("get_activity_state_web: got persisted state");
-            return Ok(web::Json(state));
+            return encoding.respond(&state);
         }
 
         // Retrieve and persist activity state
@@ -92,7 +96,7 @@ mod common {
 
         set_persisted_state(&db, &path.activity_id, state)
             .await
-            .map(web::Json)
+            .and_then(|value| encoding.respond(&value))
     }
 
     #[actix_web::get("/activity/{activity_id}/usage")]
@@ -101,7 +105,9 @@ mod common {
         path: web::Path<PathActivity>,
         query: web::Query<QueryTimeout>,
         id: Identity,
+        request: HttpRequest,
     ) -> impl Responder {
+        let encoding = Encoding::negotiate(&request);
         // check if caller is the Provider
         if authorize_activity_executor(&db, id.identity, &path.activity_id, Role::Provider)
             .await
@@ -109,7 +115,7 @@ mod common {
         {
             return get_persisted_usage(&db, &path.activity_id)
                 .await
-                .map(web::Json);
+                .and_then(|value| encoding.respond(&value));
         }
 
         // check if caller is the Requestor
@@ -120,7 +126,7 @@ mod common {
         if !state.alive() {
             return get_persisted_usage(&db, &path.activity_id)
                 .await
-                .map(web::Json);
+                .and_then(|value| encoding.respond(&value));
         }
 
         // Retrieve and persist activity usage
@@ -136,7 +142,41 @@ mod common {
 
         set_persisted_usage(&db, &path.activity_id, usage)
             .await
-            .map(web::Json)
+            .and_then(|value| encoding.respond(&value))
+    }
+
+    #[actix_web::get("/activity/{activity_id}/usage/history")]
+    async fn get_activity_usage_history_web(
+        db: web::Data<DbExecutor>,
+        path: web::Path<PathActivity>,
+        query: web::Query<QueryTimeout>,
+        id: Identity,
+        request: HttpRequest,
+    ) -> impl Responder {
+        let encoding = Encoding::negotiate(&request);
+        // Snapshots are taken by the Provider
+        if authorize_activity_executor(&db, id.identity, &path.activity_id, Role::Provider)
+            .await
+            .is_ok()
+        {
+            return get_usage_history(&db, &path.activity_id)
+                .await
+                .and_then(|value| encoding.respond(&value));
+        }
+
+        authorize_activity_initiator(&db, id.identity, &path.activity_id, Role::Requestor).await?;
+
+        let agreement = get_activity_agreement(&db, &path.activity_id, Role::Requestor).await?;
+        let provider_service = agreement_provider_service(&id, &agreement)?;
+        let history = provider_service
+            .send(activity::GetUsageHistory {
+                activity_id: path.activity_id.to_string(),
+                timeout: query.timeout.clone(),
+            })
+            .timeout(timeout_margin(query.timeout))
+            .await???;
+
+        encoding.respond(&history)
     }
 
     fn event_stream(
@@ -6,10 +6,11 @@ use std::time::Duration;
 use uuid::Uuid;
 
 use ya_client_model::{
-    activity::{ActivityState, ActivityUsage},
+    activity::{ActivityState, ActivityUsage, State},
     market::Agreement,
     NodeId,
 };
+use ya_core_model::activity::TerminationReason;
 use ya_core_model::{activity, market, Role};
 use ya_net::RemoteEndpoint;
 use ya_persistence::executor::DbExecutor;
@@ -83,10 +84,33 @@ pub(crate) async fn set_persisted_state(
     activity_id: &str,
     activity_state: ActivityState,
 ) -> Result<ActivityState, Error> {
-    Ok(db
-        .as_dao::<ActivityStateDao>()
-        .set(activity_id, activity_state)
-        .await?)
+    let dao = db.as_dao::<ActivityStateDao>();
+    let current = dao.get(activity_id).await?;
+    let activity_state = merge_termination_state(&current, activity_state);
+    Ok(dao.set(activity_id, activity_state).await?)
+}
+
+/// Keeps a forced termination reason already recorded for the Activity,
+/// so it won't be overwritten by the state reported by a stopping ExeUnit.
+pub(crate) fn merge_termination_state(
+    current: &ActivityState,
+    new_state: ActivityState,
+) -> ActivityState {
+    if new_state.state.0 != State::Terminated {
+        return new_state;
+    }
+    match TerminationReason::from_state(current) {
+        Some(reason) if reason.is_forced() => ActivityState {
+            state: new_state.state,
+            reason: current.reason.clone(),
+            error_message: current.error_message.clone().or(new_state.error_message),
+        },
+        Some(_) => ActivityState {
+            error_message: new_state.error_message.or(current.error_message.clone()),
+            ..new_state
+        },
+        None => new_state,
+    }
 }
 
 pub(crate) fn agreement_provider_service(
@@ -116,6 +140,29 @@ pub(crate) async fn set_persisted_usage(
         .await?)
 }
 
+pub(crate) async fn get_usage_history(
+    db: &DbExecutor,
+    activity_id: &str,
+) -> Result<Vec<ActivityUsage>, Error> {
+    Ok(db
+        .as_dao::<ActivityUsageDao>()
+        .get_history(activity_id)
+        .await?)
+}
+
+pub(crate) async fn set_usage_snapshot(
+    db: &DbExecutor,
+    activity_id: &str,
+    activity_usage: &ActivityUsage,
+    interval: chrono::Duration,
+    limit: u32,
+) -> Result<bool, Error> {
+    Ok(db
+        .as_dao::<ActivityUsageDao>()
+        .add_snapshot(activity_id, activity_usage, interval, limit)
+        .await?)
+}
+
 pub(crate) async fn get_agreement(
     agreement_id: impl ToString,
     role: Role,
@@ -196,3 +243,56 @@ pub(crate) fn authorize_caller(caller: &NodeId, authorized: &NodeId) -> Result<(
 pub(crate) fn timeout_margin<D: IntoDuration>(timeout: Option<D>) -> Option<Duration> {
     timeout.map(|t| t.into_duration() + Duration::from_secs_f32(DEFAULT_TIMEOUT_MARGIN))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ya_client_model::activity::StatePair;
+
+    fn ready() -> ActivityState {
+        StatePair::from(State::Ready).into()
+    }
+
+    fn reason(state: &ActivityState) -> Option<TerminationReason> {
+        TerminationReason::from_state(state)
+    }
+
+    #[test]
+    fn test_normal_exit_records_finished() {
+        let reported = TerminationReason::Finished.to_state(Some("Finished: done".into()));
+        let state = merge_termination_state(&ready(), reported);
+
+        assert_eq!(reason(&state), Some(TerminationReason::Finished));
+        assert_eq!(state.error_message.as_deref(), Some("Finished: done"));
+    }
+
+    #[test]
+    fn test_forced_termination_is_kept() {
+        let timeout = TerminationReason::Timeout.to_state(Some("inactive for 11s".into()));
+        let current = merge_termination_state(&ready(), timeout);
+        assert_eq!(reason(&current), Some(TerminationReason::Timeout));
+
+        let reported = TerminationReason::Interrupted.to_state(Some("signal 15".into()));
+        let state = merge_termination_state(&current, reported);
+
+        assert_eq!(reason(&state), Some(TerminationReason::Timeout));
+        assert_eq!(state.error_message.as_deref(), Some("inactive for 11s"));
+    }
+
+    #[test]
+    fn test_requestor_termination_overrides_exeunit_reason() {
+        let current = TerminationReason::Interrupted.to_state(Some("signal 15".into()));
+        let destroyed = TerminationReason::RequestorTerminated.to_state(None);
+        let state = merge_termination_state(&current, destroyed);
+
+        assert_eq!(reason(&state), Some(TerminationReason::RequestorTerminated));
+        assert_eq!(state.error_message.as_deref(), Some("signal 15"));
+    }
+
+    #[test]
+    fn test_reason_is_not_reported_for_live_activity() {
+        let mut state = ready();
+        state.reason = Some(TerminationReason::Finished.to_string());
+        assert_eq!(reason(&state), None);
+    }
+}
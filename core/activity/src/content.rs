@@ -0,0 +1,154 @@
+//! Response encodings negotiated with the `Accept` header
+use actix_web::http::header::{Accept, Header};
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::Result;
+
+const APPLICATION_MSGPACK: &str = "application/msgpack";
+const APPLICATION_X_MSGPACK: &str = "application/x-msgpack";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Json,
+    MsgPack,
+    /// Human readable (pretty-printed) JSON
+    Text,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Json
+    }
+}
+
+impl Encoding {
+    /// Picks the most preferred of the supported encodings. Defaults to JSON.
+    pub fn negotiate(request: &HttpRequest) -> Self {
+        let accept = match Accept::parse(request) {
+            Ok(accept) => accept,
+            Err(_) => return Self::default(),
+        };
+
+        accept
+            .ranked()
+            .iter()
+            .find_map(|mime| match (mime.type_(), mime.subtype()) {
+                (mime::APPLICATION, mime::JSON) => Some(Encoding::Json),
+                (mime::APPLICATION, _)
+                    if mime.essence_str() == APPLICATION_MSGPACK
+                        || mime.essence_str() == APPLICATION_X_MSGPACK =>
+                {
+                    Some(Encoding::MsgPack)
+                }
+                (mime::TEXT, mime::PLAIN) => Some(Encoding::Text),
+                (mime::STAR, mime::STAR) | (mime::APPLICATION, mime::STAR) => Some(Encoding::Json),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Json => mime::APPLICATION_JSON.essence_str(),
+            Encoding::MsgPack => APPLICATION_MSGPACK,
+            Encoding::Text => mime::TEXT_PLAIN_UTF_8.as_ref(),
+        }
+    }
+
+    pub fn respond<T: Serialize>(&self, value: &T) -> Result<HttpResponse> {
+        let body = match self {
+            Encoding::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Encoding::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Encoding::Text => serde_json::to_vec_pretty(value).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| Error::Service(format!("Unable to encode response: {}", e)))?;
+
+        Ok(HttpResponse::Ok()
+            .content_type(self.content_type())
+            .body(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+    use actix_web::http::header;
+    use actix_web::test::TestRequest;
+    use ya_client_model::activity::{ActivityState, State};
+
+    fn state() -> ActivityState {
+        ActivityState {
+            state: State::Ready.into(),
+            reason: Some("testing".to_string()),
+            error_message: None,
+        }
+    }
+
+    fn negotiate(accept: Option<&str>) -> Encoding {
+        let request = match accept {
+            Some(accept) => TestRequest::default().insert_header((header::ACCEPT, accept)),
+            None => TestRequest::default(),
+        };
+        Encoding::negotiate(&request.to_http_request())
+    }
+
+    async fn respond(accept: &str) -> (String, Vec<u8>) {
+        let response = negotiate(Some(accept)).respond(&state()).unwrap();
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = to_bytes(response.into_body()).await.unwrap();
+        (content_type, body.to_vec())
+    }
+
+    fn assert_decoded(decoded: ActivityState) {
+        assert_eq!(decoded.state.0, State::Ready);
+        assert_eq!(decoded.reason, state().reason);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None), Encoding::Json);
+        assert_eq!(negotiate(Some("*/*")), Encoding::Json);
+        assert_eq!(negotiate(Some("image/png")), Encoding::Json);
+        assert_eq!(negotiate(Some("application/x-msgpack")), Encoding::MsgPack);
+        assert_eq!(
+            negotiate(Some("application/json;q=0.5, text/plain")),
+            Encoding::Text
+        );
+        assert_eq!(
+            negotiate(Some("text/html, application/msgpack;q=0.9, */*;q=0.1")),
+            Encoding::MsgPack
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_json() {
+        let (content_type, body) = respond("application/json").await;
+        assert_eq!(content_type, "application/json");
+        let decoded: ActivityState = serde_json::from_slice(&body).unwrap();
+        assert_decoded(decoded);
+    }
+
+    #[actix_rt::test]
+    async fn test_msgpack() {
+        let (content_type, body) = respond("application/msgpack").await;
+        assert_eq!(content_type, "application/msgpack");
+        let decoded: ActivityState = rmp_serde::from_slice(&body).unwrap();
+        assert_decoded(decoded);
+    }
+
+    #[actix_rt::test]
+    async fn test_text() {
+        let (content_type, body) = respond("text/plain").await;
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        let text = String::from_utf8(body).unwrap();
+        assert!(text.contains('\n'));
+        let decoded: ActivityState = serde_json::from_str(&text).unwrap();
+        assert_decoded(decoded);
+    }
+}
@@ -1,14 +1,17 @@
-use chrono::Utc;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::expression::dsl::exists;
 use diesel::prelude::*;
 use serde_json;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 use ya_client_model::activity::activity_usage::ActivityUsage;
 use ya_persistence::executor::{do_with_transaction, AsDao, PoolType};
 
 use crate::dao::{DaoError, Result};
-use crate::db::{models::ActivityUsage as DbActivityUsage, schema};
+use crate::db::{
+    models::{ActivityUsage as DbActivityUsage, ActivityUsageSnapshot},
+    schema,
+};
 
 pub struct ActivityUsageDao<'c> {
     pool: &'c PoolType,
@@ -71,4 +74,164 @@ impl<'c> ActivityUsageDao<'c> {
         })
         .await
     }
+
+    /// Stores a snapshot of usage, unless the previous one was taken less than `interval` ago.
+    /// Only `limit` latest snapshots are kept. Returns true, if the snapshot was stored.
+    pub async fn add_snapshot(
+        &self,
+        activity_id: &str,
+        usage: &ActivityUsage,
+        interval: Duration,
+        limit: u32,
+    ) -> Result<bool> {
+        use schema::activity::dsl;
+        use schema::activity_usage_snapshot::dsl as dsl_snapshot;
+
+        let vector = usage
+            .current_usage
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let snapshot_date = NaiveDateTime::from_timestamp(usage.timestamp, 0);
+
+        let activity_id = activity_id.to_owned();
+
+        do_with_transaction(self.pool, move |conn| {
+            let id: i32 = dsl::activity
+                .select(dsl::id)
+                .filter(dsl::natural_id.eq(&activity_id))
+                .first(conn)
+                .map_err(|e| match e {
+                    diesel::NotFound => DaoError::NotFound(format!("activity: {}", activity_id)),
+                    e => e.into(),
+                })?;
+
+            let last: Option<NaiveDateTime> = dsl_snapshot::activity_usage_snapshot
+                .select(dsl_snapshot::snapshot_date)
+                .filter(dsl_snapshot::activity_id.eq(id))
+                .order(dsl_snapshot::snapshot_date.desc())
+                .first(conn)
+                .optional()?;
+            if let Some(last) = last {
+                if snapshot_date - last < interval {
+                    return Ok(false);
+                }
+            }
+
+            diesel::insert_into(dsl_snapshot::activity_usage_snapshot)
+                .values((
+                    dsl_snapshot::activity_id.eq(id),
+                    dsl_snapshot::vector_json.eq(vector),
+                    dsl_snapshot::snapshot_date.eq(snapshot_date),
+                ))
+                .execute(conn)?;
+
+            let count: i64 = dsl_snapshot::activity_usage_snapshot
+                .filter(dsl_snapshot::activity_id.eq(id))
+                .count()
+                .get_result(conn)?;
+            let excess = count - limit as i64;
+            if excess > 0 {
+                let oldest: Vec<i32> = dsl_snapshot::activity_usage_snapshot
+                    .select(dsl_snapshot::id)
+                    .filter(dsl_snapshot::activity_id.eq(id))
+                    .order((dsl_snapshot::snapshot_date.asc(), dsl_snapshot::id.asc()))
+                    .limit(excess)
+                    .load(conn)?;
+                diesel::delete(
+                    dsl_snapshot::activity_usage_snapshot.filter(dsl_snapshot::id.eq_any(oldest)),
+                )
+                .execute(conn)?;
+            }
+
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Returns usage snapshots, from the oldest one.
+    pub async fn get_history(&self, activity_id: &str) -> Result<Vec<ActivityUsage>> {
+        use schema::activity::dsl;
+        use schema::activity_usage_snapshot::dsl as dsl_snapshot;
+
+        let activity_id = activity_id.to_owned();
+
+        do_with_transaction(self.pool, move |conn| {
+            let snapshots: Vec<ActivityUsageSnapshot> = dsl_snapshot::activity_usage_snapshot
+                .inner_join(dsl::activity)
+                .select(schema::activity_usage_snapshot::all_columns)
+                .filter(dsl::natural_id.eq(&activity_id))
+                .order((dsl_snapshot::snapshot_date.asc(), dsl_snapshot::id.asc()))
+                .load(conn)?;
+
+            snapshots
+                .into_iter()
+                .map(|snapshot| Ok(ActivityUsage::try_from(snapshot)?))
+                .collect()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dao::ActivityDao;
+    use crate::db::migrations;
+    use ya_persistence::executor::DbExecutor;
+
+    fn usage(timestamp: i64) -> ActivityUsage {
+        ActivityUsage {
+            current_usage: Some(vec![timestamp as f64]),
+            timestamp,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_usage_snapshots_accumulate_at_interval() {
+        let db = DbExecutor::in_memory("activity-usage-snapshots").unwrap();
+        db.apply_migration(migrations::run_with_output).unwrap();
+        db.as_dao::<ActivityDao>()
+            .create_if_not_exists("a1", "ag1")
+            .await
+            .unwrap();
+
+        let dao = db.as_dao::<ActivityUsageDao>();
+        let interval = Duration::seconds(30);
+
+        // Usage is reported every 10s, but snapshots are taken every 30s.
+        let mut stored = 0;
+        for t in (0..=120).step_by(10) {
+            if dao
+                .add_snapshot("a1", &usage(t), interval, 10)
+                .await
+                .unwrap()
+            {
+                stored += 1;
+            }
+        }
+        assert_eq!(stored, 5);
+
+        let history = dao.get_history("a1").await.unwrap();
+        let timestamps = history.iter().map(|u| u.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![0, 30, 60, 90, 120]);
+        assert_eq!(history[4].current_usage, Some(vec![120.]));
+
+        // Only the latest snapshots are retained.
+        for t in (150..=300).step_by(30) {
+            assert!(dao
+                .add_snapshot("a1", &usage(t), interval, 5)
+                .await
+                .unwrap());
+        }
+        let history = dao.get_history("a1").await.unwrap();
+        let timestamps = history.iter().map(|u| u.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![180, 210, 240, 270, 300]);
+
+        assert!(dao.get_history("a2").await.unwrap().is_empty());
+        assert!(matches!(
+            dao.add_snapshot("a2", &usage(0), interval, 5).await,
+            Err(DaoError::NotFound(_))
+        ));
+    }
 }
@@ -103,6 +103,29 @@ impl TryFrom<ActivityUsage> for ya_client_model::activity::ActivityUsage {
     }
 }
 
+#[derive(Queryable, Debug, Identifiable)]
+#[table_name = "activity_usage_snapshot"]
+pub struct ActivityUsageSnapshot {
+    pub id: i32,
+    pub activity_id: i32,
+    pub vector_json: Option<String>,
+    pub snapshot_date: NaiveDateTime,
+}
+
+impl TryFrom<ActivityUsageSnapshot> for ya_client_model::activity::ActivityUsage {
+    type Error = ya_persistence::Error;
+
+    fn try_from(value: ActivityUsageSnapshot) -> Result<Self, Self::Error> {
+        Ok(ya_client_model::activity::ActivityUsage {
+            current_usage: value
+                .vector_json
+                .map(|json_str| serde_json::from_str(&json_str))
+                .transpose()?,
+            timestamp: value.snapshot_date.timestamp(),
+        })
+    }
+}
+
 #[derive(Queryable, Debug, Identifiable)]
 #[table_name = "runtime_event"]
 pub struct RuntimeEvent {
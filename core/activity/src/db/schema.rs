@@ -52,6 +52,15 @@ table! {
     }
 }
 
+table! {
+    activity_usage_snapshot (id) {
+        id -> Integer,
+        activity_id -> Integer,
+        vector_json -> Nullable<Text>,
+        snapshot_date -> Timestamp,
+    }
+}
+
 table! {
     runtime_event (id) {
         id -> Integer,
@@ -77,6 +86,7 @@ joinable!(activity -> activity_state (state_id));
 joinable!(activity -> activity_usage (usage_id));
 joinable!(activity_event -> activity (activity_id));
 joinable!(activity_event -> activity_event_type (event_type_id));
+joinable!(activity_usage_snapshot -> activity (activity_id));
 joinable!(runtime_event -> activity (activity_id));
 joinable!(runtime_event -> runtime_event_type (type_id));
 
@@ -87,6 +97,7 @@ allow_tables_to_appear_in_same_query!(
     activity_event_type,
     activity_state,
     activity_usage,
+    activity_usage_snapshot,
     runtime_event,
     runtime_event_type,
 );
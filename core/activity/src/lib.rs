@@ -13,6 +13,7 @@ pub mod db;
 
 mod api;
 mod cli;
+mod content;
 mod error;
 mod provider;
 mod requestor;
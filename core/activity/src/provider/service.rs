@@ -11,6 +11,7 @@ use ya_client_model::NodeId;
 use ya_core_model::activity;
 use ya_core_model::activity::local::Credentials;
 use ya_core_model::activity::RpcMessageError;
+use ya_core_model::activity::{BatchOperation, BatchResult, TerminationReason};
 use ya_core_model::Role;
 use ya_persistence::executor::DbExecutor;
 use ya_service_bus::{timeout::*, typed::ServiceBinder};
@@ -18,7 +19,7 @@ use ya_service_bus::{timeout::*, typed::ServiceBinder};
 use crate::common::{
     authorize_activity_initiator, authorize_agreement_initiator, generate_id,
     get_activity_agreement, get_agreement, get_persisted_state, get_persisted_usage,
-    set_persisted_state, RpcMessageResult,
+    get_usage_history, set_persisted_state, RpcMessageResult,
 };
 use crate::dao::*;
 use crate::db::models::ActivityEventType;
@@ -31,6 +32,11 @@ const DEFAULT_INACTIVITY_LIMIT_SECONDS: f64 = 10.;
 const DEFAULT_UNRESPONSIVE_LIMIT_SECONDS: f64 = 5.;
 const MIN_INACTIVITY_LIMIT_SECONDS: f64 = 2.;
 const MIN_UNRESPONSIVE_LIMIT_SECONDS: f64 = 2.;
+const USAGE_SNAPSHOT_INTERVAL_SECONDS_ENV_VAR: &str = "USAGE_SNAPSHOT_INTERVAL_SECONDS";
+const USAGE_SNAPSHOT_LIMIT_ENV_VAR: &str = "USAGE_SNAPSHOT_LIMIT";
+const DEFAULT_USAGE_SNAPSHOT_INTERVAL_SECONDS: f64 = 60.;
+const DEFAULT_USAGE_SNAPSHOT_LIMIT: u32 = 1440;
+const MIN_USAGE_SNAPSHOT_INTERVAL_SECONDS: f64 = 1.;
 
 #[inline]
 fn inactivity_limit_seconds() -> f64 {
@@ -50,6 +56,25 @@ fn unresponsive_limit_seconds() -> f64 {
     )
 }
 
+#[inline]
+fn usage_snapshot_interval() -> chrono::Duration {
+    let seconds = seconds_limit(
+        USAGE_SNAPSHOT_INTERVAL_SECONDS_ENV_VAR,
+        DEFAULT_USAGE_SNAPSHOT_INTERVAL_SECONDS,
+        MIN_USAGE_SNAPSHOT_INTERVAL_SECONDS,
+    );
+    chrono::Duration::milliseconds((seconds * 1000.) as i64)
+}
+
+/// Maximum number of usage snapshots kept per activity.
+#[inline]
+fn usage_snapshot_limit() -> u32 {
+    std::env::var(USAGE_SNAPSHOT_LIMIT_ENV_VAR)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_USAGE_SNAPSHOT_LIMIT)
+}
+
 fn seconds_limit(env_var: &str, default_val: f64, min_val: f64) -> f64 {
     let limit = std::env::var(env_var)
         .and_then(|v| v.parse().map_err(|_| std::env::VarError::NotPresent))
@@ -63,7 +88,9 @@ pub fn bind_gsb(db: &DbExecutor, tracker: TrackerRef) {
         .bind_with_processor(create_activity_gsb)
         .bind(destroy_activity_gsb)
         .bind(get_activity_state_gsb)
-        .bind(get_activity_usage_gsb);
+        .bind(get_activity_usage_gsb)
+        .bind(get_activity_usage_history_gsb)
+        .bind_with_processor(batch_gsb);
 
     // Initialize counters to 0 value. Otherwise they won't appear on metrics endpoint
     // until first change to value will be made.
@@ -244,6 +271,13 @@ async fn destroy_activity_gsb(
         .await
         .map(|_| ())?;
 
+    set_persisted_state(
+        &db,
+        &msg.activity_id,
+        TerminationReason::RequestorTerminated.to_state(None),
+    )
+    .await?;
+
     counter!("activity.provider.destroyed.by_requestor", 1);
     Ok(result)
 }
@@ -268,6 +302,80 @@ async fn get_activity_usage_gsb(
     Ok(get_persisted_usage(&db, &msg.activity_id).await?)
 }
 
+async fn get_activity_usage_history_gsb(
+    db: DbExecutor,
+    caller: String,
+    msg: activity::GetUsageHistory,
+) -> RpcMessageResult<activity::GetUsageHistory> {
+    authorize_activity_initiator(&db, caller, &msg.activity_id, Role::Provider).await?;
+
+    Ok(get_usage_history(&db, &msg.activity_id).await?)
+}
+
+/// Forwards an exe script to the ExeUnit, on behalf of the activity initiator.
+async fn exec_gsb(
+    db: DbExecutor,
+    caller: String,
+    msg: activity::Exec,
+) -> RpcMessageResult<activity::Exec> {
+    authorize_activity_initiator(&db, caller.clone(), &msg.activity_id, Role::Provider).await?;
+
+    let timeout = msg.timeout;
+    ya_service_bus::typed::service(activity::exeunit::bus_id(&msg.activity_id))
+        .send_as(caller, msg)
+        .timeout(timeout)
+        .await
+        .map_err(Error::from)?
+        .map_err(Error::from)?
+}
+
+/// Executes batched operations in order, isolating errors of each one.
+async fn batch_gsb(
+    db: DbExecutor,
+    tracker: TrackerRef,
+    caller: String,
+    msg: activity::Batch,
+) -> RpcMessageResult<activity::Batch> {
+    log::debug!(
+        "Processing a batch of {} activity operations from {}",
+        msg.operations.len(),
+        caller
+    );
+
+    Ok(process_batch(msg.operations, |operation| {
+        let db = db.clone();
+        let tracker = tracker.clone();
+        let caller = caller.clone();
+        async move {
+            match operation {
+                BatchOperation::Create(msg) => {
+                    BatchResult::Create(create_activity_gsb(db, tracker, caller, msg).await)
+                }
+                BatchOperation::GetState(msg) => {
+                    BatchResult::GetState(get_activity_state_gsb(db, caller, msg).await)
+                }
+                BatchOperation::Exec(msg) => BatchResult::Exec(exec_gsb(db, caller, msg).await),
+                BatchOperation::Destroy(msg) => {
+                    BatchResult::Destroy(destroy_activity_gsb(db, caller, msg).await)
+                }
+            }
+        }
+    })
+    .await)
+}
+
+async fn process_batch<F, Fut>(operations: Vec<BatchOperation>, mut handler: F) -> Vec<BatchResult>
+where
+    F: FnMut(BatchOperation) -> Fut,
+    Fut: Future<Output = BatchResult>,
+{
+    let mut results = Vec::with_capacity(operations.len());
+    for operation in operations {
+        results.push(handler(operation).await);
+    }
+    results
+}
+
 async fn get_activity_progress(
     db: &DbExecutor,
     activity_id: &str,
@@ -343,6 +451,11 @@ async fn monitor_activity(
             let dt = (Utc::now().timestamp() - usage.timestamp) as f64;
             if dt > limit_s {
                 log::warn!("activity {} inactive for {}s, destroying", activity_id, dt);
+                let message = format!("inactive for {}s", dt);
+                let new_state = TerminationReason::Timeout.to_state(Some(message));
+                if let Err(e) = set_persisted_state(&db, &activity_id, new_state).await {
+                    log::error!("cannot update activity {} state: {}", activity_id, e);
+                }
                 enqueue_destroy_evt(
                     db,
                     tracker.clone(),
@@ -392,7 +505,7 @@ async fn monitor_activity(
 /// Local Activity services for ExeUnit reporting.
 mod local {
     use super::*;
-    use crate::common::{set_persisted_state, set_persisted_usage};
+    use crate::common::{set_persisted_state, set_persisted_usage, set_usage_snapshot};
     use ya_core_model::activity::local::StatsResult;
 
     pub fn bind_gsb(db: &DbExecutor, tracker: TrackerRef) {
@@ -473,6 +586,22 @@ mod local {
                 .await;
         }
 
+        if let Err(e) = set_usage_snapshot(
+            &db,
+            &msg.activity_id,
+            &msg.usage,
+            usage_snapshot_interval(),
+            usage_snapshot_limit(),
+        )
+        .await
+        {
+            log::warn!(
+                "cannot store activity {} usage snapshot: {}",
+                msg.activity_id,
+                e
+            );
+        }
+
         set_persisted_usage(&db, &msg.activity_id, msg.usage).await?;
         Ok(())
     }
@@ -488,3 +617,135 @@ mod local {
         Ok(agreement.agreement_id)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::migrations;
+    use ya_client_model::market::{agreement, Agreement, Demand, Offer};
+    use ya_core_model::market;
+    use ya_service_bus::typed as bus;
+
+    const REQUESTOR_ID: &str = "0x0000000000000000000000000000000000000001";
+    const PROVIDER_ID: &str = "0x0000000000000000000000000000000000000002";
+
+    fn agreement(agreement_id: &str) -> Agreement {
+        Agreement {
+            agreement_id: agreement_id.to_string(),
+            demand: Demand {
+                properties: serde_json::json!({}),
+                constraints: "".to_string(),
+                demand_id: "".to_string(),
+                requestor_id: REQUESTOR_ID.parse().unwrap(),
+                timestamp: Utc::now(),
+            },
+            offer: Offer {
+                properties: serde_json::json!({}),
+                constraints: "".to_string(),
+                offer_id: "".to_string(),
+                provider_id: PROVIDER_ID.parse().unwrap(),
+                timestamp: Utc::now(),
+            },
+            valid_to: Utc::now(),
+            approved_date: None,
+            state: agreement::State::Approved,
+            timestamp: Utc::now(),
+            app_session_id: None,
+            proposed_signature: None,
+            approved_signature: None,
+            committed_signature: None,
+        }
+    }
+
+    fn get_state(activity_id: &str) -> BatchOperation {
+        BatchOperation::GetState(activity::GetState {
+            activity_id: activity_id.to_string(),
+            timeout: None,
+        })
+    }
+
+    #[actix_rt::test]
+    async fn test_batch_isolates_errors() {
+        let db = DbExecutor::in_memory("activity-batch").unwrap();
+        db.apply_migration(migrations::run_with_output).unwrap();
+        for activity_id in vec!["a1", "a2"] {
+            db.as_dao::<ActivityDao>()
+                .create_if_not_exists(activity_id, "ag1")
+                .await
+                .unwrap();
+        }
+        set_persisted_state(&db, "a1", StatePair::from(State::Ready).into())
+            .await
+            .unwrap();
+        set_persisted_state(&db, "a2", StatePair::from(State::Terminated).into())
+            .await
+            .unwrap();
+
+        let agreement = agreement("ag1");
+        bus::bind(market::BUS_ID, move |msg: market::GetAgreement| {
+            let agreement = agreement.clone();
+            async move {
+                match msg.agreement_id == agreement.agreement_id {
+                    true => Ok(agreement),
+                    false => Err(market::RpcMessageError::NotFound(msg.agreement_id)),
+                }
+            }
+        });
+        bus::bind(
+            &activity::exeunit::bus_id("a1"),
+            |msg: activity::Exec| async move { Ok(msg.batch_id) },
+        );
+
+        let operations = vec![
+            get_state("a1"),
+            BatchOperation::Exec(activity::Exec {
+                activity_id: "a1".to_string(),
+                batch_id: "b1".to_string(),
+                exe_script: vec![],
+                timeout: None,
+            }),
+            get_state("missing"),
+            BatchOperation::Destroy(activity::Destroy {
+                agreement_id: "ag1".to_string(),
+                activity_id: "a2".to_string(),
+                timeout: None,
+            }),
+        ];
+        let results = batch_gsb(
+            db.clone(),
+            TrackerRef::create(),
+            REQUESTOR_ID.to_string(),
+            activity::Batch { operations },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 4);
+        match &results[0] {
+            BatchResult::GetState(Ok(state)) => assert_eq!(state.state.0, State::Ready),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match &results[1] {
+            BatchResult::Exec(Ok(batch_id)) => assert_eq!(batch_id, "b1"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match &results[2] {
+            BatchResult::GetState(Err(_)) => (),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert!(results[3].is_ok());
+
+        // Only the activity initiator is authorized
+        let results = batch_gsb(
+            db,
+            TrackerRef::create(),
+            PROVIDER_ID.to_string(),
+            activity::Batch {
+                operations: vec![get_state("a1")],
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!results[0].is_ok());
+    }
+}
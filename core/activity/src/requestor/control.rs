@@ -9,10 +9,11 @@ use std::time::Duration;
 use tokio_stream::wrappers::IntervalStream;
 
 use ya_client_model::activity::{
-    ActivityState, CreateActivityRequest, CreateActivityResult, Credentials, ExeScriptCommand,
-    ExeScriptRequest, SgxCredentials, State,
+    CreateActivityRequest, CreateActivityResult, Credentials, ExeScriptCommand, ExeScriptRequest,
+    SgxCredentials,
 };
 use ya_client_model::market::Agreement;
+use ya_core_model::activity::TerminationReason;
 use ya_core_model::{activity, Role};
 use ya_net::{self as net, RemoteEndpoint};
 use ya_persistence::executor::DbExecutor;
@@ -20,6 +21,7 @@ use ya_service_api_web::middleware::Identity;
 use ya_service_bus::{timeout::IntoTimeoutFuture, RpcEndpoint};
 
 use crate::common::*;
+use crate::content::Encoding;
 use crate::dao::ActivityDao;
 use crate::{error::Error, Result};
 
@@ -146,11 +148,7 @@ async fn destroy_activity(
     set_persisted_state(
         &db,
         &path.activity_id,
-        ActivityState {
-            state: State::Terminated.into(),
-            reason: None,
-            error_message: None,
-        },
+        TerminationReason::RequestorTerminated.to_state(None),
     )
     .await
     .map(|_| {
@@ -214,8 +212,9 @@ async fn get_batch_results(
             return Ok(Either::Left(stream_results(agreement, path, id)?));
         }
     }
+    let encoding = Encoding::negotiate(&request);
     Ok(Either::Right(
-        await_results(agreement, path, query, id).await?,
+        await_results(agreement, path, query, id, encoding).await?,
     ))
 }
 
@@ -224,6 +223,7 @@ async fn await_results(
     path: web::Path<PathActivityBatch>,
     query: web::Query<QueryTimeoutCommandIndex>,
     id: Identity,
+    encoding: Encoding,
 ) -> Result<impl Responder> {
     let msg = activity::GetExecBatchResults {
         activity_id: path.activity_id.to_string(),
@@ -239,7 +239,7 @@ async fn await_results(
         .timeout(timeout_margin(query.timeout))
         .await???;
 
-    Ok::<_, Error>(web::Json(results))
+    encoding.respond(&results)
 }
 
 fn stream_results(
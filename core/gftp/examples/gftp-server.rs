@@ -144,7 +144,12 @@ async fn main() -> Result<()> {
 
     log::info!("sending publish request");
     let files = vec![args.share.clone()];
-    let req = RpcRequest::Publish { files };
+    let req = RpcRequest::Publish {
+        files,
+        growing: false,
+        chunk_size: None,
+        chunking: Default::default(),
+    };
     let urls = match send(&mut stdin, &mut reader, req).await? {
         RpcResult::Files(files) => files.into_iter().map(|r| r.url).collect::<Vec<_>>(),
         result => return Err(anyhow!("Invalid result: {:?}", result)),
@@ -174,7 +179,12 @@ async fn main() -> Result<()> {
 
     log::info!("sending publish request (for download)");
     let files = vec![args.share.clone()];
-    let req = RpcRequest::Publish { files };
+    let req = RpcRequest::Publish {
+        files,
+        growing: false,
+        chunk_size: None,
+        chunking: Default::default(),
+    };
     let url = match send(&mut stdin, &mut reader, req).await? {
         RpcResult::Files(files) => files
             .into_iter()
@@ -189,6 +199,9 @@ async fn main() -> Result<()> {
     let req = RpcRequest::Download {
         url,
         output_file: output_file.clone(),
+        follow: false,
+        mirrors: vec![],
+        chunk_size: None,
     };
     send(&mut stdin, &mut reader, req).await?;
 
@@ -212,6 +225,7 @@ async fn main() -> Result<()> {
     let req = RpcRequest::Upload {
         url,
         file: args.share,
+        chunking: Default::default(),
     };
     send(&mut stdin, &mut reader, req).await?;
 
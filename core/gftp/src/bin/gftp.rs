@@ -1,12 +1,19 @@
 use anyhow::Result;
 use env_logger::{Builder, Env, Target};
-use gftp::rpc::{RpcBody, RpcId, RpcMessage, RpcRequest, RpcResult, RpcStatusResult};
+use gftp::rpc::{
+    is_stdout, transfer_config, JsonRpcError, RpcBody, RpcId, RpcMessage, RpcRequest, RpcResult,
+    RpcStatusResult, RpcVerifyResult,
+};
 use std::mem;
+use std::sync::Mutex;
+use std::time::Instant;
 use structopt::{clap, StructOpt};
 use tokio::io;
 use tokio::io::AsyncBufReadExt;
 use tokio::time::Duration;
 
+const PROGRESS_REFRESH: Duration = Duration::from_millis(200);
+
 #[derive(StructOpt)]
 #[structopt(version = ya_compile_time_utils::version_describe!())]
 struct Args {
@@ -35,30 +42,56 @@ enum ExecMode {
     OneShot,
     Service,
     Shutdown,
+    /// Command finished with a negative result
+    Failure,
 }
 
-async fn execute(id: Option<RpcId>, request: RpcRequest, verbose: bool) -> ExecMode {
+async fn execute(
+    id: Option<RpcId>,
+    request: RpcRequest,
+    verbose: bool,
+    progress: gftp::Progress,
+) -> ExecMode {
     let id = id.as_ref();
-    match execute_inner(id, request, verbose).await {
+    let streams_to_stdout = request.streams_to_stdout();
+    match execute_inner(id, request, verbose, progress).await {
         Ok(exec_mode) => exec_mode,
         Err(error) => {
-            RpcMessage::error(id, error).print(verbose);
+            let message = RpcMessage::error(id, error);
+            match streams_to_stdout {
+                true => message.eprint(verbose),
+                false => message.print(verbose),
+            }
             ExecMode::OneShot
         }
     }
 }
 
-async fn execute_inner(id: Option<&RpcId>, request: RpcRequest, verbose: bool) -> Result<ExecMode> {
+async fn execute_inner(
+    id: Option<&RpcId>,
+    request: RpcRequest,
+    verbose: bool,
+    progress: gftp::Progress,
+) -> Result<ExecMode> {
     let exec_mode = match request {
         RpcRequest::Version {} => {
             let version = ya_compile_time_utils::version_describe!().to_string();
             RpcMessage::response(id, RpcResult::String(version)).print(verbose);
             ExecMode::OneShot
         }
-        RpcRequest::Publish { files } => {
+        RpcRequest::Publish {
+            files,
+            growing,
+            chunk_size,
+            chunking,
+        } => {
+            let config = transfer_config(chunk_size)?.with_chunking(chunking);
             let mut result = Vec::new();
             for file in files {
-                let url = gftp::publish(&file).await?;
+                let url = match growing {
+                    true => gftp::publish_growing(&file).await?,
+                    false => gftp::publish_with(&file, &config).await?,
+                };
                 result.push((file, url));
             }
             match result.len() {
@@ -68,10 +101,23 @@ async fn execute_inner(id: Option<&RpcId>, request: RpcRequest, verbose: bool) -
             .print(verbose);
             ExecMode::Service
         }
+        RpcRequest::Finish { urls } => {
+            let mut statuses = Vec::with_capacity(urls.len());
+            for url in urls {
+                let result = gftp::finish(&url).await?;
+                statuses.push(result.into())
+            }
+            match statuses.len() {
+                0 => RpcMessage::request_error(id),
+                _ => RpcMessage::response(id, RpcResult::Statuses(statuses)),
+            }
+            .print(verbose);
+            ExecMode::OneShot
+        }
         RpcRequest::Close { urls } => {
             let mut statuses = Vec::with_capacity(urls.len());
             for url in urls {
-                let result = gftp::close(&url).await?;
+                let result = gftp::close(url.as_str()).await?;
                 statuses.push(result.into())
             }
             match statuses.len() {
@@ -81,8 +127,52 @@ async fn execute_inner(id: Option<&RpcId>, request: RpcRequest, verbose: bool) -
             .print(verbose);
             ExecMode::OneShot
         }
-        RpcRequest::Download { url, output_file } => {
-            gftp::download_from_url(&url, &output_file).await?;
+        RpcRequest::Unpublish { hash } => {
+            let result = gftp::close(&hash).await?;
+            RpcMessage::response(id, RpcResult::Status(result.into())).print(verbose);
+            ExecMode::OneShot
+        }
+        RpcRequest::List {} => {
+            let published = gftp::published();
+            RpcMessage::response(id, RpcResult::Published(published)).print(verbose);
+            ExecMode::OneShot
+        }
+        RpcRequest::Download {
+            url,
+            output_file,
+            follow,
+            mirrors,
+            chunk_size,
+        } if is_stdout(&output_file) => {
+            let config = transfer_config(chunk_size)?;
+            if follow {
+                anyhow::bail!("Following a file isn't supported with stdout output");
+            }
+            let urls = std::iter::once(url.clone())
+                .chain(mirrors)
+                .collect::<Vec<_>>();
+            let stdout = std::io::stdout();
+            gftp::download_to_writer(&urls, &mut stdout.lock(), &config, progress).await?;
+            RpcMessage::file_response(id, output_file, url).eprint(verbose);
+            ExecMode::OneShot
+        }
+        RpcRequest::Download {
+            url,
+            output_file,
+            follow,
+            mirrors,
+            chunk_size,
+        } => {
+            let config = transfer_config(chunk_size)?;
+            match follow {
+                true => gftp::follow_from_url(&url, &output_file, &config).await?,
+                false => {
+                    let urls = std::iter::once(url.clone())
+                        .chain(mirrors)
+                        .collect::<Vec<_>>();
+                    gftp::download_from_urls(&urls, &output_file, &config, progress).await?
+                }
+            }
             RpcMessage::file_response(id, output_file, url).print(verbose);
             ExecMode::OneShot
         }
@@ -91,11 +181,34 @@ async fn execute_inner(id: Option<&RpcId>, request: RpcRequest, verbose: bool) -
             RpcMessage::file_response(id, output_file, url).print(verbose);
             ExecMode::Service
         }
-        RpcRequest::Upload { file, url } => {
-            gftp::upload_file(&file, &url).await?;
+        RpcRequest::Upload {
+            file,
+            url,
+            chunking,
+        } => {
+            gftp::upload_file(&file, &url, chunking, progress).await?;
             RpcMessage::file_response(id, file, url).print(verbose);
             ExecMode::OneShot
         }
+        RpcRequest::Verify {
+            file,
+            hash,
+            node_id,
+        } => {
+            let verification = gftp::verify_file(&file, &hash, node_id).await?;
+            let matches = verification.matches;
+            let result = RpcVerifyResult {
+                file,
+                hash: verification.hash,
+                matches,
+                mismatched_chunks: verification.mismatched_chunks,
+            };
+            RpcMessage::response(id, RpcResult::Verification(result)).print(verbose);
+            match matches {
+                true => ExecMode::OneShot,
+                false => ExecMode::Failure,
+            }
+        }
         RpcRequest::Shutdown {} => {
             RpcMessage::response(id, RpcResult::Status(RpcStatusResult::Ok)).print(verbose);
             ExecMode::Shutdown
@@ -105,6 +218,54 @@ async fn execute_inner(id: Option<&RpcId>, request: RpcRequest, verbose: bool) -
     Ok(exec_mode)
 }
 
+/// Renders transfer progress and throughput on stderr.
+fn progress_bar() -> gftp::Progress {
+    let started = Instant::now();
+    let last_update = Mutex::new(None::<Instant>);
+
+    gftp::Progress::new(move |done, total| {
+        let now = Instant::now();
+        {
+            let mut last_update = last_update.lock().unwrap();
+            let recent = last_update.map_or(false, |last| now - last < PROGRESS_REFRESH);
+            if done < total && recent {
+                return;
+            }
+            *last_update = Some(now);
+        }
+
+        let percent = match total {
+            0 => 100.,
+            _ => done as f64 * 100. / total as f64,
+        };
+        let elapsed = started.elapsed().as_secs_f64().max(0.001);
+        eprint!(
+            "\r{:5.1}% {} / {} ({}/s)   ",
+            percent,
+            format_bytes(done),
+            format_bytes(total),
+            format_bytes((done as f64 / elapsed) as u64)
+        );
+        if done >= total {
+            eprintln!();
+        }
+    })
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
 async fn server_loop() {
     let mut reader = io::BufReader::new(io::stdin());
     let mut buffer = String::new();
@@ -133,9 +294,16 @@ async fn server_loop() {
                     continue;
                 }
                 match msg.body {
+                    // Stdout carries JSON RPC responses in server mode.
+                    RpcBody::Request { request } if request.streams_to_stdout() => {
+                        RpcMessage::error(id.as_ref(), JsonRpcError::InvalidParams).print(verbose)
+                    }
                     RpcBody::Request { request } => {
                         tokio::task::spawn_local(async move {
-                            if let ExecMode::Shutdown = execute(id, request, verbose).await {
+                            let progress = gftp::Progress::default();
+                            if let ExecMode::Shutdown =
+                                execute(id, request, verbose, progress).await
+                            {
                                 tokio::time::sleep(Duration::from_secs(1)).await;
                                 std::process::exit(0);
                             }
@@ -159,10 +327,13 @@ async fn main() -> Result<()> {
 
     let args = Args::from_args();
     match args.command {
-        Command::Command(request) => match execute(None, request, args.verbose).await {
-            ExecMode::Service => actix_rt::signal::ctrl_c().await?,
-            _ => log::debug!("Shutting down"),
-        },
+        Command::Command(request) => {
+            match execute(None, request, args.verbose, progress_bar()).await {
+                ExecMode::Service => actix_rt::signal::ctrl_c().await?,
+                ExecMode::Failure => std::process::exit(1),
+                _ => log::debug!("Shutting down"),
+            }
+        }
         Command::Server => server_loop().await,
     }
 
@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufReader, Read};
+use std::str::FromStr;
+
+use crate::config::{DEFAULT_CHUNK_SIZE, MAX_CHUNK_SIZE};
+
+/// Strategy of splitting a file into chunks.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Chunking {
+    /// Chunks of the configured size.
+    Fixed,
+    /// Chunk boundaries are chosen by a rolling hash of file content,
+    /// so an insertion or removal changes only the chunks around it.
+    ContentDefined,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Chunking::Fixed
+    }
+}
+
+impl FromStr for Chunking {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(Chunking::Fixed),
+            "cdc" | "content_defined" => Ok(Chunking::ContentDefined),
+            _ => Err(anyhow::anyhow!(
+                "Unknown chunking strategy: {}. Expected 'fixed' or 'cdc'.",
+                s
+            )),
+        }
+    }
+}
+
+impl Chunking {
+    /// Splits `reader` into chunks of `chunk_size` bytes, exactly or on average.
+    pub fn chunker<R: Read>(self, reader: R, chunk_size: u64) -> Chunker<R> {
+        let params = match self {
+            Chunking::Fixed => ChunkerParams::fixed(chunk_size as usize),
+            Chunking::ContentDefined => ChunkerParams::content_defined(chunk_size as usize),
+        };
+        Chunker::new(reader, params)
+    }
+}
+
+/// Bounds of content-defined chunks. Expected chunk size is
+/// roughly `min_size + 2^mask_bits`.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkerParams {
+    pub min_size: usize,
+    pub max_size: usize,
+    pub mask_bits: u32,
+}
+
+impl Default for ChunkerParams {
+    fn default() -> Self {
+        ChunkerParams::content_defined(DEFAULT_CHUNK_SIZE as usize)
+    }
+}
+
+impl ChunkerParams {
+    /// Content-defined chunks of roughly `size` bytes, at most 4 times larger.
+    pub fn content_defined(size: usize) -> Self {
+        let min_size = (size / 4).max(1);
+        let mask_bits = ((size - min_size).max(1) as u64)
+            .next_power_of_two()
+            .trailing_zeros();
+        ChunkerParams {
+            min_size,
+            max_size: (4 * size).min(MAX_CHUNK_SIZE as usize).max(min_size),
+            mask_bits,
+        }
+    }
+
+    /// Every chunk (but the last one) is exactly `size` bytes long.
+    pub fn fixed(size: usize) -> Self {
+        ChunkerParams {
+            min_size: size,
+            max_size: size,
+            mask_bits: 64,
+        }
+    }
+
+    fn is_fixed(&self) -> bool {
+        self.min_size == self.max_size
+    }
+
+    fn mask(&self) -> u64 {
+        match self.mask_bits {
+            bits if bits >= 64 => u64::MAX,
+            bits => (1u64 << bits) - 1,
+        }
+    }
+}
+
+/// Iterator over chunks of the underlying reader. Boundaries are placed,
+/// where the Gear rolling hash of content matches the mask.
+/// Fixed size chunks are read as whole blocks.
+pub struct Chunker<R: Read> {
+    reader: BufReader<R>,
+    params: ChunkerParams,
+    gear: [u64; 256],
+    done: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R, params: ChunkerParams) -> Self {
+        Chunker {
+            reader: BufReader::new(reader),
+            params,
+            gear: gear_table(),
+            done: false,
+        }
+    }
+
+    fn next_fixed(&mut self) -> io::Result<Vec<u8>> {
+        let size = self.params.max_size;
+        let mut chunk = Vec::with_capacity(size);
+        let result = (&mut self.reader).take(size as u64).read_to_end(&mut chunk);
+        if result.is_err() || chunk.len() < size {
+            self.done = true;
+        }
+        result.map(|_| chunk)
+    }
+
+    fn next_content_defined(&mut self) -> io::Result<Vec<u8>> {
+        let mask = self.params.mask();
+        let mut chunk = Vec::with_capacity(self.params.min_size);
+        let mut hash = 0u64;
+        let mut bytes = (&mut self.reader).bytes();
+
+        loop {
+            let byte = match bytes.next() {
+                Some(Ok(byte)) => byte,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
+                }
+                None => {
+                    self.done = true;
+                    break;
+                }
+            };
+
+            chunk.push(byte);
+            hash = (hash << 1).wrapping_add(self.gear[byte as usize]);
+
+            let len = chunk.len();
+            if len >= self.params.max_size || (len >= self.params.min_size && hash & mask == 0) {
+                break;
+            }
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl<R: Read> Iterator for Chunker<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.params.is_fixed() {
+            true => self.next_fixed(),
+            false => self.next_content_defined(),
+        };
+        match result {
+            Ok(chunk) if chunk.is_empty() => None,
+            Ok(chunk) => Some(Ok(chunk)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Deterministic table of pseudo-random values (splitmix64).
+fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    for entry in table.iter_mut() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        *entry = z ^ (z >> 31);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+    use sha3::{Digest, Sha3_256};
+    use std::collections::HashSet;
+
+    fn chunk_hashes(data: &[u8], params: ChunkerParams) -> Vec<Vec<u8>> {
+        Chunker::new(data, params)
+            .map(|chunk| Sha3_256::digest(&chunk.unwrap()).to_vec())
+            .collect()
+    }
+
+    fn changed_chunks(before: &[Vec<u8>], after: &[Vec<u8>]) -> usize {
+        let before: HashSet<_> = before.iter().collect();
+        after.iter().filter(|h| !before.contains(h)).count()
+    }
+
+    fn sample_data() -> Vec<u8> {
+        let mut data = vec![0u8; 512 * 1024];
+        StdRng::seed_from_u64(7).fill_bytes(&mut data);
+        data
+    }
+
+    #[test]
+    fn test_chunks_cover_whole_input() {
+        let data = sample_data();
+        for params in vec![ChunkerParams::fixed(40 * 1024), ChunkerParams::default()] {
+            let chunks = Chunker::new(&data[..], params)
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert!(chunks.iter().all(|c| c.len() <= params.max_size));
+            assert_eq!(chunks.concat(), data);
+        }
+    }
+
+    /// Returns at most `limit` bytes per read
+    struct ShortReader<'a> {
+        data: &'a [u8],
+        limit: usize,
+    }
+
+    impl<'a> Read for ShortReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.limit);
+            (&mut self.data).read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_chunking_honours_chunk_size() {
+        let data = sample_data();
+        let size = 16 * 1024;
+        let fixed = Chunking::Fixed
+            .chunker(&data[..], size as u64)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(fixed.len(), data.len() / size);
+        assert!(fixed.iter().all(|c| c.len() == size));
+
+        let cdc = Chunking::ContentDefined
+            .chunker(&data[..], size as u64)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert!(cdc.iter().all(|c| c.len() <= 4 * size));
+        assert!(cdc.len() > data.len() / (4 * size));
+        assert_eq!(cdc.concat(), data);
+    }
+
+    #[test]
+    fn test_fixed_chunks_are_whole_blocks() {
+        let data = sample_data();
+        let reader = ShortReader {
+            data: &data[..],
+            limit: 1000,
+        };
+        let size = 40 * 1024;
+        let chunks = Chunker::new(reader, ChunkerParams::fixed(size))
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest.iter().all(|c| c.len() == size));
+        assert_eq!(last.len(), data.len() % size);
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[test]
+    fn test_cdc_insertion_changes_bounded_number_of_chunks() {
+        let params = ChunkerParams {
+            min_size: 1024,
+            max_size: 32 * 1024,
+            mask_bits: 12,
+        };
+        let data = sample_data();
+        let mut modified = data.clone();
+        modified.insert(data.len() / 2, 0x42);
+
+        let before = chunk_hashes(&data, params);
+        let after = chunk_hashes(&modified, params);
+        assert!(before.len() > 32);
+        assert!(changed_chunks(&before, &after) <= 2);
+
+        // Fixed chunking shifts every chunk after the insertion.
+        let fixed = ChunkerParams::fixed(4 * 1024);
+        let before = chunk_hashes(&data, fixed);
+        let after = chunk_hashes(&modified, fixed);
+        assert!(changed_chunks(&before, &after) >= before.len() / 2);
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Result};
+
+use crate::chunking::Chunking;
+
+pub const DEFAULT_CHUNK_SIZE: u64 = 40 * 1024;
+/// Chunks are sent as single GSB messages, which are limited by the network
+/// layer (64 MiB by default). Serialized chunks take more space than raw bytes.
+pub const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Size of chunks requested when downloading and indexed when publishing.
+    pub chunk_size: u64,
+    /// Strategy of splitting published files into indexed chunks.
+    pub chunking: Chunking,
+}
+
+impl Config {
+    pub fn with_chunk_size(chunk_size: u64) -> Result<Self> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(anyhow!(
+                "Chunk size {} is out of range (1 - {} bytes).",
+                chunk_size,
+                MAX_CHUNK_SIZE
+            ));
+        }
+        Ok(Config {
+            chunk_size,
+            ..Config::default()
+        })
+    }
+
+    pub fn with_chunking(mut self, chunking: Chunking) -> Self {
+        self.chunking = chunking;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunking: Chunking::default(),
+        }
+    }
+}
+
+/// Parses size given in bytes, optionally with `K`, `M` or `G` suffix
+/// (powers of 1024), e.g. `64K`, `1M`.
+pub fn parse_chunk_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((idx, c)) if c.is_ascii_alphabetic() => {
+            let multiplier = match c.to_ascii_uppercase() {
+                'K' => 1024,
+                'M' => 1024 * 1024,
+                'G' => 1024 * 1024 * 1024,
+                _ => return Err(anyhow!("Unknown size suffix in: {}", s)),
+            };
+            (&s[..idx], multiplier)
+        }
+        _ => (s, 1),
+    };
+    let number: u64 = number
+        .trim()
+        .parse()
+        .map_err(|e| anyhow!("Invalid size {}: {}", s, e))?;
+    let size = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Size {} is too large", s))?;
+    Config::with_chunk_size(size).map(|config| config.chunk_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chunk_size() {
+        assert_eq!(parse_chunk_size("4096").unwrap(), 4096);
+        assert_eq!(parse_chunk_size("64K").unwrap(), 64 * 1024);
+        assert_eq!(parse_chunk_size("64k").unwrap(), 64 * 1024);
+        assert_eq!(parse_chunk_size("1M").unwrap(), 1024 * 1024);
+        assert_eq!(parse_chunk_size("16M").unwrap(), MAX_CHUNK_SIZE);
+
+        assert!(parse_chunk_size("17M").is_err());
+        assert!(parse_chunk_size("1G").is_err());
+        assert!(parse_chunk_size("0").is_err());
+        assert!(parse_chunk_size("1X").is_err());
+        assert!(parse_chunk_size("K").is_err());
+        assert!(parse_chunk_size("-1").is_err());
+    }
+}
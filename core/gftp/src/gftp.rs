@@ -3,12 +3,16 @@ use futures::lock::Mutex;
 use futures::prelude::*;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use std::{fs, io};
 use url::{quirks::hostname, Position, Url};
 
@@ -18,7 +22,14 @@ use ya_core_model::net::TryRemoteEndpoint;
 use ya_core_model::NodeId;
 use ya_service_bus::{typed as bus, RpcEndpoint};
 
-pub const DEFAULT_CHUNK_SIZE: u64 = 40 * 1024;
+use crate::chunking::Chunking;
+use crate::config::{Config, DEFAULT_CHUNK_SIZE};
+use crate::index::{chunk_hash, fetch_verified, verify_chunk, ChunkIndex};
+use crate::manifest::{dest_path, is_symlink, DirManifest, ManifestEntry};
+use crate::progress::Progress;
+use crate::sources::Sources;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 // =========================================== //
 // File download - publisher side ("requestor")
@@ -26,34 +37,103 @@ pub const DEFAULT_CHUNK_SIZE: u64 = 40 * 1024;
 
 struct FileDesc {
     hash: String,
+    /// Local path of the file, or of the directory for manifests.
+    path: PathBuf,
     file: Mutex<fs::File>,
     meta: model::GftpMetadata,
+    /// Set for files published as growing, until publisher finishes them.
+    growing: AtomicBool,
+    /// Hash of a growing file, computed when it's finished.
+    finished_hash: std::sync::Mutex<Option<String>>,
+    /// Not available for growing files.
+    index: Option<ChunkIndex>,
 }
 
 impl FileDesc {
-    fn new(file: fs::File, hash: String, meta: model::GftpMetadata) -> Arc<Self> {
+    fn new(
+        file: fs::File,
+        path: PathBuf,
+        hash: String,
+        meta: model::GftpMetadata,
+        index: Option<ChunkIndex>,
+    ) -> Arc<Self> {
         let file = Mutex::new(file);
+        let growing = AtomicBool::new(meta.growing);
+
+        Arc::new(FileDesc {
+            hash,
+            path,
+            file,
+            meta,
+            growing,
+            finished_hash: Default::default(),
+            index,
+        })
+    }
+
+    pub fn open(path: &Path, config: &Config) -> Result<Arc<FileDesc>> {
+        Self::open_with(path, path, config, false, false)
+    }
 
-        Arc::new(FileDesc { hash, file, meta })
+    pub fn open_growing(path: &Path) -> Result<Arc<FileDesc>> {
+        Self::open_with(path, path, &Config::default(), true, false)
     }
 
-    pub fn open(path: &Path) -> Result<Arc<FileDesc>> {
+    /// Manifest is always split into fixed chunks, which are downloaded in order.
+    pub fn open_manifest(manifest: &Path, dir: &Path, chunk_size: u64) -> Result<Arc<FileDesc>> {
+        let config = Config {
+            chunk_size,
+            chunking: Chunking::Fixed,
+        };
+        Self::open_with(manifest, dir, &config, false, true)
+    }
+
+    fn open_with(
+        path: &Path,
+        published_path: &Path,
+        config: &Config,
+        growing: bool,
+        directory: bool,
+    ) -> Result<Arc<FileDesc>> {
         let mut file = fs::File::open(&path)
             .with_context(|| format!("Can't open file {}.", path.display()))?;
 
-        let hash = hash_file_sha256(&mut file)?;
+        // Content of growing files is hashed only when they're finished
+        let (hash, index) = match growing {
+            true => (random_hash_name(), None),
+            false => {
+                let (index, hash) =
+                    ChunkIndex::build_with(&mut file, config.chunking, config.chunk_size)?;
+                (hash, Some(index))
+            }
+        };
         let meta = model::GftpMetadata {
             file_size: file.metadata()?.len(),
+            growing,
+            directory,
+            hash: None,
         };
 
-        Ok(FileDesc::new(file, hash, meta))
+        Ok(FileDesc::new(
+            file,
+            published_path.to_path_buf(),
+            hash,
+            meta,
+            index,
+        ))
     }
 
     pub fn bind_handlers(self: &Arc<Self>) {
+        PUBLISHED
+            .lock()
+            .unwrap()
+            .insert(self.hash.clone(), self.clone());
+
         let gsb_address = model::file_bus_id(&self.hash);
         let desc = self.clone();
         let _ = bus::bind(&gsb_address, move |_msg: model::GetMetadata| {
-            future::ok(desc.meta.clone())
+            let desc = desc.clone();
+            async move { desc.metadata().await }
         });
 
         let desc = self.clone();
@@ -61,6 +141,83 @@ impl FileDesc {
             let desc = desc.clone();
             async move { desc.get_chunk(msg.offset, msg.size).await }
         });
+
+        let desc = self.clone();
+        let _ = bus::bind(&gsb_address, move |_msg: model::GetChunkIndex| {
+            let desc = desc.clone();
+            async move {
+                desc.index
+                    .as_ref()
+                    .map(ChunkIndex::to_model)
+                    .ok_or_else(|| {
+                        model::Error::ReadError("Chunk index is not available".to_string())
+                    })
+            }
+        });
+
+        let desc = self.clone();
+        let _ = bus::bind(&gsb_address, move |msg: model::GetChunkByHash| {
+            let desc = desc.clone();
+            async move { desc.get_chunk_by_hash(&msg.hash).await }
+        });
+    }
+
+    async fn get_chunk_by_hash(&self, hash: &str) -> Result<model::GftpChunk, model::Error> {
+        let info = self
+            .index
+            .as_ref()
+            .and_then(|index| index.get(hash))
+            .ok_or_else(|| model::Error::ReadError(format!("Unknown chunk {}", hash)))?;
+        self.get_chunk(info.offset, info.size).await
+    }
+
+    fn is_growing(&self) -> bool {
+        self.growing.load(Ordering::SeqCst)
+    }
+
+    /// Growing files are published with their current size.
+    async fn metadata(&self) -> Result<model::GftpMetadata, model::Error> {
+        if !self.meta.growing {
+            return Ok(self.meta.clone());
+        }
+
+        // Read the flag first, so the size and hash are final, when `growing` is false.
+        let growing = self.is_growing();
+        let file_size = self.current_size().await?;
+        let hash = match growing {
+            true => None,
+            false => self.finished_hash.lock().unwrap().clone(),
+        };
+        Ok(model::GftpMetadata {
+            file_size,
+            growing,
+            directory: false,
+            hash,
+        })
+    }
+
+    /// Hashes the complete content before marking the file as finished.
+    async fn finish(&self) -> Result<()> {
+        let hash = {
+            let mut file = self.file.lock().await;
+            hash_file_sha256(&mut file)?
+        };
+        log::debug!("File {} finished with hash {}", self.path.display(), hash);
+        self.finished_hash.lock().unwrap().replace(hash);
+        self.growing.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn current_size(&self) -> Result<u64, model::Error> {
+        if !self.meta.growing {
+            return Ok(self.meta.file_size);
+        }
+
+        let file = self.file.lock().await;
+        let metadata = file.metadata().map_err(|error| {
+            model::Error::ReadError(format!("Can't read file metadata, {}", error))
+        })?;
+        Ok(metadata.len())
     }
 
     async fn get_chunk(
@@ -68,8 +225,15 @@ impl FileDesc {
         offset: u64,
         chunk_size: u64,
     ) -> Result<model::GftpChunk, model::Error> {
-        let bytes_to_read = if self.meta.file_size - offset < chunk_size {
-            self.meta.file_size - offset
+        let file_size = self.current_size().await?;
+        if offset > file_size {
+            return Err(model::Error::ReadError(format!(
+                "Offset {} exceeds file size {}",
+                offset, file_size
+            )));
+        }
+        let bytes_to_read = if file_size - offset < chunk_size {
+            file_size - offset
         } else {
             chunk_size
         } as usize;
@@ -98,25 +262,156 @@ impl FileDesc {
     }
 }
 
+lazy_static! {
+    static ref PUBLISHED: std::sync::Mutex<HashMap<String, Arc<FileDesc>>> = Default::default();
+    /// Hashes of files published as a part of directory, keyed by manifest hash.
+    static ref DIRECTORIES: std::sync::Mutex<HashMap<String, Vec<String>>> = Default::default();
+}
+
+/// Publishes a file or a directory. Files of a directory are published
+/// separately and listed in a manifest, which url is returned.
 pub async fn publish(path: &Path) -> Result<Url> {
-    let filedesc = FileDesc::open(path)?;
+    publish_with(path, &Config::default()).await
+}
+
+/// Publishes a file or a directory indexed in chunks of `config.chunk_size`,
+/// split according to `config.chunking`.
+pub async fn publish_with(path: &Path, config: &Config) -> Result<Url> {
+    if path.is_dir() {
+        return publish_dir(path, config).await;
+    }
+    let filedesc = FileDesc::open(path, config)?;
     filedesc.bind_handlers();
 
     Ok(gftp_url(&filedesc.hash).await?)
 }
 
-pub async fn close(url: &Url) -> Result<bool> {
-    let hash_name = match url.path_segments() {
-        Some(segments) => match segments.last() {
-            Some(segment) => segment,
-            _ => return Err(anyhow!("Invalid URL: {:?}", url)),
-        },
-        _ => return Err(anyhow!("Invalid URL: {:?}", url)),
+async fn publish_dir(path: &Path, config: &Config) -> Result<Url> {
+    let mut files = HashMap::new();
+    let manifest = DirManifest::build(path, |file_path| {
+        let filedesc = FileDesc::open(file_path, config)?;
+        let entry = (filedesc.hash.clone(), filedesc.meta.file_size);
+        files.entry(filedesc.hash.clone()).or_insert(filedesc);
+        Ok(entry)
+    })?;
+
+    // Manifest is served from an unlinked temporary file.
+    let manifest_path = std::env::temp_dir().join(format!(
+        "gftp-{}.manifest",
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .map(char::from)
+            .take(16)
+            .collect::<String>()
+    ));
+    fs::write(&manifest_path, manifest.to_bytes()?)?;
+    let manifest_desc = FileDesc::open_manifest(&manifest_path, path, config.chunk_size);
+    if let Err(e) = fs::remove_file(&manifest_path) {
+        log::debug!("Can't remove {}: {}", manifest_path.display(), e);
+    }
+    let manifest_desc = manifest_desc?;
+
+    for filedesc in files.values() {
+        filedesc.bind_handlers();
+    }
+    manifest_desc.bind_handlers();
+    log::debug!(
+        "Published directory {} with {} entries.",
+        path.display(),
+        manifest.entries.len()
+    );
+    DIRECTORIES.lock().unwrap().insert(
+        manifest_desc.hash.clone(),
+        files.into_iter().map(|(hash, _)| hash).collect(),
+    );
+
+    Ok(gftp_url(&manifest_desc.hash).await?)
+}
+
+/// Publishes file, which is still being written. Downloaders in follow mode
+/// keep fetching appended content until the file is marked as finished.
+pub async fn publish_growing(path: &Path) -> Result<Url> {
+    let filedesc = FileDesc::open_growing(path)?;
+    filedesc.bind_handlers();
+
+    Ok(gftp_url(&filedesc.hash).await?)
+}
+
+/// Marks growing file as complete. File is still published until closed.
+/// Returns false, if url doesn't point to a growing file.
+pub async fn finish(url: &Url) -> Result<bool> {
+    let (_, hash) = extract_url(url)?;
+    let filedesc = PUBLISHED.lock().unwrap().get(&hash).cloned();
+    match filedesc {
+        Some(filedesc) if filedesc.is_growing() => {
+            filedesc.finish().await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// File published by this process.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PublishedFile {
+    pub hash: String,
+    /// Local path of the file or directory
+    pub path: PathBuf,
+    /// Current size of growing files; size of the manifest for directories.
+    pub size: u64,
+    pub growing: bool,
+    pub directory: bool,
+}
+
+/// Lists files currently published, ordered by path.
+pub fn published() -> Vec<PublishedFile> {
+    let mut published = PUBLISHED
+        .lock()
+        .unwrap()
+        .values()
+        .map(|desc| {
+            let size = match desc.meta.growing {
+                true => fs::metadata(&desc.path)
+                    .map(|meta| meta.len())
+                    .unwrap_or(desc.meta.file_size),
+                false => desc.meta.file_size,
+            };
+            PublishedFile {
+                hash: desc.hash.clone(),
+                path: desc.path.clone(),
+                size,
+                growing: desc.is_growing(),
+                directory: desc.meta.directory,
+            }
+        })
+        .collect::<Vec<_>>();
+    published.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.hash.cmp(&b.hash)));
+    published
+}
+
+/// Stops publishing a file given either by its gftp url or hash.
+/// Returns false, if the file wasn't published.
+pub async fn close(url_or_hash: &str) -> Result<bool> {
+    let hash = match Url::parse(url_or_hash) {
+        Ok(url) => extract_url(&url)?.1,
+        Err(_) => url_or_hash.to_string(),
     };
 
-    bus::unbind(model::file_bus_id(hash_name).as_str())
+    PUBLISHED.lock().unwrap().remove(&hash);
+    let files = DIRECTORIES.lock().unwrap().remove(&hash);
+    for file_hash in files.unwrap_or_default() {
+        PUBLISHED.lock().unwrap().remove(&file_hash);
+        bus::unbind(model::file_bus_id(&file_hash).as_str())
+            .await
+            .map_err(|e| anyhow!(e))?;
+    }
+    let unbound = bus::unbind(model::file_bus_id(&hash).as_str())
         .await
-        .map_err(|e| anyhow!(e))
+        .map_err(|e| anyhow!(e))?;
+    if !unbound {
+        log::warn!("File {} is not published.", hash);
+    }
+    Ok(unbound)
 }
 
 // =========================================== //
@@ -124,44 +419,664 @@ pub async fn close(url: &Url) -> Result<bool> {
 // =========================================== //
 
 pub async fn download_from_url(url: &Url, dst_path: &Path) -> Result<()> {
+    let urls = std::slice::from_ref(url);
+    download_from_urls(urls, dst_path, &Config::default(), Progress::default()).await
+}
+
+/// Downloads file and keeps polling publisher for appended content,
+/// until publisher marks the file as finished.
+pub async fn follow_from_url(url: &Url, dst_path: &Path, config: &Config) -> Result<()> {
     let (node_id, hash) = extract_url(url)?;
-    download_file(node_id, &hash, dst_path).await
+    follow_file(node_id, &hash, dst_path, config).await
 }
 
-pub async fn download_file(node_id: NodeId, hash: &str, dst_path: &Path) -> Result<()> {
-    let remote = node_id.try_service(&model::file_bus_id(hash))?;
-    log::debug!("Creating target file {}", dst_path.display());
+/// Downloads file mirrored on several nodes. All urls must point to the same file.
+pub async fn download_from_urls(
+    urls: &[Url],
+    dst_path: &Path,
+    config: &Config,
+    progress: Progress,
+) -> Result<()> {
+    let (sources, hash) = split_urls(urls)?;
+    download_file(&sources, &hash, dst_path, config, progress).await
+}
 
-    let mut file = create_dest_file(dst_path)?;
+/// Downloads file sequentially, writing chunks to `writer` as they arrive.
+/// Content is verified after the whole file is written, so on mismatch
+/// the error is returned, but written data can't be taken back.
+pub async fn download_to_writer<W: Write>(
+    urls: &[Url],
+    writer: &mut W,
+    config: &Config,
+    progress: Progress,
+) -> Result<()> {
+    let (sources, hash) = split_urls(urls)?;
+    let remotes = file_sources(&sources, &hash)?;
+    let metadata = fetch_metadata(&remotes).await?;
+    if metadata.directory {
+        return Err(anyhow!("Directory {} can't be streamed.", hash));
+    }
+
+    let mut writer = HashingWriter::new(writer);
+    progress.start(metadata.file_size);
+    let file_size = metadata.file_size;
+    download_chunks(
+        &remotes,
+        file_size,
+        config.chunk_size,
+        &mut writer,
+        &progress,
+    )
+    .await?;
+    writer.flush()?;
+
+    if !metadata.growing {
+        let downloaded = writer.hash();
+        if downloaded != hash {
+            return Err(anyhow!(
+                "Downloaded content hash {} is different than expected hash {}.",
+                downloaded,
+                hash
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns nodes publishing the same file and its hash.
+fn split_urls(urls: &[Url]) -> Result<(Vec<NodeId>, String)> {
+    let mut sources = Vec::with_capacity(urls.len());
+    let mut file_hash: Option<String> = None;
+    for url in urls {
+        let (node_id, hash) = extract_url(url)?;
+        match &file_hash {
+            Some(file_hash) if file_hash != &hash => {
+                return Err(anyhow!("Url {} points to a different file", url))
+            }
+            Some(_) => (),
+            None => file_hash = Some(hash),
+        }
+        sources.push(node_id);
+    }
+    let hash = file_hash.ok_or_else(|| anyhow!("No source url"))?;
+    Ok((sources, hash))
+}
+
+/// Downloads file from all of the `sources` in parallel, spreading chunk requests
+/// across them. Content of finished files is verified against `hash` and
+/// removed, if it doesn't match.
+///
+/// When `hash` points to a directory manifest, the directory tree is recreated
+/// at `dst_path`.
+pub async fn download_file(
+    sources: &[NodeId],
+    hash: &str,
+    dst_path: &Path,
+    config: &Config,
+    progress: Progress,
+) -> Result<()> {
+    let remotes = file_sources(sources, hash)?;
+    let chunk_size = config.chunk_size;
 
     log::debug!("Loading file {} metadata.", dst_path.display());
-    let metadata = remote.send(model::GetMetadata {}).await??;
+    let metadata = fetch_metadata(&remotes).await?;
+
+    match metadata.directory {
+        true => {
+            download_dir(
+                sources, &remotes, metadata, hash, dst_path, chunk_size, &progress,
+            )
+            .await
+        }
+        false => {
+            progress.start(metadata.file_size);
+            download_content(&remotes, metadata, hash, dst_path, chunk_size, &progress).await
+        }
+    }
+}
 
-    log::debug!("Metadata: file size {}.", metadata.file_size);
+fn file_sources(sources: &[NodeId], hash: &str) -> Result<Sources<bus::Endpoint>> {
+    let remotes = sources
+        .iter()
+        .map(|node_id| node_id.try_service(&model::file_bus_id(hash)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Sources::new(remotes)
+}
 
-    let chunk_size = DEFAULT_CHUNK_SIZE;
-    let num_chunks = (metadata.file_size + (chunk_size - 1)) / chunk_size; // Divide and round up.
+async fn fetch_metadata(remotes: &Sources<bus::Endpoint>) -> Result<model::GftpMetadata> {
+    remotes
+        .fetch(0, |remote| async move {
+            Ok(remote.send(model::GetMetadata {}).await??)
+        })
+        .await
+}
 
-    file.set_len(metadata.file_size)?;
+async fn download_dir(
+    sources: &[NodeId],
+    remotes: &Sources<bus::Endpoint>,
+    metadata: model::GftpMetadata,
+    hash: &str,
+    dst_path: &Path,
+    chunk_size: u64,
+    progress: &Progress,
+) -> Result<()> {
+    let mut content = Vec::with_capacity(metadata.file_size as usize);
+    while (content.len() as u64) < metadata.file_size {
+        let offset = content.len() as u64;
+        let chunk = remotes
+            .fetch(offset / chunk_size, |remote| async move {
+                let msg = model::GetChunk {
+                    offset,
+                    size: chunk_size,
+                };
+                Ok(remote.call(msg).await??)
+            })
+            .await?;
+        if chunk.content.is_empty() {
+            break;
+        }
+        content.extend(chunk.content);
+    }
+    if chunk_hash(&content) != hash {
+        return Err(anyhow!("Directory manifest {} is corrupted.", hash));
+    }
+    let manifest = DirManifest::from_bytes(&content)?;
+
+    log::debug!(
+        "Downloading directory {} with {} entries.",
+        dst_path.display(),
+        manifest.entries.len()
+    );
+    fs::create_dir_all(dst_path)
+        .with_context(|| format!("Can't create directory {}.", dst_path.display()))?;
+    progress.start(manifest.total_size());
+
+    let mut symlinks = Vec::new();
+    for entry in manifest.entries.iter() {
+        let path = dest_path(dst_path, entry.path())?;
+        match entry {
+            ManifestEntry::Dir { .. } => fs::create_dir_all(&path)?,
+            ManifestEntry::Symlink { target, .. } => symlinks.push((target, path)),
+            ManifestEntry::File { hash, .. } => {
+                if is_symlink(&path) {
+                    return Err(anyhow!(
+                        "Refusing to write through symlink {}.",
+                        path.display()
+                    ));
+                }
+                let remotes = file_sources(sources, hash)?;
+                let metadata = fetch_metadata(&remotes).await?;
+                download_content(&remotes, metadata, hash, &path, chunk_size, progress).await?;
+            }
+        }
+    }
+    // Created last, so that nothing is written through them
+    for (target, path) in symlinks {
+        create_symlink(target, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &str, path: &Path) -> Result<()> {
+    ensure_dir_exists(path)?;
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(target, path)
+        .with_context(|| format!("Can't create symlink {}.", path.display()))
+}
+
+#[cfg(not(unix))]
+fn create_symlink(target: &str, path: &Path) -> Result<()> {
+    log::warn!(
+        "Symlinks are not supported. Skipping {} -> {}.",
+        path.display(),
+        target
+    );
+    Ok(())
+}
+
+async fn download_content(
+    remotes: &Sources<bus::Endpoint>,
+    metadata: model::GftpMetadata,
+    hash: &str,
+    dst_path: &Path,
+    chunk_size: u64,
+    progress: &Progress,
+) -> Result<()> {
+    let file_size = metadata.file_size;
+    log::debug!("Metadata: file size {}.", file_size);
+
+    if metadata.growing {
+        let mut file = create_sized_file(dst_path, file_size)?;
+        return download_chunks(remotes, file_size, chunk_size, &mut file, progress).await;
+    }
+
+    let index = remotes
+        .fetch(0, |remote| async move {
+            Ok(remote.send(model::GetChunkIndex {}).await??)
+        })
+        .await;
+    let mut file = match index {
+        Ok(index) => {
+            let index = ChunkIndex::from(index);
+            let previous = PreviousFile::take(dst_path, &index);
+            let mut file = create_sized_file(dst_path, file_size)?;
+            download_indexed(remotes, index, previous, &mut file, progress).await?;
+            file
+        }
+        // Publishers not aware of chunk index
+        Err(e) => {
+            log::debug!("Chunk index not available: {}", e);
+            let mut file = create_sized_file(dst_path, file_size)?;
+            download_chunks(remotes, file_size, chunk_size, &mut file, progress).await?;
+            file
+        }
+    };
+    if let Err(e) = verify_file_hash(&mut file, hash) {
+        drop(file);
+        remove_corrupted(dst_path);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn create_sized_file(dst_path: &Path, file_size: u64) -> Result<File> {
+    log::debug!("Creating target file {}.", dst_path.display());
+    let file = create_dest_file(dst_path)?;
+    file.set_len(file_size)?;
+    Ok(file)
+}
+
+async fn download_chunks<W: Write>(
+    remotes: &Sources<bus::Endpoint>,
+    file_size: u64,
+    chunk_size: u64,
+    file: &mut W,
+    progress: &Progress,
+) -> Result<()> {
+    let num_chunks = (file_size + (chunk_size - 1)) / chunk_size; // Divide and round up.
 
     futures::stream::iter(0..num_chunks)
         .map(|chunk_number| {
-            remote.call(model::GetChunk {
-                offset: chunk_number * chunk_size,
-                size: chunk_size,
+            remotes.fetch(chunk_number, move |remote| async move {
+                let msg = model::GetChunk {
+                    offset: chunk_number * chunk_size,
+                    size: chunk_size,
+                };
+                Ok(remote.call(msg).await??)
             })
         })
         .buffered(12)
-        .map_err(anyhow::Error::from)
-        .try_for_each(move |result| {
+        .try_for_each(move |chunk| {
             future::ready((|| {
-                let chunk = result?;
                 file.write_all(&chunk.content[..])?;
+                progress.advance(chunk.content.len() as u64);
+                Ok(())
+            })())
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Unique chunks of the index along with all of their offsets.
+type UniqueChunks<'a> = HashMap<&'a str, (&'a model::GftpChunkInfo, Vec<u64>)>;
+
+fn unique_chunks(index: &ChunkIndex) -> UniqueChunks<'_> {
+    let mut unique: UniqueChunks = HashMap::new();
+    for info in index.chunks() {
+        unique
+            .entry(info.hash.as_str())
+            .or_insert_with(|| (info, Vec::new()))
+            .1
+            .push(info.offset);
+    }
+    unique
+}
+
+fn write_chunk(file: &mut fs::File, offsets: &[u64], content: &[u8]) -> io::Result<()> {
+    for offset in offsets {
+        file.seek(SeekFrom::Start(*offset))?;
+        file.write_all(content)?;
+    }
+    Ok(())
+}
+
+/// Downloads unique chunks by their hashes, verifying each of them.
+/// Corrupted chunks are re-fetched individually. Chunks found in
+/// the `previous` version of the file are copied instead.
+/// Each verified chunk is written to all of its offsets as soon as it arrives.
+async fn download_indexed(
+    remotes: &Sources<bus::Endpoint>,
+    index: ChunkIndex,
+    previous: Option<PreviousFile>,
+    file: &mut fs::File,
+    progress: &Progress,
+) -> Result<()> {
+    // Repeated chunks are fetched once, but written at each of their offsets.
+    let mut unique = unique_chunks(&index);
+    let reused = match previous {
+        Some(mut previous) => reuse_chunks(&mut previous, &mut unique, file, progress)?,
+        None => 0,
+    };
+    log::debug!(
+        "Downloading {} unique out of {} chunks. {} chunks reused.",
+        unique.len(),
+        index.chunks().len(),
+        reused
+    );
+
+    futures::stream::iter(unique.into_iter().enumerate())
+        .map(|(chunk_number, (hash, (info, offsets)))| async move {
+            let content = fetch_verified(info, || {
+                remotes.fetch(chunk_number as u64, move |remote| async move {
+                    let msg = model::GetChunkByHash {
+                        hash: hash.to_string(),
+                    };
+                    Ok(remote.call(msg).await??.content)
+                })
+            })
+            .await?;
+            Ok::<_, anyhow::Error>((info, offsets, content))
+        })
+        .buffer_unordered(12)
+        .try_for_each(|(info, offsets, content)| {
+            future::ready((|| {
+                write_chunk(file, &offsets, &content)?;
+                progress.advance(info.size * offsets.len() as u64);
                 Ok(())
             })())
         })
         .await?;
 
+    file.flush()?;
+    Ok(())
+}
+
+/// Copies chunks present in the previous version of the file and removes
+/// them from `unique`. Returns the number of reused chunks.
+fn reuse_chunks(
+    previous: &mut PreviousFile,
+    unique: &mut UniqueChunks,
+    file: &mut fs::File,
+    progress: &Progress,
+) -> Result<usize> {
+    let reusable = unique
+        .keys()
+        .copied()
+        .filter(|hash| previous.index.get(hash).is_some())
+        .collect::<Vec<_>>();
+
+    let mut reused = 0;
+    for hash in reusable {
+        let (info, offsets) = &unique[hash];
+        match previous.read(hash) {
+            Ok(content) if verify_chunk(info, &content).is_ok() => {
+                write_chunk(file, offsets, &content)?;
+                progress.advance(info.size * offsets.len() as u64);
+            }
+            // Fetched from remotes instead
+            _ => continue,
+        }
+        unique.remove(hash);
+        reused += 1;
+    }
+    Ok(reused)
+}
+
+/// Previous version of a downloaded file, moved aside, so that its chunks
+/// can be reused. It's removed when dropped.
+struct PreviousFile {
+    path: PathBuf,
+    file: fs::File,
+    index: ChunkIndex,
+}
+
+impl PreviousFile {
+    /// Indexes the file at `path` like the `remote` one.
+    fn take(path: &Path, remote: &ChunkIndex) -> Option<PreviousFile> {
+        if !path.is_file() || is_symlink(path) {
+            return None;
+        }
+        let mut name = path.file_name()?.to_os_string();
+        name.push(".gftp-previous");
+        let previous_path = path.with_file_name(name);
+        if let Err(e) = fs::rename(path, &previous_path) {
+            log::debug!("Can't reuse {}: {}", path.display(), e);
+            return None;
+        }
+
+        let file = match fs::File::open(&previous_path) {
+            Ok(file) => file,
+            Err(e) => {
+                log::debug!("Can't open {}: {}", previous_path.display(), e);
+                let _ = fs::remove_file(&previous_path);
+                return None;
+            }
+        };
+        let mut previous = PreviousFile {
+            path: previous_path,
+            file,
+            index: Default::default(),
+        };
+        match ChunkIndex::build_with(&mut previous.file, remote.chunking(), remote.chunk_size()) {
+            Ok((index, _)) => previous.index = index,
+            Err(e) => {
+                log::debug!("Can't index {}: {}", previous.path.display(), e);
+                return None;
+            }
+        }
+        Some(previous)
+    }
+
+    fn read(&mut self, hash: &str) -> Result<Vec<u8>> {
+        let info = self
+            .index
+            .get(hash)
+            .ok_or_else(|| anyhow!("Unknown chunk {}", hash))?;
+        let mut content = vec![0u8; info.size as usize];
+        self.file.seek(SeekFrom::Start(info.offset))?;
+        self.file.read_exact(&mut content)?;
+        Ok(content)
+    }
+}
+
+impl Drop for PreviousFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::debug!("Can't remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn verify_file_hash(file: &mut fs::File, expected_hash: &str) -> Result<()> {
+    let hash = hash_file_sha256(file)?;
+    if hash != expected_hash {
+        return Err(anyhow!(
+            "Downloaded file hash {} is different than expected hash {}.",
+            hash,
+            expected_hash
+        ));
+    }
+    log::debug!("File hash matches expected hash {}.", expected_hash);
+    Ok(())
+}
+
+fn remove_corrupted(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(_) => log::debug!("Removed corrupted file {}.", path.display()),
+        Err(e) => log::warn!("Can't remove corrupted file {}: {}", path.display(), e),
+    }
+}
+
+/// Computes hash of the written content on the fly.
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha3_256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: Sha3_256::new(),
+        }
+    }
+
+    fn hash(self) -> String {
+        format!("{:x}", self.hasher.result())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.input(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Result of checking a local file against a published hash.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Verification {
+    /// Hash of the local file
+    pub hash: String,
+    pub matches: bool,
+    /// Offsets of local chunks, which differ from the remote chunk index.
+    /// Empty, if the index wasn't available.
+    pub mismatched_chunks: Vec<u64>,
+}
+
+/// Checks whether a local file matches `hash` without downloading it.
+/// When `node_id` publishing the file is given, chunks of the local file
+/// are compared against the remote chunk index to locate the differences.
+pub async fn verify_file(path: &Path, hash: &str, node_id: Option<NodeId>) -> Result<Verification> {
+    let index = match node_id {
+        Some(node_id) => {
+            let remote = node_id.try_service(&model::file_bus_id(hash))?;
+            match remote.send(model::GetChunkIndex {}).await? {
+                Ok(index) => Some(ChunkIndex::from(index)),
+                Err(e) => {
+                    log::warn!("Chunk index of {} is not available: {}", hash, e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    verify_local(path, hash, index.as_ref())
+}
+
+fn verify_local(path: &Path, hash: &str, remote: Option<&ChunkIndex>) -> Result<Verification> {
+    let file = File::open(path).with_context(|| format!("Can't open file {}.", path.display()))?;
+    let (chunking, chunk_size) = remote
+        .map(|remote| (remote.chunking(), remote.chunk_size()))
+        .unwrap_or((Chunking::Fixed, DEFAULT_CHUNK_SIZE));
+    let (local, local_hash) = ChunkIndex::build_with(file, chunking, chunk_size)?;
+
+    let matches = local_hash == hash;
+    let mismatched_chunks = match remote {
+        Some(remote) if !matches => mismatched_chunks(&local, remote),
+        _ => Vec::new(),
+    };
+    Ok(Verification {
+        hash: local_hash,
+        matches,
+        mismatched_chunks,
+    })
+}
+
+/// Offsets of chunks, which are missing, redundant or different in `local` index.
+/// Content-defined chunks are matched by hash, since they move with insertions.
+fn mismatched_chunks(local: &ChunkIndex, remote: &ChunkIndex) -> Vec<u64> {
+    if remote.chunking() == Chunking::ContentDefined {
+        return remote
+            .chunks()
+            .iter()
+            .filter(|info| local.get(&info.hash).is_none())
+            .map(|info| info.offset)
+            .collect();
+    }
+    let local = local.chunks();
+    let remote = remote.chunks();
+    (0..local.len().max(remote.len()))
+        .filter_map(|idx| match (local.get(idx), remote.get(idx)) {
+            (Some(l), Some(r)) if l.size == r.size && l.hash == r.hash => None,
+            (Some(info), _) | (None, Some(info)) => Some(info.offset),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+pub async fn follow_file(
+    node_id: NodeId,
+    hash: &str,
+    dst_path: &Path,
+    config: &Config,
+) -> Result<()> {
+    let remote = node_id.try_service(&model::file_bus_id(hash))?;
+    log::debug!("Creating target file {}", dst_path.display());
+
+    let mut file = create_dest_file(dst_path)?;
+    let mut offset = 0u64;
+
+    loop {
+        let metadata = remote.send(model::GetMetadata {}).await??;
+
+        if metadata.file_size < offset {
+            log::warn!(
+                "File {} was truncated ({} < {} bytes). Downloading from the beginning.",
+                dst_path.display(),
+                metadata.file_size,
+                offset
+            );
+            file.set_len(0)?;
+            offset = 0;
+        }
+
+        while offset < metadata.file_size {
+            let size = config.chunk_size.min(metadata.file_size - offset);
+            let chunk = match remote.call(model::GetChunk { offset, size }).await? {
+                Ok(chunk) => chunk,
+                // File may shrink between requests. We'll detect it with next metadata.
+                Err(e) if metadata.growing => {
+                    log::debug!("Can't get chunk at offset {}: {}", offset, e);
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if chunk.content.is_empty() {
+                break;
+            }
+
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&chunk.content[..])?;
+            offset += chunk.content.len() as u64;
+        }
+        file.flush()?;
+
+        if !metadata.growing {
+            if let Some(hash) = metadata.hash.as_ref() {
+                file.set_len(offset)?;
+                verify_file_hash(&mut file, hash)?;
+            }
+            break;
+        }
+        tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+    }
+
+    log::debug!(
+        "File {} finished. Downloaded {} bytes.",
+        dst_path.display(),
+        offset
+    );
     Ok(())
 }
 
@@ -170,11 +1085,7 @@ pub async fn download_file(node_id: NodeId, hash: &str, dst_path: &Path) -> Resu
 // =========================================== //
 
 pub async fn open_for_upload(filepath: &Path) -> Result<Url> {
-    let hash_name = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .map(char::from)
-        .take(65)
-        .collect::<String>();
+    let hash_name = random_hash_name();
 
     let file = Arc::new(Mutex::new(create_dest_file(&filepath)?));
 
@@ -255,24 +1166,33 @@ async fn upload_finished(
 // File upload - client side ("provider")
 // =========================================== //
 
-pub async fn upload_file(path: &Path, url: &Url) -> Result<()> {
+pub async fn upload_file(
+    path: &Path,
+    url: &Url,
+    chunking: Chunking,
+    progress: Progress,
+) -> Result<()> {
     let (node_id, random_filename) = extract_url(url)?;
     let remote = node_id.try_service(&model::file_bus_id(&random_filename))?;
 
     log::debug!("Opening file to send {}.", path.display());
+    progress.start(fs::metadata(path)?.len());
 
-    let chunk_size = DEFAULT_CHUNK_SIZE;
-
-    futures::stream::iter(get_chunks(path, chunk_size)?)
+    futures::stream::iter(get_chunks(path, chunking)?)
         .map(|chunk| {
             let remote = remote.clone();
             async move {
                 let chunk = chunk?;
-                Ok::<_, anyhow::Error>(remote.call(model::UploadChunk { chunk }).await??)
+                let size = chunk.content.len() as u64;
+                remote.call(model::UploadChunk { chunk }).await??;
+                Ok::<_, anyhow::Error>(size)
             }
         })
         .buffered(3)
-        .try_for_each(|_| future::ok(()))
+        .try_for_each(|size| {
+            progress.advance(size);
+            future::ok(())
+        })
         .await?;
 
     log::debug!("Computing file hash.");
@@ -292,28 +1212,32 @@ pub async fn upload_file(path: &Path, url: &Url) -> Result<()> {
 
 fn get_chunks(
     file_path: &Path,
-    chunk_size: u64,
+    chunking: Chunking,
 ) -> Result<impl Iterator<Item = Result<model::GftpChunk, std::io::Error>> + 'static, std::io::Error>
 {
-    let mut file = OpenOptions::new().read(true).open(file_path)?;
-
-    let file_size = file.metadata()?.len();
-    let n_chunks = (file_size + chunk_size - 1) / chunk_size;
+    let file = OpenOptions::new().read(true).open(file_path)?;
+    let mut offset = 0u64;
+
+    Ok(chunking
+        .chunker(file, DEFAULT_CHUNK_SIZE)
+        .map(move |content| {
+            let chunk = model::GftpChunk {
+                offset,
+                content: content?,
+            };
+            offset += chunk.content.len() as u64;
+            Ok(chunk)
+        }))
+}
 
-    Ok((0..n_chunks).map(move |n| {
-        let offset = n * chunk_size;
-        let bytes_to_read = if offset + chunk_size > file_size {
-            file_size - offset
-        } else {
-            chunk_size
-        };
-        let mut buffer = vec![0u8; bytes_to_read as usize];
-        file.read_exact(&mut buffer)?;
-        Ok(model::GftpChunk {
-            offset,
-            content: buffer,
-        })
-    }))
+/// Cryptographically strong random string, used in place of a hash of
+/// content, which isn't known yet.
+fn random_hash_name() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .map(char::from)
+        .take(65)
+        .collect::<String>()
 }
 
 fn hash_file_sha256(mut file: &mut fs::File) -> Result<String> {
@@ -376,3 +1300,172 @@ fn create_dest_file(file_path: &Path) -> Result<File> {
         .open(file_path)
         .with_context(|| format!("Can't create destination file: [{}].", file_path.display()))?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+
+    const CHUNK_SIZE: u64 = 1024;
+
+    fn sample_data() -> Vec<u8> {
+        let mut data = vec![0u8; 8 * CHUNK_SIZE as usize + 10];
+        StdRng::seed_from_u64(5).fill_bytes(&mut data);
+        data
+    }
+
+    #[test]
+    fn test_verify_file_hash() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let path = dir.path().join("file");
+        let data = sample_data();
+        fs::write(&path, &data).unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let hash = hash_file_sha256(&mut file).unwrap();
+        // Same algorithm is used to identify published files
+        assert_eq!(hash, ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap().1);
+        assert!(verify_file_hash(&mut file, &hash).is_ok());
+
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert!(verify_file_hash(&mut file, &hash).is_err());
+
+        drop(file);
+        remove_corrupted(&path);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        let data = sample_data();
+        let mut output = Vec::new();
+        let mut writer = HashingWriter::new(&mut output);
+        for chunk in data.chunks(CHUNK_SIZE as usize) {
+            writer.write_all(chunk).unwrap();
+        }
+        let hash = writer.hash();
+
+        assert_eq!(output, data);
+        assert_eq!(hash, ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap().1);
+    }
+
+    #[actix_rt::test]
+    async fn test_published_files() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, sample_data()).unwrap();
+
+        let config = Config::with_chunk_size(CHUNK_SIZE).unwrap();
+        let filedesc = FileDesc::open(&path, &config).unwrap();
+        filedesc.bind_handlers();
+        let published = published()
+            .into_iter()
+            .find(|file| file.hash == filedesc.hash)
+            .unwrap();
+        assert_eq!(published.path, path);
+        assert_eq!(published.size, sample_data().len() as u64);
+        assert!(!published.growing && !published.directory);
+
+        assert!(close(&filedesc.hash).await.unwrap());
+        assert!(published().iter().all(|file| file.hash != filedesc.hash));
+        // Unpublishing again is a no-op
+        assert!(!close(&filedesc.hash).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn test_growing_file_hashed_on_finish() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let path = dir.path().join("file");
+        let data = sample_data();
+        let (head, tail) = data.split_at(CHUNK_SIZE as usize);
+        fs::write(&path, head).unwrap();
+
+        let filedesc = FileDesc::open_growing(&path).unwrap();
+        let meta = filedesc.metadata().await.unwrap();
+        assert!(meta.growing && meta.hash.is_none());
+
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(tail)
+            .unwrap();
+        filedesc.finish().await.unwrap();
+
+        let meta = filedesc.metadata().await.unwrap();
+        assert!(!meta.growing);
+        assert_eq!(meta.file_size, data.len() as u64);
+        assert_eq!(
+            meta.hash,
+            Some(ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap().1)
+        );
+    }
+
+    #[test]
+    fn test_verify_local_file() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let path = dir.path().join("file");
+        let data = sample_data();
+        let (remote, hash) = ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap();
+
+        fs::write(&path, &data).unwrap();
+        let verification = verify_local(&path, &hash, Some(&remote)).unwrap();
+        assert!(verification.matches);
+        assert_eq!(verification.hash, hash);
+        assert!(verification.mismatched_chunks.is_empty());
+
+        let mut corrupted = data.clone();
+        corrupted[3 * CHUNK_SIZE as usize + 7] ^= 0xff;
+        corrupted.truncate(8 * CHUNK_SIZE as usize);
+        fs::write(&path, &corrupted).unwrap();
+
+        let verification = verify_local(&path, &hash, Some(&remote)).unwrap();
+        assert!(!verification.matches);
+        assert_eq!(
+            verification.mismatched_chunks,
+            vec![3 * CHUNK_SIZE, 8 * CHUNK_SIZE]
+        );
+
+        // Without the chunk index only the hash is compared
+        let verification = verify_local(&path, &hash, None).unwrap();
+        assert!(!verification.matches);
+        assert!(verification.mismatched_chunks.is_empty());
+    }
+
+    #[test]
+    fn test_previous_version_chunks_are_reused() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let path = dir.path().join("file");
+        let data = sample_data();
+        let mut modified = data.clone();
+        modified.insert(data.len() / 2, 0x42);
+        fs::write(&path, &data).unwrap();
+
+        let (remote, hash) =
+            ChunkIndex::build_with(&modified[..], Chunking::ContentDefined, CHUNK_SIZE).unwrap();
+        let mut previous = PreviousFile::take(&path, &remote).unwrap();
+        assert!(!path.exists());
+        assert!(previous.path.exists());
+
+        let mut file = create_sized_file(&path, modified.len() as u64).unwrap();
+        let mut unique = unique_chunks(&remote);
+        let total = unique.len();
+        let reused =
+            reuse_chunks(&mut previous, &mut unique, &mut file, &Progress::default()).unwrap();
+        assert_eq!(reused + unique.len(), total);
+        assert!(unique.len() <= 2);
+
+        // Remaining chunks are downloaded
+        for (_, (info, offsets)) in unique {
+            let content = &modified[info.offset as usize..(info.offset + info.size) as usize];
+            write_chunk(&mut file, &offsets, content).unwrap();
+        }
+        assert!(verify_file_hash(&mut file, &hash).is_ok());
+
+        let previous_path = previous.path.clone();
+        drop(previous);
+        assert!(!previous_path.exists());
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use futures::Future;
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::io::Read;
+
+use ya_core_model::gftp as model;
+
+use crate::chunking::Chunking;
+
+/// Number of attempts to fetch a chunk, which fails verification.
+pub const CHUNK_FETCH_ATTEMPTS: usize = 3;
+
+/// Content-addressed index of file chunks.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkIndex {
+    index: model::GftpChunkIndex,
+    by_hash: HashMap<String, usize>,
+}
+
+impl ChunkIndex {
+    /// Splits `reader` into chunks of `chunk_size` bytes and hashes each of them.
+    /// Returns the index along with the hash of the whole content.
+    pub fn build<R: Read>(reader: R, chunk_size: u64) -> Result<(Self, String)> {
+        Self::build_with(reader, Chunking::Fixed, chunk_size)
+    }
+
+    /// Same as `build`, but chunk boundaries are chosen by `chunking`.
+    pub fn build_with<R: Read>(
+        reader: R,
+        chunking: Chunking,
+        chunk_size: u64,
+    ) -> Result<(Self, String)> {
+        let mut file_hasher = Sha3_256::new();
+        let mut chunks = Vec::new();
+        let mut offset = 0u64;
+
+        for content in chunking.chunker(reader, chunk_size) {
+            let content = content?;
+            file_hasher.input(&content);
+            chunks.push(model::GftpChunkInfo {
+                offset,
+                size: content.len() as u64,
+                hash: chunk_hash(&content),
+            });
+            offset += content.len() as u64;
+        }
+
+        let index = model::GftpChunkIndex {
+            chunk_size,
+            content_defined: chunking == Chunking::ContentDefined,
+            chunks,
+        };
+        Ok((Self::from(index), format!("{:x}", file_hasher.result())))
+    }
+
+    /// Strategy used to split the indexed content.
+    pub fn chunking(&self) -> Chunking {
+        match self.index.content_defined {
+            true => Chunking::ContentDefined,
+            false => Chunking::Fixed,
+        }
+    }
+
+    pub fn chunk_size(&self) -> u64 {
+        self.index.chunk_size
+    }
+
+    pub fn chunks(&self) -> &[model::GftpChunkInfo] {
+        &self.index.chunks
+    }
+
+    /// First chunk with given content hash. Duplicated chunks share a single entry.
+    pub fn get(&self, hash: &str) -> Option<&model::GftpChunkInfo> {
+        self.by_hash.get(hash).map(|idx| &self.index.chunks[*idx])
+    }
+
+    pub fn to_model(&self) -> model::GftpChunkIndex {
+        self.index.clone()
+    }
+}
+
+impl From<model::GftpChunkIndex> for ChunkIndex {
+    fn from(index: model::GftpChunkIndex) -> Self {
+        let mut by_hash = HashMap::new();
+        for (idx, chunk) in index.chunks.iter().enumerate() {
+            by_hash.entry(chunk.hash.clone()).or_insert(idx);
+        }
+        ChunkIndex { index, by_hash }
+    }
+}
+
+pub fn chunk_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha3_256::digest(content))
+}
+
+pub fn verify_chunk(info: &model::GftpChunkInfo, content: &[u8]) -> Result<(), model::Error> {
+    if content.len() as u64 != info.size || chunk_hash(content) != info.hash {
+        return Err(model::Error::IntegrityError);
+    }
+    Ok(())
+}
+
+/// Fetches chunk content, until it matches the index entry.
+pub async fn fetch_verified<F, Fut>(info: &model::GftpChunkInfo, fetch: F) -> Result<Vec<u8>>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Vec<u8>>>,
+{
+    for attempt in 1..=CHUNK_FETCH_ATTEMPTS {
+        let content = fetch().await?;
+        match verify_chunk(info, &content) {
+            Ok(_) => return Ok(content),
+            Err(_) => log::warn!(
+                "Chunk at offset {} failed verification (attempt {}/{})",
+                info.offset,
+                attempt,
+                CHUNK_FETCH_ATTEMPTS
+            ),
+        }
+    }
+    Err(model::Error::IntegrityError.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+    use std::cell::Cell;
+
+    const CHUNK_SIZE: u64 = 4 * 1024;
+
+    fn sample_data() -> Vec<u8> {
+        let mut data = vec![0u8; 10 * CHUNK_SIZE as usize + 100];
+        StdRng::seed_from_u64(7).fill_bytes(&mut data);
+        // Duplicated chunk
+        let (head, tail) = data.split_at_mut(CHUNK_SIZE as usize);
+        tail[..CHUNK_SIZE as usize].copy_from_slice(head);
+        data
+    }
+
+    fn content(data: &[u8], info: &model::GftpChunkInfo) -> Vec<u8> {
+        data[info.offset as usize..(info.offset + info.size) as usize].to_vec()
+    }
+
+    #[test]
+    fn test_chunk_hashes_are_stable() {
+        let data = sample_data();
+        let (index, hash) = ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap();
+        let (again, hash_again) = ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap();
+
+        assert_eq!(index.to_model(), again.to_model());
+        assert_eq!(hash, hash_again);
+        assert_eq!(hash, format!("{:x}", Sha3_256::digest(&data)));
+        assert_eq!(index.chunks().len(), 11);
+        assert_eq!(index.chunks()[10].size, 100);
+
+        let first = &index.chunks()[0];
+        assert_eq!(first.hash, index.chunks()[1].hash);
+        assert_eq!(index.get(&first.hash).unwrap().offset, 0);
+        for info in index.chunks() {
+            verify_chunk(info, &content(&data, info)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_content_defined_index_shares_chunks() {
+        let data = sample_data();
+        let mut modified = data.clone();
+        modified.insert(data.len() / 2, 0x42);
+
+        let (index, hash) =
+            ChunkIndex::build_with(&data[..], Chunking::ContentDefined, CHUNK_SIZE).unwrap();
+        let (again, _) = ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap();
+        assert_eq!(hash, format!("{:x}", Sha3_256::digest(&data)));
+        assert_eq!(index.chunking(), Chunking::ContentDefined);
+        assert_eq!(again.chunking(), Chunking::Fixed);
+        for info in index.chunks() {
+            verify_chunk(info, &content(&data, info)).unwrap();
+        }
+
+        let (modified, _) =
+            ChunkIndex::build_with(&modified[..], Chunking::ContentDefined, CHUNK_SIZE).unwrap();
+        let missing = modified
+            .chunks()
+            .iter()
+            .filter(|info| index.get(&info.hash).is_none())
+            .count();
+        assert!(index.chunks().len() > 4);
+        assert!(missing <= 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_corrupted_chunk_is_detected() {
+        let data = sample_data();
+        let (index, _) = ChunkIndex::build(&data[..], CHUNK_SIZE).unwrap();
+        let info = &index.chunks()[3];
+
+        let mut corrupted = content(&data, info);
+        corrupted[17] ^= 0xff;
+        assert!(matches!(
+            verify_chunk(info, &corrupted),
+            Err(model::Error::IntegrityError)
+        ));
+
+        // Corrupted once, re-fetched
+        let attempts = Cell::new(0);
+        let fetched = fetch_verified(info, || {
+            attempts.set(attempts.get() + 1);
+            let content = match attempts.get() {
+                1 => corrupted.clone(),
+                _ => content(&data, info),
+            };
+            async move { Ok(content) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(fetched, content(&data, info));
+        assert_eq!(attempts.get(), 2);
+
+        // Always corrupted
+        let result = fetch_verified(info, || {
+            let content = corrupted.clone();
+            async move { Ok(content) }
+        })
+        .await;
+        assert!(result.is_err());
+    }
+}
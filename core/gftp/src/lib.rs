@@ -1,7 +1,23 @@
+#[macro_use]
+extern crate lazy_static;
+
+mod chunking;
+mod config;
 mod gftp;
+mod index;
+mod manifest;
+mod progress;
 pub mod rpc;
+mod sources;
+
+pub use self::chunking::{Chunker, ChunkerParams, Chunking};
+pub use self::config::{parse_chunk_size, Config, DEFAULT_CHUNK_SIZE, MAX_CHUNK_SIZE};
+pub use self::index::ChunkIndex;
+pub use self::manifest::{DirManifest, ManifestEntry};
+pub use self::progress::Progress;
 
 pub use self::gftp::{
-    close, download_file, download_from_url, extract_url, open_for_upload, publish, upload_file,
-    DEFAULT_CHUNK_SIZE,
+    close, download_file, download_from_url, download_from_urls, download_to_writer, extract_url,
+    finish, follow_file, follow_from_url, open_for_upload, publish, publish_growing, publish_with,
+    published, upload_file, verify_file, PublishedFile, Verification,
 };
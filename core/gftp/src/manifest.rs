@@ -0,0 +1,324 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Entry of a published directory. Paths are relative to the directory root
+/// and use `/` as a separator.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ManifestEntry {
+    File {
+        path: String,
+        hash: String,
+        size: u64,
+    },
+    /// Symlinks are not followed. Target is recreated as is.
+    Symlink { path: String, target: String },
+    /// Listed explicitly, so that empty directories are recreated.
+    Dir { path: String },
+}
+
+impl ManifestEntry {
+    pub fn path(&self) -> &str {
+        match self {
+            ManifestEntry::File { path, .. }
+            | ManifestEntry::Symlink { path, .. }
+            | ManifestEntry::Dir { path } => path,
+        }
+    }
+}
+
+/// Listing of a published directory, published as a blob of its own.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DirManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl DirManifest {
+    /// Walks `root` in a deterministic order. `publish_file` is called for
+    /// every regular file and returns its hash and size.
+    pub fn build<F>(root: &Path, mut publish_file: F) -> Result<DirManifest>
+    where
+        F: FnMut(&Path) -> Result<(String, u64)>,
+    {
+        let mut manifest = DirManifest::default();
+        manifest.walk(root, "", &mut publish_file)?;
+        Ok(manifest)
+    }
+
+    fn walk<F>(&mut self, dir: &Path, prefix: &str, publish_file: &mut F) -> Result<()>
+    where
+        F: FnMut(&Path) -> Result<(String, u64)>,
+    {
+        let mut entries = fs::read_dir(dir)
+            .with_context(|| format!("Can't read directory {}.", dir.display()))?
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| anyhow!("Non UTF-8 file name: {:?}", name))?;
+            let path = format!("{}{}", prefix, name);
+            let file_type = entry.file_type()?;
+
+            if file_type.is_symlink() {
+                let target = fs::read_link(entry.path())?;
+                let target = target
+                    .to_str()
+                    .ok_or_else(|| anyhow!("Non UTF-8 symlink target: {:?}", target))?
+                    .to_string();
+                self.entries.push(ManifestEntry::Symlink { path, target });
+            } else if file_type.is_dir() {
+                self.entries.push(ManifestEntry::Dir { path: path.clone() });
+                self.walk(&entry.path(), &format!("{}/", path), publish_file)?;
+            } else {
+                let (hash, size) = publish_file(&entry.path())?;
+                self.entries.push(ManifestEntry::File { path, hash, size });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DirManifest> {
+        let manifest: DirManifest = serde_json::from_slice(bytes)
+            .map_err(|e| anyhow!("Invalid directory manifest: {}", e))?;
+        let symlinks = manifest
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ManifestEntry::Symlink { path, .. } => Some(path.as_str()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        for entry in manifest.entries.iter() {
+            relative_path(entry.path())?;
+            // Nothing is written through symlinks
+            if let Some(link) = ancestors(entry.path()).find(|dir| symlinks.contains(dir)) {
+                return Err(anyhow!(
+                    "Invalid path in directory manifest: {} lies under symlink {}",
+                    entry.path(),
+                    link
+                ));
+            }
+            if let ManifestEntry::Symlink { path, target } = entry {
+                check_symlink_target(path, target, &symlinks)?;
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|entry| match entry {
+                ManifestEntry::File { size, .. } => *size,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// Converts manifest path to a local path, refusing the ones escaping
+/// the destination directory.
+pub fn relative_path(path: &str) -> Result<PathBuf> {
+    let relative = PathBuf::from(path);
+    let valid = !path.is_empty()
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    match valid {
+        true => Ok(relative),
+        false => Err(anyhow!("Invalid path in directory manifest: {}", path)),
+    }
+}
+
+/// Joins manifest `path` to `root`. Refuses paths leading through symlinks
+/// already existing under `root`.
+pub fn dest_path(root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = relative_path(path)?;
+    let mut dest = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        dest.push(component);
+        if components.peek().is_some() && is_symlink(&dest) {
+            return Err(anyhow!(
+                "Refusing to write {} through symlink {}.",
+                path,
+                dest.display()
+            ));
+        }
+    }
+    Ok(dest)
+}
+
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|meta| meta.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+/// Parent directories of a manifest path, e.g. `a` and `a/b` for `a/b/c`.
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').map(move |(idx, _)| &path[..idx])
+}
+
+/// Symlink targets have to be relative and can't point outside of the
+/// directory root. Targets leading through other symlinks are refused,
+/// as they can't be resolved without following them.
+fn check_symlink_target(path: &str, target: &str, symlinks: &HashSet<&str>) -> Result<()> {
+    let invalid = || {
+        anyhow!(
+            "Invalid symlink in directory manifest: {} -> {}",
+            path,
+            target
+        )
+    };
+    let target_path = Path::new(target);
+    if target.is_empty() || target_path.has_root() {
+        return Err(invalid());
+    }
+
+    let mut resolved = ancestors(path).last().map(str::to_string);
+    let mut components = target_path.components().peekable();
+    while let Some(component) = components.next() {
+        resolved = match (component, resolved) {
+            (Component::CurDir, resolved) => resolved,
+            (Component::ParentDir, Some(dir)) => ancestors(&dir).last().map(str::to_string),
+            (Component::Normal(name), resolved) => {
+                let name = name.to_str().ok_or_else(invalid)?;
+                Some(match resolved {
+                    Some(dir) => format!("{}/{}", dir, name),
+                    None => name.to_string(),
+                })
+            }
+            _ => return Err(invalid()),
+        };
+        let through_symlink = resolved
+            .as_deref()
+            .map(|dir| symlinks.contains(dir))
+            .unwrap_or(false);
+        if components.peek().is_some() && through_symlink {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_lists_directory_tree() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("sub/empty")).unwrap();
+        fs::write(root.join("a.txt"), b"abc").unwrap();
+        fs::write(root.join("sub/b.bin"), b"12345").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("../a.txt", root.join("sub/link")).unwrap();
+
+        let manifest = DirManifest::build(root, |path| {
+            let size = fs::metadata(path)?.len();
+            Ok((format!("hash-{}", size), size))
+        })
+        .unwrap();
+
+        let mut expected = vec![
+            ManifestEntry::File {
+                path: "a.txt".into(),
+                hash: "hash-3".into(),
+                size: 3,
+            },
+            ManifestEntry::Dir { path: "sub".into() },
+            ManifestEntry::File {
+                path: "sub/b.bin".into(),
+                hash: "hash-5".into(),
+                size: 5,
+            },
+            ManifestEntry::Dir {
+                path: "sub/empty".into(),
+            },
+        ];
+        #[cfg(unix)]
+        expected.push(ManifestEntry::Symlink {
+            path: "sub/link".into(),
+            target: "../a.txt".into(),
+        });
+        assert_eq!(manifest.entries, expected);
+        assert_eq!(manifest.total_size(), 8);
+
+        let bytes = manifest.to_bytes().unwrap();
+        assert_eq!(DirManifest::from_bytes(&bytes).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_escaping_paths_are_rejected() {
+        assert!(relative_path("sub/file").is_ok());
+        assert!(relative_path("../file").is_err());
+        assert!(relative_path("sub/../../file").is_err());
+        assert!(relative_path("/etc/passwd").is_err());
+        assert!(relative_path("").is_err());
+
+        let manifest = r#"{"entries":[{"type":"dir","path":"../outside"}]}"#;
+        assert!(DirManifest::from_bytes(manifest.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_malicious_symlinks_are_rejected() {
+        let manifest = |entries: &str| {
+            DirManifest::from_bytes(format!(r#"{{"entries":[{}]}}"#, entries).as_bytes())
+        };
+        let link = |path: &str, target: &str| {
+            format!(
+                r#"{{"type":"symlink","path":"{}","target":"{}"}}"#,
+                path, target
+            )
+        };
+        let file =
+            |path: &str| format!(r#"{{"type":"file","path":"{}","hash":"h","size":1}}"#, path);
+
+        assert!(manifest(&link("sub/link", "../a.txt")).is_ok());
+        assert!(manifest(&link("sub/link", "..")).is_ok());
+        assert!(manifest(&link("link", "/etc")).is_err());
+        assert!(manifest(&link("link", "..")).is_err());
+        assert!(manifest(&link("sub/link", "../../etc")).is_err());
+        assert!(manifest(&link("link", "sub/../../etc")).is_err());
+
+        // File written through a symlink pointing outside
+        let entries = format!("{},{}", link("link", "/tmp"), file("link/passwd"));
+        assert!(manifest(&entries).is_err());
+        // Even if the symlink looks harmless
+        let entries = format!("{},{}", link("link", "sub"), file("link/passwd"));
+        assert!(manifest(&entries).is_err());
+        // Target escaping through another symlink
+        let entries = format!("{},{}", link("a", "."), link("b", "a/a/.."));
+        assert!(manifest(&entries).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dest_path_refuses_existing_symlinks() {
+        let dir = tempdir::TempDir::new("gftp").unwrap();
+        let outside = tempdir::TempDir::new("gftp").unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("link")).unwrap();
+
+        assert_eq!(dest_path(root, "sub/file").unwrap(), root.join("sub/file"));
+        assert!(dest_path(root, "link/file").is_err());
+        assert!(dest_path(root, "link/sub/file").is_err());
+        // Existing symlinks can be replaced
+        assert_eq!(dest_path(root, "link").unwrap(), root.join("link"));
+        assert!(is_symlink(&root.join("link")));
+    }
+}
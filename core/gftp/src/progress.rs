@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+type Callback = dyn Fn(u64, u64) + Send + Sync;
+
+/// Reports progress of a transfer as number of bytes transferred so far
+/// and the total number of bytes. Default instance reports nothing.
+#[derive(Clone, Default)]
+pub struct Progress {
+    callback: Option<Arc<Callback>>,
+    transferred: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+}
+
+impl Progress {
+    pub fn new<F: Fn(u64, u64) + Send + Sync + 'static>(callback: F) -> Self {
+        Progress {
+            callback: Some(Arc::new(callback)),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn start(&self, total: u64) {
+        self.total.store(total, Ordering::SeqCst);
+        self.transferred.store(0, Ordering::SeqCst);
+        self.report(0);
+    }
+
+    pub(crate) fn advance(&self, bytes: u64) {
+        let transferred = self.transferred.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        self.report(transferred);
+    }
+
+    fn report(&self, transferred: u64) {
+        if let Some(callback) = &self.callback {
+            callback(transferred, self.total.load(Ordering::SeqCst));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_accumulates() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let reports = reports.clone();
+            Progress::new(move |done, total| reports.lock().unwrap().push((done, total)))
+        };
+
+        progress.start(100);
+        progress.clone().advance(40);
+        progress.advance(60);
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![(0, 100), (40, 100), (100, 100)]
+        );
+
+        // Silent by default
+        Progress::default().advance(10);
+    }
+}
@@ -1,10 +1,20 @@
 use serde::{Deserialize, Serialize};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use url::Url;
 
+use ya_core_model::NodeId;
+
+use crate::{parse_chunk_size, Chunking, Config, PublishedFile};
+
 const JSON_RPC_VERSION: &str = "2.0";
+/// Output path streaming downloaded content to stdout.
+pub const STDOUT_PATH: &str = "-";
+
+pub fn is_stdout(path: &Path) -> bool {
+    path == Path::new(STDOUT_PATH)
+}
 
 #[allow(unused)]
 #[derive(Debug)]
@@ -115,13 +125,21 @@ impl RpcMessage {
     }
 
     pub fn print(&self, verbose: bool) {
-        let mut stdout = std::io::stdout();
+        self.write_to(&mut std::io::stdout(), verbose);
+    }
+
+    /// Used when stdout carries downloaded content.
+    pub fn eprint(&self, verbose: bool) {
+        self.write_to(&mut std::io::stderr(), verbose);
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W, verbose: bool) {
         let json = match verbose {
             true => serde_json::to_string(self).unwrap(),
             false => serde_json::to_string(&self.body).unwrap(),
         };
-        let _ = stdout.write_fmt(format_args!("{}\r\n", json));
-        let _ = stdout.flush();
+        let _ = out.write_fmt(format_args!("{}\r\n", json));
+        let _ = out.flush();
     }
 }
 
@@ -155,16 +173,48 @@ pub enum RpcBody {
 pub enum RpcRequest {
     /// Prints out version
     Version {},
-    /// Publishes files (blocking)
-    Publish { files: Vec<PathBuf> },
+    /// Publishes files or directories (blocking)
+    Publish {
+        files: Vec<PathBuf>,
+        /// Files are still being written; use `finish` once they're complete
+        #[structopt(long)]
+        #[serde(default)]
+        growing: bool,
+        /// Chunk size, e.g. `64K` or `1M`
+        #[structopt(long, parse(try_from_str = parse_chunk_size))]
+        #[serde(default)]
+        chunk_size: Option<u64>,
+        /// Chunking strategy: 'fixed' or 'cdc' (content-defined)
+        #[structopt(long, default_value = "fixed")]
+        #[serde(default)]
+        chunking: Chunking,
+    },
+    /// Marks files published as growing as complete
+    Finish { urls: Vec<Url> },
     /// Stops publishing a file
     Close { urls: Vec<Url> },
+    /// Stops publishing a file with given hash
+    Unpublish { hash: String },
+    /// Lists currently published files
+    List {},
     /// Downloads a file
     Download {
         /// Source URL
         url: Url,
-        /// Destination path
+        /// Destination path. Use `-` to write the content to stdout
         output_file: PathBuf,
+        /// Keeps downloading appended content, until publisher finishes the file
+        #[structopt(long)]
+        #[serde(default)]
+        follow: bool,
+        /// Additional URLs of the same file published on other nodes
+        #[structopt(long = "mirror")]
+        #[serde(default)]
+        mirrors: Vec<Url>,
+        /// Chunk size, e.g. `64K` or `1M`
+        #[structopt(long, parse(try_from_str = parse_chunk_size))]
+        #[serde(default)]
+        chunk_size: Option<u64>,
     },
     /// Waits for file upload (blocking)
     Receive {
@@ -177,11 +227,44 @@ pub enum RpcRequest {
         url: Url,
         /// Source path
         file: PathBuf,
+        /// Chunking strategy: 'fixed' or 'cdc' (content-defined)
+        #[structopt(long, default_value = "fixed")]
+        #[serde(default)]
+        chunking: Chunking,
+    },
+    /// Checks a local file against a published hash without downloading it
+    Verify {
+        /// Local file path
+        file: PathBuf,
+        /// Expected file hash
+        hash: String,
+        /// Node publishing the file. Used to locate mismatched chunks
+        #[structopt(long)]
+        #[serde(default)]
+        node_id: Option<NodeId>,
     },
     /// Shuts down the server
     Shutdown {},
 }
 
+/// Transfer configuration. Chunk size given in JSON RPC requests is validated here.
+pub fn transfer_config(chunk_size: Option<u64>) -> anyhow::Result<Config> {
+    match chunk_size {
+        Some(chunk_size) => Config::with_chunk_size(chunk_size),
+        None => Ok(Config::default()),
+    }
+}
+
+impl RpcRequest {
+    /// Downloaded content is written to stdout instead of a file.
+    pub fn streams_to_stdout(&self) -> bool {
+        match self {
+            RpcRequest::Download { output_file, .. } => is_stdout(output_file),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum RpcResult {
@@ -190,6 +273,8 @@ pub enum RpcResult {
     Files(Vec<RpcFileResult>),
     Status(RpcStatusResult),
     Statuses(Vec<RpcStatusResult>),
+    Verification(RpcVerifyResult),
+    Published(Vec<PublishedFile>),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
@@ -215,6 +300,16 @@ pub struct RpcFileResult {
     pub url: Url,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RpcVerifyResult {
+    pub file: PathBuf,
+    pub hash: String,
+    pub matches: bool,
+    /// Offsets of chunks, which differ from the published file
+    pub mismatched_chunks: Vec<u64>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct RpcError {
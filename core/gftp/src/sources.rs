@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Result};
+use futures::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Nodes publishing the same file.
+///
+/// Chunk requests are spread evenly across sources. When a source fails,
+/// the request is retried on the next one and the failed source is
+/// deprioritized for subsequent requests.
+pub struct Sources<S> {
+    sources: Vec<(S, AtomicBool)>,
+}
+
+impl<S> Sources<S> {
+    pub fn new(sources: impl IntoIterator<Item = S>) -> Result<Self> {
+        let sources = sources
+            .into_iter()
+            .map(|source| (source, AtomicBool::new(false)))
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
+            return Err(anyhow!("No download sources"));
+        }
+        Ok(Sources { sources })
+    }
+
+    /// Order, in which sources are tried for given chunk. Healthy sources go first.
+    fn order(&self, chunk: u64) -> Vec<&(S, AtomicBool)> {
+        let start = (chunk % self.sources.len() as u64) as usize;
+        let mut order = self.sources[start..]
+            .iter()
+            .chain(self.sources[..start].iter())
+            .collect::<Vec<_>>();
+        order.sort_by_key(|(_, failed)| failed.load(Ordering::Relaxed));
+        order
+    }
+
+    /// Fetches `chunk` from the first source, which succeeds.
+    pub async fn fetch<T, F, Fut>(&self, chunk: u64, fetch: F) -> Result<T>
+    where
+        F: Fn(&S) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = None;
+        for (source, failed) in self.order(chunk) {
+            match fetch(source).await {
+                Ok(result) => {
+                    failed.store(false, Ordering::Relaxed);
+                    return Ok(result);
+                }
+                Err(e) => {
+                    log::debug!("Failed to fetch chunk {}: {}", chunk, e);
+                    failed.store(true, Ordering::Relaxed);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("No download sources")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{StreamExt, TryStreamExt};
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+    use sha3::{Digest, Sha3_256};
+    use std::cell::Cell;
+
+    const CHUNK_SIZE: usize = 1024;
+    const NUM_CHUNKS: u64 = 20;
+
+    struct MockSource {
+        data: Vec<u8>,
+        served: Cell<usize>,
+        broken: bool,
+    }
+
+    impl MockSource {
+        fn new(data: &[u8], broken: bool) -> Self {
+            MockSource {
+                data: data.to_vec(),
+                served: Cell::new(0),
+                broken,
+            }
+        }
+
+        async fn get_chunk(&self, chunk: u64) -> Result<Vec<u8>> {
+            if self.broken {
+                return Err(anyhow!("source unavailable"));
+            }
+            self.served.set(self.served.get() + 1);
+            let offset = chunk as usize * CHUNK_SIZE;
+            Ok(self.data[offset..offset + CHUNK_SIZE].to_vec())
+        }
+    }
+
+    fn sample_data() -> Vec<u8> {
+        let mut data = vec![0u8; NUM_CHUNKS as usize * CHUNK_SIZE];
+        StdRng::seed_from_u64(3).fill_bytes(&mut data);
+        data
+    }
+
+    async fn download(sources: &Sources<MockSource>) -> Result<Vec<u8>> {
+        let chunks = futures::stream::iter(0..NUM_CHUNKS)
+            .map(|chunk| sources.fetch(chunk, move |source| source.get_chunk(chunk)))
+            .buffered(4)
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(chunks.concat())
+    }
+
+    fn hash(data: &[u8]) -> String {
+        format!("{:x}", Sha3_256::digest(data))
+    }
+
+    #[actix_rt::test]
+    async fn test_chunks_are_distributed() {
+        let data = sample_data();
+        let sources = Sources::new(vec![
+            MockSource::new(&data, false),
+            MockSource::new(&data, false),
+        ])
+        .unwrap();
+
+        let downloaded = download(&sources).await.unwrap();
+        assert_eq!(hash(&downloaded), hash(&data));
+        for (source, _) in &sources.sources {
+            assert_eq!(source.served.get(), NUM_CHUNKS as usize / 2);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_failover() {
+        let data = sample_data();
+        let sources = Sources::new(vec![
+            MockSource::new(&data, true),
+            MockSource::new(&data, false),
+        ])
+        .unwrap();
+
+        let downloaded = download(&sources).await.unwrap();
+        assert_eq!(hash(&downloaded), hash(&data));
+        assert_eq!(sources.sources[1].0.served.get(), NUM_CHUNKS as usize);
+
+        let sources = Sources::new(vec![MockSource::new(&data, true)]).unwrap();
+        assert!(download(&sources).await.is_err());
+        assert!(Sources::<MockSource>::new(vec![]).is_err());
+    }
+}
@@ -0,0 +1,2 @@
+DROP INDEX market_offer_tombstone_expiration_idx;
+DROP TABLE market_offer_tombstone;
@@ -0,0 +1,10 @@
+CREATE TABLE market_offer_tombstone (
+    id VARCHAR(97) NOT NULL PRIMARY KEY,
+    node_id VARCHAR(20) NOT NULL,
+    reason VARCHAR(20) NOT NULL,
+
+    insertion_ts DATETIME NOT NULL DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+    expiration_ts DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS market_offer_tombstone_expiration_idx ON market_offer_tombstone (expiration_ts);
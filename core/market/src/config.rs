@@ -28,6 +28,12 @@ pub struct DiscoveryConfig {
     pub offer_broadcast_delay: Duration,
     #[structopt(env, parse(try_from_str = humantime::parse_duration), default_value = "5sec")]
     pub unsub_broadcast_delay: Duration,
+    /// Interval in which expired Offers are removed from the store
+    #[structopt(env, parse(try_from_str = humantime::parse_duration), default_value = "1min")]
+    pub offer_expiry_sweep_interval: Duration,
+    /// Number of Offers stored concurrently during bulk import
+    #[structopt(env, default_value = "16")]
+    pub offer_import_concurrency: usize,
 }
 
 #[derive(StructOpt, Clone)]
@@ -55,6 +61,9 @@ pub struct DbConfig {
     /// Number of days to persist Negotiation Events
     #[structopt(env = "MARKET_EVENT_STORE_DAYS", default_value = "1")]
     pub event_store_days: i32,
+    /// How long to remember Offers removed by cleaner (unsubscribed or expired)
+    #[structopt(env = "MARKET_OFFER_TOMBSTONE_TTL", parse(try_from_str = parse_chrono_duration), default_value = "12h")]
+    pub offer_tombstone_ttl: chrono::Duration,
 }
 
 impl Config {
@@ -92,5 +101,6 @@ mod test {
         assert_eq!(4 * 3600, c.db.cleanup_interval.as_secs());
         assert_eq!(90, c.db.agreement_store_days);
         assert_eq!(1, c.db.event_store_days);
+        assert_eq!(12, c.db.offer_tombstone_ttl.num_hours());
     }
 }
@@ -14,7 +14,7 @@ pub async fn clean(db: DbMixedExecutor, cfg: &DbConfig) {
 
     let results = join!(
         async move { demand_db.as_dao::<DemandDao>().clean().await },
-        async move { offer_db.as_dao::<OfferDao>().clean().await },
+        async move { offer_db.as_dao::<OfferDao>().clean(cfg).await },
         async move { agreement_db.as_dao::<AgreementDao>().clean(cfg).await },
         async move { proposal_db.as_dao::<ProposalDao>().clean().await },
         async move { events_db.as_dao::<NegotiationEventsDao>().clean(cfg).await },
@@ -1,14 +1,20 @@
-use chrono::NaiveDateTime;
-use diesel::expression::dsl::now as sql_now;
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::sql;
 use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 
 use ya_client::model::NodeId;
-use ya_persistence::executor::{do_with_transaction, readonly_transaction, ConnType, PoolType};
+use ya_core_model::market::OfferStoreStats;
+use ya_persistence::executor::{
+    do_with_transaction, readonly_transaction, vacuum, ConnType, PoolType,
+};
 
+use crate::config::DbConfig;
 use crate::db::model::SubscriptionId;
-use crate::db::model::{Offer, OfferUnsubscribed};
+use crate::db::model::{Offer, OfferRemoval, OfferTombstone, OfferUnsubscribed};
 use crate::db::schema::market_offer::dsl as offer;
 use crate::db::schema::market_offer::dsl::market_offer;
+use crate::db::schema::market_offer_tombstone::dsl as tombstone;
+use crate::db::schema::market_offer_tombstone::dsl::market_offer_tombstone;
 use crate::db::schema::market_offer_unsubscribed::dsl as unsubscribed;
 use crate::db::schema::market_offer_unsubscribed::dsl::market_offer_unsubscribed;
 use crate::db::{AsMixedDao, DbError, DbResult};
@@ -28,6 +34,8 @@ impl<'a> AsMixedDao<'a> for OfferDao<'a> {
 /// Unsubscribed and Expired Offers are Options
 /// since we keep only Offers subscribed locally
 /// (Offers from other nodes are removed upon unsubscribe).
+/// Offers removed by cleaner are reported as `Unsubscribed(None)`
+/// or `Expired(None)` as long as their tombstone exists.
 #[derive(Clone, derive_more::Display)]
 pub enum OfferState {
     #[display(fmt = "Active")]
@@ -142,14 +150,20 @@ impl<'c> OfferDao<'c> {
         .await
     }
 
-    /// Returns only those from input Offer ids that are in `market_offer`
-    /// or in `market_offer_unsubscribed` table.
+    /// Returns only those from input Offer ids that are in `market_offer`,
+    /// `market_offer_unsubscribed` or `market_offer_tombstone` table.
     pub async fn get_known_ids(&self, ids: Vec<SubscriptionId>) -> DbResult<Vec<SubscriptionId>> {
         readonly_transaction(self.pool, move |conn| {
-            let known_unsubscribed_ids = market_offer_unsubscribed
+            let mut known_unsubscribed_ids = market_offer_unsubscribed
                 .select(unsubscribed::id)
                 .filter(unsubscribed::id.eq_any(&ids))
                 .load::<SubscriptionId>(conn)?;
+            let known_removed_ids = market_offer_tombstone
+                .select(tombstone::id)
+                .filter(tombstone::id.eq_any(&ids))
+                .filter(tombstone::id.ne_all(&known_unsubscribed_ids))
+                .load::<SubscriptionId>(conn)?;
+            known_unsubscribed_ids.extend(known_removed_ids);
 
             // diesel does not support UNION operator
             let mut known_ids = market_offer
@@ -238,38 +252,175 @@ impl<'c> OfferDao<'c> {
         .await
     }
 
-    pub async fn clean(&self) -> DbResult<()> {
+    /// Removes expired Offers and unsubscription markers.
+    /// Removed Offers are remembered as tombstones for `offer_tombstone_ttl`.
+    pub async fn clean(&self, db_config: &DbConfig) -> DbResult<()> {
         log::debug!("Clean market offers: start");
-        let num_deleted = do_with_transaction(self.pool, move |conn| {
-            let nd = diesel::delete(market_offer.filter(offer::expiration_ts.lt(sql_now)))
+        let tombstone_ttl = db_config.offer_tombstone_ttl;
+        let (num_offers, num_unsubscribes, num_tombstones) =
+            do_with_transaction(self.pool, move |conn| {
+                let now = Utc::now().naive_utc();
+                let tombstone_expiration_ts = now + tombstone_ttl;
+
+                let unsubscribed_ids = market_offer_unsubscribed
+                    .select((unsubscribed::id, unsubscribed::node_id))
+                    .filter(unsubscribed::expiration_ts.lt(now))
+                    .load::<(SubscriptionId, NodeId)>(conn)?;
+                put_tombstones(
+                    conn,
+                    unsubscribed_ids,
+                    OfferRemoval::Unsubscribed,
+                    tombstone_expiration_ts,
+                )?;
+
+                let num_offers = remove_expired_offers(conn, now, tombstone_expiration_ts)?;
+                let num_unsubscribes = diesel::delete(
+                    market_offer_unsubscribed.filter(unsubscribed::expiration_ts.lt(now)),
+                )
                 .execute(conn)?;
-            Result::<usize, DbError>::Ok(nd)
-        })
-        .await?;
-        if num_deleted > 0 {
-            log::info!("Clean market offers: {} cleaned", num_deleted);
+                let num_tombstones =
+                    diesel::delete(market_offer_tombstone.filter(tombstone::expiration_ts.lt(now)))
+                        .execute(conn)?;
+                Result::<(usize, usize, usize), DbError>::Ok((
+                    num_offers,
+                    num_unsubscribes,
+                    num_tombstones,
+                ))
+            })
+            .await?;
+        if num_offers > 0 {
+            log::info!("Clean market offers: {} cleaned", num_offers);
+        }
+        if num_unsubscribes > 0 {
+            log::info!(
+                "Clean market offers unsubscribes: {} cleaned",
+                num_unsubscribes
+            );
+        }
+        if num_tombstones > 0 {
+            log::debug!("Clean market offers tombstones: {} cleaned", num_tombstones);
         }
-        self.clean_unsubscribes().await?;
         log::debug!("Clean market offers: done");
         Ok(())
     }
 
-    pub async fn clean_unsubscribes(&self) -> DbResult<()> {
-        log::debug!("Clean market offers unsubscribes: start");
-        let num_deleted = do_with_transaction(self.pool, move |conn| {
-            let nd = diesel::delete(
-                market_offer_unsubscribed.filter(unsubscribed::expiration_ts.lt(sql_now)),
-            )
-            .execute(conn)?;
-            Result::<usize, DbError>::Ok(nd)
+    /// Removes expired Offers only, leaving tombstones for `offer_tombstone_ttl`,
+    /// so they are reported as `Expired` and not stored again.
+    pub async fn remove_expired(&self, db_config: &DbConfig) -> DbResult<usize> {
+        let tombstone_ttl = db_config.offer_tombstone_ttl;
+        do_with_transaction(self.pool, move |conn| {
+            let now = Utc::now().naive_utc();
+            remove_expired_offers(conn, now, now + tombstone_ttl)
+        })
+        .await
+    }
+
+    /// Returns counts of Offer store entries and its approximate size.
+    pub async fn stats(&self) -> DbResult<OfferStoreStats> {
+        readonly_transaction(self.pool, move |conn| {
+            let now = Utc::now().naive_utc();
+            let not_unsubscribed =
+                offer::id.ne_all(market_offer_unsubscribed.select(unsubscribed::id));
+
+            let live: i64 = market_offer
+                .filter(offer::expiration_ts.ge(now))
+                .filter(not_unsubscribed.clone())
+                .count()
+                .get_result(conn)?;
+            let expired: i64 = market_offer
+                .filter(offer::expiration_ts.lt(now))
+                .filter(not_unsubscribed)
+                .count()
+                .get_result(conn)?;
+            let unsubscribed: i64 = market_offer_unsubscribed.count().get_result(conn)?;
+            let tombstones: i64 = market_offer_tombstone.count().get_result(conn)?;
+            let memory_bytes: i64 = diesel::select(sql::<diesel::sql_types::BigInt>(
+                "(SELECT page_count FROM pragma_page_count()) * (SELECT page_size FROM pragma_page_size())",
+            ))
+            .get_result(conn)?;
+
+            Ok(OfferStoreStats {
+                live: live as u64,
+                expired: expired as u64,
+                unsubscribed: unsubscribed as u64,
+                tombstones: tombstones as u64,
+                memory_bytes: memory_bytes as u64,
+            })
         })
-        .await?;
-        if num_deleted > 0 {
-            log::info!("Clean market offers unsubscribes: {} cleaned", num_deleted);
+        .await
+    }
+
+    /// Removes expired entries (as `clean` does) and bodies of unsubscribed Offers,
+    /// then reclaims unused memory. Unsubscription markers stay until they expire,
+    /// so unsubscribed Offers are still reported as such. Live Offers are not touched.
+    pub async fn compact(&self, db_config: &DbConfig) -> DbResult<OfferStoreStats> {
+        self.clean(db_config).await?;
+
+        let num_unsubscribed =
+            do_with_transaction(self.pool, move |conn| {
+                Result::<usize, DbError>::Ok(
+                    diesel::delete(market_offer.filter(
+                        offer::id.eq_any(market_offer_unsubscribed.select(unsubscribed::id)),
+                    ))
+                    .execute(conn)?,
+                )
+            })
+            .await?;
+        if num_unsubscribed > 0 {
+            log::info!(
+                "Compact market offers: {} unsubscribed removed",
+                num_unsubscribed
+            );
         }
-        log::debug!("Clean market offers unsubscribes: done");
-        Ok(())
+
+        if let Err(e) = vacuum(self.pool).await {
+            log::warn!("Compact market offers: unable to reclaim memory: {}", e);
+        }
+
+        self.stats().await
+    }
+}
+
+/// Replaces expired Offers with tombstones. Offers, which are unsubscribed,
+/// get their tombstone, when unsubscription marker expires.
+fn remove_expired_offers(
+    conn: &ConnType,
+    now: NaiveDateTime,
+    tombstone_expiration_ts: NaiveDateTime,
+) -> DbResult<usize> {
+    let expired_ids = market_offer
+        .select((offer::id, offer::node_id))
+        .filter(offer::expiration_ts.lt(now))
+        .filter(offer::id.ne_all(market_offer_unsubscribed.select(unsubscribed::id)))
+        .load::<(SubscriptionId, NodeId)>(conn)?;
+    put_tombstones(
+        conn,
+        expired_ids,
+        OfferRemoval::Expired,
+        tombstone_expiration_ts,
+    )?;
+
+    Ok(diesel::delete(market_offer.filter(offer::expiration_ts.lt(now))).execute(conn)?)
+}
+
+fn put_tombstones(
+    conn: &ConnType,
+    ids: Vec<(SubscriptionId, NodeId)>,
+    reason: OfferRemoval,
+    expiration_ts: NaiveDateTime,
+) -> DbResult<()> {
+    for (id, node_id) in ids {
+        diesel::replace_into(market_offer_tombstone)
+            .values(OfferTombstone {
+                id,
+                node_id,
+                reason,
+                insertion_ts: None,
+                expiration_ts,
+            })
+            .execute(conn)?;
     }
+    Ok(())
 }
 
 pub(super) fn query_state(
@@ -284,7 +435,11 @@ pub(super) fn query_state(
     }
 
     Ok(match offer {
-        None => OfferState::NotFound,
+        None => match query_tombstone(conn, id)? {
+            Some(OfferRemoval::Unsubscribed) => OfferState::Unsubscribed(None),
+            Some(OfferRemoval::Expired) => OfferState::Expired(None),
+            None => OfferState::NotFound,
+        },
         Some(offer) => active_or_expired(offer, expiry_validation_ts),
     })
 }
@@ -310,3 +465,11 @@ pub(super) fn is_unsubscribed(conn: &ConnType, id: &SubscriptionId) -> DbResult<
         .optional()?
         .is_some())
 }
+
+fn query_tombstone(conn: &ConnType, id: &SubscriptionId) -> DbResult<Option<OfferRemoval>> {
+    Ok(market_offer_tombstone
+        .select(tombstone::reason)
+        .filter(tombstone::id.eq(&id))
+        .first(conn)
+        .optional()?)
+}
@@ -1,13 +1,15 @@
 use chrono::{NaiveDateTime, TimeZone, Utc};
+use diesel::sql_types::Text;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
 use ya_client::model::{market::Offer as ClientOffer, ErrorMessage, NodeId};
+use ya_diesel_utils::DbTextField;
 use ya_service_api_web::middleware::Identity;
 
 use super::SubscriptionId;
 use crate::db::model::subscription_id::SubscriptionValidationError;
-use crate::db::schema::{market_offer, market_offer_unsubscribed};
+use crate::db::schema::{market_offer, market_offer_tombstone, market_offer_unsubscribed};
 use ya_client::model::market::NewOffer;
 
 #[derive(Clone, Debug, Identifiable, Insertable, Queryable, Deserialize, Serialize)]
@@ -43,6 +45,44 @@ pub struct OfferUnsubscribed {
     pub expiration_ts: NaiveDateTime,
 }
 
+/// Reason of removing Offer from database.
+#[derive(
+    strum_macros::EnumString,
+    DbTextField,
+    derive_more::Display,
+    AsExpression,
+    FromSqlRow,
+    PartialEq,
+    Eq,
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+)]
+#[sql_type = "Text"]
+pub enum OfferRemoval {
+    Unsubscribed,
+    Expired,
+}
+
+/// Keeps track of Offers, that were recently removed from database.
+/// Thanks to this we can tell apart Offers, that never existed, from
+/// those that were unsubscribed or expired.
+/// Tombstone lives for configured period, independently of Offer expiration time.
+#[derive(Clone, Debug, Identifiable, Insertable, Queryable)]
+#[table_name = "market_offer_tombstone"]
+pub struct OfferTombstone {
+    pub id: SubscriptionId,
+    pub node_id: NodeId,
+    pub reason: OfferRemoval,
+
+    /// Timestamp of adding tombstone to database.
+    pub insertion_ts: Option<NaiveDateTime>,
+    /// Time after which tombstone will be removed by cleaner.
+    pub expiration_ts: NaiveDateTime,
+}
+
 impl Offer {
     /// Creates new model offer. If ClientOffer has id already assigned,
     /// it will be ignored and regenerated.
@@ -34,6 +34,17 @@ table! {
     }
 }
 
+table! {
+    market_offer_tombstone (id) {
+        id -> Text,
+        node_id -> Text,
+        reason -> Text,
+
+        insertion_ts -> Nullable<Timestamp>,
+        expiration_ts -> Timestamp,
+    }
+}
+
 table! {
     market_negotiation_event (id) {
         id -> Integer,
@@ -121,7 +132,12 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(market_demand, market_offer, market_offer_unsubscribed);
+allow_tables_to_appear_in_same_query!(
+    market_demand,
+    market_offer,
+    market_offer_unsubscribed,
+    market_offer_tombstone
+);
 allow_tables_to_appear_in_same_query!(market_proposal, market_negotiation);
 allow_tables_to_appear_in_same_query!(market_agreement, market_agreement_event);
 
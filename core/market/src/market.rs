@@ -32,6 +32,7 @@ use crate::db::DbMixedExecutor;
 use ya_service_api_web::scope::ExtendableScope;
 
 pub mod agreement;
+pub mod offer_store;
 
 #[derive(Error, Debug)]
 pub enum MarketError {
@@ -69,6 +70,7 @@ pub struct MarketService {
     pub matcher: Matcher,
     pub provider_engine: ProviderBroker,
     pub requestor_engine: RequestorBroker,
+    pub config: Arc<Config>,
 }
 
 impl MarketService {
@@ -110,8 +112,9 @@ impl MarketService {
             config.clone(),
         )?;
         let cleaner_db = db.clone();
+        let cleaner_config = config.db.clone();
         tokio::spawn(async move {
-            crate::db::dao::cleaner::clean_forever(cleaner_db, config.db.clone()).await;
+            crate::db::dao::cleaner::clean_forever(cleaner_db, cleaner_config).await;
         });
 
         Ok(MarketService {
@@ -119,6 +122,7 @@ impl MarketService {
             matcher,
             provider_engine,
             requestor_engine,
+            config,
         })
     }
 
@@ -135,6 +139,7 @@ impl MarketService {
             .bind_gsb(public_prefix, local_prefix)
             .await?;
         agreement::bind_gsb(self.db.clone(), public_prefix, local_prefix).await;
+        offer_store::bind_gsb(self.db.clone(), self.config.db.clone(), local_prefix).await;
         Ok(())
     }
 
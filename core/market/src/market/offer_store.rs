@@ -0,0 +1,51 @@
+use ya_core_model::market::{
+    CompactOfferStore, GetOfferStoreStats, OfferStoreStats, RpcMessageError,
+};
+use ya_service_bus::typed::ServiceBinder;
+
+use crate::config::DbConfig;
+use crate::db::dao::OfferDao;
+use crate::db::DbMixedExecutor;
+
+/// Maintenance of Offer store, available on local bus only.
+#[derive(Clone)]
+struct OfferStore {
+    db: DbMixedExecutor,
+    config: DbConfig,
+}
+
+pub async fn bind_gsb(db: DbMixedExecutor, config: DbConfig, local_prefix: &str) {
+    log::trace!("Binding market offer store local service to service bus");
+    let store = OfferStore { db, config };
+    ServiceBinder::new(local_prefix, &(), store)
+        .bind_with_processor(move |_, store, _caller: String, _msg: GetOfferStoreStats| {
+            let store = store.clone();
+            async move { store.stats().await }
+        })
+        .bind_with_processor(move |_, store, _caller: String, _msg: CompactOfferStore| {
+            let store = store.clone();
+            async move { store.compact().await }
+        });
+    log::debug!("Successfully bound market offer store local service to service bus");
+}
+
+impl OfferStore {
+    async fn stats(&self) -> Result<OfferStoreStats, RpcMessageError> {
+        self.db
+            .as_dao::<OfferDao>()
+            .stats()
+            .await
+            .map_err(|e| RpcMessageError::Market(e.to_string()))
+    }
+
+    async fn compact(&self) -> Result<OfferStoreStats, RpcMessageError> {
+        let stats = self
+            .db
+            .as_dao::<OfferDao>()
+            .compact(&self.config)
+            .await
+            .map_err(|e| RpcMessageError::Market(e.to_string()))?;
+        log::info!("Market offer store compacted: {:?}", stats);
+        Ok(stats)
+    }
+}
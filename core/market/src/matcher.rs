@@ -19,12 +19,14 @@ use crate::protocol::discovery::{builder::DiscoveryBuilder, Discovery};
 pub(crate) mod cyclic;
 pub mod error;
 pub(crate) mod handlers;
+pub mod import;
 pub(crate) mod resolver;
 pub(crate) mod store;
 
 use crate::db::dao::{DemandDao, DemandState};
 use error::{MatcherError, MatcherInitError, QueryOfferError, QueryOffersError};
 use futures::FutureExt;
+use import::ImportSummary;
 use resolver::Resolver;
 use store::SubscriptionStore;
 
@@ -67,6 +69,7 @@ impl Matcher {
             .add_data_handler(handlers::filter_out_known_offer_ids)
             .add_data_handler(handlers::receive_remote_offers)
             .add_data_handler(handlers::get_local_offers)
+            .add_data_handler(handlers::get_local_offers_with_state)
             .add_data_handler(handlers::receive_remote_offer_unsubscribes)
             .with_config(config.discovery.clone())
             .build();
@@ -108,6 +111,7 @@ impl Matcher {
         // That's why we don't spawn this in Matcher::new.
         tokio::task::spawn_local(cyclic::bcast_offers(self.clone()));
         tokio::task::spawn_local(cyclic::bcast_unsubscribes(self.clone()));
+        tokio::task::spawn_local(cyclic::sweep_expired_offers(self.clone()));
 
         self.bind_expiration_tracker()
             .await
@@ -271,6 +275,22 @@ impl Matcher {
         Ok(())
     }
 
+    /// Seeds the store with Offers from a trusted bulk source.
+    /// See [`import::import_offers`] for details.
+    pub async fn import_offers(
+        &self,
+        offers: Vec<Offer>,
+        progress: impl FnMut(&ImportSummary),
+    ) -> ImportSummary {
+        import::import_offers(
+            self.resolver.clone(),
+            offers,
+            self.config.discovery.offer_import_concurrency,
+            progress,
+        )
+        .await
+    }
+
     pub async fn get_our_active_offer_ids(&self) -> Result<Vec<SubscriptionId>, QueryOffersError> {
         let our_node_ids = self.identity.list().await?;
         Ok(self.store.get_active_offer_ids(Some(our_node_ids)).await?)
@@ -6,6 +6,7 @@ use std::collections::HashSet;
 use std::hash::Hash;
 
 use super::Matcher;
+use crate::db::dao::OfferDao;
 use std::time::Instant;
 
 pub(super) async fn bcast_offers(matcher: Matcher) {
@@ -94,6 +95,29 @@ pub(super) async fn bcast_unsubscribes(matcher: Matcher) {
     }
 }
 
+/// Removes expired Offers, so they are not matched or propagated anymore.
+/// Expired Offers get tombstones just like unsubscribed ones.
+pub(super) async fn sweep_expired_offers(matcher: Matcher) {
+    let mut interval = tokio::time::interval(matcher.config.discovery.offer_expiry_sweep_interval);
+    loop {
+        interval.tick().await;
+        match matcher
+            .store
+            .db
+            .as_dao::<OfferDao>()
+            .remove_expired(&matcher.config.db)
+            .await
+        {
+            Ok(0) => (),
+            Ok(num_expired) => {
+                log::debug!("Removed {} expired Offers.", num_expired);
+                counter!("market.offers.expired.removed", num_expired as u64);
+            }
+            Err(e) => log::warn!("Failed to remove expired Offers. Error: {}", e),
+        }
+    }
+}
+
 /// Returns vector of at most `cap_size` getting all our ids
 /// and random sample from other ids (all ids might include our ids).
 #[allow(dead_code)]
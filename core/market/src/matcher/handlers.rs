@@ -2,11 +2,16 @@
 use futures::StreamExt;
 use metrics::{counter, value};
 
+use std::collections::HashSet;
+
 use crate::db::model::{Offer, SubscriptionId};
-use crate::matcher::error::ModifyOfferError;
+use crate::matcher::error::{ModifyOfferError, QueryOfferError, SaveOfferError};
 use crate::protocol::discovery::{
     error::DiscoveryRemoteError,
-    message::{OffersBcast, OffersRetrieved, RetrieveOffers, UnsubscribedOffersBcast},
+    message::{
+        OfferUnavailable, OffersBcast, OffersRetrieved, RetrieveOffers, RetrieveOffersWithState,
+        RetrievedOffers, UnsubscribedOffersBcast,
+    },
 };
 
 use super::{resolver::Resolver, store::SubscriptionStore};
@@ -16,41 +21,57 @@ pub(super) async fn filter_out_known_offer_ids(
     store: SubscriptionStore,
     _caller: String,
     msg: OffersBcast,
-) -> Result<Vec<SubscriptionId>, ()> {
+) -> Result<Vec<SubscriptionId>, DiscoveryRemoteError> {
     // We shouldn't propagate Offer, if we already have it in our database.
     // Note that when we broadcast our Offer, it will reach us too, so it concerns
     // not only Offers from other nodes.
     Ok(store
         .filter_out_known_offer_ids(msg.offer_ids)
         .await
-        .map_err(|e| log::warn!("Error filtering Offers. Error: {}", e))?)
+        .map_err(|e| {
+            log::warn!("Error filtering Offers. Error: {}", e);
+            DiscoveryRemoteError::InternalError(format!("Error filtering Offers: {}", e))
+        })?)
 }
 
 /// Returns only ids of those from input offers, that was successfully stored locally.
 /// Also triggers Resolver to match newly stored Offers against local Demands.
+/// Offers, which couldn't be stored because of database error, are reported,
+/// but don't prevent returning the stored ones. Fails only if none was stored.
 pub(super) async fn receive_remote_offers(
     resolver: Resolver,
     caller: String,
     msg: OffersRetrieved,
-) -> Result<Vec<SubscriptionId>, ()> {
-    let added_offers_ids = futures::stream::iter(msg.offers.into_iter())
-        .filter_map(|offer| {
-            let resolver = resolver.clone();
-            async move {
-                resolver
-                    .store
-                    .save_offer(offer)
-                    .await
-                    .map(|offer| {
-                        resolver.receive(&offer);
-                        offer.id
-                    })
-                    .map_err(|e| log::info!("Skipping foreign Offer: {}", e))
-                    .ok()
+) -> Result<Vec<SubscriptionId>, DiscoveryRemoteError> {
+    let mut added_offers_ids = Vec::new();
+    let mut store_errors = Vec::new();
+    for offer in msg.offers {
+        match resolver.store.save_offer(offer).await {
+            Ok(offer) => {
+                resolver.receive(&offer);
+                added_offers_ids.push(offer.id);
             }
-        })
-        .collect::<Vec<SubscriptionId>>()
-        .await;
+            Err(e @ SaveOfferError::Save(..)) => store_errors.push(e.to_string()),
+            Err(e) => log::info!("Skipping foreign Offer: {}", e),
+        }
+    }
+
+    if !store_errors.is_empty() {
+        counter!(
+            "market.offers.incoming.store_errors",
+            store_errors.len() as u64
+        );
+        let error = format!(
+            "Failed to store {} Offers from [{}]: {}",
+            store_errors.len(),
+            caller,
+            store_errors.join("; ")
+        );
+        if added_offers_ids.is_empty() {
+            return Err(DiscoveryRemoteError::InternalError(error));
+        }
+        log::warn!("{}", error);
+    }
 
     counter!("market.offers.incoming", added_offers_ids.len() as u64);
     log::trace!(
@@ -82,12 +103,56 @@ pub(super) async fn get_local_offers(
     }
 }
 
+/// Returns requested Offers, that are active. For the rest of them
+/// returns reason, why they are unavailable.
+pub(super) async fn get_local_offers_with_state(
+    store: SubscriptionStore,
+    caller: String,
+    msg: RetrieveOffersWithState,
+) -> Result<RetrievedOffers, DiscoveryRemoteError> {
+    let offer_ids = msg.offer_ids;
+    let msg = RetrieveOffers {
+        offer_ids: offer_ids.clone(),
+    };
+    let mut offers = get_local_offers(store.clone(), caller, msg).await?;
+
+    let found = offers
+        .iter()
+        .map(|offer| offer.id.clone())
+        .collect::<HashSet<_>>();
+    let mut unavailable = Vec::new();
+    for id in offer_ids.into_iter().filter(|id| !found.contains(id)) {
+        let reason = match store.get_offer(&id).await {
+            Err(QueryOfferError::NotFound(..)) => OfferUnavailable::NotFound,
+            Err(QueryOfferError::Unsubscribed(..)) => OfferUnavailable::Unsubscribed,
+            Err(QueryOfferError::Expired(..)) => OfferUnavailable::Expired,
+            // Offer was added in the meantime.
+            Ok(offer) => {
+                offers.push(offer);
+                continue;
+            }
+            Err(e) => {
+                log::error!("Failed to get offer state. Error: {}", e);
+                return Err(DiscoveryRemoteError::InternalError(format!(
+                    "Failed to get offer state from db."
+                )));
+            }
+        };
+        unavailable.push((id, reason));
+    }
+
+    Ok(RetrievedOffers {
+        offers,
+        unavailable,
+    })
+}
+
 /// Returns only those of input offer ids, that were able to be unsubscribed locally.
 pub(super) async fn receive_remote_offer_unsubscribes(
     store: SubscriptionStore,
     caller: String,
     msg: UnsubscribedOffersBcast,
-) -> Result<Vec<SubscriptionId>, ()> {
+) -> Result<Vec<SubscriptionId>, DiscoveryRemoteError> {
     let new_unsubscribes = futures::stream::iter(msg.offer_ids.into_iter())
         .filter_map(|offer_id| {
             let store = store.clone();
@@ -0,0 +1,123 @@
+//! Bulk import of Offers from a trusted source, for example a snapshot
+//! taken on another node, to avoid waiting for broadcasts on cold start.
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use metrics::counter;
+use std::collections::HashSet;
+
+use crate::db::model::Offer;
+use crate::matcher::error::SaveOfferError;
+
+use super::resolver::Resolver;
+
+/// Tally of a bulk Offer import.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    /// Number of Offers in the batch
+    pub total: usize,
+    pub accepted: usize,
+    /// Offers repeated in the batch, already known or already unsubscribed
+    pub duplicates: usize,
+    pub expired: usize,
+    /// Offers, which id doesn't match their content
+    pub invalid: usize,
+    /// Offers, which couldn't be stored
+    pub failed: usize,
+}
+
+impl ImportSummary {
+    pub fn rejected(&self) -> usize {
+        self.duplicates + self.expired + self.invalid + self.failed
+    }
+
+    pub fn processed(&self) -> usize {
+        self.accepted + self.rejected()
+    }
+}
+
+enum Outcome {
+    Accepted,
+    Duplicate,
+    Expired,
+    Invalid,
+    Failed,
+}
+
+/// Validates and stores Offers, at most `concurrency` of them at the same time.
+/// `progress` is called after each processed Offer.
+///
+/// Imported Offers are matched against local Demands, but they are not broadcast.
+pub(super) async fn import_offers(
+    resolver: Resolver,
+    offers: Vec<Offer>,
+    concurrency: usize,
+    mut progress: impl FnMut(&ImportSummary),
+) -> ImportSummary {
+    let now = Utc::now().naive_utc();
+    let mut summary = ImportSummary {
+        total: offers.len(),
+        ..Default::default()
+    };
+
+    let mut seen = HashSet::new();
+    let mut pending = Vec::with_capacity(offers.len());
+    for offer in offers {
+        if !seen.insert(offer.id.clone()) {
+            summary.duplicates += 1;
+        } else if offer.expiration_ts <= now {
+            summary.expired += 1;
+        } else {
+            pending.push(offer);
+        }
+    }
+    if summary.processed() > 0 {
+        progress(&summary);
+    }
+
+    let mut outcomes = stream::iter(pending)
+        .map(|offer| import_offer(resolver.clone(), offer))
+        .buffer_unordered(concurrency.max(1));
+
+    while let Some(outcome) = outcomes.next().await {
+        match outcome {
+            Outcome::Accepted => summary.accepted += 1,
+            Outcome::Duplicate => summary.duplicates += 1,
+            Outcome::Expired => summary.expired += 1,
+            Outcome::Invalid => summary.invalid += 1,
+            Outcome::Failed => summary.failed += 1,
+        }
+        progress(&summary);
+    }
+
+    counter!("market.offers.imported", summary.accepted as u64);
+    counter!("market.offers.import.rejected", summary.rejected() as u64);
+    log::info!(
+        "Imported {}/{} Offers. Rejected: {} duplicated, {} expired, {} invalid, {} failed.",
+        summary.accepted,
+        summary.total,
+        summary.duplicates,
+        summary.expired,
+        summary.invalid,
+        summary.failed
+    );
+    summary
+}
+
+async fn import_offer(resolver: Resolver, offer: Offer) -> Outcome {
+    match resolver.store.save_offer(offer).await {
+        Ok(offer) => {
+            resolver.receive(&offer);
+            Outcome::Accepted
+        }
+        Err(SaveOfferError::Exists(_)) | Err(SaveOfferError::Unsubscribed(_)) => Outcome::Duplicate,
+        Err(SaveOfferError::Expired(_)) => Outcome::Expired,
+        Err(e @ SaveOfferError::SubscriptionValidation(_)) => {
+            log::debug!("Skipping imported Offer: {}", e);
+            Outcome::Invalid
+        }
+        Err(e) => {
+            log::warn!("Failed to import Offer: {}", e);
+            Outcome::Failed
+        }
+    }
+}
@@ -88,3 +88,24 @@ where
     T: Future<Output = CallbackResult<MsgType>> + Send + Sync + 'static,
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::discovery::{error::DiscoveryRemoteError, message::OffersRetrieved};
+
+    #[actix_rt::test]
+    async fn handler_errors_are_returned_to_caller() {
+        let slot = HandlerSlot::new(|_caller: String, _msg: OffersRetrieved| async {
+            Err(DiscoveryRemoteError::InternalError("db locked".to_string()))
+        });
+
+        let result = slot
+            .call("caller".to_string(), OffersRetrieved { offers: vec![] })
+            .await;
+        match result {
+            Err(DiscoveryRemoteError::InternalError(e)) => assert_eq!(e, "db locked"),
+            Ok(_) => panic!("handler error was dropped"),
+        }
+    }
+}
@@ -9,10 +9,10 @@ use tokio::time::sleep;
 
 use ya_client::model::NodeId;
 use ya_core_model::market::BUS_ID;
-use ya_core_model::net::local::{BroadcastMessage, SendBroadcastMessage};
+use ya_core_model::net::local::{self as local_net, BroadcastMessage, SendBroadcastMessage};
 use ya_net::{self as net, RemoteEndpoint};
 use ya_service_bus::timeout::{IntoDuration, IntoTimeoutFuture};
-use ya_service_bus::typed::ServiceBinder;
+use ya_service_bus::typed::{self as bus, ServiceBinder};
 use ya_service_bus::{Error as BusError, RpcEndpoint, RpcMessage};
 
 use super::callback::HandlerSlot;
@@ -20,13 +20,23 @@ use crate::config::DiscoveryConfig;
 use crate::db::model::{Offer as ModelOffer, SubscriptionId};
 use crate::identity::{IdentityApi, IdentityError};
 
+pub mod backoff;
 pub mod builder;
 pub mod error;
+pub mod fanout;
+pub mod latency;
 pub mod message;
+pub mod rate;
+pub mod seen;
 
 use crate::PROTOCOL_VERSION;
+use backoff::{BackoffPolicy, PeerBackoff};
 use error::*;
+use fanout::FanoutOrder;
+use latency::DiscoveryLatency;
 use message::*;
+use rate::BroadcastRate;
+use seen::SeenOffers;
 
 const MAX_OFFER_IDS_PER_BROADCAST: usize = 8;
 
@@ -51,9 +61,18 @@ pub struct DiscoveryImpl {
 
     offer_handlers: Mutex<OfferHandlers>,
     get_local_offers_handler: HandlerSlot<RetrieveOffers>,
+    /// Without it, reasons why Offers weren't returned are not reported.
+    get_offers_with_state_handler: Option<HandlerSlot<RetrieveOffersWithState>>,
     offer_unsubscribe_handler: HandlerSlot<UnsubscribedOffersBcast>,
 
     config: DiscoveryConfig,
+    backoff: BackoffPolicy,
+    peer_backoff: PeerBackoff,
+    fanout: FanoutOrder,
+    fanout_size: Option<u32>,
+    latency: DiscoveryLatency,
+    seen_offers: SeenOffers,
+    broadcast_rate: Option<BroadcastRate>,
 }
 
 impl Discovery {
@@ -130,13 +149,56 @@ impl Discovery {
             let mut iter = offer_ids.into_iter().peekable();
             while iter.peek().is_some() {
                 let chunk = iter.by_ref().take(MAX_OFFER_IDS_PER_BROADCAST).collect();
-                broadcast_offers(default_id, chunk).await;
+                self.acquire_broadcast().await;
+                let targets = self.fanout_targets().await;
+                broadcast_offers(default_id, chunk, targets).await;
             }
         } else {
-            broadcast_offers(default_id, offer_ids).await;
+            self.acquire_broadcast().await;
+            broadcast_offers(default_id, offer_ids, self.fanout_targets().await).await;
         }
     }
 
+    /// Peers picked by the fanout order among reachable ones.
+    async fn fanout_targets(&self) -> Targets {
+        let fanout_size = self.inner.fanout_size;
+        if !self.inner.fanout.is_seeded() {
+            return Targets::Random(fanout_size);
+        }
+        let peers = match bus::service(local_net::BUS_ID)
+            .send(local_net::ReachablePeers {})
+            .await
+        {
+            Ok(Ok(peers)) => peers.into_iter().map(|peer| peer.node_id).collect(),
+            _ => return Targets::Random(fanout_size),
+        };
+        match self.inner.fanout.targets(peers, fanout_size) {
+            Some(peers) if !peers.is_empty() => Targets::Peers(peers),
+            _ => Targets::Random(fanout_size),
+        }
+    }
+
+    async fn acquire_broadcast(&self) {
+        if let Some(rate) = &self.inner.broadcast_rate {
+            rate.acquire().await;
+        }
+    }
+
+    /// Backoff policy used by all retryable operations.
+    pub fn backoff(&self) -> &BackoffPolicy {
+        &self.inner.backoff
+    }
+
+    /// Number of peers each broadcast is sent to. `None` means net default.
+    pub fn fanout(&self) -> Option<u32> {
+        self.inner.fanout_size
+    }
+
+    /// Latencies of retrieving Offers from peers and storing them.
+    pub fn latency(&self) -> &DiscoveryLatency {
+        &self.inner.latency
+    }
+
     /// Ask remote Node for specified Offers.
     pub async fn get_remote_offers(
         &self,
@@ -147,22 +209,62 @@ impl Discovery {
         let target_node = NodeId::from_str(&target_node_id)
             .map_err(|e| DiscoveryError::InternalError(e.to_string()))?;
 
-        Ok(net::from(self.default_identity().await?)
+        let start = Instant::now();
+        let offers = net::from(self.default_identity().await?)
             .to(target_node)
             .service(&get_offers_addr(BUS_ID))
             .send(RetrieveOffers { offer_ids })
             .timeout(Some(timeout))
-            .map_err(|_| {
-                DiscoveryError::GsbError(
-                    BusError::Timeout(format!(
-                        "{}/{}",
-                        get_offers_addr(BUS_ID),
-                        RetrieveOffers::ID
-                    ))
-                    .to_string(),
-                )
+            .map_err(|_| retrieve_timeout::<RetrieveOffers>())
+            .await???;
+        self.inner
+            .latency
+            .record_retrieve(&target_node.to_string(), start.elapsed());
+        Ok(offers)
+    }
+
+    /// Ask remote Node for specified Offers. Offers, that remote Node
+    /// couldn't return, are listed in `RetrievedOffers::unavailable` with a reason.
+    /// Nodes, which don't support it, are asked with `get_remote_offers`
+    /// and report no reasons.
+    pub async fn get_remote_offers_with_state(
+        &self,
+        target_node_id: String,
+        offer_ids: Vec<SubscriptionId>,
+        timeout: impl IntoDuration,
+    ) -> Result<RetrievedOffers, DiscoveryError> {
+        let target_node = NodeId::from_str(&target_node_id)
+            .map_err(|e| DiscoveryError::InternalError(e.to_string()))?;
+        let timeout = timeout.into_duration();
+
+        let start = Instant::now();
+        let result = net::from(self.default_identity().await?)
+            .to(target_node)
+            .service(&get_offers_addr(BUS_ID))
+            .send(RetrieveOffersWithState {
+                offer_ids: offer_ids.clone(),
             })
-            .await???)
+            .timeout(Some(timeout))
+            .map_err(|_| retrieve_timeout::<RetrieveOffersWithState>())
+            .await?;
+        let retrieved = match result {
+            Err(BusError::NoEndpoint(_)) => {
+                log::trace!(
+                    "[{}] doesn't report Offers state. Falling back to `{}`.",
+                    target_node,
+                    RetrieveOffers::ID
+                );
+                return Ok(self
+                    .get_remote_offers(target_node_id, offer_ids, timeout)
+                    .await?
+                    .into());
+            }
+            result => result??,
+        };
+        self.inner
+            .latency
+            .record_retrieve(&target_node.to_string(), start.elapsed());
+        Ok(retrieved)
     }
 
     pub async fn bcast_unsubscribes(
@@ -228,10 +330,11 @@ impl Discovery {
             let mut iter = offer_ids.into_iter().peekable();
             while iter.peek().is_some() {
                 let chunk = iter.by_ref().take(MAX_OFFER_IDS_PER_BROADCAST).collect();
-                broadcast_unsubscribed(default_id, chunk).await;
+                let targets = self.fanout_targets().await;
+                broadcast_unsubscribed(default_id, chunk, targets).await;
             }
         } else {
-            broadcast_unsubscribed(default_id, offer_ids).await;
+            broadcast_unsubscribed(default_id, offer_ids, self.fanout_targets().await).await;
         }
     }
 
@@ -248,6 +351,12 @@ impl Discovery {
                 myself.on_get_remote_offers(caller, msg)
             },
         );
+        ServiceBinder::new(&get_offers_addr(public_prefix), &(), self.clone()).bind_with_processor(
+            move |_, myself, caller: String, msg: RetrieveOffersWithState| {
+                let myself = myself.clone();
+                myself.on_get_remote_offers_with_state(caller, msg)
+            },
+        );
         // Subscribe to offer broadcasts.
         {
             let mut prefix_guard = self.inner.lazy_binder_prefix.lock().await;
@@ -332,16 +441,47 @@ impl Discovery {
             let filter_out_known_ids = offer_handlers.filter_out_known_ids.clone();
             let receive_remote_offers = offer_handlers.receive_remote_offers.clone();
 
-            let unknown_offer_ids = filter_out_known_ids.call(caller.clone(), msg).await?;
+            let unknown_offer_ids = filter_out_known_ids
+                .call(caller.clone(), msg)
+                .await
+                .map_err(|e| log::warn!("Failed to filter Offers from [{}]: {}", &caller, e))?;
+
+            if let Some(remaining) = self.inner.peer_backoff.remaining(&caller) {
+                // Offers will come again with cyclic broadcasts.
+                log::trace!(
+                    "Not asking [{}] for Offers for next {:?} after failures.",
+                    &caller,
+                    remaining
+                );
+                counter!("market.offers.incoming.backoff", 1);
+                return Ok(());
+            }
 
             if !unknown_offer_ids.is_empty() {
                 let start_remote = Instant::now();
-                let offers = self
-                    .get_remote_offers(caller.clone(), unknown_offer_ids, 3)
+                let RetrievedOffers {
+                    offers,
+                    unavailable,
+                } = self
+                    .backoff()
+                    .retry_if(DiscoveryError::is_transient, |_| {
+                        self.get_remote_offers_with_state(
+                            caller.clone(),
+                            unknown_offer_ids.clone(),
+                            3,
+                        )
+                    })
                     .await
                     .map_err(|e| {
-                        log::debug!("Can't get Offers from [{}]. Error: {}", &caller, e)
+                        let delay = self.inner.peer_backoff.failure(&caller);
+                        log::debug!(
+                            "Can't get Offers from [{}]. Backing off for {:?}. Error: {}",
+                            &caller,
+                            delay,
+                            e
+                        )
                     })?;
+                self.inner.peer_backoff.success(&caller);
                 let end_remote = Instant::now();
                 timing!(
                     "market.offers.incoming.get_remote.time",
@@ -349,16 +489,35 @@ impl Discovery {
                     end_remote
                 );
 
+                if !unavailable.is_empty() {
+                    counter!(
+                        "market.offers.incoming.unavailable",
+                        unavailable.len() as u64
+                    );
+                    log::trace!(
+                        "[{}] couldn't return {} Offers: {:?}",
+                        &caller,
+                        unavailable.len(),
+                        unavailable
+                    );
+                }
+
                 // We still could fail to add some Offers to database. If we fail to add them, we don't
                 // want to propagate subscription further.
-                receive_remote_offers
+                let received = Instant::now();
+                let stored = receive_remote_offers
                     .call(caller.clone(), OffersRetrieved { offers })
-                    .await?
+                    .await
+                    .map_err(|e| log::warn!("Failed to store Offers from [{}]: {}", &caller, e))?;
+                self.inner.latency.record_store(&caller, received.elapsed());
+                stored
             } else {
                 vec![]
             }
         };
 
+        // Offers can reach us by many paths. Don't propagate the same Offer again.
+        let new_offer_ids = self.inner.seen_offers.filter_unseen(new_offer_ids);
         if self.re_broadcast_enabled() && !new_offer_ids.is_empty() {
             log::trace!(
                 "Propagating {}/{} Offers received from [{}].",
@@ -389,6 +548,28 @@ impl Discovery {
         Ok(get_local_offers.call(caller, msg).await?)
     }
 
+    async fn on_get_remote_offers_with_state(
+        self,
+        caller: String,
+        msg: RetrieveOffersWithState,
+    ) -> Result<RetrievedOffers, DiscoveryRemoteError> {
+        let handler = match self.inner.get_offers_with_state_handler.clone() {
+            Some(handler) => handler,
+            None => {
+                let msg = RetrieveOffers {
+                    offer_ids: msg.offer_ids,
+                };
+                return Ok(self.on_get_remote_offers(caller, msg).await?.into());
+            }
+        };
+        log::trace!(
+            "[{}] asks for {} Offers with state.",
+            &caller,
+            msg.offer_ids.len()
+        );
+        Ok(handler.call(caller, msg).await?)
+    }
+
     async fn on_bcast_unsubscribes(
         self,
         caller: String,
@@ -406,7 +587,10 @@ impl Discovery {
         }
 
         let offer_unsubscribe_handler = self.inner.offer_unsubscribe_handler.clone();
-        let unsubscribed_offer_ids = offer_unsubscribe_handler.call(caller.clone(), msg).await?;
+        let unsubscribed_offer_ids = offer_unsubscribe_handler
+            .call(caller.clone(), msg)
+            .await
+            .map_err(|e| log::warn!("Failed to unsubscribe Offers from [{}]: {}", &caller, e))?;
 
         if self.re_broadcast_enabled() && !unsubscribed_offer_ids.is_empty() {
             log::trace!(
@@ -431,15 +615,39 @@ impl Discovery {
     }
 }
 
-async fn broadcast_offers(node_id: NodeId, offer_ids: Vec<SubscriptionId>) {
-    if let Err(e) = net::broadcast(node_id, OffersBcast { offer_ids }).await {
+fn retrieve_timeout<M: RpcMessage>() -> DiscoveryError {
+    BusError::Timeout(format!("{}/{}", get_offers_addr(BUS_ID), M::ID)).into()
+}
+
+/// Peers a broadcast is sent to.
+enum Targets {
+    /// Random peers chosen by the net.
+    Random(Option<u32>),
+    Peers(Vec<NodeId>),
+}
+
+async fn broadcast<M: BroadcastMessage + Send + Sync + Unpin + 'static>(
+    node_id: NodeId,
+    msg: M,
+    targets: Targets,
+) -> Result<(), BusError> {
+    match targets {
+        Targets::Random(fanout) => net::broadcast_with_fanout(node_id, msg, fanout).await,
+        Targets::Peers(peers) => net::broadcast_to_peers(node_id, msg, peers).await,
+    }
+    .map(|_| ())
+}
+
+async fn broadcast_offers(node_id: NodeId, offer_ids: Vec<SubscriptionId>, targets: Targets) {
+    if let Err(e) = broadcast(node_id, OffersBcast { offer_ids }, targets).await {
         log::error!("Error broadcasting offers: {:?}", e);
         counter!("market.offers.broadcasts.net_errors", 1);
     };
 }
 
-async fn broadcast_unsubscribed(node_id: NodeId, offer_ids: Vec<SubscriptionId>) {
-    if let Err(e) = net::broadcast(node_id, UnsubscribedOffersBcast { offer_ids }).await {
+async fn broadcast_unsubscribed(node_id: NodeId, offer_ids: Vec<SubscriptionId>, targets: Targets) {
+    let msg = UnsubscribedOffersBcast { offer_ids };
+    if let Err(e) = broadcast(node_id, msg, targets).await {
         log::error!("Error broadcasting unsubscribed offers: {:?}", e);
         counter!("market.offers.unsubscribes.broadcasts.net_errors", 1);
     };
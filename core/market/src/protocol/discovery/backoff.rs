@@ -0,0 +1,229 @@
+//! Retry policy shared by all retryable discovery operations.
+use rand::Rng;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Exponential backoff applied between consecutive attempts of
+/// retryable discovery operations (e.g. `RetrieveOffers`).
+#[derive(Clone, Debug)]
+pub struct BackoffPolicy {
+    /// Delay before the first retry.
+    pub base: Duration,
+    /// Factor by which delay grows after each failed retry.
+    pub multiplier: f64,
+    /// Upper bound for a single delay (before jitter is applied).
+    pub cap: Duration,
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Fraction of delay (0.0 - 1.0) randomly added or subtracted.
+    pub jitter: f64,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            base: Duration::from_millis(500),
+            multiplier: 2.0,
+            cap: Duration::from_secs(10),
+            max_attempts: 3,
+            jitter: 0.1,
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// Delay without jitter, before retry number `retry` (counted from 1).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exp = retry.saturating_sub(1) as i32;
+        let delay = self.base.as_secs_f64() * self.multiplier.powi(exp);
+        Duration::from_secs_f64(delay.min(self.cap.as_secs_f64()))
+    }
+
+    fn jittered_delay(&self, retry: u32) -> Duration {
+        let delay = self.delay(retry);
+        let jitter = self.jitter.max(0.0).min(1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        let factor = rand::thread_rng().gen_range((1.0 - jitter)..=(1.0 + jitter));
+        delay.mul_f64(factor)
+    }
+
+    /// Runs `operation` until it succeeds or `max_attempts` is exhausted.
+    /// Closure gets attempt number (counted from 1). Returns last error on failure.
+    pub async fn retry<T, E, F, Fut>(&self, operation: F) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.retry_if(|_| true, operation).await
+    }
+
+    /// Like `retry`, but gives up immediately on errors, for which
+    /// `retryable` returns false.
+    pub async fn retry_if<T, E, R, F, Fut>(&self, retryable: R, mut operation: F) -> Result<T, E>
+    where
+        R: Fn(&E) -> bool,
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let max_attempts = self.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match operation(attempt).await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= max_attempts || !retryable(&e) => return Err(e),
+                Err(_) => {
+                    let delay = self.jittered_delay(attempt);
+                    log::trace!(
+                        "Discovery operation attempt {} failed. Retrying in {:?}.",
+                        attempt,
+                        delay
+                    );
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Backoff of peers, which failed to return Offers. Peer isn't asked for Offers
+/// again until its delay elapses; delay grows with consecutive failures and is
+/// reset on success. Jitter spreads retries of many nodes asking the same peer.
+/// Peers, whose backoff expired more than `cap` ago, are forgotten.
+pub struct PeerBackoff {
+    policy: BackoffPolicy,
+    peers: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+impl PeerBackoff {
+    pub fn new(policy: BackoffPolicy) -> Self {
+        PeerBackoff {
+            policy,
+            peers: Default::default(),
+        }
+    }
+
+    /// Time left until `peer` can be asked again, if it is backed off.
+    pub fn remaining(&self, peer: &str) -> Option<Duration> {
+        let peers = self.peers.lock().unwrap();
+        let (_, until) = peers.get(peer)?;
+        until.checked_duration_since(Instant::now())
+    }
+
+    /// Records failure and returns delay, for which `peer` is backed off.
+    pub fn failure(&self, peer: &str) -> Duration {
+        let mut peers = self.peers.lock().unwrap();
+        let now = Instant::now();
+        let cap = self.policy.cap;
+        peers.retain(|_, (_, until)| *until + cap > now);
+
+        let (failures, until) = peers.entry(peer.to_string()).or_insert((0, Instant::now()));
+        *failures += 1;
+        let delay = self.policy.jittered_delay(*failures);
+        *until = Instant::now() + delay;
+        delay
+    }
+
+    pub fn success(&self, peer: &str) {
+        self.peers.lock().unwrap().remove(peer);
+    }
+}
+
+impl Default for PeerBackoff {
+    fn default() -> Self {
+        PeerBackoff::new(BackoffPolicy {
+            base: Duration::from_secs(5),
+            multiplier: 2.0,
+            cap: Duration::from_secs(300),
+            max_attempts: 1,
+            jitter: 0.5,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn peer_backoff_grows_until_success() {
+        let backoff = PeerBackoff::new(BackoffPolicy {
+            base: Duration::from_secs(10),
+            multiplier: 2.0,
+            cap: Duration::from_secs(30),
+            max_attempts: 1,
+            jitter: 0.0,
+        });
+        assert!(backoff.remaining("peer").is_none());
+
+        assert_eq!(backoff.failure("peer"), Duration::from_secs(10));
+        assert_eq!(backoff.failure("peer"), Duration::from_secs(20));
+        assert_eq!(backoff.failure("peer"), Duration::from_secs(30));
+        assert_eq!(backoff.failure("peer"), Duration::from_secs(30));
+        assert!(backoff.remaining("peer").unwrap() > Duration::from_secs(20));
+        assert!(backoff.remaining("other").is_none());
+
+        backoff.success("peer");
+        assert!(backoff.remaining("peer").is_none());
+        assert_eq!(backoff.failure("peer"), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn peer_backoff_forgets_expired_peers() {
+        let backoff = PeerBackoff::new(BackoffPolicy {
+            base: Duration::from_millis(10),
+            multiplier: 1.0,
+            cap: Duration::from_millis(10),
+            max_attempts: 1,
+            jitter: 0.0,
+        });
+        backoff.failure("peer");
+        backoff.failure("other");
+        backoff.success("other");
+        assert_eq!(backoff.peers.lock().unwrap().len(), 1);
+
+        std::thread::sleep(Duration::from_millis(30));
+        backoff.failure("another");
+        let peers = backoff.peers.lock().unwrap();
+        assert!(!peers.contains_key("peer"));
+        assert!(peers.contains_key("another"));
+    }
+
+    #[actix_rt::test]
+    async fn retry_if_gives_up_on_permanent_errors() {
+        use crate::protocol::discovery::error::DiscoveryError;
+        use std::cell::Cell;
+
+        let policy = BackoffPolicy {
+            base: Duration::from_millis(1),
+            multiplier: 1.0,
+            cap: Duration::from_millis(1),
+            max_attempts: 5,
+            jitter: 0.0,
+        };
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = policy
+            .retry_if(DiscoveryError::is_transient, |_| {
+                attempts.set(attempts.get() + 1);
+                async { Err(DiscoveryError::Timeout("test".into())) }
+            })
+            .await;
+        assert!(matches!(result, Err(DiscoveryError::Timeout(_))));
+        assert_eq!(attempts.get(), 1);
+
+        attempts.set(0);
+        let result: Result<(), _> = policy
+            .retry_if(DiscoveryError::is_transient, |_| {
+                attempts.set(attempts.get() + 1);
+                async { Err(DiscoveryError::GsbError("test".into())) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 5);
+    }
+}
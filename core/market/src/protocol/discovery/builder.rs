@@ -1,11 +1,16 @@
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 use crate::protocol::callback::{CallbackFuture, OutputFuture};
 use crate::protocol::callback::{CallbackHandler, CallbackMessage, HandlerSlot};
 
+use super::backoff::{BackoffPolicy, PeerBackoff};
+use super::fanout::FanoutOrder;
+use super::rate::BroadcastRate;
+use super::seen::SeenOffers;
 use super::{Discovery, DiscoveryImpl};
 use crate::config::DiscoveryConfig;
 use crate::protocol::discovery::OfferHandlers;
@@ -15,6 +20,12 @@ pub struct DiscoveryBuilder {
     data: HashMap<TypeId, Box<dyn Any>>,
     handlers: HashMap<TypeId, Box<dyn Any>>,
     config: Option<DiscoveryConfig>,
+    backoff: Option<BackoffPolicy>,
+    peer_backoff: Option<BackoffPolicy>,
+    fanout: Option<FanoutOrder>,
+    fanout_size: Option<u32>,
+    seen_offers: Option<SeenOffers>,
+    broadcast_rate: Option<u32>,
 }
 
 impl DiscoveryBuilder {
@@ -49,6 +60,11 @@ impl DiscoveryBuilder {
         *(boxed as Box<dyn Any + 'static>).downcast().unwrap()
     }
 
+    fn get_optional_handler<M: CallbackMessage>(&mut self) -> Option<HandlerSlot<M>> {
+        let boxed = self.handlers.remove(&TypeId::of::<M>())?;
+        Some(*(boxed as Box<dyn Any + 'static>).downcast().unwrap())
+    }
+
     fn get_data<T: Clone + Send + Sync + 'static>(&mut self) -> T {
         let boxed = self
             .data
@@ -64,6 +80,48 @@ impl DiscoveryBuilder {
         self
     }
 
+    /// Backoff for retryable operations. Defaults to `BackoffPolicy::default()`.
+    pub fn with_backoff(mut self, backoff: BackoffPolicy) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Backoff of peers, which failed to return Offers. Defaults to `PeerBackoff::default()`.
+    pub fn with_peer_backoff(mut self, backoff: BackoffPolicy) -> Self {
+        self.peer_backoff = Some(backoff);
+        self
+    }
+
+    /// Choice of peers broadcasts are sent to. Defaults to `FanoutOrder::as_is()`.
+    pub fn with_fanout_order(mut self, fanout: FanoutOrder) -> Self {
+        self.fanout = Some(fanout);
+        self
+    }
+
+    /// Cache of `capacity` Offers, which aren't propagated again for `ttl`.
+    /// Defaults to `SeenOffers::default()`.
+    pub fn with_seen_offers_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.seen_offers = Some(SeenOffers::new(capacity, ttl));
+        self
+    }
+
+    /// Limit of Offer broadcasts sent per second. Broadcasts are not limited by default.
+    pub fn with_broadcast_rate(mut self, per_second: u32) -> Self {
+        self.broadcast_rate = Some(per_second);
+        self
+    }
+
+    /// Sends each broadcast to `k` random peers only, instead of all peers
+    /// reached by net broadcast (`YA_NET_BROADCAST_SIZE`). Receiving peers
+    /// re-broadcast new Offers, so they still reach the whole network, but
+    /// it takes more hops and Offers lost on the way arrive only with next
+    /// cyclic broadcast. Smaller `k` trades propagation time for traffic.
+    /// Central Net ignores this setting.
+    pub fn with_fanout(mut self, k: u32) -> Self {
+        self.fanout_size = Some(k);
+        self
+    }
+
     pub fn build(mut self) -> Discovery {
         let offer_handlers = Mutex::new(OfferHandlers {
             filter_out_known_ids: self.get_handler(),
@@ -78,8 +136,16 @@ impl DiscoveryBuilder {
                 unsub_queue: Mutex::new(vec![]),
                 lazy_binder_prefix: Mutex::new(None),
                 get_local_offers_handler: self.get_handler(),
+                get_offers_with_state_handler: self.get_optional_handler(),
                 offer_unsubscribe_handler: self.get_handler(),
                 config: self.config.unwrap(),
+                backoff: self.backoff.unwrap_or_default(),
+                peer_backoff: self.peer_backoff.map(PeerBackoff::new).unwrap_or_default(),
+                fanout: self.fanout.unwrap_or_default(),
+                fanout_size: self.fanout_size,
+                latency: Default::default(),
+                seen_offers: self.seen_offers.unwrap_or_default(),
+                broadcast_rate: self.broadcast_rate.map(BroadcastRate::new),
             }),
         }
     }
@@ -104,6 +170,7 @@ impl<
 #[cfg(test)]
 mod test {
     use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::time::{Duration, Instant};
 
     use crate::testing::mock_identity::{generate_identity, MockIdentity};
     use crate::testing::mock_offer::sample_retrieve_offers;
@@ -158,6 +225,22 @@ mod test {
             .build();
     }
 
+    #[test]
+    fn build_with_fanout_should_limit_broadcasts() {
+        let builder = || {
+            DiscoveryBuilder::default()
+                .add_data(MockIdentity::new("test") as Arc<dyn IdentityApi>)
+                .add_handler(|_, _: OffersRetrieved| async { Ok(vec![]) })
+                .add_handler(|_, _: UnsubscribedOffersBcast| async { Ok(vec![]) })
+                .add_handler(|_, _: OffersBcast| async { Ok(vec![]) })
+                .add_handler(|_, _: RetrieveOffers| async { Ok(vec![]) })
+                .with_config(Config::from_env().unwrap().discovery)
+        };
+
+        assert_eq!(builder().build().fanout(), None);
+        assert_eq!(builder().with_fanout(3).build().fanout(), Some(3));
+    }
+
     #[test]
     fn build_from_with_mixed_handlers_should_pass() {
         DiscoveryBuilder::default()
@@ -208,4 +291,44 @@ mod test {
         // then
         assert_eq!(7, counter.load(SeqCst));
     }
+
+    #[actix_rt::test]
+    async fn build_with_custom_backoff_should_respect_retry_timing() {
+        let policy = BackoffPolicy {
+            base: Duration::from_millis(20),
+            multiplier: 2.0,
+            cap: Duration::from_millis(50),
+            max_attempts: 4,
+            jitter: 0.0,
+        };
+        let discovery = DiscoveryBuilder::default()
+            .add_data(MockIdentity::new("test") as Arc<dyn IdentityApi>)
+            .add_handler(|_, _: OffersRetrieved| async { Ok(vec![]) })
+            .add_handler(|_, _: UnsubscribedOffersBcast| async { Ok(vec![]) })
+            .add_handler(|_, _: OffersBcast| async { Ok(vec![]) })
+            .add_handler(|_, _: RetrieveOffers| async { Ok(vec![]) })
+            .with_config(Config::from_env().unwrap().discovery)
+            .with_backoff(policy)
+            .build();
+
+        let backoff = discovery.backoff();
+        assert_eq!(backoff.delay(1), Duration::from_millis(20));
+        assert_eq!(backoff.delay(2), Duration::from_millis(40));
+        assert_eq!(backoff.delay(3), Duration::from_millis(50));
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let start = Instant::now();
+        let result: Result<(), ()> = backoff
+            .retry(|_| {
+                attempts.fetch_add(1, SeqCst);
+                async { Err(()) }
+            })
+            .await;
+        let elapsed = start.elapsed();
+
+        assert!(result.is_err());
+        assert_eq!(4, attempts.load(SeqCst));
+        assert!(elapsed >= Duration::from_millis(110));
+        assert!(elapsed < Duration::from_millis(1000));
+    }
 }
@@ -12,6 +12,10 @@ pub enum DiscoveryError {
     RemoteError(#[from] DiscoveryRemoteError),
     #[error("Failed to broadcast caused by gsb error: {0}.")]
     GsbError(String),
+    #[error("Timeout: {0}.")]
+    Timeout(String),
+    #[error("Peer unreachable: {0}.")]
+    Unreachable(String),
     #[error("Internal error: {0}.")]
     InternalError(String),
     #[error(transparent)]
@@ -45,9 +49,30 @@ impl DiscoveryInitError {
     }
 }
 
+impl DiscoveryError {
+    /// Errors worth retrying right away. Timeouts and unreachable peers
+    /// are left to `PeerBackoff` instead.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DiscoveryError::RemoteError(_) | DiscoveryError::GsbError(_) => true,
+            _ => false,
+        }
+    }
+}
+
 impl From<ya_service_bus::error::Error> for DiscoveryError {
     fn from(e: ya_service_bus::error::Error) -> Self {
-        DiscoveryError::GsbError(e.to_string())
+        use ya_service_bus::error::Error as BusError;
+
+        match e {
+            BusError::Timeout(_) | BusError::ConnectionTimeout(_) => {
+                DiscoveryError::Timeout(e.to_string())
+            }
+            BusError::Closed(_) | BusError::NoEndpoint(_) | BusError::ConnectionFail(_, _) => {
+                DiscoveryError::Unreachable(e.to_string())
+            }
+            e => DiscoveryError::GsbError(e.to_string()),
+        }
     }
 }
 
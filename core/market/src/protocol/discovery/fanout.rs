@@ -0,0 +1,88 @@
+//! Choice of peers Offers are fanned out to in discovery broadcasts.
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::sync::Mutex;
+
+use ya_client::model::NodeId;
+
+/// Decides which peers discovery broadcasts are sent to.
+///
+/// By default the net picks random neighbours. Seeded ordering picks
+/// them from reachable peers, shuffled with a generator shared across
+/// broadcasts, so a given seed always reproduces the same propagation sequence.
+#[derive(Debug, Default)]
+pub struct FanoutOrder {
+    rng: Option<Mutex<StdRng>>,
+}
+
+impl FanoutOrder {
+    /// Leaves the choice of peers to the net.
+    pub fn as_is() -> Self {
+        FanoutOrder { rng: None }
+    }
+
+    /// Deterministic pseudo-random order, intended for tests.
+    pub fn seeded(seed: u64) -> Self {
+        FanoutOrder {
+            rng: Some(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        self.rng.is_some()
+    }
+
+    /// Picks at most `fanout` of `peers`, all of them when `fanout` is `None`.
+    /// Returns `None`, when the choice is left to the net.
+    pub fn targets(&self, mut peers: Vec<NodeId>, fanout: Option<u32>) -> Option<Vec<NodeId>> {
+        let rng = self.rng.as_ref()?;
+        // Reachable peers come in no particular order
+        peers.sort_by_cached_key(|peer| peer.to_string());
+        peers.shuffle(&mut *rng.lock().unwrap());
+        if let Some(fanout) = fanout {
+            peers.truncate(fanout as usize);
+        }
+        Some(peers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peers() -> Vec<NodeId> {
+        (0..20)
+            .map(|i| format!("0x{:040x}", i).parse().unwrap())
+            .collect()
+    }
+
+    fn propagation(order: &FanoutOrder) -> Vec<Vec<NodeId>> {
+        (0..5)
+            .map(|round| {
+                let mut peers = peers();
+                peers.rotate_left(round);
+                order.targets(peers, Some(3)).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_seeded_order_is_reproducible() {
+        let first = propagation(&FanoutOrder::seeded(42));
+        let second = propagation(&FanoutOrder::seeded(42));
+        assert_eq!(first, second);
+
+        for targets in first.iter() {
+            assert_eq!(targets.len(), 3);
+            assert!(targets.iter().all(|peer| peers().contains(peer)));
+        }
+
+        assert_ne!(first, propagation(&FanoutOrder::seeded(7)));
+        assert_eq!(
+            FanoutOrder::seeded(7).targets(peers(), None).unwrap().len(),
+            20
+        );
+        assert!(FanoutOrder::as_is().targets(peers(), Some(3)).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+//! Latency of Discovery operations.
+//!
+//! Latencies are reported as `metrics` histograms labeled by peer and
+//! additionally summarized in memory, so they can be inspected without
+//! a metrics exporter.
+use metrics::timing;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Round-trip time of `RetrieveOffers` calls.
+const RETRIEVE_METRIC: &str = "market.offers.retrieve.rtt";
+/// Time from receiving Offers from peer until they are stored locally.
+const STORE_METRIC: &str = "market.offers.incoming.store.time";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count {
+            0 => None,
+            count => Some(self.total / count as u32),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DiscoveryLatency {
+    retrieve: Mutex<HashMap<String, LatencySummary>>,
+    store: Mutex<HashMap<String, LatencySummary>>,
+}
+
+impl DiscoveryLatency {
+    pub(super) fn record_retrieve(&self, peer: &str, latency: Duration) {
+        timing!(RETRIEVE_METRIC, latency.as_nanos() as u64, "peer" => peer.to_string());
+        record(&self.retrieve, peer, latency);
+    }
+
+    pub(super) fn record_store(&self, peer: &str, latency: Duration) {
+        timing!(STORE_METRIC, latency.as_nanos() as u64, "peer" => peer.to_string());
+        record(&self.store, peer, latency);
+    }
+
+    /// `RetrieveOffers` round-trip times for given peer.
+    pub fn retrieve(&self, peer: &str) -> LatencySummary {
+        summary(&self.retrieve, peer)
+    }
+
+    /// Times from receiving Offers from given peer until storing them.
+    pub fn store(&self, peer: &str) -> LatencySummary {
+        summary(&self.store, peer)
+    }
+}
+
+fn record(summaries: &Mutex<HashMap<String, LatencySummary>>, peer: &str, latency: Duration) {
+    summaries
+        .lock()
+        .unwrap()
+        .entry(peer.to_string())
+        .or_default()
+        .record(latency);
+}
+
+fn summary(summaries: &Mutex<HashMap<String, LatencySummary>>, peer: &str) -> LatencySummary {
+    summaries
+        .lock()
+        .unwrap()
+        .get(peer)
+        .copied()
+        .unwrap_or_default()
+}
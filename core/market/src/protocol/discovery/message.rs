@@ -18,7 +18,7 @@ pub struct OffersBcast {
 /// Those will be retrieved directly from the bcast sender.
 impl CallbackMessage for OffersBcast {
     type Ok = Vec<SubscriptionId>;
-    type Error = ();
+    type Error = DiscoveryRemoteError;
 }
 
 impl BroadcastMessage for OffersBcast {
@@ -46,6 +46,51 @@ impl RpcMessage for RetrieveOffers {
     type Error = DiscoveryRemoteError;
 }
 
+/// Same as `RetrieveOffers`, but tells why some of requested Offers weren't returned.
+/// Nodes, which don't handle it, are asked with `RetrieveOffers` instead.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetrieveOffersWithState {
+    pub offer_ids: Vec<SubscriptionId>,
+}
+
+impl RpcMessage for RetrieveOffersWithState {
+    const ID: &'static str = "GetWithState";
+    type Item = RetrievedOffers;
+    type Error = DiscoveryRemoteError;
+}
+
+/// Reason why requested Offer wasn't returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OfferUnavailable {
+    /// Offer is unknown. It could be not yet propagated to this Node,
+    /// or it could be removed long time ago.
+    NotFound,
+    /// Offer was unsubscribed and won't be available anymore.
+    Unsubscribed,
+    /// Offer expired and won't be available anymore.
+    Expired,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetrievedOffers {
+    pub offers: Vec<ModelOffer>,
+    /// Requested Offers, that weren't returned, with reason.
+    #[serde(default)]
+    pub unavailable: Vec<(SubscriptionId, OfferUnavailable)>,
+}
+
+impl From<Vec<ModelOffer>> for RetrievedOffers {
+    fn from(offers: Vec<ModelOffer>) -> Self {
+        RetrievedOffers {
+            offers,
+            unavailable: vec![],
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OffersRetrieved {
@@ -56,7 +101,7 @@ pub struct OffersRetrieved {
 /// Those will be bcasted further to the network.
 impl CallbackMessage for OffersRetrieved {
     type Ok = Vec<SubscriptionId>;
-    type Error = ();
+    type Error = DiscoveryRemoteError;
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -69,7 +114,7 @@ pub struct UnsubscribedOffersBcast {
 /// Those will be bcasted further to the network.
 impl CallbackMessage for UnsubscribedOffersBcast {
     type Ok = Vec<SubscriptionId>;
-    type Error = ();
+    type Error = DiscoveryRemoteError;
 }
 
 impl BroadcastMessage for UnsubscribedOffersBcast {
@@ -0,0 +1,92 @@
+//! Rate limiting of outbound broadcasts.
+use metrics::counter;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Token bucket limiting number of broadcasts sent per second.
+///
+/// Broadcasts exceeding the limit wait for their turn, so bursts are
+/// drained at configured rate instead of being dropped.
+pub struct BroadcastRate {
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl BroadcastRate {
+    /// Allows `per_second` broadcasts per second with bursts of the same size.
+    pub fn new(per_second: u32) -> Self {
+        let per_second = per_second.max(1) as f64;
+        BroadcastRate {
+            per_second,
+            burst: per_second,
+            bucket: Mutex::new(Bucket {
+                tokens: per_second,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token. Returns time to wait before broadcasting.
+    fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.refilled = now;
+
+        // Tokens go below zero for waiting broadcasts, which keeps them in order.
+        bucket.tokens -= 1.0;
+        match bucket.tokens < 0.0 {
+            true => Duration::from_secs_f64(-bucket.tokens / self.per_second),
+            false => Duration::from_secs(0),
+        }
+    }
+
+    /// Waits until next broadcast can be sent.
+    pub async fn acquire(&self) {
+        let delay = self.reserve();
+        if delay > Duration::from_secs(0) {
+            log::trace!("Broadcast rate limit reached. Delaying by {:?}.", delay);
+            counter!("market.offers.broadcasts.delayed", 1);
+            sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn broadcasts_over_limit_are_delayed() {
+        let rate = BroadcastRate::new(10);
+        for _ in 0..10 {
+            assert_eq!(rate.reserve(), Duration::from_secs(0));
+        }
+        // Waiting broadcasts are queued one after another.
+        let first = rate.reserve();
+        let second = rate.reserve();
+        assert!(first > Duration::from_millis(50) && first <= Duration::from_millis(100));
+        assert!(second > Duration::from_millis(150) && second <= Duration::from_millis(200));
+    }
+
+    #[actix_rt::test]
+    async fn burst_is_drained_at_configured_rate() {
+        let rate = BroadcastRate::new(20);
+        let start = Instant::now();
+        for _ in 0..30 {
+            rate.acquire().await;
+        }
+        // 20 broadcasts fit in the bucket, 10 more need half a second.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(450));
+        assert!(elapsed < Duration::from_millis(1500));
+    }
+}
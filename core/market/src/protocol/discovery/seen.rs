@@ -0,0 +1,139 @@
+//! Cache of Offers already propagated to other nodes.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::db::model::SubscriptionId;
+
+/// Offers seen within `ttl` aren't propagated again. Offer ids contain
+/// the hash of Offer content, so changed Offer is never treated as seen.
+pub struct SeenOffers {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    entries: HashMap<SubscriptionId, Instant>,
+    order: VecDeque<(SubscriptionId, Instant)>,
+}
+
+impl SeenOffers {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        SeenOffers {
+            capacity,
+            ttl,
+            inner: Default::default(),
+        }
+    }
+
+    /// Returns Offers not seen within `ttl` and marks them as seen.
+    pub fn filter_unseen(&self, offer_ids: Vec<SubscriptionId>) -> Vec<SubscriptionId> {
+        let now = Instant::now();
+        let mut seen = self.inner.lock().unwrap();
+        seen.evict(now, self.ttl, self.capacity);
+
+        offer_ids
+            .into_iter()
+            .filter(|id| {
+                if seen.entries.contains_key(id) {
+                    return false;
+                }
+                seen.entries.insert(id.clone(), now);
+                seen.order.push_back((id.clone(), now));
+                true
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+}
+
+impl Default for SeenOffers {
+    fn default() -> Self {
+        SeenOffers::new(10_000, Duration::from_secs(300))
+    }
+}
+
+impl Seen {
+    fn evict(&mut self, now: Instant, ttl: Duration, capacity: usize) {
+        while let Some((id, seen_at)) = self.order.front() {
+            if now.duration_since(*seen_at) < ttl && self.order.len() < capacity {
+                break;
+            }
+            // Entry could have been re-inserted after expiration.
+            if self.entries.get(id) == Some(seen_at) {
+                self.entries.remove(id);
+            }
+            self.order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::mock_offer::sample_offer;
+
+    #[test]
+    fn same_offer_is_propagated_once() {
+        let seen = SeenOffers::new(100, Duration::from_secs(60));
+        let offer_id = sample_offer().id;
+
+        assert_eq!(
+            seen.filter_unseen(vec![offer_id.clone()]),
+            vec![offer_id.clone()]
+        );
+        assert!(seen.filter_unseen(vec![offer_id.clone()]).is_empty());
+        assert_eq!(seen.len(), 1);
+    }
+
+    #[test]
+    fn offer_from_two_peers_is_propagated_once() {
+        let seen = std::sync::Arc::new(SeenOffers::new(100, Duration::from_secs(60)));
+        let (first, shared, second) = (sample_offer().id, sample_offer().id, sample_offer().id);
+
+        // Each peer announces the shared Offer along with its own one.
+        assert_eq!(
+            seen.filter_unseen(vec![first.clone(), shared.clone()]),
+            vec![first, shared.clone()]
+        );
+        assert_eq!(
+            seen.filter_unseen(vec![shared, second.clone()]),
+            vec![second]
+        );
+
+        // Broadcasts from both peers handled at the same time.
+        let offer_id = sample_offer().id;
+        let propagated = (0..2)
+            .map(|_| {
+                let (seen, offer_id) = (seen.clone(), offer_id.clone());
+                std::thread::spawn(move || seen.filter_unseen(vec![offer_id]))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(propagated, vec![offer_id]);
+        assert_eq!(seen.len(), 4);
+    }
+
+    #[test]
+    fn seen_offers_expire() {
+        let offer_id = sample_offer().id;
+
+        let seen = SeenOffers::new(100, Duration::from_millis(0));
+        assert_eq!(seen.filter_unseen(vec![offer_id.clone()]).len(), 1);
+        assert_eq!(seen.filter_unseen(vec![offer_id.clone()]).len(), 1);
+
+        // Oldest entries are evicted, when cache is full.
+        let seen = SeenOffers::new(1, Duration::from_secs(60));
+        let other_id = sample_offer().id;
+        assert_eq!(seen.filter_unseen(vec![offer_id.clone()]).len(), 1);
+        assert_eq!(seen.filter_unseen(vec![other_id]).len(), 1);
+        assert_eq!(seen.filter_unseen(vec![offer_id]).len(), 1);
+    }
+}
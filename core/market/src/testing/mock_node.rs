@@ -604,14 +604,14 @@ pub mod default {
     pub async fn empty_on_offers_retrieved(
         _caller: String,
         _msg: OffersRetrieved,
-    ) -> Result<Vec<SubscriptionId>, ()> {
+    ) -> Result<Vec<SubscriptionId>, DiscoveryRemoteError> {
         Ok(vec![])
     }
 
     pub async fn empty_on_offers_bcast(
         _caller: String,
         _msg: OffersBcast,
-    ) -> Result<Vec<SubscriptionId>, ()> {
+    ) -> Result<Vec<SubscriptionId>, DiscoveryRemoteError> {
         Ok(vec![])
     }
 
@@ -625,7 +625,7 @@ pub mod default {
     pub async fn empty_on_offer_unsubscribed_bcast(
         _caller: String,
         _msg: UnsubscribedOffersBcast,
-    ) -> Result<Vec<SubscriptionId>, ()> {
+    ) -> Result<Vec<SubscriptionId>, DiscoveryRemoteError> {
         Ok(vec![])
     }
 
@@ -702,6 +702,8 @@ pub fn create_market_config_for_test() -> Config {
         mean_cyclic_unsubscribes_interval: Duration::from_millis(200),
         offer_broadcast_delay: Duration::from_millis(200),
         unsub_broadcast_delay: Duration::from_millis(200),
+        offer_expiry_sweep_interval: Duration::from_millis(200),
+        offer_import_concurrency: 4,
     };
 
     let mut cfg = Config::from_env().unwrap();
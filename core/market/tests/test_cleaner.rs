@@ -9,7 +9,7 @@ use ya_market::testing::mock_offer::{generate_demand, generate_offer};
 use ya_market::testing::proposal_util::{generate_negotiation, generate_proposal};
 use ya_market::testing::{
     Agreement, AgreementDao, DbConfig, DbProposal, Demand, DemandDao, MarketsNetwork, Negotiation,
-    Offer, OfferDao,
+    Offer, OfferDao, OfferState,
 };
 use ya_persistence::executor::PoolType;
 
@@ -129,6 +129,142 @@ async fn test_offer() {
     );
 }
 
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_offer_store_compaction() {
+    let live_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a57",
+        future(),
+        );
+    let expired_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a58",
+        past(),
+        );
+    let unsubscribed_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a59",
+        future(),
+        );
+    let db = MarketsNetwork::new(None)
+        .await
+        .init_database("test_offer_store_compaction");
+    let offer_dao = db.as_dao::<OfferDao>();
+    let validation_ts = (Utc::now() - Duration::days(100)).naive_utc();
+    for offer in vec![&live_offer, &expired_offer, &unsubscribed_offer] {
+        offer_dao
+            .put(offer.clone(), validation_ts.clone())
+            .await
+            .unwrap();
+    }
+    offer_dao
+        .unsubscribe(&unsubscribed_offer.id, Utc::now().naive_utc())
+        .await
+        .unwrap();
+
+    let stats = offer_dao.stats().await.unwrap();
+    assert_eq!(
+        (
+            stats.live,
+            stats.expired,
+            stats.unsubscribed,
+            stats.tombstones
+        ),
+        (1, 1, 1, 0)
+    );
+    assert!(stats.memory_bytes > 0);
+
+    let stats = offer_dao.compact(&db_config()).await.unwrap();
+    assert_eq!(
+        (
+            stats.live,
+            stats.expired,
+            stats.unsubscribed,
+            stats.tombstones
+        ),
+        (1, 0, 1, 1)
+    );
+    assert_eq!(stats, offer_dao.stats().await.unwrap());
+
+    let now = Utc::now().naive_utc();
+    assert!(matches!(
+        offer_dao.get_state(&live_offer.id, now).await.unwrap(),
+        OfferState::Active(_)
+    ));
+    assert!(matches!(
+        offer_dao.get_state(&expired_offer.id, now).await.unwrap(),
+        OfferState::Expired(None)
+    ));
+    assert!(matches!(
+        offer_dao
+            .get_state(&unsubscribed_offer.id, now)
+            .await
+            .unwrap(),
+        OfferState::Unsubscribed(None)
+    ));
+    assert_eq!(
+        <PoolType as TestingDao<Offer>>::exists(&db.ram_db.pool, unsubscribed_offer.id).await,
+        false
+    );
+}
+
+/// Expiry sweep removes expired Offers only. They are remembered as expired,
+/// while unsubscribed Offers stay untouched until cleaner runs.
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_offer_expiry_sweep() {
+    let live_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a60",
+        future(),
+        );
+    let expired_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a61",
+        past(),
+        );
+    let unsubscribed_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a62",
+        future(),
+        );
+    let db = MarketsNetwork::new(None)
+        .await
+        .init_database("test_offer_expiry_sweep");
+    let offer_dao = db.as_dao::<OfferDao>();
+    let validation_ts = (Utc::now() - Duration::days(100)).naive_utc();
+    for offer in vec![&live_offer, &expired_offer, &unsubscribed_offer] {
+        offer_dao
+            .put(offer.clone(), validation_ts.clone())
+            .await
+            .unwrap();
+    }
+    offer_dao
+        .unsubscribe(&unsubscribed_offer.id, Utc::now().naive_utc())
+        .await
+        .unwrap();
+
+    assert_eq!(offer_dao.remove_expired(&db_config()).await.unwrap(), 1);
+    assert_eq!(offer_dao.remove_expired(&db_config()).await.unwrap(), 0);
+
+    let now = Utc::now().naive_utc();
+    assert!(matches!(
+        offer_dao.get_state(&live_offer.id, now).await.unwrap(),
+        OfferState::Active(_)
+    ));
+    assert!(matches!(
+        offer_dao.get_state(&expired_offer.id, now).await.unwrap(),
+        OfferState::Expired(None)
+    ));
+    assert!(matches!(
+        offer_dao
+            .get_state(&unsubscribed_offer.id, now)
+            .await
+            .unwrap(),
+        OfferState::Unsubscribed(Some(_))
+    ));
+    // Expired Offer can't be stored again, when received from other Node.
+    assert!(matches!(
+        offer_dao.put(expired_offer.clone(), now).await.unwrap(),
+        (false, OfferState::Expired(Some(_)))
+    ));
+}
+
 #[cfg_attr(not(feature = "test-suite"), ignore)]
 #[serial_test::serial]
 async fn test_events() {
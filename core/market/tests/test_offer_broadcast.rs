@@ -201,6 +201,45 @@ async fn test_broadcast_expired_offer() {
     );
 }
 
+/// Unsubscribing Offer, that Node has never seen, should be no-op.
+/// It can't affect Node's own Offers.
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_broadcast_unknown_unsubscribe() {
+    let _ = env_logger::builder().try_init();
+    let network = MarketsNetwork::new(None)
+        .await
+        .add_market_instance("Node-1")
+        .await;
+
+    let mkt1 = network.get_market("Node-1");
+    let id1 = network.get_default_id("Node-1");
+    let offer_id = mkt1
+        .subscribe_offer(&client::sample_offer(), &id1)
+        .await
+        .unwrap();
+    let offer = mkt1.get_offer(&offer_id).await.unwrap();
+
+    let discovery_builder = network.discovery_builder();
+    let network = network
+        .add_discovery_instance("Node-2", discovery_builder)
+        .await;
+    let discovery2: Discovery = network.get_discovery("Node-2");
+
+    let unknown_id = sample_offer().id;
+    discovery2
+        .bcast_unsubscribes(vec![unknown_id.clone()])
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert_err_eq!(
+        QueryOfferError::NotFound(unknown_id.clone()),
+        mkt1.get_offer(&unknown_id).await,
+    );
+    assert_eq!(offer, mkt1.get_offer(&offer_id).await.unwrap());
+}
+
 /// Note: Disabled after #1474 (Lazy broadcasts)
 ///// Nodes shouldn't broadcast unsubscribed Offers.
 ///// This test broadcasts unsubscribed Offer and checks how other market Nodes
@@ -339,6 +378,106 @@ async fn test_discovery_get_offers() {
     assert_eq!(offers[0].id, subscription_id);
 }
 
+/// Discovery `RetrieveOffersWithState` GSB endpoint should return existing Offers
+/// and tell apart unsubscribed Offer from the one, that Node never knew.
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_discovery_get_offers_with_state() {
+    let _ = env_logger::builder().try_init();
+    let network = MarketsNetwork::new(None)
+        .await
+        .add_market_instance("Node-1")
+        .await;
+
+    let discovery_builder = network.discovery_builder();
+    let network = network
+        .add_discovery_instance("Node-2", discovery_builder)
+        .await;
+
+    let mkt1 = network.get_market("Node-1");
+    let id1 = network.get_default_id("Node-1");
+    let discovery2 = network.get_discovery("Node-2");
+
+    let subscription_id = mkt1
+        .subscribe_offer(&client::sample_offer(), &id1)
+        .await
+        .unwrap();
+    let unsubscribed_id = mkt1
+        .subscribe_offer(&client::sample_offer(), &id1)
+        .await
+        .unwrap();
+    mkt1.unsubscribe_offer(&unsubscribed_id, &id1)
+        .await
+        .unwrap();
+    let invalid_subscription: SubscriptionId = "00000000000000000000000000000001-0000000000000000000000000000000000000000000000000000000000000002".parse().unwrap();
+
+    let retrieved = discovery2
+        .get_remote_offers_with_state(
+            id1.identity.to_string(),
+            vec![
+                subscription_id.clone(),
+                unsubscribed_id.clone(),
+                invalid_subscription.clone(),
+            ],
+            5,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(retrieved.offers.len(), 1);
+    assert_eq!(retrieved.offers[0].id, subscription_id);
+    assert_eq!(
+        retrieved.unavailable,
+        vec![
+            (unsubscribed_id, OfferUnavailable::Unsubscribed),
+            (invalid_subscription, OfferUnavailable::NotFound),
+        ]
+    );
+}
+
+/// Discovery should record round-trip time of `RetrieveOffers` calls per peer.
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_discovery_retrieve_latency() {
+    let _ = env_logger::builder().try_init();
+    let offer = sample_offer();
+    let offer_id = offer.id.clone();
+
+    let network = MarketsNetwork::new(None).await;
+    let discovery_builder = network.discovery_builder();
+    let network = network
+        .add_discovery_instance(
+            "Node-1",
+            discovery_builder.add_handler(move |_: String, _: RetrieveOffers| {
+                let offer = offer.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Ok(vec![offer])
+                }
+            }),
+        )
+        .await;
+    let discovery_builder = network.discovery_builder();
+    let network = network
+        .add_discovery_instance("Node-2", discovery_builder)
+        .await;
+
+    let peer = network.get_default_id("Node-1").identity.to_string();
+    let discovery2 = network.get_discovery("Node-2");
+    assert_eq!(discovery2.latency().retrieve(&peer).count, 0);
+
+    let offers = discovery2
+        .get_remote_offers(peer.clone(), vec![offer_id.clone()], 5)
+        .await
+        .unwrap();
+    assert_eq!(offers[0].id, offer_id);
+
+    let latency = discovery2.latency().retrieve(&peer);
+    assert_eq!(latency.count, 1);
+    assert!(latency.max >= Duration::from_millis(100));
+    assert_eq!(latency.mean(), Some(latency.max));
+}
+
 /// Ensure that node is ready to handle broadcast message with more offers than
 /// `max_bcasted_offers` or more unsubscribes than `max_bcasted_unsubscribes`. We will use sets
 /// larger than 32766 as it's SQLITE_MAX_VARIABLE_NUMBER as of 3.32.0 (2020-05-22).
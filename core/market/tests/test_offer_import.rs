@@ -0,0 +1,58 @@
+use chrono::{Duration, Utc};
+
+use ya_market::testing::import::ImportSummary;
+use ya_market::testing::mock_offer::{sample_offer, sample_offer_with_expiration};
+use ya_market::testing::MarketsNetwork;
+
+/// Bulk import should store only valid, unexpired Offers, which weren't known before,
+/// and report why the rest of them was rejected.
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_import_mixed_validity_batch() {
+    let _ = env_logger::builder().try_init();
+    let network = MarketsNetwork::new(None)
+        .await
+        .add_matcher_instance("Node-1")
+        .await;
+    let matcher = network.get_matcher("Node-1");
+
+    let valid = (0..10).map(|_| sample_offer()).collect::<Vec<_>>();
+    let expired = sample_offer_with_expiration(Utc::now().naive_utc() - Duration::hours(1));
+    let mut tampered = sample_offer();
+    tampered.properties = r#"{"golem.runtime.name":"tampered"}"#.to_string();
+
+    let mut batch = valid.clone();
+    batch.push(valid[0].clone());
+    batch.push(valid[1].clone());
+    batch.push(expired);
+    batch.push(tampered);
+
+    let mut reports = vec![];
+    let summary = matcher
+        .import_offers(batch, |progress| reports.push(progress.processed()))
+        .await;
+
+    assert_eq!(
+        summary,
+        ImportSummary {
+            total: 14,
+            accepted: 10,
+            duplicates: 2,
+            expired: 1,
+            invalid: 1,
+            failed: 0,
+        }
+    );
+    assert_eq!(summary.rejected(), 4);
+    assert_eq!(reports.last(), Some(&14));
+    assert!(reports.windows(2).all(|w| w[0] < w[1]));
+
+    for offer in &valid {
+        assert_eq!(&matcher.store.get_offer(&offer.id).await.unwrap(), offer);
+    }
+
+    // Importing the same snapshot again shouldn't add anything.
+    let summary = matcher.import_offers(valid, |_| ()).await;
+    assert_eq!(summary.accepted, 0);
+    assert_eq!(summary.duplicates, 10);
+}
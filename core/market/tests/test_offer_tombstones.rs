@@ -0,0 +1,86 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use structopt::StructOpt;
+
+use ya_market::testing::cleaner::clean;
+use ya_market::testing::mock_offer::generate_offer;
+use ya_market::testing::{DbConfig, MarketsNetwork, OfferDao, OfferState};
+
+fn past() -> NaiveDateTime {
+    (Utc::now() - Duration::days(91)).naive_utc()
+}
+
+fn db_config() -> DbConfig {
+    DbConfig::from_iter(&[""])
+}
+
+#[cfg_attr(not(feature = "test-suite"), ignore)]
+#[serial_test::serial]
+async fn test_offer_tombstones() {
+    let expired_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a54",
+        past(),
+        );
+    let unsubscribed_offer = generate_offer(
+        "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a55",
+        past(),
+        );
+    let unknown_id = "c76161077d0343ab85ac986eb5f6ea38-edb0016d9f8bafb54540da34f05a8d510de8114488f23916276bdead05509a56".parse().unwrap();
+    let db = MarketsNetwork::new(None)
+        .await
+        .init_database("test_offer_tombstones");
+    let offer_dao = db.as_dao::<OfferDao>();
+    let validation_ts = (Utc::now() - Duration::days(100)).naive_utc();
+    offer_dao
+        .put(expired_offer.clone(), validation_ts.clone())
+        .await
+        .unwrap();
+    offer_dao
+        .put(unsubscribed_offer.clone(), validation_ts.clone())
+        .await
+        .unwrap();
+    // Offers from other nodes are removed upon unsubscribe.
+    offer_dao
+        .unsubscribe(&unsubscribed_offer.id, validation_ts.clone())
+        .await
+        .unwrap();
+    offer_dao.delete(&unsubscribed_offer.id).await.unwrap();
+
+    clean(db.clone(), &db_config()).await;
+
+    let now = Utc::now().naive_utc();
+    assert!(matches!(
+        offer_dao.get_state(&expired_offer.id, now).await.unwrap(),
+        OfferState::Expired(None)
+    ));
+    assert!(matches!(
+        offer_dao
+            .get_state(&unsubscribed_offer.id, now)
+            .await
+            .unwrap(),
+        OfferState::Unsubscribed(None)
+    ));
+    assert!(matches!(
+        offer_dao.get_state(&unknown_id, now).await.unwrap(),
+        OfferState::NotFound
+    ));
+
+    // Tombstones are removed after their own ttl passes.
+    let mut config = db_config();
+    config.offer_tombstone_ttl = Duration::zero();
+    clean(db.clone(), &config).await;
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    clean(db.clone(), &config).await;
+
+    let now = Utc::now().naive_utc();
+    assert!(matches!(
+        offer_dao.get_state(&expired_offer.id, now).await.unwrap(),
+        OfferState::NotFound
+    ));
+    assert!(matches!(
+        offer_dao
+            .get_state(&unsubscribed_offer.id, now)
+            .await
+            .unwrap(),
+        OfferState::NotFound
+    ));
+}
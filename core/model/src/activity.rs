@@ -4,11 +4,12 @@
 //! Local and Exeunit are in dedicated submodules.
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use strum_macros::{Display, EnumString};
 
 use crate::Role;
 use ya_client_model::activity::{
     ActivityState, ActivityUsage, ExeScriptCommand, ExeScriptCommandResult, ExeScriptCommandState,
-    RuntimeEvent,
+    RuntimeEvent, State,
 };
 use ya_client_model::NodeId;
 use ya_service_bus::{RpcMessage, RpcStreamMessage};
@@ -20,6 +21,61 @@ use ya_service_bus::{RpcMessage, RpcStreamMessage};
 ///  * [`exeunit::bus_id`](exeunit/fn.bus_id.html)
 pub const BUS_ID: &str = "/public/activity";
 
+/// Machine-readable reason of Activity termination.
+///
+/// Recorded as `ActivityState::reason` of a `Terminated` Activity,
+/// while human-readable details are kept in `ActivityState::error_message`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum TerminationReason {
+    /// ExeUnit finished its work normally.
+    Finished,
+    /// ExeUnit was interrupted by a signal.
+    Interrupted,
+    /// Activity was destroyed, because it was inactive for too long.
+    Timeout,
+    /// Usage limits specified in the Agreement were exceeded.
+    UsageLimitExceeded,
+    /// ExeUnit failed to start, crashed or reported an error.
+    ExeUnitCrashed,
+    /// Requestor destroyed the Activity.
+    RequestorTerminated,
+    /// Provider has no capacity left to run the Activity.
+    ProviderCapacity,
+}
+
+impl TerminationReason {
+    /// Reads reason of termination recorded in the `state`.
+    /// Returns `None` for live Activities and unrecognized reasons.
+    pub fn from_state(state: &ActivityState) -> Option<Self> {
+        if state.state.0 != State::Terminated {
+            return None;
+        }
+        state.reason.as_ref().and_then(|r| r.parse().ok())
+    }
+
+    /// Termination was forced from outside of the ExeUnit. Such reasons take
+    /// precedence over the ones reported later by the ExeUnit being stopped.
+    pub fn is_forced(&self) -> bool {
+        matches!(
+            self,
+            TerminationReason::Timeout
+                | TerminationReason::RequestorTerminated
+                | TerminationReason::ProviderCapacity
+        )
+    }
+
+    /// Builds `Terminated` Activity state, recording this reason.
+    pub fn to_state(self, message: Option<String>) -> ActivityState {
+        ActivityState {
+            state: State::Terminated.into(),
+            reason: Some(self.to_string()),
+            error_message: message,
+        }
+    }
+}
+
 /// Public Exe Unit service bus API.
 pub mod exeunit {
     /// Public exeunit bus address for given `activity_id`.
@@ -144,6 +200,20 @@ impl RpcMessage for GetUsage {
     type Error = RpcMessageError;
 }
 
+/// Get periodic snapshots of the activity usage counters, from the oldest one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetUsageHistory {
+    pub activity_id: String,
+    pub timeout: Option<f32>,
+}
+
+impl RpcMessage for GetUsageHistory {
+    const ID: &'static str = "GetActivityUsageHistory";
+    type Item = Vec<ActivityUsage>;
+    type Error = RpcMessageError;
+}
+
 /// Update remote network configuration
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -273,6 +343,65 @@ impl RpcMessage for GetRunningCommand {
     type Error = RpcMessageError;
 }
 
+/// Turn VPN traffic forwarding off or on, leaving the activity running.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetVpnEnabled {
+    pub activity_id: String,
+    pub enabled: bool,
+}
+
+impl RpcMessage for SetVpnEnabled {
+    const ID: &'static str = "SetVpnEnabled";
+    type Item = ();
+    type Error = RpcMessageError;
+}
+
+/// Execute multiple activity operations within a single round-trip.
+///
+/// Operations are processed sequentially and their results are returned in
+/// the same order. A failed operation does not abort the rest of the batch.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Batch {
+    pub operations: Vec<BatchOperation>,
+}
+
+impl RpcMessage for Batch {
+    const ID: &'static str = "ActivityBatch";
+    type Item = Vec<BatchResult>;
+    type Error = RpcMessageError;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchOperation {
+    Create(Create),
+    GetState(GetState),
+    Exec(Exec),
+    Destroy(Destroy),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchResult {
+    Create(Result<CreateResponseCompat, RpcMessageError>),
+    GetState(Result<ActivityState, RpcMessageError>),
+    Exec(Result<String, RpcMessageError>),
+    Destroy(Result<(), RpcMessageError>),
+}
+
+impl BatchResult {
+    pub fn is_ok(&self) -> bool {
+        match self {
+            BatchResult::Create(r) => r.is_ok(),
+            BatchResult::GetState(r) => r.is_ok(),
+            BatchResult::Exec(r) => r.is_ok(),
+            BatchResult::Destroy(r) => r.is_ok(),
+        }
+    }
+}
+
 /// Local activity bus API (used by ExeUnit).
 ///
 /// Should be accessible only from local service bus (not via net ie. from remote hosts).
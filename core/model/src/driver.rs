@@ -540,6 +540,101 @@ impl RpcMessage for ShutDown {
     type Error = GenericError;
 }
 
+// ************************* RECONCILE *************************
+
+/// Brings driver's database in line with the on-chain state of transactions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reconcile {
+    network: Option<String>,
+}
+
+impl Reconcile {
+    pub fn new(network: Option<String>) -> Self {
+        Self { network }
+    }
+    pub fn network(&self) -> Option<String> {
+        self.network.clone()
+    }
+}
+
+impl RpcMessage for Reconcile {
+    const ID: &'static str = "Reconcile";
+    type Item = ReconcileReport;
+    type Error = GenericError;
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub network: String,
+    /// Number of transactions checked against the chain
+    pub checked: usize,
+    /// Corrections applied to the database
+    pub changes: Vec<ReconcileChange>,
+    /// Inconsistencies left for manual inspection
+    pub flagged: Vec<ReconcileChange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ReconcileChange {
+    /// Transaction was mined and succeeded
+    #[serde(rename_all = "camelCase")]
+    Confirmed { tx_id: String, tx_hash: String },
+    /// Transaction was mined, but failed. Its payments are marked as failed
+    #[serde(rename_all = "camelCase")]
+    FailedOnChain { tx_id: String, tx_hash: String },
+    /// None of the transaction hashes were found on chain. Transaction is queued for resending
+    #[serde(rename_all = "camelCase")]
+    Requeued { tx_id: String },
+    /// Transaction recorded as confirmed was not found on chain
+    #[serde(rename_all = "camelCase")]
+    MissingOnChain { tx_id: String, tx_hash: String },
+    /// Payment is marked as processed, but its transaction does not exist
+    #[serde(rename_all = "camelCase")]
+    OrphanedPayment {
+        order_id: String,
+        tx_id: Option<String>,
+    },
+}
+
+// ************************* UNSETTLED PAYMENTS *************************
+
+/// Lists payments, which are neither confirmed nor failed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GetUnsettledPayments {
+    network: Option<String>,
+}
+
+impl GetUnsettledPayments {
+    pub fn new(network: Option<String>) -> Self {
+        Self { network }
+    }
+    pub fn network(&self) -> Option<String> {
+        self.network.clone()
+    }
+}
+
+impl RpcMessage for GetUnsettledPayments {
+    const ID: &'static str = "GetUnsettledPayments";
+    type Item = Vec<UnsettledPayment>;
+    type Error = GenericError;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsettledPayment {
+    pub order_id: String,
+    pub sender: String,
+    pub recipient: String,
+    pub amount: BigDecimal,
+    pub due_date: DateTime<Utc>,
+    pub tx_id: Option<String>,
+    /// Last error, which prevented the payment from being sent
+    pub last_error: Option<String>,
+    pub last_error_time: Option<DateTime<Utc>>,
+}
+
 // ************************* GAS DETAILS *************************
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
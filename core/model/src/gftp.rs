@@ -39,6 +39,15 @@ impl RpcMessage for GetMetadata {
 #[serde(rename_all = "camelCase")]
 pub struct GftpMetadata {
     pub file_size: u64,
+    /// File is still being written by publisher, so `file_size` may change.
+    #[serde(default)]
+    pub growing: bool,
+    /// Content is a manifest of a published directory.
+    #[serde(default)]
+    pub directory: bool,
+    /// Hash of the complete content of a growing file, set once it's finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
 }
 
 /// Gets chunk of file. Returns GftpChunk.
@@ -57,6 +66,31 @@ impl RpcMessage for GetChunk {
     type Error = Error;
 }
 
+/// Gets index of fixed-size chunks of a published file, with their hashes.
+/// Returns GftpChunkIndex. Not available for growing files.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChunkIndex;
+
+impl RpcMessage for GetChunkIndex {
+    const ID: &'static str = "GetChunkIndex";
+    type Item = GftpChunkIndex;
+    type Error = Error;
+}
+
+/// Gets chunk with given hash from the chunk index. Returns GftpChunk.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChunkByHash {
+    pub hash: String,
+}
+
+impl RpcMessage for GetChunkByHash {
+    const ID: &'static str = "GetChunkByHash";
+    type Item = GftpChunk;
+    type Error = Error;
+}
+
 // =========================================== //
 // Upload messages
 // =========================================== //
@@ -99,3 +133,22 @@ pub struct GftpChunk {
     #[serde(with = "serde_bytes")]
     pub content: Vec<u8>,
 }
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GftpChunkInfo {
+    pub offset: u64,
+    pub size: u64,
+    /// Hex encoded sha3-256 of chunk content.
+    pub hash: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GftpChunkIndex {
+    pub chunk_size: u64,
+    pub chunks: Vec<GftpChunkInfo>,
+    /// Chunk boundaries depend on content; `chunk_size` is the average size.
+    #[serde(default)]
+    pub content_defined: bool,
+}
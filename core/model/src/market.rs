@@ -41,6 +41,45 @@ impl RpcMessage for GetAgreement {
     type Error = RpcMessageError;
 }
 
+/// Statistics of the Offer store.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OfferStoreStats {
+    /// Offers neither expired nor unsubscribed.
+    pub live: u64,
+    /// Expired Offers not removed yet.
+    pub expired: u64,
+    /// Unsubscription markers.
+    pub unsubscribed: u64,
+    /// Offers removed by cleaner, remembered to reject their rebroadcasts.
+    pub tombstones: u64,
+    /// Approximate memory used by the store in bytes.
+    pub memory_bytes: u64,
+}
+
+/// Returns Offer store statistics. Local Market bus only.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetOfferStoreStats {}
+
+impl RpcMessage for GetOfferStoreStats {
+    const ID: &'static str = "GetOfferStoreStats";
+    type Item = OfferStoreStats;
+    type Error = RpcMessageError;
+}
+
+/// Purges dead entries from Offer store and returns its statistics
+/// after compaction. Live Offers are never removed. Local Market bus only.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactOfferStore {}
+
+impl RpcMessage for CompactOfferStore {
+    const ID: &'static str = "CompactOfferStore";
+    type Item = OfferStoreStats;
+    type Error = RpcMessageError;
+}
+
 /// Error message for market service bus API.
 #[derive(thiserror::Error, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -204,6 +204,27 @@ pub mod local {
         pub metrics: StatusMetrics,
     }
 
+    /// Peers, which this node has recently exchanged messages with.
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ReachablePeers {}
+
+    impl RpcMessage for ReachablePeers {
+        const ID: &'static str = "ReachablePeers";
+        type Item = Vec<ReachablePeer>;
+        type Error = StatusError;
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ReachablePeer {
+        pub node_id: NodeId,
+        /// Time elapsed since the last successful exchange
+        pub seen: Duration,
+        pub successes: u64,
+        pub failures: u64,
+    }
+
     /// Measures time between sending GSB message and getting response.
     /// This is different from session ping, because it takes into account
     /// Virtual TCP overhead. Moreover we can measure ping between Nodes
@@ -218,6 +239,29 @@ pub mod local {
         type Error = StatusError;
     }
 
+    /// Transport capabilities negotiated with peers.
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PeerCapabilities {}
+
+    impl RpcMessage for PeerCapabilities {
+        const ID: &'static str = "PeerCapabilities";
+        type Item = Vec<PeerCapability>;
+        type Error = StatusError;
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PeerCapability {
+        pub node_id: NodeId,
+        /// Tag of the payload codec
+        pub codec: u8,
+        pub compression: String,
+        pub framing: u32,
+        /// Peer doesn't support the capability handshake
+        pub legacy: bool,
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct GsbPingResponse {
@@ -240,6 +284,34 @@ impl RpcMessage for GsbRemotePing {
     type Error = GenericNetError;
 }
 
+/// Transport options supported by a node, in order of preference.
+/// Unknown options, e.g. coming from newer nodes, are ignored.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Tags of supported payload codecs
+    #[serde(default)]
+    pub codecs: Vec<u8>,
+    #[serde(default)]
+    pub compression: Vec<String>,
+    /// Supported framing versions
+    #[serde(default)]
+    pub framing: Vec<u32>,
+}
+
+/// Capability handshake. Both sides exchange their capabilities
+/// and choose the best mutually supported options.
+/// Sent to the `DIAGNOSTIC` address of a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GsbCapabilities(pub Capabilities);
+
+impl RpcMessage for GsbCapabilities {
+    const ID: &'static str = "GsbCapabilities";
+    type Item = Capabilities;
+    type Error = GenericNetError;
+}
+
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 #[error("{0}")]
 pub struct GenericNetError(pub String);
@@ -233,6 +233,21 @@ pub mod local {
         type Error = GenericError;
     }
 
+    /// Sent by drivers, when payment orders won't be realized.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct NotifyPaymentFailed {
+        pub driver: String,
+        pub platform: String,
+        pub order_ids: Vec<String>,
+        pub reason: String,
+    }
+
+    impl RpcMessage for NotifyPaymentFailed {
+        const ID: &'static str = "NotifyPaymentFailed";
+        type Item = ();
+        type Error = GenericError;
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct GetStatus {
         pub address: String,
@@ -510,6 +525,26 @@ pub mod local {
         }
     }
 
+    #[derive(StructOpt, Debug, Clone)]
+    pub struct NetworkCli {
+        /// Payment driver
+        #[structopt(long, possible_values = DriverName::VARIANTS, default_value = DriverName::Erc20.into())]
+        pub driver: DriverName,
+        /// Payment network
+        #[structopt(long, possible_values = NetworkName::VARIANTS, default_value = NetworkName::Rinkeby.into())]
+        pub network: NetworkName,
+    }
+
+    impl NetworkCli {
+        pub fn driver(&self) -> String {
+            self.driver.to_string()
+        }
+
+        pub fn network(&self) -> String {
+            self.network.to_string()
+        }
+    }
+
     #[cfg(test)]
     mod test {
         use super::*;
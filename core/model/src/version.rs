@@ -2,11 +2,14 @@
 
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
 
 use ya_client_model::ErrorMessage;
 use ya_service_bus::RpcMessage;
 
 pub const BUS_ID: &'static str = "/local/version";
+/// Version queries available to other nodes.
+pub const PUBLIC_BUS_ID: &'static str = "/public/version";
 
 /// Skip upgrading to the latest Yagna release.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -19,6 +22,19 @@ impl RpcMessage for Skip {
     type Error = ErrorMessage;
 }
 
+/// Stop notifying about given version and all the older ones.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkipVersion {
+    pub version: String,
+}
+
+impl RpcMessage for SkipVersion {
+    const ID: &'static str = "skip-version";
+    type Item = ();
+    type Error = ErrorMessage;
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Get {
@@ -41,6 +57,67 @@ impl RpcMessage for Get {
     type Error = ErrorMessage;
 }
 
+/// Kind of releases, about which the version notifier reports.
+/// Pre-releases are reported on the `Beta` channel only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ReleaseChannel {
+    Stable,
+    Beta,
+}
+
+impl Default for ReleaseChannel {
+    fn default() -> Self {
+        ReleaseChannel::Stable
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetReleaseChannel {
+    pub channel: ReleaseChannel,
+}
+
+impl RpcMessage for SetReleaseChannel {
+    const ID: &'static str = "set-release-channel";
+    type Item = ();
+    type Error = ErrorMessage;
+}
+
+/// Get the version of the running Yagna daemon.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBuildInfo {}
+
+impl RpcMessage for GetBuildInfo {
+    const ID: &'static str = "build-info";
+    type Item = BuildInfo;
+    type Error = ErrorMessage;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_commit: String,
+    pub build_date: String,
+    pub build_number: Option<String>,
+}
+
+/// Subscribe to version events, which are sent to `endpoint`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscribe {
+    pub endpoint: String,
+}
+
+impl RpcMessage for Subscribe {
+    const ID: &'static str = "subscribe";
+    type Item = ();
+    type Error = ErrorMessage;
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, thiserror::Error)]
 #[serde(rename_all = "camelCase")]
 #[error("Version {version} '{name}' released {}", release_ts.format("%Y-%m-%d"))]
@@ -58,6 +135,33 @@ pub struct Release {
 pub struct VersionInfo {
     pub current: Release,
     pub pending: Option<Release>,
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+}
+
+pub mod event {
+    use serde::{Deserialize, Serialize};
+
+    use ya_client_model::ErrorMessage;
+    use ya_service_bus::RpcMessage;
+
+    use super::Release;
+
+    /// Sent once for each newly detected Yagna release, which is newer
+    /// than the running one.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct VersionUpgradeAvailable {
+        pub current: String,
+        pub available: Release,
+        pub release_notes_url: String,
+    }
+
+    impl RpcMessage for VersionUpgradeAvailable {
+        const ID: &'static str = "VersionUpgradeAvailable";
+        type Item = ();
+        type Error = ErrorMessage;
+    }
 }
 
 #[cfg(test)]
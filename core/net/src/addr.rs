@@ -0,0 +1,86 @@
+//! Rewriting addresses of incoming calls to local bus addresses.
+use ya_core_model::net::PUBLIC_PREFIX;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AddrError {
+    #[error("wrong routing: {0}; address doesn't match any of local node ids")]
+    UnknownNode(String),
+    #[error("missing service name in address: {0}")]
+    MissingService(String),
+    #[error("empty segment in address: {0}")]
+    EmptySegment(String),
+}
+
+/// Rewrites `/net/<node_id>/<service>` to `/public/<service>`, where `/net/<node_id>`
+/// is one of `prefixes`. Address has to contain a non-empty service path.
+pub(crate) fn to_local_addr<'a>(
+    addr: &str,
+    prefixes: impl IntoIterator<Item = &'a String>,
+) -> Result<String, AddrError> {
+    let service = prefixes
+        .into_iter()
+        .find_map(|prefix| match addr.strip_prefix(prefix.as_str()) {
+            // Prefix has to end at segment boundary.
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => Some(rest),
+            _ => None,
+        })
+        .ok_or_else(|| AddrError::UnknownNode(addr.to_string()))?;
+
+    let service = service.trim_start_matches('/');
+    if service.is_empty() {
+        return Err(AddrError::MissingService(addr.to_string()));
+    }
+    if service.split('/').any(str::is_empty) {
+        return Err(AddrError::EmptySegment(addr.to_string()));
+    }
+
+    Ok(format!("{}/{}", PUBLIC_PREFIX, service))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE: &str = "/net/0x99402605903da83901151b0871ebeae9296ef66b";
+
+    fn rewrite(addr: &str) -> Result<String, AddrError> {
+        to_local_addr(addr, &vec![NODE.to_string()])
+    }
+
+    #[test]
+    fn test_normal_address() {
+        let addr = format!("{}/market/protocol/mk1/discovery", NODE);
+        assert_eq!(
+            rewrite(&addr).unwrap(),
+            "/public/market/protocol/mk1/discovery"
+        );
+        let addr = format!("{}/test", NODE);
+        assert_eq!(rewrite(&addr).unwrap(), "/public/test");
+    }
+
+    #[test]
+    fn test_over_long_address() {
+        let service = (0..32).map(|i| i.to_string()).collect::<Vec<_>>().join("/");
+        let addr = format!("{}/{}", NODE, service);
+        assert_eq!(rewrite(&addr).unwrap(), format!("/public/{}", service));
+    }
+
+    #[test]
+    fn test_short_address() {
+        for addr in vec![NODE.to_string(), format!("{}/", NODE)] {
+            assert_eq!(rewrite(&addr), Err(AddrError::MissingService(addr)));
+        }
+        for addr in vec!["", "/", "/net", "/net/0x9940"] {
+            assert_eq!(rewrite(addr), Err(AddrError::UnknownNode(addr.into())));
+        }
+    }
+
+    #[test]
+    fn test_malformed_address() {
+        let addr = format!("{}ff/test", NODE);
+        assert_eq!(rewrite(&addr), Err(AddrError::UnknownNode(addr)));
+
+        let addr = format!("{}/test//1", NODE);
+        assert_eq!(rewrite(&addr), Err(AddrError::EmptySegment(addr)));
+    }
+}
@@ -0,0 +1,127 @@
+//! Negotiation of transport options between peers.
+//!
+//! Nodes exchange their `Capabilities` when they start talking to each other
+//! and use the most preferred options supported by both sides. Peers, which
+//! don't take part in the handshake, are treated as legacy nodes and get
+//! the baseline: plain JSON payloads, no compression, first framing version.
+//! Calls and replies sent to a peer are encoded with the negotiated codec;
+//! the baseline applies until the handshake completes.
+use std::convert::TryFrom;
+
+use ya_core_model::net::Capabilities;
+
+use crate::payload::PayloadCodec;
+
+pub const COMPRESSION_NONE: &str = "none";
+pub const BASELINE_FRAMING: u32 = 1;
+
+/// Capabilities of this node, in order of preference.
+pub fn supported() -> Capabilities {
+    Capabilities {
+        codecs: vec![PayloadCodec::MsgPack.tag(), PayloadCodec::Json.tag()],
+        compression: vec![COMPRESSION_NONE.to_string()],
+        framing: vec![BASELINE_FRAMING],
+    }
+}
+
+/// Transport options agreed with a peer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Negotiated {
+    pub codec: PayloadCodec,
+    pub compression: String,
+    pub framing: u32,
+    /// Peer didn't take part in the handshake
+    pub legacy: bool,
+}
+
+impl Negotiated {
+    /// Options supported by every node.
+    pub fn baseline() -> Self {
+        Negotiated {
+            codec: PayloadCodec::Json,
+            compression: COMPRESSION_NONE.to_string(),
+            framing: BASELINE_FRAMING,
+            legacy: false,
+        }
+    }
+
+    pub fn legacy() -> Self {
+        Negotiated {
+            legacy: true,
+            ..Self::baseline()
+        }
+    }
+}
+
+/// Picks the first option of `local`, which `remote` supports as well.
+/// `remote` is `None` for legacy peers.
+pub fn negotiate(local: &Capabilities, remote: Option<&Capabilities>) -> Negotiated {
+    let remote = match remote {
+        Some(remote) => remote,
+        None => return Negotiated::legacy(),
+    };
+    let baseline = Negotiated::baseline();
+
+    Negotiated {
+        codec: common(&local.codecs, &remote.codecs)
+            .and_then(|tag| PayloadCodec::try_from(*tag).ok())
+            .unwrap_or(baseline.codec),
+        compression: common(&local.compression, &remote.compression)
+            .cloned()
+            .unwrap_or(baseline.compression),
+        framing: common(&local.framing, &remote.framing)
+            .copied()
+            .unwrap_or(baseline.framing),
+        legacy: false,
+    }
+}
+
+fn common<'a, T: PartialEq>(local: &'a [T], remote: &[T]) -> Option<&'a T> {
+    local.iter().find(|option| remote.contains(option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capable_peers_use_preferred_options() {
+        let negotiated = negotiate(&supported(), Some(&supported()));
+        assert_eq!(negotiated.codec, PayloadCodec::MsgPack);
+        assert_eq!(negotiated.compression, COMPRESSION_NONE);
+        assert!(!negotiated.legacy);
+    }
+
+    #[test]
+    fn test_legacy_peer_gets_baseline() {
+        let negotiated = negotiate(&supported(), None);
+        assert_eq!(negotiated, Negotiated::legacy());
+        assert_eq!(negotiated.codec, PayloadCodec::Json);
+        assert_eq!(negotiated.compression, COMPRESSION_NONE);
+        assert_eq!(negotiated.framing, BASELINE_FRAMING);
+
+        // Peer taking part in the handshake, but supporting nothing beyond the baseline
+        let remote = Capabilities {
+            codecs: vec![PayloadCodec::Json.tag()],
+            ..Default::default()
+        };
+        assert_eq!(
+            negotiate(&supported(), Some(&remote)),
+            Negotiated::baseline()
+        );
+    }
+
+    #[test]
+    fn test_unknown_options_are_ignored() {
+        // Newer peer preferring options unknown to this node
+        let remote = Capabilities {
+            codecs: vec![7, PayloadCodec::MsgPack.tag()],
+            compression: vec!["zstd".to_string(), COMPRESSION_NONE.to_string()],
+            framing: vec![2, BASELINE_FRAMING],
+        };
+        let negotiated = negotiate(&supported(), Some(&remote));
+        assert_eq!(negotiated.codec, PayloadCodec::MsgPack);
+        assert_eq!(negotiated.compression, COMPRESSION_NONE);
+        assert_eq!(negotiated.framing, BASELINE_FRAMING);
+    }
+}
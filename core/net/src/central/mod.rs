@@ -4,7 +4,7 @@ mod handler;
 mod service;
 
 pub use api::*;
-pub use service::{bind_remote, Net};
+pub use service::{active_hub, bind_remote, Net, NetHandle};
 
 use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::rc::Rc;
+use std::sync::Mutex;
 
 use futures::channel::oneshot;
 use futures::prelude::*;
@@ -17,21 +18,82 @@ use ya_service_bus::{
 };
 use ya_utils_networking::resolver;
 
+use crate::addr::to_local_addr;
 use crate::bcast::BCastService;
 use crate::central::handler::CentralBusHandler;
 use crate::central::SUBSCRIPTIONS;
 use crate::config::Config;
+use crate::error::NetError;
+use crate::identity::IdentityProvider;
 
 const CENTRAL_ADDR_ENV_VAR: &str = "CENTRAL_NET_HOST";
 
-async fn central_net_addr() -> std::io::Result<SocketAddr> {
-    Ok(match std::env::var(CENTRAL_ADDR_ENV_VAR) {
+lazy_static::lazy_static! {
+    static ref ACTIVE_HUB: Mutex<Option<SocketAddr>> = Default::default();
+}
+
+thread_local! {
+    static NET_HANDLE: RefCell<Option<NetHandle>> = Default::default();
+}
+
+/// Address of the hub, which the net service is currently connected to.
+pub fn active_hub() -> Option<SocketAddr> {
+    *ACTIVE_HUB.lock().unwrap()
+}
+
+/// Hub addresses in order of preference. `CENTRAL_NET_HOST` may contain
+/// a comma separated list of fallback hubs.
+async fn central_net_addrs() -> std::io::Result<Vec<SocketAddr>> {
+    let hosts = match std::env::var(CENTRAL_ADDR_ENV_VAR) {
         Ok(v) => v,
         Err(_) => resolver::resolve_yagna_srv_record("_net._tcp").await?,
+    };
+
+    let mut addrs = Vec::new();
+    for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+        match host.to_socket_addrs().map(|mut a| a.next()) {
+            Ok(Some(addr)) => addrs.push(addr),
+            Ok(None) => log::warn!("Central net hub {} has no address", host),
+            Err(e) => log::warn!("Unable to resolve central net hub {}: {}", host, e),
+        }
     }
-    .to_socket_addrs()?
-    .next()
-    .expect("central net hub addr needed"))
+    if addrs.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no central net hub address in: {}", hosts),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Connects to the first available hub, starting with the `active` one
+/// and cycling through the remaining ones.
+async fn connect_hub<T, F, Fut>(
+    hubs: &[SocketAddr],
+    active: Option<SocketAddr>,
+    connect: F,
+) -> std::io::Result<(SocketAddr, T)>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = std::io::Result<T>>,
+{
+    let start = active
+        .and_then(|active| hubs.iter().position(|hub| *hub == active))
+        .unwrap_or(0);
+
+    let mut last_error = None;
+    for hub in hubs[start..].iter().chain(hubs[..start].iter()) {
+        match connect(*hub).await {
+            Ok(conn) => return Ok((*hub, conn)),
+            Err(e) => {
+                log::warn!("Unable to connect to central net hub {}: {}", hub, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "no central net hubs")
+    }))
 }
 
 /// Initialize net module on a hub.
@@ -39,37 +101,52 @@ pub async fn bind_remote(
     client_info: ClientInfo,
     default_node_id: NodeId,
     nodes: Vec<NodeId>,
-) -> std::io::Result<oneshot::Receiver<()>> {
-    let hub_addr = central_net_addr().await?;
-    let conn = connection::tcp(hub_addr).await?;
+    prefix: String,
+    max_message_size: usize,
+) -> Result<oneshot::Receiver<()>, NetError> {
+    let hubs = central_net_addrs().await.map_err(NetError::HubConnect)?;
+    let (hub_addr, conn) = connect_hub(&hubs, active_hub(), |addr| connection::tcp(addr))
+        .await
+        .map_err(NetError::HubConnect)?;
+    if active_hub() != Some(hub_addr) {
+        log::info!("Connected to central net hub {}", hub_addr);
+        ACTIVE_HUB.lock().unwrap().replace(hub_addr);
+    }
     let bcast = BCastService::default();
     let bcast_service_id = <SendBroadcastMessage<()> as RpcMessage>::ID;
 
     // connect to hub with forwarding handler
-    let own_net_nodes: Vec<_> = nodes.iter().map(|id| net_service(id)).collect();
+    let own_net_nodes: Vec<_> = nodes.iter().map(|id| prefixed(&prefix, id)).collect();
 
     let forward_call = move |request_id: String, caller: String, addr: String, data: Vec<u8>| {
-        let prefix = own_net_nodes
-            .iter()
-            .find(|&own_net_node_id| addr.starts_with(own_net_node_id));
-        if let Some(prefix) = prefix {
-            // replaces  /net/<dest_node_id>/test/1 --> /public/test/1
-            let local_addr: String = addr.replacen(prefix, net::PUBLIC_PREFIX, 1);
-            log::trace!(
-                "Incoming msg from = {}, to = {}, fwd to local addr = {}, request_id: {}",
+        if let Err(e) = check_message_size(data.len(), max_message_size) {
+            log::warn!(
+                "Dropping incoming msg from = {}, to = {}, request_id: {}: {}",
                 caller,
                 addr,
-                local_addr,
-                request_id
+                request_id,
+                e
             );
-            // actual forwarding to my local bus
-            local_bus::call_stream(&local_addr, &caller, &data).right_stream()
-        } else {
-            return stream::once(future::err(Error::GsbBadRequest(format!(
-                "wrong routing: {}; I'll accept only addrs starting with: {:?}",
-                addr, own_net_nodes
-            ))))
-            .left_stream();
+            return stream::once(future::err(e)).left_stream();
+        }
+        // replaces  /net/<dest_node_id>/test/1 --> /public/test/1
+        match to_local_addr(&addr, &own_net_nodes) {
+            Ok(local_addr) => {
+                log::trace!(
+                    "Incoming msg from = {}, to = {}, fwd to local addr = {}, request_id: {}",
+                    caller,
+                    addr,
+                    local_addr,
+                    request_id
+                );
+                crate::stats::record_received(data.len());
+                // actual forwarding to my local bus
+                local_bus::call_stream(&local_addr, &caller, &data).right_stream()
+            }
+            Err(e) => {
+                log::debug!("Rejecting incoming msg from {}: {}", caller, e);
+                stream::once(future::err(Error::GsbBadRequest(e.to_string()))).left_stream()
+            }
         }
     };
 
@@ -97,19 +174,19 @@ pub async fn bind_remote(
 
     // bind my local net service(s) on remote centralised bus under /net/<my_identity>
     for node in &nodes {
-        let addr = net_service(node);
+        let addr = prefixed(&prefix, node);
         central_bus
             .bind(addr.clone())
             .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e)))?;
+            .map_err(|e| NetError::Bind(addr.clone(), e.to_string()))?;
         log::info!("network service bound at: {} under: {}", hub_addr, addr);
     }
 
-    bind_net_handler(net::BUS_ID, central_bus.clone(), default_node_id);
-    bind_net_handler(net::BUS_ID_UDP, central_bus.clone(), default_node_id);
+    bind_net_handler(&prefix, central_bus.clone(), default_node_id);
+    bind_net_handler(&udp(&prefix), central_bus.clone(), default_node_id);
 
-    bind_from_handler("/from", central_bus.clone(), nodes.clone());
-    bind_from_handler("/udp/from", central_bus.clone(), nodes.clone());
+    bind_from_handler("/from", central_bus.clone(), nodes.clone(), prefix.clone());
+    bind_from_handler("/udp/from", central_bus.clone(), nodes.clone(), prefix);
 
     // Subscribe broadcast on remote
     {
@@ -177,6 +254,24 @@ pub async fn bind_remote(
     Ok(done_rx)
 }
 
+fn prefixed(prefix: &str, node: impl ToString) -> String {
+    format!("{}/{}", prefix, node.to_string())
+}
+
+fn udp(prefix: &str) -> String {
+    format!("/udp{}", prefix)
+}
+
+fn check_message_size(size: usize, max_message_size: usize) -> Result<(), Error> {
+    match size > max_message_size {
+        true => Err(Error::GsbBadRequest(format!(
+            "message too large: {} B exceeds the limit of {} B",
+            size, max_message_size
+        ))),
+        false => Ok(()),
+    }
+}
+
 fn strip_udp(addr: &str) -> &str {
     // Central NET doesn't support unreliable transport, so we just remove prefix
     // and use reliable protocol.
@@ -215,6 +310,7 @@ fn bind_net_handler<Transport, H>(
         let addr = strip_udp(addr);
 
         log_message("rpc", &caller, addr);
+        crate::stats::record_sent(msg.len());
         let addr = addr.to_string();
         central_bus_rpc
             .call(caller, addr.clone(), Vec::from(msg))
@@ -228,6 +324,7 @@ fn bind_net_handler<Transport, H>(
         let addr = strip_udp(addr);
 
         log_message("stream", &caller, addr);
+        crate::stats::record_sent(msg.len());
         let addr = addr.to_string();
         central_bus_stream
             .call_streaming(caller, addr.clone(), Vec::from(msg))
@@ -241,6 +338,7 @@ fn bind_from_handler<Transport, H>(
     addr: &str,
     central_bus: ConnectionRef<Transport, H>,
     nodes: Vec<NodeId>,
+    prefix: String,
 ) where
     Transport: Sink<GsbMessage, Error = ProtocolError>
         + Stream<Item = Result<GsbMessage, ProtocolError>>
@@ -251,10 +349,11 @@ fn bind_from_handler<Transport, H>(
     // bind /from/<caller>/to/<addr> on my local bus and forward all calls to remote bus under /net
     let nodes_rpc = nodes.clone();
     let central_bus_rpc = central_bus.clone();
+    let prefix_rpc = prefix.clone();
     let rpc = move |_caller: &str, addr: &str, msg: &[u8]| {
         let addr = strip_udp(addr);
 
-        let (from_node, to_addr) = match parse_from_addr(addr) {
+        let (from_node, to_addr) = match parse_from_addr(addr, &prefix_rpc) {
             Ok(v) => v,
             Err(e) => return future::err(Error::GsbBadRequest(e.to_string())).left_future(),
         };
@@ -267,6 +366,7 @@ fn bind_from_handler<Transport, H>(
             .left_future();
         }
 
+        crate::stats::record_sent(msg.len());
         central_bus_rpc
             .call(from_node.to_string(), to_addr.clone(), Vec::from(msg))
             .map_err(|e| Error::RemoteError(to_addr, e.to_string()))
@@ -278,7 +378,7 @@ fn bind_from_handler<Transport, H>(
     let stream = move |_caller: &str, addr: &str, msg: &[u8]| {
         let addr = strip_udp(addr);
 
-        let (from_node, to_addr) = match parse_from_addr(addr) {
+        let (from_node, to_addr) = match parse_from_addr(addr, &prefix) {
             Ok(v) => v,
             Err(e) => {
                 let err = Error::GsbBadRequest(e.to_string());
@@ -298,6 +398,7 @@ fn bind_from_handler<Transport, H>(
                 .left_stream();
         }
 
+        crate::stats::record_sent(msg.len());
         central_bus_stream
             .call_streaming(from_node.to_string(), to_addr, Vec::from(msg))
             .right_stream()
@@ -306,20 +407,17 @@ fn bind_from_handler<Transport, H>(
     local_bus::subscribe(addr, rpc, stream);
 }
 
-async fn unbind_remote(nodes: Vec<NodeId>) {
+async fn unbind_remote(nodes: Vec<NodeId>, prefix: String) {
     let addrs = nodes
         .into_iter()
-        .map(|node_id| net_service(node_id))
+        .map(|node_id| prefixed(&prefix, node_id))
         .chain(std::iter::once(format!(
             "{}/{}",
             local_net::BUS_ID,
             <SendBroadcastMessage<()> as RpcMessage>::ID
         )))
-        .chain(
-            [net::BUS_ID, local_net::BUS_ID, "/from"]
-                .iter()
-                .map(|s| s.to_string()),
-        )
+        .chain([local_net::BUS_ID, "/from"].iter().map(|s| s.to_string()))
+        .chain(std::iter::once(prefix))
         .collect::<Vec<_>>();
 
     log::debug!("Unbinding remote handlers");
@@ -360,18 +458,25 @@ where
     let unbind_clone = unbind.clone();
 
     loop {
+        if reconnect.borrow().stopped {
+            log::debug!("Net service stopped, not binding handlers");
+            return Ok(());
+        }
         match bind().await {
             Ok(dc_rx) => {
                 if let Some(start) = reconnect.borrow_mut().last_disconnect {
                     let end = Instant::now();
                     metrics::timing!("net.reconnect.time", start, end);
                 }
-                reconnect.replace(Default::default());
+                reconnect.borrow_mut().reset();
                 metrics::counter!("net.connect", 1);
 
                 let reconnect_clone = reconnect.clone();
                 tokio::task::spawn_local(async move {
                     if let Ok(_) = dc_rx.await {
+                        if reconnect_clone.borrow().stopped {
+                            return;
+                        }
                         metrics::counter!("net.disconnect", 1);
                         reconnect_clone.borrow_mut().last_disconnect = Some(Instant::now());
                         log::warn!("Handlers disconnected");
@@ -382,11 +487,26 @@ where
                 break;
             }
             Err(error) => {
-                let delay = { reconnect.borrow_mut().next().unwrap() };
+                let (delay, attempt) = {
+                    let mut reconnect = reconnect.borrow_mut();
+                    (reconnect.next(), reconnect.attempts)
+                };
+                let delay = match delay {
+                    Some(delay) => delay,
+                    None => {
+                        log::error!(
+                            "Failed to bind handlers: {}; giving up after {} attempts",
+                            error,
+                            attempt - 1
+                        );
+                        return Err(anyhow::anyhow!("Unable to connect to the net hub"));
+                    }
+                };
                 log::warn!(
-                    "Failed to bind handlers: {}; retrying in {} s",
+                    "Failed to bind handlers: {}; retrying in {} s (attempt {})",
                     error,
-                    delay.as_secs_f32()
+                    delay.as_secs_f32(),
+                    attempt
                 );
                 tokio::select! {
                     _ = tokio::signal::ctrl_c() => {
@@ -398,23 +518,61 @@ where
         }
     }
 
-    tokio::task::spawn_local(
-        rx.then(move |_| rebind(reconnect, bind, unbind).then(|_| futures::future::ready(()))),
-    );
+    tokio::task::spawn_local(rx.then(move |_| {
+        rebind(reconnect, bind, unbind).then(|result| {
+            if let Err(e) = result {
+                log::error!("Net service is disconnected from the hub: {}", e);
+            }
+            futures::future::ready(())
+        })
+    }));
     Ok(())
 }
 
+/// Exponential back-off of reconnection attempts.
+/// Yields no more delays after `max_retries` consecutive failed attempts.
 pub(crate) struct ReconnectContext {
+    pub initial: f32, // s
     pub current: f32, // s
     pub max: f32,     // s
     pub factor: f32,
+    pub attempts: u32,
+    pub max_retries: Option<u32>,
     pub last_disconnect: Option<Instant>,
+    /// Set when the net service is shut down; prevents reconnecting
+    pub stopped: bool,
+}
+
+impl ReconnectContext {
+    pub fn from_config(config: &Config) -> Self {
+        let initial = config.hub_reconnect_delay.as_secs_f32();
+        ReconnectContext {
+            initial,
+            current: initial,
+            max: config.hub_reconnect_max_delay.as_secs_f32(),
+            max_retries: config.hub_reconnect_max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// Restarts the back-off after a successful connection.
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+        self.attempts = 0;
+        self.last_disconnect = None;
+    }
 }
 
 impl Iterator for ReconnectContext {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.attempts += 1;
+        if let Some(max_retries) = self.max_retries {
+            if self.attempts > max_retries {
+                return None;
+            }
+        }
         self.current = self.max.min(self.current * self.factor);
         Some(Duration::from_secs_f32(self.current))
     }
@@ -423,19 +581,55 @@ impl Iterator for ReconnectContext {
 impl Default for ReconnectContext {
     fn default() -> Self {
         ReconnectContext {
+            initial: 1.,
             current: 1.,
             max: 1800.,
             factor: 2.,
+            attempts: 0,
+            max_retries: None,
             last_disconnect: None,
+            stopped: false,
         }
     }
 }
 
+/// Stops the central net service, so that it can be re-initialized
+/// (e.g. with another identity) without leaking the hub connection.
+#[derive(Clone)]
+pub struct NetHandle {
+    ids: Vec<NodeId>,
+    prefix: String,
+    reconnect: Rc<RefCell<ReconnectContext>>,
+}
+
+impl NetHandle {
+    /// Handle of the net service running on this thread.
+    pub fn current() -> Option<NetHandle> {
+        NET_HANDLE.with(|h| h.borrow().clone())
+    }
+
+    /// Stops reconnecting to the hub and unbinds net handlers,
+    /// which releases the hub connection.
+    pub async fn stop(&self) {
+        if std::mem::replace(&mut self.reconnect.borrow_mut().stopped, true) {
+            return;
+        }
+        unbind_remote(self.ids.clone(), self.prefix.clone()).await;
+        ACTIVE_HUB.lock().unwrap().take();
+        NET_HANDLE.with(|h| h.borrow_mut().take());
+        log::info!("Central net service stopped");
+    }
+}
+
 pub struct Net;
 
 impl Net {
-    pub async fn gsb<Context>(_: Context, _config: Config) -> anyhow::Result<()> {
-        let (default_id, ids) = crate::service::identities().await?;
+    pub async fn gsb<Context>(
+        _: Context,
+        config: Config,
+        identity: Rc<dyn IdentityProvider>,
+    ) -> anyhow::Result<()> {
+        let (default_id, ids) = identity.identities().await?;
         log::info!(
             "CENTRAL_NET - Using default identity as network id: {:?}",
             default_id
@@ -443,24 +637,56 @@ impl Net {
 
         let client_info = ClientInfo::new("sb-client-net");
         let ids_clone = ids.clone();
+        let handle_ids = ids.clone();
+
+        let prefix = config.net_prefix.clone();
+        let unbind_prefix = prefix.clone();
+        let max_message_size = config.max_message_size;
 
         let bind = move || {
             let client_info = client_info.clone();
             let ids = ids.clone();
+            let prefix = prefix.clone();
             async move {
-                let rx = bind_remote(client_info.clone(), default_id, ids.clone()).await?;
+                let rx = bind_remote(
+                    client_info.clone(),
+                    default_id,
+                    ids.clone(),
+                    prefix,
+                    max_message_size,
+                )
+                .await?;
                 resubscribe().await;
                 Ok(rx)
             }
         };
-        let unbind = Rc::new(RefCell::new(move || unbind_remote(ids_clone.clone())));
+        let unbind = Rc::new(RefCell::new(move || {
+            unbind_remote(ids_clone.clone(), unbind_prefix.clone())
+        }));
+
+        let reconnect = Rc::new(RefCell::new(ReconnectContext::from_config(&config)));
+        let handle = NetHandle {
+            ids: handle_ids,
+            prefix: config.net_prefix.clone(),
+            reconnect: reconnect.clone(),
+        };
+        NET_HANDLE.with(|h| h.borrow_mut().replace(handle));
 
-        rebind(Default::default(), bind, unbind).await?;
+        rebind(reconnect, bind, unbind).await?;
+        Ok(())
+    }
+
+    pub async fn shutdown() -> anyhow::Result<()> {
+        if let Some(handle) = NetHandle::current() {
+            handle.stop().await;
+        }
         Ok(())
     }
 }
 
-pub(crate) fn parse_from_addr(from_addr: &str) -> anyhow::Result<(NodeId, String)> {
+/// Parses `/from/<from_node>/to/<to_node>/<service>` into `from_node`
+/// and `<prefix>/<to_node>/<service>`.
+pub(crate) fn parse_from_addr(from_addr: &str, prefix: &str) -> anyhow::Result<(NodeId, String)> {
     let mut it = from_addr.split("/").fuse();
     if let (Some(""), Some("from"), Some(from_node_id), Some("to"), Some(to_node_id)) =
         (it.next(), it.next(), it.next(), it.next(), it.next())
@@ -469,7 +695,7 @@ pub(crate) fn parse_from_addr(from_addr: &str) -> anyhow::Result<(NodeId, String
         let prefix = 10 + from_node_id.len();
         let service_id = &from_addr[prefix..];
         if let Some(_) = it.next() {
-            return Ok((from_node_id.parse()?, net_service(service_id)));
+            return Ok((from_node_id.parse()?, prefixed(prefix, service_id)));
         }
     }
     anyhow::bail!("invalid net-from destination: {}", from_addr)
@@ -478,6 +704,7 @@ pub(crate) fn parse_from_addr(from_addr: &str) -> anyhow::Result<(NodeId, String
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::executor::block_on;
     use ya_core_model::net::RemoteEndpoint;
 
     #[test]
@@ -493,8 +720,8 @@ mod tests {
             .to(dst)
             .service("/public/test/echo");
         let addr = remote_service.addr();
-        eprintln!("from/to service address: {}", addr);
-        let (parsed_from, parsed_to) = parse_from_addr(addr).unwrap();
+        log::trace!("from/to service address: {}", addr);
+        let (parsed_from, parsed_to) = parse_from_addr(addr, net::BUS_ID).unwrap();
         assert_eq!(parsed_from, from_id);
         assert_eq!(
             parsed_to,
@@ -502,15 +729,105 @@ mod tests {
         );
     }
 
+    #[test]
+    fn messages_over_size_limit_are_rejected() {
+        assert!(check_message_size(0, 1024).is_ok());
+        assert!(check_message_size(1024, 1024).is_ok());
+        match check_message_size(1025, 1024) {
+            Err(Error::GsbBadRequest(_)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reconnect_backs_off_until_max_retries() {
+        let mut reconnect = ReconnectContext {
+            max: 5.,
+            max_retries: Some(4),
+            ..Default::default()
+        };
+        let delays = reconnect.by_ref().map(|d| d.as_secs()).collect::<Vec<_>>();
+        assert_eq!(delays, vec![2, 4, 5, 5]);
+        assert!(reconnect.next().is_none());
+
+        reconnect.reset();
+        assert_eq!(reconnect.next(), Some(Duration::from_secs(2)));
+        assert_eq!(reconnect.attempts, 1);
+    }
+
+    #[test]
+    fn stopped_service_does_not_rebind() {
+        let reconnect = Rc::new(RefCell::new(ReconnectContext {
+            stopped: true,
+            ..Default::default()
+        }));
+        let bind = || -> future::Ready<std::io::Result<future::Ready<Result<(), ()>>>> {
+            panic!("handlers bound after shutdown")
+        };
+        let unbind = Rc::new(RefCell::new(|| future::ready(())));
+
+        futures::executor::block_on(rebind(reconnect, bind, unbind)).unwrap();
+    }
+
+    #[test]
+    fn hubs_fail_over_in_order() {
+        let hubs = ["127.0.0.1:7001", "127.0.0.1:7002", "127.0.0.1:7003"]
+            .iter()
+            .map(|h| h.parse::<SocketAddr>().unwrap())
+            .collect::<Vec<_>>();
+        let tried = RefCell::new(Vec::new());
+        let connect = |down: &'static [usize]| {
+            let hubs = hubs.clone();
+            let tried = &tried;
+            move |hub: SocketAddr| {
+                tried.borrow_mut().push(hub);
+                let idx = hubs.iter().position(|h| *h == hub).unwrap();
+                future::ready(match down.contains(&idx) {
+                    true => Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)),
+                    false => Ok(idx),
+                })
+            }
+        };
+
+        let connected = block_on(connect_hub(&hubs, None, connect(&[0]))).unwrap();
+        assert_eq!(connected, (hubs[1], 1));
+
+        // Starts with the active hub and cycles back to the first one
+        tried.borrow_mut().clear();
+        let (hub, _) = block_on(connect_hub(&hubs, Some(hubs[1]), connect(&[1, 2]))).unwrap();
+        assert_eq!(hub, hubs[0]);
+        assert_eq!(*tried.borrow(), vec![hubs[1], hubs[2], hubs[0]]);
+
+        assert!(block_on(connect_hub(&hubs, None, connect(&[0, 1, 2]))).is_err());
+    }
+
     #[test]
     fn parse_no_service_should_fail() {
-        let out = parse_from_addr("/from/0xe93ab94a2095729ad0b7cfa5bfd7d33e1b44d6df/to/0x99402605903da83901151b0871ebeae9296ef66b");
+        let out = parse_from_addr("/from/0xe93ab94a2095729ad0b7cfa5bfd7d33e1b44d6df/to/0x99402605903da83901151b0871ebeae9296ef66b", net::BUS_ID);
         assert!(out.is_err())
     }
 
     #[test]
     fn parse_with_service_should_pass() {
-        let out = parse_from_addr("/from/0xe93ab94a2095729ad0b7cfa5bfd7d33e1b44d6df/to/0x99402605903da83901151b0871ebeae9296ef66b/x");
+        let out = parse_from_addr("/from/0xe93ab94a2095729ad0b7cfa5bfd7d33e1b44d6df/to/0x99402605903da83901151b0871ebeae9296ef66b/x", net::BUS_ID);
         assert!(out.is_ok())
     }
+
+    #[test]
+    fn instances_on_different_prefixes_are_isolated() {
+        let from_id = "0xe93ab94a2095729ad0b7cfa5bfd7d33e1b44d6df";
+        let node = "0x99402605903da83901151b0871ebeae9296ef66b";
+        let from_addr = format!("/from/{}/to/{}/test", from_id, node);
+
+        let (_, to_a) = parse_from_addr(&from_addr, "/net-a").unwrap();
+        let (_, to_b) = parse_from_addr(&from_addr, "/net-b").unwrap();
+        assert_eq!(to_a, format!("/net-a/{}/test", node));
+        assert_eq!(to_b, format!("/net-b/{}/test", node));
+
+        let own_a = vec![prefixed("/net-a", node)];
+        let own_b = vec![prefixed("/net-b", node)];
+        assert_eq!(to_local_addr(&to_a, &own_a).unwrap(), "/public/test");
+        assert!(to_local_addr(&to_a, &own_b).is_err());
+        assert!(to_local_addr(&to_b, &own_a).is_err());
+    }
 }
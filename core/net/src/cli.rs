@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt::Display;
 use std::time::Duration;
 
@@ -9,6 +10,8 @@ use ya_core_model::net::local as model;
 use ya_service_api::{CliCtx, CommandOutput, ResponseTable};
 use ya_service_bus::{typed as bus, RpcEndpoint};
 
+use crate::payload::PayloadCodec;
+
 #[derive(StructOpt, Debug)]
 #[structopt(setting = clap::AppSettings::DeriveDisplayOrder)]
 /// Network management
@@ -21,6 +24,10 @@ pub enum NetCommand {
     Sockets {},
     /// Ping connected nodes
     Ping {},
+    /// List recently reachable peers
+    Peers {},
+    /// List transport capabilities negotiated with peers
+    Capabilities {},
 }
 
 impl NetCommand {
@@ -152,6 +159,67 @@ impl NetCommand {
                 }
                 .into())
             }
+            NetCommand::Peers {} => {
+                let peers = bus::service(model::BUS_ID)
+                    .send(model::ReachablePeers {})
+                    .await
+                    .map_err(|e| anyhow::Error::msg(e))??;
+
+                Ok(ResponseTable {
+                    columns: vec![
+                        "nodeId".into(),
+                        "seen".into(),
+                        "successes".into(),
+                        "failures".into(),
+                    ],
+                    values: peers
+                        .into_iter()
+                        .map(|p| {
+                            let seen = Duration::from_secs(p.seen.as_secs());
+                            serde_json::json! {[
+                                p.node_id.to_string(),
+                                format_duration(seen).to_string(),
+                                p.successes,
+                                p.failures,
+                            ]}
+                        })
+                        .collect(),
+                }
+                .into())
+            }
+            NetCommand::Capabilities {} => {
+                let peers = bus::service(model::BUS_ID)
+                    .send(model::PeerCapabilities {})
+                    .await
+                    .map_err(|e| anyhow::Error::msg(e))??;
+
+                Ok(ResponseTable {
+                    columns: vec![
+                        "nodeId".into(),
+                        "codec".into(),
+                        "compression".into(),
+                        "framing".into(),
+                        "legacy".into(),
+                    ],
+                    values: peers
+                        .into_iter()
+                        .map(|p| {
+                            let codec = match PayloadCodec::try_from(p.codec) {
+                                Ok(codec) => format!("{:?}", codec),
+                                Err(tag) => format!("unknown ({})", tag),
+                            };
+                            serde_json::json! {[
+                                p.node_id.to_string(),
+                                codec,
+                                p.compression,
+                                p.framing,
+                                p.legacy,
+                            ]}
+                        })
+                        .collect(),
+                }
+                .into())
+            }
         }
     }
 }
@@ -4,6 +4,8 @@ use strum::VariantNames;
 use strum::{EnumString, EnumVariantNames, IntoStaticStr};
 use url::Url;
 
+use ya_core_model::NodeId;
+
 #[derive(StructOpt, EnumString, EnumVariantNames, IntoStaticStr, Clone)]
 #[strum(serialize_all = "lowercase")]
 pub enum NetType {
@@ -26,6 +28,33 @@ pub struct Config {
     pub session_expiration: Duration,
     #[structopt(env = "YA_NET_VIRTUAL_TCP_BUFFER_SIZE_MULTIPLIER", default_value = "4")]
     pub vtcp_buffer_size_multiplier: usize,
+    #[structopt(env = "YA_NET_QUEUE_SATURATION_WARN", parse(try_from_str = humantime::parse_duration), default_value = "10s")]
+    pub queue_saturation_warn: Duration,
+    #[structopt(env = "YA_NET_QUEUE_SATURATION_WARN_INTERVAL", parse(try_from_str = humantime::parse_duration), default_value = "1min")]
+    pub queue_saturation_warn_interval: Duration,
+    #[structopt(env = "YA_NET_PEER_IDLE_TIMEOUT", parse(try_from_str = humantime::parse_duration), default_value = "5min")]
+    pub peer_idle_timeout: Duration,
+    /// Comma separated node ids. When not empty, only these peers are allowed
+    #[structopt(long, env = "YA_NET_ALLOW_NODES", use_delimiter = true)]
+    pub allow_nodes: Vec<NodeId>,
+    /// Comma separated node ids of peers, which are not allowed
+    #[structopt(long, env = "YA_NET_DENY_NODES", use_delimiter = true)]
+    pub deny_nodes: Vec<NodeId>,
+    /// Maximum number of consecutive attempts to reconnect to the central net hub.
+    /// Retries indefinitely, when not set
+    #[structopt(long, env = "YA_NET_HUB_RECONNECT_MAX_RETRIES")]
+    pub hub_reconnect_max_retries: Option<u32>,
+    #[structopt(env = "YA_NET_HUB_RECONNECT_DELAY", parse(try_from_str = humantime::parse_duration), default_value = "1s")]
+    pub hub_reconnect_delay: Duration,
+    #[structopt(env = "YA_NET_HUB_RECONNECT_MAX_DELAY", parse(try_from_str = humantime::parse_duration), default_value = "30min")]
+    pub hub_reconnect_max_delay: Duration,
+    /// GSB prefix, under which central net services of this node are bound.
+    /// Non-default prefix isolates the instance from other ones sharing the hub
+    #[structopt(env = "YA_NET_PREFIX", default_value = "/net")]
+    pub net_prefix: String,
+    /// Size in bytes of the largest message accepted from the central net hub
+    #[structopt(env = "YA_NET_MAX_MESSAGE_SIZE", default_value = "67108864")]
+    pub max_message_size: usize,
 }
 
 impl Config {
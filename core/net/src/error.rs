@@ -0,0 +1,25 @@
+use std::io;
+
+#[derive(thiserror::Error, Debug)]
+pub enum NetError {
+    #[error("unable to connect to the net hub: {0}")]
+    HubConnect(#[source] io::Error),
+    #[error("unable to bind {0}: {1}")]
+    Bind(String, String),
+    #[error("unable to subscribe to {0}: {1}")]
+    Subscribe(String, String),
+    #[error("unable to send message to {0}: {1}")]
+    Send(String, String),
+    #[error("call to {0} timed out")]
+    Timeout(String),
+}
+
+impl From<NetError> for io::Error {
+    fn from(e: NetError) -> Self {
+        match e {
+            NetError::HubConnect(e) => e,
+            NetError::Timeout(_) => io::Error::new(io::ErrorKind::TimedOut, e),
+            e => io::Error::new(io::ErrorKind::Other, e),
+        }
+    }
+}
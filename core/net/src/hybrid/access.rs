@@ -0,0 +1,66 @@
+//! Restriction of peers, which this node exchanges messages with.
+use std::collections::HashSet;
+
+use ya_core_model::NodeId;
+
+/// Peers allowed to exchange messages with this node.
+///
+/// The denylist takes precedence over the allowlist.
+/// An empty allowlist allows all peers, which are not denied.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PeerAccess {
+    allow: HashSet<NodeId>,
+    deny: HashSet<NodeId>,
+}
+
+impl PeerAccess {
+    pub fn new(
+        allow: impl IntoIterator<Item = NodeId>,
+        deny: impl IntoIterator<Item = NodeId>,
+    ) -> Self {
+        PeerAccess {
+            allow: allow.into_iter().collect(),
+            deny: deny.into_iter().collect(),
+        }
+    }
+
+    pub fn is_allowed(&self, node_id: &NodeId) -> bool {
+        if self.deny.contains(node_id) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.contains(node_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(n: u8) -> NodeId {
+        format!("0x{:040x}", n).parse().unwrap()
+    }
+
+    #[test]
+    fn test_empty_lists_allow_all() {
+        let access = PeerAccess::default();
+        assert!(access.is_allowed(&node(1)));
+        assert!(access.is_allowed(&node(2)));
+    }
+
+    #[test]
+    fn test_denylist() {
+        let access = PeerAccess::new(vec![], vec![node(2)]);
+        assert!(access.is_allowed(&node(1)));
+        assert!(!access.is_allowed(&node(2)));
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let access = PeerAccess::new(vec![node(1), node(2)], vec![node(2)]);
+        assert!(access.is_allowed(&node(1)));
+        // Denylist takes precedence
+        assert!(!access.is_allowed(&node(2)));
+        // Not in the allowlist
+        assert!(!access.is_allowed(&node(3)));
+    }
+}
@@ -6,11 +6,12 @@ use futures::SinkExt;
 use ya_core_model::net::local::{
     BindBroadcastError, BroadcastMessage, SendBroadcastMessage, ToEndpoint,
 };
+use ya_core_model::NodeId;
 use ya_sb_proto::codec::GsbMessage;
 use ya_service_bus::{serialization, Error, RpcMessage};
 
 use crate::hybrid::codec::encode_message;
-use crate::hybrid::service::{BCAST, BCAST_HANDLERS, BCAST_SENDER};
+use crate::hybrid::service::{BcastTargets, BCAST, BCAST_HANDLERS, BCAST_SENDER};
 
 pub async fn broadcast<M, S>(
     caller: S,
@@ -22,6 +23,63 @@ pub async fn broadcast<M, S>(
     >,
     Error,
 >
+where
+    M: BroadcastMessage + Send + Sync + Unpin + 'static,
+    S: ToString + 'static,
+{
+    broadcast_with_fanout(caller, message, None).await
+}
+
+/// Broadcasts `message` to at most `fanout` random peers.
+/// Uses configured broadcast size, when `fanout` is `None`.
+pub async fn broadcast_with_fanout<M, S>(
+    caller: S,
+    message: M,
+    fanout: Option<u32>,
+) -> Result<
+    Result<
+        <SendBroadcastMessage<M> as RpcMessage>::Item,
+        <SendBroadcastMessage<M> as RpcMessage>::Error,
+    >,
+    Error,
+>
+where
+    M: BroadcastMessage + Send + Sync + Unpin + 'static,
+    S: ToString + 'static,
+{
+    send_broadcast(caller, message, BcastTargets::Random(fanout)).await
+}
+
+/// Broadcasts `message` to the given peers only.
+pub async fn broadcast_to_peers<M, S>(
+    caller: S,
+    message: M,
+    peers: Vec<NodeId>,
+) -> Result<
+    Result<
+        <SendBroadcastMessage<M> as RpcMessage>::Item,
+        <SendBroadcastMessage<M> as RpcMessage>::Error,
+    >,
+    Error,
+>
+where
+    M: BroadcastMessage + Send + Sync + Unpin + 'static,
+    S: ToString + 'static,
+{
+    send_broadcast(caller, message, BcastTargets::Peers(peers)).await
+}
+
+async fn send_broadcast<M, S>(
+    caller: S,
+    message: M,
+    targets: BcastTargets,
+) -> Result<
+    Result<
+        <SendBroadcastMessage<M> as RpcMessage>::Item,
+        <SendBroadcastMessage<M> as RpcMessage>::Error,
+    >,
+    Error,
+>
 where
     M: BroadcastMessage + Send + Sync + Unpin + 'static,
     S: ToString + 'static,
@@ -40,7 +98,7 @@ where
 
     let bytes = encode_message(request).map_err(|e| Error::EncodingProblem(e.to_string()))?;
     sender
-        .send(bytes)
+        .send((bytes, targets))
         .await
         .map_err(|_| Error::Closed("broadcast channel is closed".to_string()))?;
 
@@ -1,10 +1,15 @@
+use std::convert::TryFrom;
+
 use prost::Message;
+use serde::{Deserialize, Serialize};
 
 use ya_core_model::NodeId;
 use ya_sb_proto::codec::{GsbMessage, ProtocolError};
 use ya_sb_proto::CallReplyCode;
 use ya_service_bus::{Error, ResponseChunk};
 
+use crate::payload::{decode_payload, PayloadCodec};
+
 pub(crate) fn encode_message(msg: GsbMessage) -> Result<Vec<u8>, Error> {
     let packet = ya_sb_proto::Packet { packet: Some(msg) };
     let len: usize = packet.encoded_len();
@@ -41,6 +46,109 @@ pub(crate) fn decode_message(src: &[u8]) -> Result<Option<GsbMessage>, Error> {
     }
 }
 
+/// GSB message serialized with a negotiated non-default codec.
+#[derive(Serialize, Deserialize, Debug)]
+enum WireMessage {
+    CallRequest {
+        caller: String,
+        address: String,
+        request_id: String,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    CallReply {
+        request_id: String,
+        code: i32,
+        reply_type: i32,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+}
+
+impl WireMessage {
+    fn from_message(msg: GsbMessage) -> Result<Self, GsbMessage> {
+        Ok(match msg {
+            GsbMessage::CallRequest(request) => WireMessage::CallRequest {
+                caller: request.caller,
+                address: request.address,
+                request_id: request.request_id,
+                data: request.data,
+            },
+            GsbMessage::CallReply(reply) => WireMessage::CallReply {
+                request_id: reply.request_id,
+                code: reply.code,
+                reply_type: reply.reply_type,
+                data: reply.data,
+            },
+            msg => return Err(msg),
+        })
+    }
+
+    fn into_message(self) -> GsbMessage {
+        match self {
+            WireMessage::CallRequest {
+                caller,
+                address,
+                request_id,
+                data,
+            } => GsbMessage::CallRequest(ya_sb_proto::CallRequest {
+                caller,
+                address,
+                request_id,
+                data,
+            }),
+            WireMessage::CallReply {
+                request_id,
+                code,
+                reply_type,
+                data,
+            } => GsbMessage::CallReply(ya_sb_proto::CallReply {
+                request_id,
+                code,
+                reply_type,
+                data,
+            }),
+        }
+    }
+}
+
+/// Encodes a message for a peer, which negotiated `codec`. The default codec
+/// keeps the legacy, length-prefixed protobuf framing. Calls encoded with
+/// other codecs start with the codec tag.
+pub(crate) fn encode_message_with(codec: PayloadCodec, msg: GsbMessage) -> Result<Vec<u8>, Error> {
+    if codec == PayloadCodec::default() {
+        return encode_message(msg);
+    }
+    match WireMessage::from_message(msg) {
+        Ok(wire) => codec
+            .encode(&wire)
+            .map_err(|e| Error::EncodingProblem(e.to_string())),
+        Err(msg) => encode_message(msg),
+    }
+}
+
+/// Decodes a message encoded with `encode_message_with` and returns the codec
+/// used by the sender. Legacy messages start with their big-endian length,
+/// which tells them apart from the tagged ones.
+pub(crate) fn decode_message_with(src: &[u8]) -> Result<(PayloadCodec, Option<GsbMessage>), Error> {
+    let codec = match src.first().map(|tag| PayloadCodec::try_from(*tag)) {
+        Some(Ok(codec)) if codec != PayloadCodec::default() && !is_framed(src) => codec,
+        _ => return Ok((PayloadCodec::default(), decode_message(src)?)),
+    };
+    let wire: WireMessage =
+        decode_payload(src).map_err(|e| Error::EncodingProblem(e.to_string()))?;
+    Ok((codec, Some(wire.into_message())))
+}
+
+fn is_framed(src: &[u8]) -> bool {
+    if src.len() < 4 {
+        return false;
+    }
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&src[0..4]);
+    4 + u32::from_be_bytes(buf) as usize == src.len()
+}
+
 pub(crate) fn decode_reply(data: Vec<u8>) -> Result<Vec<u8>, Error> {
     use std::convert::TryInto;
 
@@ -68,19 +176,116 @@ pub(crate) fn decode_reply(data: Vec<u8>) -> Result<Vec<u8>, Error> {
     }
 }
 
-pub(crate) fn encode_request(
+pub(crate) fn build_request(
     caller: NodeId,
     address: String,
     request_id: String,
     data: Vec<u8>,
-) -> anyhow::Result<Vec<u8>> {
-    let message = GsbMessage::CallRequest(ya_sb_proto::CallRequest {
-        caller: caller.to_string(),
-        address,
-        request_id,
-        data,
-    });
-    Ok(encode_message(message)?)
+) -> Result<GsbMessage, RequestError> {
+    let request = RequestBuilder::new(caller)
+        .address(address)
+        .request_id(request_id)
+        .data(data)
+        .build()?;
+    Ok(GsbMessage::CallRequest(request))
+}
+
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum FieldError {
+    #[error("missing {0}")]
+    Missing(&'static str),
+    #[error("invalid {0}: '{1}'")]
+    Invalid(&'static str, String),
+}
+
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+#[error("invalid request: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub(crate) struct RequestError(pub Vec<FieldError>);
+
+/// Builds a validated `CallRequest`. Address has to be absolute and consist
+/// of at least two non-empty segments (service and method). Request id
+/// is generated, when not provided.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RequestBuilder {
+    caller: Option<NodeId>,
+    address: Option<String>,
+    request_id: Option<String>,
+    data: Vec<u8>,
+}
+
+impl RequestBuilder {
+    pub fn new(caller: NodeId) -> Self {
+        Self::default().caller(caller)
+    }
+
+    /// Node the reply will be sent to.
+    pub fn caller(mut self, caller: NodeId) -> Self {
+        self.caller = Some(caller);
+        self
+    }
+
+    pub fn address(mut self, address: impl ToString) -> Self {
+        self.address = Some(address.to_string());
+        self
+    }
+
+    pub fn request_id(mut self, request_id: impl ToString) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    pub fn build(self) -> Result<ya_sb_proto::CallRequest, RequestError> {
+        let mut errors = Vec::new();
+
+        if self.caller.is_none() {
+            errors.push(FieldError::Missing("caller"));
+        }
+        match &self.address {
+            None => errors.push(FieldError::Missing("address")),
+            Some(address) if !is_valid_address(address) => {
+                errors.push(FieldError::Invalid("address", address.clone()))
+            }
+            _ => (),
+        }
+        match &self.request_id {
+            Some(id) if id.trim().is_empty() => {
+                errors.push(FieldError::Invalid("request id", id.clone()))
+            }
+            _ => (),
+        }
+
+        if !errors.is_empty() {
+            return Err(RequestError(errors));
+        }
+
+        Ok(ya_sb_proto::CallRequest {
+            caller: self.caller.unwrap().to_string(),
+            address: self.address.unwrap(),
+            request_id: self.request_id.unwrap_or_else(|| gen_id().to_string()),
+            data: self.data,
+        })
+    }
+}
+
+fn is_valid_address(address: &str) -> bool {
+    match address.strip_prefix('/') {
+        Some(path) => {
+            let segments = path.split('/').collect::<Vec<_>>();
+            segments.len() >= 2 && segments.iter().all(|s| !s.trim().is_empty())
+        }
+        None => false,
+    }
+}
+
+pub(crate) fn gen_id() -> u64 {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    rng.gen::<u64>() & 0x1f_ff_ff__ff_ff_ff_ffu64
 }
 
 #[inline]
@@ -140,6 +345,118 @@ mod tests {
     use std::iter::FromIterator;
 
     use crate::hybrid::codec::{decode_message, encode_message};
+    use crate::hybrid::codec::{FieldError, RequestBuilder, RequestError};
+    use ya_core_model::NodeId;
+
+    const CALLER: &str = "0x99402605903da83901151b0871ebeae9296ef66b";
+
+    #[test]
+    fn build_request() {
+        let caller: NodeId = CALLER.parse().unwrap();
+        let request = RequestBuilder::new(caller)
+            .address("/net/0xbabe000000000000000000000000000000000000/market/discovery")
+            .request_id("42")
+            .data(vec![1, 2, 3])
+            .build()
+            .unwrap();
+
+        assert_eq!(request.caller, CALLER);
+        assert_eq!(request.request_id, "42");
+        assert_eq!(request.data, vec![1, 2, 3]);
+
+        let first = RequestBuilder::new(caller)
+            .address("/public/test")
+            .build()
+            .unwrap();
+        let second = RequestBuilder::new(caller)
+            .address("/public/test")
+            .build()
+            .unwrap();
+        assert!(!first.request_id.is_empty());
+        assert_ne!(first.request_id, second.request_id);
+    }
+
+    #[test]
+    fn build_invalid_request() {
+        assert_eq!(
+            RequestBuilder::default().build(),
+            Err(RequestError(vec![
+                FieldError::Missing("caller"),
+                FieldError::Missing("address"),
+            ]))
+        );
+
+        let caller: NodeId = CALLER.parse().unwrap();
+        for address in vec![
+            "",
+            "public/test",
+            "/public",
+            "/public//test",
+            "/public/test/",
+        ] {
+            assert_eq!(
+                RequestBuilder::new(caller)
+                    .address(address)
+                    .request_id(" ")
+                    .build(),
+                Err(RequestError(vec![
+                    FieldError::Invalid("address", address.to_string()),
+                    FieldError::Invalid("request id", " ".to_string()),
+                ]))
+            );
+        }
+    }
+
+    #[test]
+    fn messages_round_trip_across_codecs() {
+        use crate::hybrid::codec::{decode_message_with, encode_message_with};
+        use crate::payload::PayloadCodec;
+        use ya_sb_proto::codec::GsbMessage;
+
+        let reply = GsbMessage::CallReply(ya_sb_proto::CallReply {
+            request_id: "1".to_string(),
+            code: ya_sb_proto::CallReplyCode::CallReplyOk as i32,
+            reply_type: ya_sb_proto::CallReplyType::Full as i32,
+            data: (0..=255).collect(),
+        });
+        let request = GsbMessage::CallRequest(ya_sb_proto::CallRequest {
+            caller: CALLER.to_string(),
+            address: "/public/test".to_string(),
+            request_id: "2".to_string(),
+            data: vec![1u8; 64],
+        });
+
+        for msg in vec![reply, request] {
+            let legacy = encode_message(msg.clone()).unwrap();
+            let baseline = encode_message_with(PayloadCodec::Json, msg.clone()).unwrap();
+            assert_eq!(baseline, legacy);
+            assert_eq!(
+                decode_message_with(&baseline).unwrap(),
+                (PayloadCodec::Json, Some(msg.clone()))
+            );
+
+            let encoded = encode_message_with(PayloadCodec::MsgPack, msg.clone()).unwrap();
+            assert_eq!(encoded[0], PayloadCodec::MsgPack.tag());
+            assert_ne!(&encoded[1..], legacy.as_slice());
+            assert_eq!(
+                decode_message_with(&encoded).unwrap(),
+                (PayloadCodec::MsgPack, Some(msg))
+            );
+        }
+
+        // Legacy message with a length prefix starting with a codec tag
+        let broadcast = GsbMessage::BroadcastRequest(ya_sb_proto::BroadcastRequest {
+            caller: CALLER.to_string(),
+            topic: "test".to_string(),
+            data: vec![0u8; 1 << 24],
+        });
+        let large = encode_message_with(PayloadCodec::MsgPack, broadcast.clone()).unwrap();
+        assert_eq!(large[0], 1);
+        assert_eq!(
+            decode_message_with(&large).unwrap(),
+            (PayloadCodec::Json, Some(broadcast))
+        );
+    }
 
     #[test]
     fn encode_message_compat() {
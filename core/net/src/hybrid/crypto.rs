@@ -7,21 +7,24 @@ use ethsign::{PublicKey, Signature};
 use futures::future::LocalBoxFuture;
 use futures::FutureExt;
 
-use ya_core_model::{identity, NodeId};
+use ya_core_model::NodeId;
 use ya_relay_client::crypto::{Crypto, CryptoProvider};
-use ya_service_bus::RpcEndpoint;
+
+use crate::identity::IdentityProvider;
 
 #[derive(Clone)]
 pub struct IdentityCryptoProvider {
     default_id: NodeId,
+    identity: Rc<dyn IdentityProvider>,
     aliases: Rc<RefCell<AliasCache>>,
     cache: Rc<RefCell<HashMap<NodeId, Rc<dyn Crypto>>>>,
 }
 
 impl IdentityCryptoProvider {
-    pub fn new(default_id: NodeId) -> Self {
+    pub fn new(default_id: NodeId, identity: Rc<dyn IdentityProvider>) -> Self {
         Self {
             default_id,
+            identity,
             aliases: Default::default(),
             cache: Default::default(),
         }
@@ -46,17 +49,9 @@ impl CryptoProvider for IdentityCryptoProvider {
         }
 
         let aliases_rfc = self.aliases.clone();
+        let node_ids = self.identity.aliases();
         async move {
-            let identities = ya_service_bus::typed::service(identity::BUS_ID)
-                .send(identity::List {})
-                .await
-                .map_err(anyhow::Error::msg)??;
-
-            let node_ids: Vec<_> = identities
-                .into_iter()
-                .filter(|info| !(info.is_default || info.is_locked))
-                .map(|info| info.node_id)
-                .collect();
+            let node_ids = node_ids.await?;
 
             let mut aliases = aliases_rfc.borrow_mut();
             aliases.update(node_ids.clone());
@@ -72,15 +67,10 @@ impl CryptoProvider for IdentityCryptoProvider {
         }
 
         let cache = self.cache.clone();
+        let identity = self.identity.clone();
         async move {
-            let bytes = ya_service_bus::typed::service(identity::BUS_ID)
-                .send(identity::GetPubKey(node_id))
-                .await
-                .map_err(anyhow::Error::msg)??;
-
-            let key =
-                PublicKey::from_slice(&bytes).map_err(|_| anyhow::anyhow!("invalid public key"))?;
-            let crypto: Box<dyn Crypto> = Box::new(IdentityCrypto::new(node_id, key));
+            let key = identity.public_key(node_id).await?;
+            let crypto: Box<dyn Crypto> = Box::new(IdentityCrypto::new(node_id, key, identity));
             let crypto: Rc<dyn Crypto> = crypto.into();
             cache.borrow_mut().insert(node_id, crypto.clone());
 
@@ -93,15 +83,17 @@ impl CryptoProvider for IdentityCryptoProvider {
 pub struct IdentityCrypto {
     node_id: NodeId,
     key: PublicKey,
+    identity: Rc<dyn IdentityProvider>,
     #[allow(unused)]
     created: Instant,
 }
 
 impl IdentityCrypto {
-    pub fn new(node_id: NodeId, key: PublicKey) -> Self {
+    pub fn new(node_id: NodeId, key: PublicKey, identity: Rc<dyn IdentityProvider>) -> Self {
         Self {
             node_id,
             key,
+            identity,
             created: Instant::now(),
         }
     }
@@ -113,24 +105,7 @@ impl Crypto for IdentityCrypto {
     }
 
     fn sign<'a>(&self, message: &'a [u8]) -> LocalBoxFuture<'a, anyhow::Result<Signature>> {
-        let node_id = self.node_id;
-        let payload = message.to_vec();
-
-        async move {
-            let bytes = ya_service_bus::typed::service(identity::BUS_ID)
-                .send(identity::Sign { node_id, payload })
-                .await
-                .map_err(anyhow::Error::msg)??;
-
-            let v = bytes[0];
-            let mut r = [0u8; 32];
-            let mut s = [0u8; 32];
-            r.copy_from_slice(&bytes[1..33]);
-            s.copy_from_slice(&bytes[33..65]);
-
-            Ok(Signature { v, r, s })
-        }
-        .boxed_local()
+        self.identity.sign(self.node_id, message.to_vec())
     }
 
     fn encrypt<'a>(
@@ -166,3 +141,91 @@ impl AliasCache {
         self.data.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use ethsign::SecretKey;
+
+    use super::*;
+    use crate::identity::KeyFileIdentityProvider;
+
+    struct MockIdentity {
+        inner: KeyFileIdentityProvider,
+        aliases: Vec<NodeId>,
+        key_requests: Rc<Cell<usize>>,
+    }
+
+    impl IdentityProvider for MockIdentity {
+        fn identities<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<(NodeId, Vec<NodeId>)>> {
+            self.inner.identities()
+        }
+
+        fn aliases<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<Vec<NodeId>>> {
+            futures::future::ok(self.aliases.clone()).boxed_local()
+        }
+
+        fn public_key<'a>(&self, node_id: NodeId) -> LocalBoxFuture<'a, anyhow::Result<PublicKey>> {
+            self.key_requests.set(self.key_requests.get() + 1);
+            self.inner.public_key(node_id)
+        }
+
+        fn sign<'a>(
+            &self,
+            node_id: NodeId,
+            payload: Vec<u8>,
+        ) -> LocalBoxFuture<'a, anyhow::Result<Signature>> {
+            self.inner.sign(node_id, payload)
+        }
+    }
+
+    fn mock_identity(aliases: Vec<NodeId>) -> (NodeId, Rc<Cell<usize>>, Rc<dyn IdentityProvider>) {
+        let secret = SecretKey::from_raw(&[7u8; 32]).unwrap();
+        let inner = KeyFileIdentityProvider::new(secret);
+        let key_requests = Rc::new(Cell::new(0));
+        let identity = MockIdentity {
+            inner: inner.clone(),
+            aliases,
+            key_requests: key_requests.clone(),
+        };
+        (inner.node_id(), key_requests, Rc::new(identity))
+    }
+
+    #[test]
+    fn crypto_provider_uses_identity_provider() {
+        futures::executor::block_on(async {
+            let alias: NodeId = "0xbabe000000000000000000000000000000000000"
+                .parse()
+                .unwrap();
+            let (node_id, key_requests, identity) = mock_identity(vec![alias]);
+            let provider = IdentityCryptoProvider::new(node_id, identity);
+
+            assert_eq!(provider.default_id().await.unwrap(), node_id);
+            assert_eq!(provider.aliases().await.unwrap(), vec![alias]);
+
+            let crypto = provider.get(node_id).await.unwrap();
+            let _ = provider.get(node_id).await.unwrap();
+            assert_eq!(key_requests.get(), 1);
+
+            let message = [3u8; 32];
+            let signature = crypto.sign(&message).await.unwrap();
+            let public_key = crypto.public_key().await.unwrap();
+            let recovered = signature.recover(&message).unwrap();
+            assert_eq!(recovered.bytes()[..], public_key.bytes()[..]);
+        })
+    }
+
+    #[test]
+    fn crypto_provider_rejects_unknown_identity() {
+        futures::executor::block_on(async {
+            let (node_id, _, identity) = mock_identity(vec![]);
+            let provider = IdentityCryptoProvider::new(node_id, identity);
+
+            let unknown: NodeId = "0xbabe000000000000000000000000000000000000"
+                .parse()
+                .unwrap();
+            assert!(provider.get(unknown).await.is_err());
+        })
+    }
+}
@@ -1,7 +1,10 @@
+mod access;
 mod api;
 pub(crate) mod cli;
 mod codec;
 mod crypto;
+mod reachability;
+mod saturation;
 mod service;
 
 pub use api::*;
@@ -0,0 +1,131 @@
+//! Reachability of peers, which this node has recently exchanged messages with.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ya_core_model::net::local::ReachablePeer;
+use ya_core_model::NodeId;
+
+#[derive(Clone, Debug)]
+struct PeerStats {
+    last_success: Option<Instant>,
+    last_activity: Instant,
+    successes: u64,
+    failures: u64,
+}
+
+impl PeerStats {
+    fn new(now: Instant) -> Self {
+        PeerStats {
+            last_success: None,
+            last_activity: now,
+            successes: 0,
+            failures: 0,
+        }
+    }
+}
+
+/// Outcomes of exchanges with peers. Entries idle for longer
+/// than `idle_timeout` are dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct Reachability {
+    idle_timeout: Duration,
+    peers: HashMap<NodeId, PeerStats>,
+}
+
+impl Reachability {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Reachability {
+            idle_timeout,
+            peers: Default::default(),
+        }
+    }
+
+    pub fn success(&mut self, node_id: NodeId, now: Instant) {
+        let stats = self.stats(node_id, now);
+        stats.last_success = Some(now);
+        stats.successes += 1;
+    }
+
+    pub fn failure(&mut self, node_id: NodeId, now: Instant) {
+        self.stats(node_id, now).failures += 1;
+    }
+
+    /// Peers with a successful exchange within the idle period.
+    pub fn reachable(&mut self, now: Instant) -> Vec<ReachablePeer> {
+        self.prune(now);
+
+        let idle_timeout = self.idle_timeout;
+        let mut peers = self
+            .peers
+            .iter()
+            .filter_map(|(node_id, stats)| {
+                let seen = now.saturating_duration_since(stats.last_success?);
+                (seen <= idle_timeout).then(|| ReachablePeer {
+                    node_id: *node_id,
+                    seen,
+                    successes: stats.successes,
+                    failures: stats.failures,
+                })
+            })
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|peer| peer.seen);
+        peers
+    }
+
+    fn stats(&mut self, node_id: NodeId, now: Instant) -> &mut PeerStats {
+        self.prune(now);
+
+        let stats = self
+            .peers
+            .entry(node_id)
+            .or_insert_with(|| PeerStats::new(now));
+        stats.last_activity = now;
+        stats
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let idle_timeout = self.idle_timeout;
+        self.peers
+            .retain(|_, stats| now.saturating_duration_since(stats.last_activity) <= idle_timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE: Duration = Duration::from_secs(60);
+
+    fn node(n: u8) -> NodeId {
+        format!("0x{:040x}", n).parse().unwrap()
+    }
+
+    #[test]
+    fn test_sent_peer_is_reachable_until_idle() {
+        let mut reachability = Reachability::new(IDLE);
+        let start = Instant::now();
+
+        reachability.success(node(1), start);
+        reachability.failure(node(2), start);
+        reachability.success(node(1), start + Duration::from_secs(10));
+        reachability.failure(node(1), start + Duration::from_secs(20));
+
+        let now = start + Duration::from_secs(30);
+        let peers = reachability.reachable(now);
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].node_id, node(1));
+        assert_eq!(peers[0].seen, Duration::from_secs(20));
+        assert_eq!((peers[0].successes, peers[0].failures), (2, 1));
+
+        // Last success is older than the idle period
+        assert!(reachability
+            .reachable(start + IDLE + Duration::from_secs(11))
+            .is_empty());
+
+        // Entry aged out; counters start over
+        let later = start + Duration::from_secs(20) + IDLE + Duration::from_secs(1);
+        reachability.success(node(1), later);
+        let peers = reachability.reachable(later);
+        assert_eq!((peers[0].successes, peers[0].failures), (1, 0));
+    }
+}
@@ -0,0 +1,103 @@
+//! Detection of an outbound queue, which stays full for too long.
+use std::time::{Duration, Instant};
+
+/// Tracks for how long the outbound queue has been at capacity.
+///
+/// A warning is due once the queue remains saturated for longer than
+/// `threshold`. Subsequent warnings are emitted no more often than
+/// once per `warn_interval`, even if the queue drains in the meantime.
+#[derive(Clone, Debug)]
+pub(crate) struct SaturationMonitor {
+    threshold: Duration,
+    warn_interval: Duration,
+    saturated_since: Option<Instant>,
+    last_warning: Option<Instant>,
+}
+
+impl SaturationMonitor {
+    pub fn new(threshold: Duration, warn_interval: Duration) -> Self {
+        SaturationMonitor {
+            threshold,
+            warn_interval,
+            saturated_since: None,
+            last_warning: None,
+        }
+    }
+
+    /// Records the queue being full at `now`.
+    /// Returns the saturation period, when a warning should be emitted.
+    pub fn full(&mut self, now: Instant) -> Option<Duration> {
+        let since = *self.saturated_since.get_or_insert(now);
+        let saturated = now.saturating_duration_since(since);
+        if saturated < self.threshold {
+            return None;
+        }
+
+        match self.last_warning {
+            Some(last) if now.saturating_duration_since(last) < self.warn_interval => None,
+            _ => {
+                self.last_warning = Some(now);
+                Some(saturated)
+            }
+        }
+    }
+
+    /// Records the queue accepting a message. Resets the saturation period.
+    pub fn drained(&mut self) {
+        self.saturated_since = None;
+    }
+
+    pub fn is_saturated(&self) -> bool {
+        self.saturated_since.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: Duration = Duration::from_secs(10);
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_sustained_saturation_warns_once() {
+        let mut monitor = SaturationMonitor::new(THRESHOLD, INTERVAL);
+        let start = Instant::now();
+
+        let warnings = (0..=300)
+            .map(|ms| start + Duration::from_millis(ms * 100))
+            .filter_map(|now| monitor.full(now))
+            .collect::<Vec<_>>();
+
+        assert_eq!(warnings, vec![THRESHOLD]);
+        assert!(monitor.is_saturated());
+    }
+
+    #[test]
+    fn test_drained_queue_resets_saturation() {
+        let mut monitor = SaturationMonitor::new(THRESHOLD, INTERVAL);
+        let start = Instant::now();
+
+        assert_eq!(monitor.full(start), None);
+        assert_eq!(monitor.full(start + Duration::from_secs(9)), None);
+        monitor.drained();
+        assert!(!monitor.is_saturated());
+
+        // Saturation period starts over
+        let restart = start + Duration::from_secs(9);
+        assert_eq!(monitor.full(restart + Duration::from_secs(1)), None);
+        assert_eq!(monitor.full(restart + Duration::from_secs(10)), None);
+        assert_eq!(
+            monitor.full(restart + THRESHOLD + Duration::from_secs(1)),
+            Some(THRESHOLD)
+        );
+
+        // Warnings are rate-limited across saturation periods
+        monitor.drained();
+        let later = restart + Duration::from_secs(30);
+        assert_eq!(monitor.full(later), None);
+        assert_eq!(monitor.full(later + THRESHOLD), None);
+        let later = restart + THRESHOLD + Duration::from_secs(1) + INTERVAL;
+        assert!(monitor.full(later).is_some());
+    }
+}
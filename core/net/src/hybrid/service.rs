@@ -8,39 +8,53 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
 use std::task::Poll;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context as AnyhowContext};
 use futures::channel::mpsc;
+use futures::future::poll_fn;
 use futures::lock::Mutex;
 use futures::stream::LocalBoxStream;
-use futures::{FutureExt, SinkExt, Stream, StreamExt, TryStreamExt};
-use metrics::counter;
+use futures::{FutureExt, Sink, SinkExt, Stream, StreamExt, TryStreamExt};
+use metrics::{counter, gauge};
 use tokio::sync::RwLock;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use url::Url;
 
+use ya_core_model::net::RemoteEndpoint;
 use ya_core_model::{identity, net, NodeId};
 use ya_relay_client::codec::forward::{PrefixedSink, PrefixedStream, SinkKind};
 use ya_relay_client::{Client, ClientBuilder, ForwardReceiver};
 use ya_sb_proto::codec::GsbMessage;
 use ya_sb_proto::CallReplyCode;
+use ya_service_bus::timeout::IntoTimeoutFuture;
 use ya_service_bus::{typed, untyped as local_bus, Error, ResponseChunk, RpcEndpoint};
 use ya_utils_networking::resolver;
 
+use crate::addr::to_local_addr;
 use crate::bcast::BCastService;
+use crate::capabilities::{self, Negotiated};
 use crate::config::Config;
+use crate::hybrid::access::PeerAccess;
 use crate::hybrid::codec;
 use crate::hybrid::crypto::IdentityCryptoProvider;
+use crate::hybrid::reachability::Reachability;
+use crate::hybrid::saturation::SaturationMonitor;
+use crate::identity::{IdentityProvider, IdentityServiceProvider};
+use crate::payload::PayloadCodec;
 
 const DEFAULT_NET_RELAY_HOST: &str = "127.0.0.1:7464";
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub type BCastHandler = Box<dyn FnMut(String, &[u8]) + Send>;
 
 type BusSender = mpsc::Sender<ResponseChunk>;
 type BusReceiver = mpsc::Receiver<ResponseChunk>;
 type NetSender = mpsc::Sender<Vec<u8>>;
-type NetReceiver = mpsc::Receiver<Vec<u8>>;
 type NetSinkKind = SinkKind<NetSender, mpsc::SendError>;
+/// Broadcast payload with optional number of peers to reach.
+pub(crate) type BcastSender = mpsc::Sender<(Vec<u8>, BcastTargets)>;
+type BcastReceiver = mpsc::Receiver<(Vec<u8>, BcastTargets)>;
 type NetSinkKey = (NodeId, bool);
 
 type ArcMap<K, V> = Arc<RwLock<HashMap<K, V>>>;
@@ -54,7 +68,7 @@ lazy_static::lazy_static! {
 }
 
 lazy_static::lazy_static! {
-    pub(crate) static ref BCAST_SENDER: Arc<RwLock<Option<NetSender>>> = Default::default();
+    pub(crate) static ref BCAST_SENDER: Arc<RwLock<Option<BcastSender>>> = Default::default();
 }
 
 thread_local! {
@@ -85,13 +99,17 @@ async fn relay_addr(config: &Config) -> anyhow::Result<SocketAddr> {
 pub struct Net;
 
 impl Net {
-    pub async fn gsb<Context>(_: Context, config: Config) -> anyhow::Result<()> {
-        let (default_id, ids) = crate::service::identities().await?;
+    pub async fn gsb<Context>(
+        _: Context,
+        config: Config,
+        identity: Rc<dyn IdentityProvider>,
+    ) -> anyhow::Result<()> {
+        let (default_id, ids) = identity.identities().await?;
         log::info!(
             "HYBRID_NET - Using default identity as network id: {:?}",
             default_id
         );
-        start_network(Arc::new(config), default_id, ids).await?;
+        start_network(Arc::new(config), identity, default_id, ids).await?;
         Ok(())
     }
 
@@ -106,11 +124,18 @@ impl Net {
 // FIXME: examples compatibility
 #[allow(unused)]
 pub async fn bind_remote<T>(_: T, default_id: NodeId, ids: Vec<NodeId>) -> anyhow::Result<()> {
-    start_network(Arc::new(Config::from_env()?), default_id, ids).await
+    start_network(
+        Arc::new(Config::from_env()?),
+        Rc::new(IdentityServiceProvider),
+        default_id,
+        ids,
+    )
+    .await
 }
 
 pub async fn start_network(
     config: Arc<Config>,
+    identity: Rc<dyn IdentityProvider>,
     default_id: NodeId,
     ids: Vec<NodeId>,
 ) -> anyhow::Result<()> {
@@ -127,7 +152,7 @@ pub async fn start_network(
     log::debug!("Setting up hybrid net with url: {}", url);
     log::info!("Starting network (hybrid) with identity: {}", default_id);
 
-    let crypto = IdentityCryptoProvider::new(default_id);
+    let crypto = IdentityCryptoProvider::new(default_id, identity);
     let client = ClientBuilder::from_url(url)
         .crypto(crypto.clone())
         .listen(config.bind_url.clone())
@@ -152,7 +177,13 @@ pub async fn start_network(
         services.insert(format!("/udp{}", service));
         services.insert(service);
     });
-    let state = State::new(ids, services);
+    let saturation = SaturationMonitor::new(
+        config.queue_saturation_warn,
+        config.queue_saturation_warn_interval,
+    );
+    let reachability = Reachability::new(config.peer_idle_timeout);
+    let access = PeerAccess::new(config.allow_nodes.clone(), config.deny_nodes.clone());
+    let state = State::new(ids, services, saturation, reachability, access);
 
     // outbound traffic
     let net_handler = || {
@@ -182,6 +213,37 @@ pub async fn start_network(
     bind_local_bus("/from", state.clone(), true, from_handler());
     bind_local_bus("/udp/from", state.clone(), false, from_handler());
 
+    let state_peers = state.clone();
+    let _ = typed::bind(net::local::BUS_ID, move |_: net::local::ReachablePeers| {
+        let peers = state_peers.reachable_peers();
+        async move { Ok(peers) }
+    });
+
+    let state_caps = state.clone();
+    let _ = typed::bind(
+        net::local::BUS_ID,
+        move |_: net::local::PeerCapabilities| {
+            let capabilities = state_caps.peer_capabilities();
+            async move { Ok(capabilities) }
+        },
+    );
+
+    let state_caps = state.clone();
+    let _ = typed::bind_with_caller(
+        net::DIAGNOSTIC,
+        move |caller: String, msg: net::GsbCapabilities| {
+            let local = capabilities::supported();
+            match caller.parse::<NodeId>() {
+                Ok(remote_id) => {
+                    let negotiated = capabilities::negotiate(&local, Some(&msg.0));
+                    state_caps.set_capabilities(remote_id, negotiated);
+                }
+                Err(e) => log::debug!("Capability handshake from invalid caller {}: {}", caller, e),
+            }
+            async move { Ok(local) }
+        },
+    );
+
     tokio::task::spawn_local(broadcast_handler(brx, config.clone()));
     tokio::task::spawn_local(forward_handler(receiver, state.clone()));
 
@@ -373,14 +435,14 @@ fn forward_bus_to_net(
 ) -> BusReceiver {
     let address = address.to_string();
     let state = state.clone();
-    let request_id = gen_id().to_string();
+    let request_id = codec::gen_id().to_string();
 
     log::trace!("forward net {}", address);
 
     let (tx, rx) = mpsc::channel(1);
     let msg =
-        match codec::encode_request(caller_id, address.clone(), request_id.clone(), msg.to_vec()) {
-            Ok(vec) => vec,
+        match codec::build_request(caller_id, address.clone(), request_id.clone(), msg.to_vec()) {
+            Ok(msg) => msg,
             Err(err) => {
                 log::debug!("forward net: invalid request: {}", err);
                 handler_reply_bad_request(request_id, format!("invalid request: {}", err), tx);
@@ -388,6 +450,18 @@ fn forward_bus_to_net(
             }
         };
 
+    if !state.is_allowed(&remote_id) {
+        log::warn!(
+            "Dropping message to {} ({}): peer not allowed",
+            remote_id,
+            address
+        );
+        counter!("net.access.denied.outbound", 1);
+        let err = format!("peer not allowed: {}", remote_id);
+        handler_reply_service_err(request_id, err, tx);
+        return rx;
+    }
+
     let request = Request {
         caller_id,
         remote_id,
@@ -400,19 +474,22 @@ fn forward_bus_to_net(
     }
 
     tokio::task::spawn_local(async move {
-        log::trace!(
-            "local bus handler -> send message to remote ({} B)",
-            msg.len()
-        );
-
         match state.forward_sink(remote_id, reliable).await {
             Ok(mut sink) => {
-                let _ = sink.send(msg).await.map_err(|_| {
-                    let err = format!("error sending message: session closed");
+                state.check_saturation(remote_id, &mut sink).await;
+                let result = send_message(&state, remote_id, &mut sink, msg).await;
+                state.record_exchange(remote_id, result.is_ok());
+                if let Ok(size) = result {
+                    crate::stats::record_sent(size);
+                    handshake(&state, caller_id, remote_id);
+                }
+                let _ = result.map_err(|e| {
+                    let err = format!("error sending message: {}", e);
                     handler_reply_service_err(request_id, err, tx);
                 });
             }
             Err(error) => {
+                state.record_exchange(remote_id, false);
                 let err = format!("error forwarding message: {}", error);
                 handler_reply_service_err(request_id, err, tx);
             }
@@ -422,21 +499,109 @@ fn forward_bus_to_net(
     rx
 }
 
+/// Sends a message to `remote_id`, encoded with the codec negotiated
+/// with that peer. Returns the number of bytes sent.
+async fn send_message<S>(
+    state: &State,
+    remote_id: NodeId,
+    sink: &mut S,
+    msg: GsbMessage,
+) -> anyhow::Result<usize>
+where
+    S: Sink<Vec<u8>> + Unpin,
+{
+    let msg = codec::encode_message_with(state.codec(&remote_id), msg)?;
+    let size = msg.len();
+    log::trace!("local bus handler -> send message to remote ({} B)", size);
+    sink.send(msg)
+        .await
+        .map_err(|_| anyhow::anyhow!("session closed"))?;
+    Ok(size)
+}
+
+/// Exchanges capabilities with a peer, unless already done. Peers, which don't
+/// respond to the handshake, are assumed to support only the baseline options.
+fn handshake(state: &State, caller_id: NodeId, remote_id: NodeId) {
+    if !state.inner.borrow_mut().handshakes.insert(remote_id) {
+        return;
+    }
+
+    let state = state.clone();
+    tokio::task::spawn_local(async move {
+        let local = capabilities::supported();
+        let result = net::from(caller_id)
+            .to(remote_id)
+            .service(net::DIAGNOSTIC)
+            .send(net::GsbCapabilities(local.clone()))
+            .timeout(Some(HANDSHAKE_TIMEOUT))
+            .await;
+        let remote = match result {
+            Ok(Ok(Ok(remote))) => Some(remote),
+            Ok(Ok(Err(e))) => {
+                log::debug!("Capability handshake with {} failed: {}", remote_id, e);
+                None
+            }
+            Ok(Err(e)) => {
+                log::debug!("Capability handshake with {} failed: {}", remote_id, e);
+                None
+            }
+            Err(_) => {
+                log::debug!("Capability handshake with {} timed out", remote_id);
+                None
+            }
+        };
+        let negotiated = capabilities::negotiate(&local, remote.as_ref());
+        log::debug!(
+            "Negotiated capabilities with {}: {:?}",
+            remote_id,
+            negotiated
+        );
+        state.set_capabilities(remote_id, negotiated);
+    });
+}
+
+/// Peers a broadcast is sent to
+#[derive(Clone, Debug)]
+pub(crate) enum BcastTargets {
+    /// At most given number of random neighbours. Configured broadcast size, when `None`.
+    Random(Option<u32>),
+    /// Given peers only
+    Peers(Vec<NodeId>),
+}
+
 /// Forward broadcast messages from the network to the local bus
 fn broadcast_handler(
-    rx: NetReceiver,
+    rx: BcastReceiver,
     config: Arc<Config>,
 ) -> impl Future<Output = ()> + Unpin + 'static {
-    StreamExt::for_each(rx, move |payload| {
+    StreamExt::for_each(rx, move |(payload, targets)| {
         let config = config.clone();
         async move {
             let client = CLIENT
                 .with(|c| c.borrow().clone())
                 .ok_or_else(|| anyhow::anyhow!("network not initialized"))?;
-            client
-                .broadcast(payload, config.broadcast_size)
-                .await
-                .map_err(|e| anyhow!("Broadcast failed: {}", e))
+            let peers = match targets {
+                BcastTargets::Random(size) => {
+                    return client
+                        .broadcast(payload, size.unwrap_or(config.broadcast_size))
+                        .await
+                        .map_err(|e| anyhow!("Broadcast failed: {}", e))
+                }
+                BcastTargets::Peers(peers) => peers,
+            };
+            for node_id in peers {
+                let result = match client.forward_unreliable(node_id).await {
+                    Ok(mut sink) => sink
+                        .send(payload.clone())
+                        .await
+                        .map_err(|e| anyhow!("{}", e)),
+                    Err(e) => Err(anyhow!("{}", e)),
+                };
+                if let Err(e) = result {
+                    log::debug!("unable to broadcast message to {}: {}", node_id, e);
+                }
+            }
+            Ok(())
         }
         .then(|result: anyhow::Result<()>| async move {
             if let Err(e) = result {
@@ -515,7 +680,19 @@ fn inbound_handler(
         log::trace!("local bus handler -> inbound message");
 
         async move {
-            match codec::decode_message(payload.as_slice()) {
+            if !state.is_allowed(&remote_id) {
+                log::warn!("Dropping message from {}: peer not allowed", remote_id);
+                counter!("net.access.denied.inbound", 1);
+                return Ok(());
+            }
+
+            state.record_exchange(remote_id, true);
+            crate::stats::record_received(payload.len());
+            let decoded = codec::decode_message_with(payload.as_slice()).map(|(c, msg)| {
+                log::trace!("inbound message codec: {:?}", c);
+                msg
+            });
+            match decoded {
                 Ok(Some(GsbMessage::CallRequest(request @ ya_sb_proto::CallRequest { .. }))) => {
                     handle_request(request, remote_id, state, reliable)
                 }
@@ -567,26 +744,21 @@ fn handle_request(
         remote_id
     );
 
+    let reply_codec = state.codec(&caller_id);
     let eos = Rc::new(AtomicBool::new(false));
     let eos_map = eos.clone();
     let eos_chain = eos.clone();
 
-    let stream = match {
-        let inner = state.inner.borrow();
-        inner
-            .services
-            .iter()
-            .find(|&id| address.starts_with(id))
-            // replaces  /net/<dest_node_id>/test/1 --> /public/test/1
-            .map(|s| address.replacen(s, net::PUBLIC_PREFIX, 1))
-    } {
-        Some(address) => {
+    // replaces  /net/<dest_node_id>/test/1 --> /public/test/1
+    let local_addr = to_local_addr(&address, state.inner.borrow().services.iter());
+    let stream = match local_addr {
+        Ok(address) => {
             log::trace!("handle request: calling: {}", address);
             local_bus::call_stream(&address, &request.caller, &request.data).left_stream()
         }
-        None => {
-            log::trace!("handle request failed: unknown address: {}", address);
-            let err = Error::GsbBadRequest(format!("unknown address: {}", address));
+        Err(e) => {
+            log::trace!("handle request failed: {}", e);
+            let err = Error::GsbBadRequest(e.to_string());
             futures::stream::once(futures::future::err(err)).right_stream()
         }
     }
@@ -615,7 +787,7 @@ fn handle_request(
         }
     }))
     .filter_map(move |reply| {
-        let filtered = match codec::encode_message(reply) {
+        let filtered = match codec::encode_message_with(reply_codec, reply) {
             Ok(vec) => {
                 log::trace!(
                     "handle request {}: reply chunk ({} B)",
@@ -748,19 +920,125 @@ struct StateInner {
     routes: HashMap<NetSinkKey, NetSender>,
     ids: HashSet<NodeId>,
     services: HashSet<String>,
+    saturation: Option<SaturationMonitor>,
+    reachability: Option<Reachability>,
+    access: PeerAccess,
+    /// Peers, with which capability handshake was started
+    handshakes: HashSet<NodeId>,
+    capabilities: HashMap<NodeId, Negotiated>,
 }
 
 impl State {
-    fn new(ids: impl IntoIterator<Item = NodeId>, services: HashSet<String>) -> Self {
+    fn new(
+        ids: impl IntoIterator<Item = NodeId>,
+        services: HashSet<String>,
+        saturation: SaturationMonitor,
+        reachability: Reachability,
+        access: PeerAccess,
+    ) -> Self {
         Self {
             inner: Rc::new(RefCell::new(StateInner {
                 ids: ids.into_iter().collect(),
                 services,
+                saturation: Some(saturation),
+                reachability: Some(reachability),
+                access,
                 ..Default::default()
             })),
         }
     }
 
+    fn is_allowed(&self, remote_id: &NodeId) -> bool {
+        self.inner.borrow().access.is_allowed(remote_id)
+    }
+
+    fn record_exchange(&self, remote_id: NodeId, success: bool) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(reachability) = inner.reachability.as_mut() {
+            match success {
+                true => reachability.success(remote_id, Instant::now()),
+                false => reachability.failure(remote_id, Instant::now()),
+            }
+        }
+    }
+
+    fn reachable_peers(&self) -> Vec<net::local::ReachablePeer> {
+        let mut inner = self.inner.borrow_mut();
+        inner
+            .reachability
+            .as_mut()
+            .map(|reachability| reachability.reachable(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Capabilities negotiated by either side take precedence over the ones of a legacy peer.
+    fn set_capabilities(&self, remote_id: NodeId, negotiated: Negotiated) {
+        let mut inner = self.inner.borrow_mut();
+        inner.handshakes.insert(remote_id);
+        match inner.capabilities.get(&remote_id) {
+            Some(current) if negotiated.legacy && !current.legacy => (),
+            _ => {
+                inner.capabilities.insert(remote_id, negotiated);
+            }
+        }
+    }
+
+    /// Codec negotiated with the peer. Baseline is used until the handshake completes.
+    fn codec(&self, remote_id: &NodeId) -> PayloadCodec {
+        self.inner
+            .borrow()
+            .capabilities
+            .get(remote_id)
+            .map(|negotiated| negotiated.codec)
+            .unwrap_or_else(|| Negotiated::baseline().codec)
+    }
+
+    fn peer_capabilities(&self) -> Vec<net::local::PeerCapability> {
+        self.inner
+            .borrow()
+            .capabilities
+            .iter()
+            .map(|(node_id, negotiated)| net::local::PeerCapability {
+                node_id: *node_id,
+                codec: negotiated.codec.tag(),
+                compression: negotiated.compression.clone(),
+                framing: negotiated.framing,
+                legacy: negotiated.legacy,
+            })
+            .collect()
+    }
+
+    /// Updates outbound queue saturation state, depending on whether `sink`
+    /// is able to accept a message right away.
+    async fn check_saturation(&self, remote_id: NodeId, sink: &mut NetSinkKind) {
+        let full = poll_fn(|cx| Poll::Ready(sink.poll_ready_unpin(cx).is_pending())).await;
+
+        let mut inner = self.inner.borrow_mut();
+        let monitor = match inner.saturation.as_mut() {
+            Some(monitor) => monitor,
+            None => return,
+        };
+
+        if !full {
+            if monitor.is_saturated() {
+                log::debug!("Outbound net queue drained");
+                gauge!("net.queue.saturated", 0);
+            }
+            monitor.drained();
+            return;
+        }
+
+        gauge!("net.queue.saturated", 1);
+        if let Some(period) = monitor.full(Instant::now()) {
+            log::warn!(
+                "Outbound net queue has been full for {} (last destination: {})",
+                humantime::format_duration(period),
+                remote_id
+            );
+            counter!("net.queue.saturation.warnings", 1);
+        }
+    }
+
     async fn forward_sink(&self, remote_id: NodeId, reliable: bool) -> anyhow::Result<NetSinkKind> {
         let client = CLIENT
             .with(|c| c.borrow().clone())
@@ -858,8 +1136,115 @@ fn parse_from_to_addr(addr: &str) -> anyhow::Result<(NodeId, NodeId, String)> {
     Ok((from_id, to_id, format!("{}{}", prefix, addr)))
 }
 
-fn gen_id() -> u64 {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    rng.gen::<u64>() & 0x1f_ff_ff__ff_ff_ff_ffu64
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(request_id: &str, data: &[u8]) -> ya_sb_proto::CallReply {
+        ya_sb_proto::CallReply {
+            request_id: request_id.to_string(),
+            code: CallReplyCode::CallReplyOk as i32,
+            reply_type: ya_sb_proto::CallReplyType::Full as i32,
+            data: data.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn overlapping_replies_are_routed_by_request_id() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let caller_id: NodeId = "0xe93ab94a2095729ad0b7cfa5bfd7d33e1b44d6df"
+                    .parse()
+                    .unwrap();
+                let remote_id: NodeId = "0x99402605903da83901151b0871ebeae9296ef66b"
+                    .parse()
+                    .unwrap();
+                let state = State {
+                    inner: Default::default(),
+                };
+
+                let mut receivers = Vec::new();
+                for request_id in &["1", "2"] {
+                    let (tx, rx) = mpsc::channel(1);
+                    let request = Request {
+                        caller_id,
+                        remote_id,
+                        address: "/net/test".to_string(),
+                        tx,
+                    };
+                    let mut inner = state.inner.borrow_mut();
+                    inner.requests.insert(request_id.to_string(), request);
+                    receivers.push(rx);
+                }
+
+                // Replies from other nodes and for unknown requests are rejected
+                assert!(handle_reply(reply("1", b"other"), caller_id, state.clone()).is_err());
+                assert!(handle_reply(reply("3", b"unknown"), remote_id, state.clone()).is_err());
+                // Replies arrive in reverse order
+                handle_reply(reply("2", b"second"), remote_id, state.clone()).unwrap();
+                handle_reply(reply("1", b"first"), remote_id, state.clone()).unwrap();
+
+                let mut replies = Vec::new();
+                for rx in receivers.iter_mut() {
+                    match rx.next().await {
+                        Some(ResponseChunk::Full(data)) => replies.push(data),
+                        _ => panic!("missing reply"),
+                    }
+                }
+                assert_eq!(replies, vec![b"first".to_vec(), b"second".to_vec()]);
+                assert!(state.inner.borrow().requests.is_empty());
+            })
+            .await;
+    }
+    #[test]
+    fn send_path_uses_negotiated_codec() {
+        let remote_id: NodeId = "0x99402605903da83901151b0871ebeae9296ef66b"
+            .parse()
+            .unwrap();
+        let local = capabilities::supported();
+        let reply = GsbMessage::CallReply(reply("1", b"data"));
+        let msg = codec::encode_message(reply.clone()).unwrap();
+        let (mut sink, mut rx) = mpsc::channel::<Vec<u8>>(4);
+
+        let mut send = |state: &State| {
+            let size = futures::executor::block_on(send_message(
+                state,
+                remote_id,
+                &mut sink,
+                reply.clone(),
+            ))
+            .unwrap();
+            let sent = rx.try_next().unwrap().unwrap();
+            assert_eq!(size, sent.len());
+
+            let (payload_codec, received) = codec::decode_message_with(&sent).unwrap();
+            match received {
+                Some(GsbMessage::CallReply(reply)) => assert_eq!(reply.data, b"data".to_vec()),
+                _ => panic!("invalid message"),
+            }
+            (payload_codec, sent)
+        };
+
+        let state = State {
+            inner: Default::default(),
+        };
+        // Handshake not started
+        assert_eq!(send(&state), (PayloadCodec::Json, msg.clone()));
+        // Handshake in progress
+        state.inner.borrow_mut().handshakes.insert(remote_id);
+        assert_eq!(send(&state), (PayloadCodec::Json, msg.clone()));
+
+        state.set_capabilities(remote_id, capabilities::negotiate(&local, Some(&local)));
+        let (payload_codec, sent) = send(&state);
+        assert_eq!(payload_codec, PayloadCodec::MsgPack);
+        assert_eq!(sent[0], PayloadCodec::MsgPack.tag());
+        assert_ne!(&sent[1..], msg.as_slice());
+
+        // Legacy peer gets the baseline
+        let state = State {
+            inner: Default::default(),
+        };
+        state.set_capabilities(remote_id, capabilities::negotiate(&local, None));
+        assert_eq!(send(&state), (PayloadCodec::Json, msg.clone()));
+    }
 }
@@ -0,0 +1,178 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use ethsign::{KeyFile, Protected, PublicKey, SecretKey, Signature};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use ya_core_model::{identity, NodeId};
+use ya_service_bus::{typed as bus, RpcEndpoint};
+
+/// Source of node identities and key material used by the network layer
+/// for signing and encryption.
+pub trait IdentityProvider {
+    /// Returns the default identity and all identities (including the default one).
+    fn identities<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<(NodeId, Vec<NodeId>)>>;
+
+    /// Returns unlocked identities, other than the default one.
+    fn aliases<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<Vec<NodeId>>>;
+
+    fn public_key<'a>(&self, node_id: NodeId) -> LocalBoxFuture<'a, anyhow::Result<PublicKey>>;
+
+    fn sign<'a>(
+        &self,
+        node_id: NodeId,
+        payload: Vec<u8>,
+    ) -> LocalBoxFuture<'a, anyhow::Result<Signature>>;
+}
+
+/// Default provider, backed by the identity service and its key store.
+#[derive(Clone, Default)]
+pub struct IdentityServiceProvider;
+
+impl IdentityProvider for IdentityServiceProvider {
+    fn identities<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<(NodeId, Vec<NodeId>)>> {
+        async move {
+            let ids = list_identities().await?;
+
+            let mut default_id = None;
+            let ids = ids
+                .into_iter()
+                .map(|id| {
+                    if id.is_default {
+                        default_id = Some(id.node_id);
+                    }
+                    id.node_id
+                })
+                .collect::<Vec<NodeId>>();
+
+            let default_id = default_id.ok_or_else(|| anyhow::anyhow!("no default identity"))?;
+            Ok((default_id, ids))
+        }
+        .boxed_local()
+    }
+
+    fn aliases<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<Vec<NodeId>>> {
+        async move {
+            Ok(list_identities()
+                .await?
+                .into_iter()
+                .filter(|info| !(info.is_default || info.is_locked))
+                .map(|info| info.node_id)
+                .collect())
+        }
+        .boxed_local()
+    }
+
+    fn public_key<'a>(&self, node_id: NodeId) -> LocalBoxFuture<'a, anyhow::Result<PublicKey>> {
+        async move {
+            let bytes = bus::service(identity::BUS_ID)
+                .send(identity::GetPubKey(node_id))
+                .await
+                .map_err(anyhow::Error::msg)??;
+
+            PublicKey::from_slice(&bytes).map_err(|_| anyhow::anyhow!("invalid public key"))
+        }
+        .boxed_local()
+    }
+
+    fn sign<'a>(
+        &self,
+        node_id: NodeId,
+        payload: Vec<u8>,
+    ) -> LocalBoxFuture<'a, anyhow::Result<Signature>> {
+        async move {
+            let bytes = bus::service(identity::BUS_ID)
+                .send(identity::Sign { node_id, payload })
+                .await
+                .map_err(anyhow::Error::msg)??;
+
+            if bytes.len() != 65 {
+                anyhow::bail!("invalid signature length: {}", bytes.len());
+            }
+
+            let v = bytes[0];
+            let mut r = [0u8; 32];
+            let mut s = [0u8; 32];
+            r.copy_from_slice(&bytes[1..33]);
+            s.copy_from_slice(&bytes[33..65]);
+
+            Ok(Signature { v, r, s })
+        }
+        .boxed_local()
+    }
+}
+
+async fn list_identities() -> anyhow::Result<Vec<identity::IdentityInfo>> {
+    Ok(bus::service(identity::BUS_ID)
+        .send(identity::List::default())
+        .await
+        .map_err(anyhow::Error::msg)??)
+}
+
+/// Provider holding a single identity, loaded from an encrypted key file.
+#[derive(Clone)]
+pub struct KeyFileIdentityProvider {
+    node_id: NodeId,
+    secret: Rc<SecretKey>,
+}
+
+impl KeyFileIdentityProvider {
+    pub fn new(secret: SecretKey) -> Self {
+        let node_id = NodeId::from(secret.public().address().as_ref());
+        Self {
+            node_id,
+            secret: Rc::new(secret),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, password: Protected) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|e| anyhow::anyhow!("unable to open key file {}: {}", path.display(), e))?;
+        let key_file: KeyFile = serde_json::from_reader(file)?;
+        let secret = key_file
+            .to_secret_key(&password)
+            .map_err(|e| anyhow::anyhow!("unable to decrypt key file: {}", e))?;
+        Ok(Self::new(secret))
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    fn check(&self, node_id: NodeId) -> anyhow::Result<()> {
+        match node_id == self.node_id {
+            true => Ok(()),
+            false => anyhow::bail!("unknown identity: {}", node_id),
+        }
+    }
+}
+
+impl IdentityProvider for KeyFileIdentityProvider {
+    fn identities<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<(NodeId, Vec<NodeId>)>> {
+        futures::future::ok((self.node_id, vec![self.node_id])).boxed_local()
+    }
+
+    fn aliases<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<Vec<NodeId>>> {
+        futures::future::ok(Vec::new()).boxed_local()
+    }
+
+    fn public_key<'a>(&self, node_id: NodeId) -> LocalBoxFuture<'a, anyhow::Result<PublicKey>> {
+        let result = self.check(node_id).map(|_| self.secret.public());
+        futures::future::ready(result).boxed_local()
+    }
+
+    fn sign<'a>(
+        &self,
+        node_id: NodeId,
+        payload: Vec<u8>,
+    ) -> LocalBoxFuture<'a, anyhow::Result<Signature>> {
+        let result = self.check(node_id).and_then(|_| {
+            self.secret
+                .sign(&payload)
+                .map_err(|e| anyhow::anyhow!("signing failed: {}", e))
+        });
+        futures::future::ready(result).boxed_local()
+    }
+}
@@ -2,12 +2,25 @@ pub use ya_core_model::net::{
     from, NetApiError, NetDst, NetSrc, RemoteEndpoint, TryRemoteEndpoint,
 };
 
-pub use service::{bind_broadcast_with_caller, broadcast, Net};
+pub use error::NetError;
+pub use identity::{IdentityProvider, IdentityServiceProvider, KeyFileIdentityProvider};
+pub use payload::{decode_payload, encode_payload, PayloadCodec, PayloadError};
+pub use service::{
+    bind_broadcast_with_caller, broadcast, broadcast_to_peers, broadcast_with_fanout, send_timeout,
+    send_to_all, Net,
+};
+pub use stats::{net_stats, NetStats};
 
+mod addr;
 mod bcast;
+mod capabilities;
 pub mod central;
+mod error;
 pub mod hybrid;
+mod identity;
+mod payload;
 mod service;
+mod stats;
 
 mod cli;
 mod config;
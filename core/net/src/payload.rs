@@ -0,0 +1,170 @@
+//! Serialization of structured message payloads.
+//!
+//! The default JSON codec produces plain, untagged JSON, understood by older
+//! nodes. Payloads encoded with a negotiated non-default codec start with
+//! a single byte tag identifying that codec. JSON never starts with a tag
+//! byte, so the receiver can decode both kinds of payloads.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryFrom;
+
+use ya_service_bus::serialization;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PayloadError {
+    #[error("Failed to encode {codec:?} payload: {msg}")]
+    Encode { codec: PayloadCodec, msg: String },
+    #[error("Failed to decode {codec:?} payload: {msg}")]
+    Decode { codec: PayloadCodec, msg: String },
+    #[error("Empty payload")]
+    Empty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PayloadCodec {
+    /// Human readable, useful for debugging.
+    Json = 0,
+    /// Compact binary format for high-volume traffic.
+    MsgPack = 1,
+}
+
+impl Default for PayloadCodec {
+    fn default() -> Self {
+        PayloadCodec::Json
+    }
+}
+
+impl TryFrom<u8> for PayloadCodec {
+    type Error = u8;
+
+    fn try_from(tag: u8) -> Result<Self, Self::Error> {
+        match tag {
+            0 => Ok(PayloadCodec::Json),
+            1 => Ok(PayloadCodec::MsgPack),
+            tag => Err(tag),
+        }
+    }
+}
+
+impl PayloadCodec {
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+
+    /// Serializes `value`. Codec tag is prepended for non-default codecs only.
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, PayloadError> {
+        let encode_err = |msg: String| PayloadError::Encode { codec: self, msg };
+
+        match self {
+            PayloadCodec::Json => serde_json::to_vec(value).map_err(|e| encode_err(e.to_string())),
+            PayloadCodec::MsgPack => {
+                let mut buf = vec![self.tag()];
+                buf.extend(serialization::to_vec(value).map_err(|e| encode_err(e.to_string()))?);
+                Ok(buf)
+            }
+        }
+    }
+
+    fn decode_body<T: DeserializeOwned>(self, body: &[u8]) -> Result<T, PayloadError> {
+        let decode_err = |msg: String| PayloadError::Decode { codec: self, msg };
+
+        match self {
+            PayloadCodec::Json => {
+                serde_json::from_slice(body).map_err(|e| decode_err(e.to_string()))
+            }
+            PayloadCodec::MsgPack => {
+                serialization::from_slice(body).map_err(|e| decode_err(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Encodes payload with the default codec, i.e. as untagged JSON.
+pub fn encode_payload<T: Serialize>(value: &T) -> Result<Vec<u8>, PayloadError> {
+    PayloadCodec::default().encode(value)
+}
+
+/// Decodes payload using codec indicated by its tag.
+pub fn decode_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T, PayloadError> {
+    let (tag, body) = payload.split_first().ok_or(PayloadError::Empty)?;
+    match PayloadCodec::try_from(*tag) {
+        Ok(codec) => codec.decode_body(body),
+        // Untagged JSON payload
+        Err(_) => PayloadCodec::Json.decode_body(payload),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Message {
+        id: u64,
+        topic: String,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+        reply_to: Option<String>,
+    }
+
+    fn message() -> Message {
+        Message {
+            id: 42,
+            topic: "market/offers".into(),
+            payload: (0..=255).collect(),
+            headers: vec![("version".to_string(), "0.10.0".to_string())]
+                .into_iter()
+                .collect(),
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip_across_codecs() {
+        let msg = message();
+        for codec in vec![PayloadCodec::Json, PayloadCodec::MsgPack] {
+            let encoded = codec.encode(&msg).unwrap();
+            assert_eq!(decode_payload::<Message>(&encoded).unwrap(), msg);
+        }
+
+        let json = PayloadCodec::Json.encode(&msg).unwrap();
+        let msgpack = PayloadCodec::MsgPack.encode(&msg).unwrap();
+        assert_eq!(msgpack[0], PayloadCodec::MsgPack.tag());
+        assert!(msgpack.len() < json.len());
+    }
+
+    #[test]
+    fn test_decode_untagged_json() {
+        let msg = message();
+        let untagged = serde_json::to_vec(&msg).unwrap();
+        assert_eq!(decode_payload::<Message>(&untagged).unwrap(), msg);
+        assert_eq!(encode_payload(&msg).unwrap(), untagged);
+        assert_eq!(PayloadCodec::Json.encode(&msg).unwrap(), untagged);
+    }
+
+    #[test]
+    fn test_decode_tagged_json() {
+        let msg = message();
+        let mut tagged = vec![PayloadCodec::Json.tag()];
+        tagged.extend(serde_json::to_vec(&msg).unwrap());
+        assert_eq!(decode_payload::<Message>(&tagged).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_decode_invalid_payload() {
+        assert!(matches!(
+            decode_payload::<Message>(&[]),
+            Err(PayloadError::Empty)
+        ));
+        assert!(matches!(
+            decode_payload::<Message>(&[PayloadCodec::MsgPack.tag(), 0xc1]),
+            Err(PayloadError::Decode {
+                codec: PayloadCodec::MsgPack,
+                ..
+            })
+        ));
+    }
+}
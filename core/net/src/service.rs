@@ -1,32 +1,19 @@
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::future;
 
 use ya_core_model::net::local::{BindBroadcastError, BroadcastMessage, SendBroadcastMessage};
-use ya_core_model::{identity, NodeId};
+use ya_core_model::net::RemoteEndpoint;
+use ya_core_model::NodeId;
 use ya_service_api_interfaces::Service;
-use ya_service_bus::{Error, RpcEndpoint, RpcMessage};
+use ya_service_bus::timeout::IntoTimeoutFuture;
+use ya_service_bus::{typed::Endpoint, Error, RpcEndpoint, RpcMessage};
 
 use crate::config::{Config, NetType};
-
-pub(crate) async fn identities() -> anyhow::Result<(NodeId, Vec<NodeId>)> {
-    let ids: Vec<identity::IdentityInfo> = ya_service_bus::typed::service(identity::BUS_ID)
-        .send(identity::List::default())
-        .await
-        .map_err(anyhow::Error::msg)??;
-
-    let mut default_id = None;
-    let ids = ids
-        .into_iter()
-        .map(|id| {
-            if id.is_default {
-                default_id = Some(id.node_id);
-            }
-            id.node_id
-        })
-        .collect::<Vec<NodeId>>();
-
-    let default_id = default_id.ok_or_else(|| anyhow::anyhow!("no default identity"))?;
-    Ok((default_id, ids))
-}
+use crate::error::NetError;
+use crate::identity::{IdentityProvider, IdentityServiceProvider};
 
 /// Both Hybrid and Central Net implementation. Only one of them is initialized.
 /// TODO: Remove after transitioning to Hybrid Net.
@@ -42,6 +29,14 @@ impl Service for Net {
 
 impl Net {
     pub async fn gsb<Context>(ctx: Context) -> anyhow::Result<()> {
+        Self::gsb_with_identity(ctx, Rc::new(IdentityServiceProvider)).await
+    }
+
+    /// Starts the network using identities and keys from given provider.
+    pub async fn gsb_with_identity<Context>(
+        ctx: Context,
+        identity: Rc<dyn IdentityProvider>,
+    ) -> anyhow::Result<()> {
         let config = Config::from_env()?;
 
         {
@@ -51,11 +46,11 @@ impl Net {
         match &config.net_type {
             NetType::Central => {
                 crate::central::cli::bind_service();
-                crate::central::Net::gsb(ctx, config).await
+                crate::central::Net::gsb(ctx, config, identity).await
             }
             NetType::Hybrid => {
                 crate::hybrid::cli::bind_service();
-                crate::hybrid::Net::gsb(ctx, config).await
+                crate::hybrid::Net::gsb(ctx, config, identity).await
             }
         }
     }
@@ -68,7 +63,7 @@ impl Net {
         }
 
         match &config.net_type {
-            NetType::Central => Ok(()),
+            NetType::Central => crate::central::Net::shutdown().await,
             NetType::Hybrid => crate::hybrid::Net::shutdown().await,
         }
     }
@@ -96,6 +91,100 @@ where
     }
 }
 
+/// Broadcasts `message` to at most `fanout` random peers. Peers propagate
+/// it further, so it still reaches the whole network, but more slowly.
+/// Central Net delivers broadcasts to all subscribers and ignores `fanout`.
+pub async fn broadcast_with_fanout<M, S>(
+    caller: S,
+    message: M,
+    fanout: Option<u32>,
+) -> Result<
+    Result<
+        <SendBroadcastMessage<M> as RpcMessage>::Item,
+        <SendBroadcastMessage<M> as RpcMessage>::Error,
+    >,
+    Error,
+>
+where
+    M: BroadcastMessage + Send + Sync + Unpin + 'static,
+    S: ToString + 'static,
+{
+    match { NET_TYPE.read().unwrap().clone() } {
+        NetType::Central => crate::central::broadcast(caller, message).await,
+        NetType::Hybrid => crate::hybrid::broadcast_with_fanout(caller, message, fanout).await,
+    }
+}
+
+/// Broadcasts `message` to the given peers only. Peers propagate it further.
+/// Central Net delivers broadcasts to all subscribers and ignores `peers`.
+pub async fn broadcast_to_peers<M, S>(
+    caller: S,
+    message: M,
+    peers: Vec<NodeId>,
+) -> Result<
+    Result<
+        <SendBroadcastMessage<M> as RpcMessage>::Item,
+        <SendBroadcastMessage<M> as RpcMessage>::Error,
+    >,
+    Error,
+>
+where
+    M: BroadcastMessage + Send + Sync + Unpin + 'static,
+    S: ToString + 'static,
+{
+    match { NET_TYPE.read().unwrap().clone() } {
+        NetType::Central => crate::central::broadcast(caller, message).await,
+        NetType::Hybrid => crate::hybrid::broadcast_to_peers(caller, message, peers).await,
+    }
+}
+
+/// Sends `msg` to `endpoint` and waits at most `timeout` for the reply.
+/// Resolves with `NetError::Timeout` when the destination doesn't answer in time,
+/// dropping the pending call.
+pub async fn send_timeout<M>(
+    endpoint: &Endpoint,
+    msg: M,
+    timeout: Duration,
+) -> Result<Result<M::Item, M::Error>, NetError>
+where
+    M: RpcMessage + Unpin,
+{
+    let addr = format!("{}/{}", endpoint.addr(), M::ID);
+    match endpoint.send(msg).timeout(Some(timeout)).await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(Error::Timeout(_))) => Err(NetError::Timeout(addr)),
+        Ok(Err(e)) => Err(NetError::Send(addr, e.to_string())),
+        Err(_) => {
+            log::debug!("Call to {} timed out after {:?}", addr, timeout);
+            Err(NetError::Timeout(addr))
+        }
+    }
+}
+
+/// Sends the same `msg` to `service` on each of `nodes` concurrently, so that
+/// slow peers don't delay delivery to others. Results are in order of `nodes`.
+pub async fn send_to_all<M>(
+    caller: NodeId,
+    nodes: &[NodeId],
+    service: &str,
+    msg: M,
+    timeout: Duration,
+) -> Vec<Result<Result<M::Item, M::Error>, NetError>>
+where
+    M: RpcMessage + Clone + Unpin,
+{
+    let endpoints = nodes
+        .iter()
+        .map(|node| crate::from(caller).to(*node).service(service))
+        .collect::<Vec<_>>();
+    future::join_all(
+        endpoints
+            .iter()
+            .map(|endpoint| send_timeout(endpoint, msg.clone(), timeout)),
+    )
+    .await
+}
+
 /// Chooses one of implementations of `bind_broadcast_with_caller` function
 /// for Hybrid Net or for Central Net.
 pub async fn bind_broadcast_with_caller<M, T, F>(
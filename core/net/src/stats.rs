@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static MESSAGES_SENT: AtomicU64 = AtomicU64::new(0);
+static MESSAGES_RECEIVED: AtomicU64 = AtomicU64::new(0);
+static BYTES_SENT: AtomicU64 = AtomicU64::new(0);
+static BYTES_RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Snapshot of messages exchanged with the network since process start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetStats {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+pub fn net_stats() -> NetStats {
+    NetStats {
+        messages_sent: MESSAGES_SENT.load(Ordering::Relaxed),
+        messages_received: MESSAGES_RECEIVED.load(Ordering::Relaxed),
+        bytes_sent: BYTES_SENT.load(Ordering::Relaxed),
+        bytes_received: BYTES_RECEIVED.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn record_sent(bytes: usize) {
+    MESSAGES_SENT.fetch_add(1, Ordering::Relaxed);
+    BYTES_SENT.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn record_received(bytes: usize) {
+    MESSAGES_RECEIVED.fetch_add(1, Ordering::Relaxed);
+    BYTES_RECEIVED.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_accumulate() {
+        let before = net_stats();
+        record_sent(10);
+        record_sent(5);
+        record_received(7);
+
+        let after = net_stats();
+        assert!(after.messages_sent >= before.messages_sent + 2);
+        assert!(after.bytes_sent >= before.bytes_sent + 15);
+        assert!(after.messages_received >= before.messages_received + 1);
+        assert!(after.bytes_received >= before.bytes_received + 7);
+    }
+}
@@ -0,0 +1 @@
+DROP TABLE nonce_allocation;
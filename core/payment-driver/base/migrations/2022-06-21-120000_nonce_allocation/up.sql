@@ -0,0 +1,8 @@
+CREATE TABLE nonce_allocation(
+    sender TEXT NOT NULL,
+    network INTEGER NOT NULL,
+    nonce INTEGER NOT NULL,
+    tx_id TEXT NULL,
+    time_allocated DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY(sender, network, nonce)
+);
@@ -0,0 +1,6 @@
+CREATE TABLE payment_error(
+    order_id TEXT NOT NULL PRIMARY KEY,
+    error_msg TEXT NOT NULL,
+    time_occurred DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(order_id) REFERENCES payment (order_id)
+);
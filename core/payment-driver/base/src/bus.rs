@@ -80,6 +80,12 @@ pub async fn bind_service<Driver: PaymentDriver + 'static>(
         )
         .bind_with_processor(
             move |db, dr, c, m| async move { dr.shut_down(db, c, m).await }
+        )
+        .bind_with_processor(
+            move |db, dr, c, m| async move { dr.reconcile(db, c, m).await }
+        )
+        .bind_with_processor(
+            move |db, dr, c, m| async move { dr.get_unsettled_payments(db, c, m).await }
         );
 
     log::debug!("Successfully bound payment driver service to service bus.");
@@ -178,3 +184,23 @@ pub async fn notify_payment(
         .map_err(GenericError::new)?;
     Ok(())
 }
+
+pub async fn notify_payment_failed(
+    driver_name: &str,
+    platform: &str,
+    order_ids: Vec<String>,
+    reason: &str,
+) -> Result<(), GenericError> {
+    let msg = payment_srv::NotifyPaymentFailed {
+        driver: driver_name.to_string(),
+        platform: platform.to_string(),
+        order_ids,
+        reason: reason.to_string(),
+    };
+    service(payment_srv::BUS_ID)
+        .send(msg)
+        .await
+        .map_err(GenericError::new)?
+        .map_err(GenericError::new)?;
+    Ok(())
+}
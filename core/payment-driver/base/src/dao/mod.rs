@@ -8,6 +8,7 @@
 mod error;
 
 pub use error::DbError;
+pub mod nonce;
 pub mod payment;
 pub mod transaction;
 
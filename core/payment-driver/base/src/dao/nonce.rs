@@ -0,0 +1,146 @@
+/*
+    Data access object for nonce allocations, linking `NonceAllocationEntity` with `nonce_allocation`
+*/
+
+// External crates
+use chrono::Utc;
+use diesel::dsl::max;
+use diesel::{self, BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+// Workspace uses
+use ya_persistence::executor::{do_with_transaction, readonly_transaction, AsDao, PoolType};
+
+// Local uses
+use crate::{
+    dao::DbResult,
+    db::{
+        models::{Network, NonceAllocationEntity},
+        schema::nonce_allocation::dsl,
+        schema::transaction::dsl as tx_dsl,
+    },
+};
+
+pub struct NonceDao<'c> {
+    pool: &'c PoolType,
+}
+
+impl<'c> AsDao<'c> for NonceDao<'c> {
+    fn as_dao(pool: &'c PoolType) -> Self {
+        Self { pool }
+    }
+}
+
+impl<'c> NonceDao<'c> {
+    /// Persists and returns next nonce for the sender, not lower than `floor`.
+    pub async fn allocate(&self, sender: &str, network: Network, floor: i32) -> DbResult<i32> {
+        let sender = sender.to_string();
+        do_with_transaction(self.pool, move |conn| {
+            let last: Option<i32> = dsl::nonce_allocation
+                .filter(dsl::sender.eq(&sender).and(dsl::network.eq(network)))
+                .select(max(dsl::nonce))
+                .first(conn)?;
+            let nonce = last.map(|n| n + 1).unwrap_or(floor).max(floor);
+
+            diesel::insert_into(dsl::nonce_allocation)
+                .values(NonceAllocationEntity {
+                    sender,
+                    network,
+                    nonce,
+                    tx_id: None,
+                    time_allocated: Utc::now().naive_utc(),
+                })
+                .execute(conn)?;
+            Ok(nonce)
+        })
+        .await
+    }
+
+    /// Marks allocated nonce as used by stored transaction.
+    pub async fn bind(
+        &self,
+        sender: &str,
+        network: Network,
+        nonce: i32,
+        tx_id: &str,
+    ) -> DbResult<()> {
+        let sender = sender.to_string();
+        let tx_id = tx_id.to_string();
+        do_with_transaction(self.pool, move |conn| {
+            diesel::update(
+                dsl::nonce_allocation.filter(
+                    dsl::sender
+                        .eq(sender)
+                        .and(dsl::network.eq(network))
+                        .and(dsl::nonce.eq(nonce)),
+                ),
+            )
+            .set(dsl::tx_id.eq(tx_id))
+            .execute(conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Removes allocation of nonce, that wasn't used.
+    pub async fn release(&self, sender: &str, network: Network, nonce: i32) -> DbResult<()> {
+        let sender = sender.to_string();
+        do_with_transaction(self.pool, move |conn| {
+            diesel::delete(
+                dsl::nonce_allocation.filter(
+                    dsl::sender
+                        .eq(sender)
+                        .and(dsl::network.eq(network))
+                        .and(dsl::nonce.eq(nonce)),
+                ),
+            )
+            .execute(conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Removes allocations already reflected on chain (below `chain_nonce`)
+    /// and allocations without stored transaction, which were interrupted before
+    /// the transaction was saved. Allocations used by a stored transaction are kept,
+    /// even if binding them failed. Returns number of released unbound allocations.
+    pub async fn reconcile(
+        &self,
+        sender: &str,
+        network: Network,
+        chain_nonce: i32,
+    ) -> DbResult<usize> {
+        let sender = sender.to_string();
+        do_with_transaction(self.pool, move |conn| {
+            let allocations =
+                dsl::nonce_allocation.filter(dsl::sender.eq(&sender).and(dsl::network.eq(network)));
+            let used_nonces = tx_dsl::transaction
+                .filter(tx_dsl::sender.eq(&sender).and(tx_dsl::network.eq(network)))
+                .select(tx_dsl::nonce);
+            let released = diesel::delete(
+                allocations.clone().filter(
+                    dsl::tx_id
+                        .is_null()
+                        .and(dsl::nonce.ge(chain_nonce))
+                        .and(dsl::nonce.ne_all(used_nonces)),
+                ),
+            )
+            .execute(conn)?;
+            diesel::delete(allocations.filter(dsl::nonce.lt(chain_nonce))).execute(conn)?;
+            Ok(released)
+        })
+        .await
+    }
+
+    /// Returns nonce following the highest allocated one.
+    pub async fn get_next(&self, sender: &str, network: Network) -> DbResult<Option<i32>> {
+        let sender = sender.to_string();
+        readonly_transaction(self.pool, move |conn| {
+            let last: Option<i32> = dsl::nonce_allocation
+                .filter(dsl::sender.eq(sender).and(dsl::network.eq(network)))
+                .select(max(dsl::nonce))
+                .first(conn)?;
+            Ok(last.map(|n| n + 1))
+        })
+        .await
+    }
+}
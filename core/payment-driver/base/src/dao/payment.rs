@@ -3,7 +3,11 @@
 */
 
 // External crates
-use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::Utc;
+use diesel::{
+    self, BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, QueryDsl,
+    RunQueryDsl,
+};
 
 // Workspace uses
 use ya_persistence::executor::{do_with_transaction, readonly_transaction, AsDao, PoolType};
@@ -12,8 +16,11 @@ use ya_persistence::executor::{do_with_transaction, readonly_transaction, AsDao,
 use crate::{
     dao::DbResult,
     db::{
-        models::{Network, PaymentEntity, PAYMENT_STATUS_NOT_YET, PAYMENT_STATUS_OK},
-        schema::{payment, payment::dsl, transaction},
+        models::{
+            Network, PaymentEntity, PaymentErrorEntity, TransactionStatus, PAYMENT_STATUS_NOT_YET,
+            PAYMENT_STATUS_OK,
+        },
+        schema::{payment, payment::dsl, payment_error, transaction},
     },
 };
 
@@ -85,6 +92,69 @@ impl<'c> PaymentDao<'c> {
         .await
     }
 
+    /// Payments marked as processed, which have no transaction.
+    pub async fn get_orphaned(&self, network: Network) -> DbResult<Vec<PaymentEntity>> {
+        readonly_transaction(self.pool, move |conn| {
+            let payments: Vec<PaymentEntity> = dsl::payment
+                .left_join(transaction::table)
+                .select(payment::all_columns)
+                .filter(dsl::network.eq(network))
+                .filter(dsl::status.eq(PAYMENT_STATUS_OK))
+                .filter(transaction::dsl::tx_id.nullable().is_null())
+                .load(conn)?;
+            Ok(payments)
+        })
+        .await
+    }
+
+    /// Replaces the previously recorded error of the payment.
+    pub async fn record_error(&self, order_id: String, error_msg: String) -> DbResult<()> {
+        let entity = PaymentErrorEntity {
+            order_id,
+            error_msg,
+            time_occurred: Utc::now().naive_utc(),
+        };
+        do_with_transaction(self.pool, move |conn| {
+            diesel::replace_into(payment_error::table)
+                .values(entity)
+                .execute(conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Payments, which are neither confirmed nor failed, along with the last error,
+    /// which prevented them from being sent.
+    pub async fn get_unsettled(
+        &self,
+        network: Network,
+    ) -> DbResult<Vec<(PaymentEntity, Option<PaymentErrorEntity>)>> {
+        readonly_transaction(self.pool, move |conn| {
+            let payments = dsl::payment
+                .left_join(payment_error::table)
+                .left_join(transaction::table)
+                .select((payment::all_columns, payment_error::all_columns.nullable()))
+                .filter(dsl::network.eq(network))
+                .filter(
+                    dsl::status
+                        .eq(PAYMENT_STATUS_NOT_YET)
+                        .or(dsl::status.eq(PAYMENT_STATUS_OK)),
+                )
+                .filter(
+                    transaction::dsl::tx_id
+                        .nullable()
+                        .is_null()
+                        .or(transaction::dsl::status
+                            .nullable()
+                            .ne(TransactionStatus::Confirmed as i32)),
+                )
+                .order(dsl::payment_due_date.asc())
+                .load(conn)?;
+            Ok(payments)
+        })
+        .await
+    }
+
     pub async fn get_first_by_tx_hash(&self, tx_hash: String) -> DbResult<PaymentEntity> {
         readonly_transaction(self.pool, move |conn| {
             let payments: PaymentEntity = dsl::payment
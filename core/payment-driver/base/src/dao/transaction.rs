@@ -119,6 +119,27 @@ impl<'c> TransactionDao<'c> {
         .await
     }
 
+    /// Transactions, which reached a final status on chain after `since`.
+    pub async fn get_confirmed_since(
+        &self,
+        network: Network,
+        since: NaiveDateTime,
+    ) -> DbResult<Vec<TransactionEntity>> {
+        readonly_transaction(self.pool, move |conn| {
+            let txs: Vec<TransactionEntity> = dsl::transaction
+                .filter(
+                    (dsl::status
+                        .eq(TransactionStatus::Confirmed as i32)
+                        .or(dsl::status.eq(TransactionStatus::ErrorOnChain as i32)))
+                    .and(dsl::network.eq(network))
+                    .and(dsl::time_confirmed.gt(since)),
+                )
+                .load(conn)?;
+            Ok(txs)
+        })
+        .await
+    }
+
     pub async fn has_unconfirmed_txs(&self) -> DbResult<bool> {
         readonly_transaction(self.pool, move |conn| {
             let tx: Option<TransactionEntity> = dsl::transaction
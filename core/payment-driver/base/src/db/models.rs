@@ -97,6 +97,27 @@ pub struct PaymentEntity {
     pub network: Network,
 }
 
+/// Last error, which prevented the payment from being sent.
+#[derive(Queryable, Clone, Debug, Insertable, PartialEq)]
+#[table_name = "payment_error"]
+pub struct PaymentErrorEntity {
+    pub order_id: String,
+    pub error_msg: String,
+    pub time_occurred: NaiveDateTime,
+}
+
+/// Nonce assigned to a transaction before it is stored.
+/// `tx_id` is set once the transaction is saved in `transaction` table.
+#[derive(Queryable, Clone, Debug, Insertable, PartialEq)]
+#[table_name = "nonce_allocation"]
+pub struct NonceAllocationEntity {
+    pub sender: String,
+    pub network: Network,
+    pub nonce: i32,
+    pub tx_id: Option<String>,
+    pub time_allocated: NaiveDateTime,
+}
+
 #[derive(AsExpression, FromSqlRow, PartialEq, Debug, Clone, Copy, FromPrimitive)]
 #[sql_type = "Integer"]
 pub enum Network {
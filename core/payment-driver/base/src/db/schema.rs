@@ -1,3 +1,13 @@
+table! {
+    nonce_allocation (sender, network, nonce) {
+        sender -> Text,
+        network -> Integer,
+        nonce -> Integer,
+        tx_id -> Nullable<Text>,
+        time_allocated -> Timestamp,
+    }
+}
+
 table! {
     payment (order_id) {
         order_id -> Text,
@@ -12,6 +22,14 @@ table! {
     }
 }
 
+table! {
+    payment_error (order_id) {
+        order_id -> Text,
+        error_msg -> Text,
+        time_occurred -> Timestamp,
+    }
+}
+
 table! {
     payment_status (status_id) {
         status_id -> Integer,
@@ -61,13 +79,16 @@ table! {
     }
 }
 
+joinable!(payment_error -> payment (order_id));
 joinable!(payment -> payment_status (status));
 joinable!(payment -> transaction (tx_id));
 joinable!(transaction -> transaction_status (status));
 joinable!(transaction -> transaction_type (tx_type));
 
 allow_tables_to_appear_in_same_query!(
+    nonce_allocation,
     payment,
+    payment_error,
     payment_status,
     transaction,
     transaction_status,
@@ -133,4 +133,28 @@ pub trait PaymentDriver {
         caller: String,
         msg: ShutDown,
     ) -> Result<(), GenericError>;
+
+    async fn reconcile(
+        &self,
+        _db: DbExecutor,
+        _caller: String,
+        _msg: Reconcile,
+    ) -> Result<ReconcileReport, GenericError> {
+        Err(GenericError::new(format!(
+            "Reconciliation is not supported by {} driver",
+            self.get_name()
+        )))
+    }
+
+    async fn get_unsettled_payments(
+        &self,
+        _db: DbExecutor,
+        _caller: String,
+        _msg: GetUnsettledPayments,
+    ) -> Result<Vec<UnsettledPayment>, GenericError> {
+        Err(GenericError::new(format!(
+            "Listing unsettled payments is not supported by {} driver",
+            self.get_name()
+        )))
+    }
 }
@@ -2,14 +2,15 @@
     Database Access Object, all you need to interact with the database.
 */
 
+use chrono::NaiveDateTime;
 use web3::types::U256;
 
 // Workspace uses
 use ya_payment_driver::{
-    dao::{payment::PaymentDao, transaction::TransactionDao, DbExecutor},
+    dao::{nonce::NonceDao, payment::PaymentDao, transaction::TransactionDao, DbExecutor},
     db::models::{
-        Network, PaymentEntity, TransactionEntity, TransactionStatus, PAYMENT_STATUS_FAILED,
-        PAYMENT_STATUS_NOT_YET,
+        Network, PaymentEntity, PaymentErrorEntity, TransactionEntity, TransactionStatus,
+        PAYMENT_STATUS_FAILED, PAYMENT_STATUS_NOT_YET,
     },
     model::{GenericError, SchedulePayment},
     utils,
@@ -34,6 +35,10 @@ impl Erc20Dao {
         self.db.as_dao::<TransactionDao>()
     }
 
+    fn nonce(&self) -> NonceDao {
+        self.db.as_dao::<NonceDao>()
+    }
+
     pub async fn get_pending_payments(
         &self,
         node_id: &str,
@@ -56,6 +61,28 @@ impl Erc20Dao {
         }
     }
 
+    /// Unsettled payments along with the last error, which prevented them from being sent.
+    pub async fn get_unsettled_payments(
+        &self,
+        network: Network,
+    ) -> Result<Vec<(PaymentEntity, Option<PaymentErrorEntity>)>, GenericError> {
+        self.payment()
+            .get_unsettled(network)
+            .await
+            .map_err(GenericError::new)
+    }
+
+    /// Records a retryable failure of the payment.
+    pub async fn payment_error(&self, order_id: &str, error: &str) {
+        if let Err(e) = self
+            .payment()
+            .record_error(order_id.to_string(), error.to_string())
+            .await
+        {
+            log::error!("Failed to record error of payment {:?} : {:?}", order_id, e)
+        }
+    }
+
     pub async fn insert_payment(
         &self,
         order_id: &str,
@@ -101,13 +128,81 @@ impl Erc20Dao {
             .map_err(GenericError::new)?;
 
         let max_nonce = list_of_nonces.into_iter().max();
+        let allocated_nonce = self
+            .nonce()
+            .get_next(address, network)
+            .await
+            .map_err(GenericError::new)?;
 
         let next_nonce = match max_nonce {
-            Some(nonce) => U256::from(nonce + 1),
-            None => U256::from(0),
+            Some(nonce) => nonce + 1,
+            None => 0,
         };
 
-        Ok(next_nonce)
+        Ok(U256::from(next_nonce.max(allocated_nonce.unwrap_or(0))))
+    }
+
+    /// Persists nonce assigned to a transaction, which is about to be created.
+    /// Returns `floor` or the next not yet allocated nonce, if it is higher.
+    pub async fn allocate_nonce(
+        &self,
+        address: &str,
+        network: Network,
+        floor: U256,
+    ) -> Result<U256, GenericError> {
+        let nonce = self
+            .nonce()
+            .allocate(address, network, floor.as_u32() as i32)
+            .await
+            .map_err(GenericError::new)?;
+        Ok(U256::from(nonce))
+    }
+
+    pub async fn bind_nonce(
+        &self,
+        address: &str,
+        network: Network,
+        nonce: U256,
+        tx_id: &str,
+    ) -> Result<(), GenericError> {
+        self.nonce()
+            .bind(address, network, nonce.as_u32() as i32, tx_id)
+            .await
+            .map_err(GenericError::new)
+    }
+
+    pub async fn release_nonce(&self, address: &str, network: Network, nonce: U256) {
+        if let Err(e) = self
+            .nonce()
+            .release(address, network, nonce.as_u32() as i32)
+            .await
+        {
+            log::error!("Failed to release nonce {} : {:?}", nonce, e)
+        }
+    }
+
+    /// Drops nonce allocations that were never saved as transaction
+    /// (e.g. because of a crash) or are already reflected on chain.
+    pub async fn reconcile_nonces(
+        &self,
+        address: &str,
+        network: Network,
+        chain_nonce: U256,
+    ) -> Result<(), GenericError> {
+        let released = self
+            .nonce()
+            .reconcile(address, network, chain_nonce.as_u32() as i32)
+            .await
+            .map_err(GenericError::new)?;
+        if released > 0 {
+            log::warn!(
+                "Released {} nonce(s) allocated for {} ({}), but never used.",
+                released,
+                address,
+                network
+            );
+        }
+        Ok(())
     }
 
     pub async fn insert_raw_transaction(&self, tx: TransactionEntity) -> String {
@@ -289,6 +384,9 @@ impl Erc20Dao {
             )
             // TO CHECK: Should it continue or stop the process...
         }
+        for payment in self.get_payments_based_on_tx(tx_id).await {
+            self.payment_error(&payment.order_id, error).await;
+        }
     }
 
     pub async fn payment_failed(&self, order_id: &str) {
@@ -326,6 +424,27 @@ impl Erc20Dao {
         }
     }
 
+    pub async fn get_confirmed_txs_since(
+        &self,
+        network: Network,
+        since: NaiveDateTime,
+    ) -> Result<Vec<TransactionEntity>, GenericError> {
+        self.transaction()
+            .get_confirmed_since(network, since)
+            .await
+            .map_err(GenericError::new)
+    }
+
+    pub async fn get_orphaned_payments(
+        &self,
+        network: Network,
+    ) -> Result<Vec<PaymentEntity>, GenericError> {
+        self.payment()
+            .get_orphaned(network)
+            .await
+            .map_err(GenericError::new)
+    }
+
     pub async fn has_unconfirmed_txs(&self) -> Result<bool, GenericError> {
         self.transaction()
             .has_unconfirmed_txs()
@@ -351,3 +470,123 @@ impl Erc20Dao {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya_payment_driver::db::models::TxType;
+
+    const SENDER: &str = "0xfeaed3f817169c012d040f05c6c52bce5740fc37";
+
+    async fn dao(name: &str) -> Erc20Dao {
+        let db = DbExecutor::in_memory(name).unwrap();
+        ya_payment_driver::dao::init(&db).await.unwrap();
+        Erc20Dao::new(db)
+    }
+
+    #[actix_rt::test]
+    async fn resume_nonce_after_crash_before_transaction_saved() {
+        let dao = dao("erc20-nonce-resume").await;
+        let network = Network::Rinkeby;
+
+        // First payment is saved as transaction, but not broadcast yet.
+        let first = dao
+            .allocate_nonce(SENDER, network, U256::from(5))
+            .await
+            .unwrap();
+        assert_eq!(first, U256::from(5));
+        dao.bind_nonce(SENDER, network, first, "tx-1")
+            .await
+            .unwrap();
+
+        // Second nonce is assigned, then process crashes before saving transaction.
+        let second = dao
+            .allocate_nonce(SENDER, network, first + 1)
+            .await
+            .unwrap();
+        assert_eq!(second, U256::from(6));
+        assert_eq!(
+            dao.get_next_nonce(SENDER, network).await.unwrap(),
+            U256::from(7)
+        );
+
+        // After restart chain state lags behind: `tx-1` wasn't sent.
+        dao.reconcile_nonces(SENDER, network, U256::from(5))
+            .await
+            .unwrap();
+
+        // Nonce of saved transaction stays taken, unused one is reused.
+        assert_eq!(
+            dao.get_next_nonce(SENDER, network).await.unwrap(),
+            U256::from(6)
+        );
+        let resumed = dao
+            .allocate_nonce(SENDER, network, U256::from(5))
+            .await
+            .unwrap();
+        assert_eq!(resumed, U256::from(6));
+        dao.bind_nonce(SENDER, network, resumed, "tx-2")
+            .await
+            .unwrap();
+
+        // Once both are on chain, allocations are pruned and chain nonce is followed.
+        dao.reconcile_nonces(SENDER, network, U256::from(7))
+            .await
+            .unwrap();
+        assert_eq!(
+            dao.allocate_nonce(SENDER, network, U256::from(7))
+                .await
+                .unwrap(),
+            U256::from(7)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn reconcile_keeps_nonce_of_stored_transaction() {
+        let dao = dao("erc20-nonce-unbound").await;
+        let network = Network::Rinkeby;
+
+        // Transaction is stored, but binding its nonce failed.
+        let nonce = dao
+            .allocate_nonce(SENDER, network, U256::from(3))
+            .await
+            .unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        dao.insert_raw_transaction(TransactionEntity {
+            tx_id: "tx-1".to_string(),
+            sender: SENDER.to_string(),
+            nonce: nonce.as_u32() as i32,
+            status: TransactionStatus::Created as i32,
+            tx_type: TxType::Transfer as i32,
+            tmp_onchain_txs: None,
+            final_tx: None,
+            network,
+            starting_gas_price: None,
+            current_gas_price: None,
+            max_gas_price: None,
+            final_gas_used: None,
+            amount_base: None,
+            amount_erc20: None,
+            gas_limit: None,
+            time_created: now,
+            time_last_action: now,
+            time_sent: None,
+            time_confirmed: None,
+            last_error_msg: None,
+            resent_times: 0,
+            signature: None,
+            encoded: Default::default(),
+        })
+        .await;
+
+        dao.reconcile_nonces(SENDER, network, U256::from(3))
+            .await
+            .unwrap();
+        assert_eq!(
+            dao.allocate_nonce(SENDER, network, U256::from(3))
+                .await
+                .unwrap(),
+            U256::from(4)
+        );
+    }
+}
@@ -24,11 +24,21 @@ use ya_payment_driver::{
 };
 
 // Local uses
-use crate::{dao::Erc20Dao, network::SUPPORTED_NETWORKS, DRIVER_NAME, RINKEBY_NETWORK};
+use crate::{
+    dao::Erc20Dao,
+    network::{self, SUPPORTED_NETWORKS},
+    DRIVER_NAME, RINKEBY_NETWORK,
+};
 
 mod api;
 mod cli;
 mod cron;
+mod metrics;
+mod ordering;
+mod reconcile;
+
+pub use metrics::{driver_metrics, Erc20Metrics, NetworkMetrics};
+use ordering::ConfirmationQueue;
 
 lazy_static::lazy_static! {
     static ref TX_SENDOUT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(
@@ -44,6 +54,18 @@ lazy_static::lazy_static! {
                 .and_then(|x| x.parse().ok())
                 .unwrap_or(30),
         );
+
+    static ref ERC20_ORDERED_CONFIRMATIONS: bool = std::env::var("ERC20_ORDERED_CONFIRMATIONS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(false);
+
+    static ref ERC20_CONFIRMATION_ORDER_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(
+            std::env::var("ERC20_CONFIRMATION_ORDER_MAX_DELAY_SECS")
+                .ok()
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(120),
+        );
 }
 
 pub struct Erc20Driver {
@@ -51,6 +73,11 @@ pub struct Erc20Driver {
     dao: Erc20Dao,
     sendout_lock: Mutex<()>,
     confirmation_lock: Mutex<()>,
+    /// Confirmations buffered per network. `None` unless ordered confirmations are enabled.
+    confirmation_order: Option<Mutex<HashMap<String, ConfirmationQueue<cron::ConfirmedTx>>>>,
+    /// Last block checked for confirmations, per network
+    checked_blocks: Mutex<HashMap<String, Option<u64>>>,
+    cron_config: cron::CronConfig,
 }
 
 impl Erc20Driver {
@@ -60,6 +87,12 @@ impl Erc20Driver {
             dao: Erc20Dao::new(db),
             sendout_lock: Default::default(),
             confirmation_lock: Default::default(),
+            confirmation_order: match *ERC20_ORDERED_CONFIRMATIONS {
+                true => Some(Default::default()),
+                false => None,
+            },
+            checked_blocks: Default::default(),
+            cron_config: cron::CronConfig::from_env(),
         }
     }
 
@@ -222,8 +255,36 @@ impl PaymentDriver for Erc20Driver {
         } {
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
         }
+        // Don't hold back confirmations of transactions, which were confirmed before the deadline
+        if let Some(order) = &self.confirmation_order {
+            for (network_key, queue) in order.lock().await.iter_mut() {
+                cron::flush_confirmations(&self.dao, &self.get_name(), network_key, queue).await;
+            }
+        }
         Ok(())
     }
+
+    async fn reconcile(
+        &self,
+        _db: DbExecutor,
+        _caller: String,
+        msg: Reconcile,
+    ) -> Result<ReconcileReport, GenericError> {
+        let network = network::network_like_to_network(msg.network());
+        // Wait for the confirmation job, which updates the same transactions
+        let _guard = self.confirmation_lock.lock().await;
+        reconcile::reconcile(&self.dao, &self.get_name(), network, &self.cron_config).await
+    }
+
+    async fn get_unsettled_payments(
+        &self,
+        _db: DbExecutor,
+        _caller: String,
+        msg: GetUnsettledPayments,
+    ) -> Result<Vec<UnsettledPayment>, GenericError> {
+        let network = network::network_like_to_network(msg.network());
+        api::get_unsettled_payments(&self.dao, network).await
+    }
 }
 
 #[async_trait(?Send)]
@@ -237,8 +298,27 @@ impl PaymentDriverCron for Erc20Driver {
             Some(guard) => guard,
         };
         log::trace!("Running ERC-20 confirmation job...");
+        let mut confirmation_order = match &self.confirmation_order {
+            Some(order) => Some(order.lock().await),
+            None => None,
+        };
+        let mut checked_blocks = self.checked_blocks.lock().await;
         for network_key in self.get_networks().keys() {
-            cron::confirm_payments(&self.dao, &self.get_name(), network_key).await;
+            let ordering = confirmation_order.as_mut().map(|order| {
+                order
+                    .entry(network_key.clone())
+                    .or_insert_with(|| ConfirmationQueue::new(*ERC20_CONFIRMATION_ORDER_MAX_DELAY))
+            });
+            let last_block = checked_blocks.entry(network_key.clone()).or_default();
+            cron::confirm_payments(
+                &self.dao,
+                &self.get_name(),
+                network_key,
+                &self.cron_config,
+                last_block,
+                ordering,
+            )
+            .await;
         }
         log::trace!("ERC-20 confirmation job complete.");
         drop(guard); // Explicit drop to tell Rust that guard is not unused variable
@@ -258,8 +338,14 @@ impl PaymentDriverCron for Erc20Driver {
             let network = Network::from_str(&network_key).unwrap();
             // Process payment rows
             for node_id in self.active_accounts.borrow().list_accounts() {
-                if let Err(e) =
-                    cron::process_payments_for_account(&self.dao, &node_id, network).await
+                if let Err(e) = cron::process_payments_for_account(
+                    &self.dao,
+                    &self.get_name(),
+                    &node_id,
+                    network,
+                    &self.cron_config,
+                )
+                .await
                 {
                     log::error!(
                         "Cron: processing payment for account [{}] failed with error: {}",
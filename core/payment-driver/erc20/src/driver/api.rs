@@ -4,15 +4,18 @@
 // Extrnal crates
 // use lazy_static::lazy_static;
 // use num_bigint::BigInt;
+use chrono::{TimeZone, Utc};
 use uuid::Uuid;
 
 // Workspace uses
 use ya_payment_driver::{
+    db::models::Network,
     driver::BigDecimal,
     model::{
         GasDetails, GenericError, GetAccountBalance, GetAccountGasBalance, SchedulePayment,
-        ValidateAllocation, VerifyPayment,
+        UnsettledPayment, ValidateAllocation, VerifyPayment,
     },
+    utils as base_utils,
 };
 
 // Local uses
@@ -87,6 +90,28 @@ pub async fn schedule_payment(
     Ok(order_id)
 }
 
+pub async fn get_unsettled_payments(
+    dao: &Erc20Dao,
+    network: Network,
+) -> Result<Vec<UnsettledPayment>, GenericError> {
+    let payments = dao.get_unsettled_payments(network).await?;
+    Ok(payments
+        .into_iter()
+        .map(|(payment, error)| UnsettledPayment {
+            amount: base_utils::db_amount_to_big_dec(payment.amount),
+            due_date: Utc.from_utc_datetime(&payment.payment_due_date),
+            order_id: payment.order_id,
+            sender: payment.sender,
+            recipient: payment.recipient,
+            tx_id: payment.tx_id,
+            last_error_time: error
+                .as_ref()
+                .map(|error| Utc.from_utc_datetime(&error.time_occurred)),
+            last_error: error.map(|error| error.error_msg),
+        })
+        .collect())
+}
+
 pub async fn verify_payment(msg: VerifyPayment) -> Result<PaymentDetails, GenericError> {
     log::debug!("verify_payment: {:?}", msg);
     let (network, _) = network::platform_to_network_token(msg.platform())?;
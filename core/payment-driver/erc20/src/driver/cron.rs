@@ -5,6 +5,9 @@
 use anyhow::anyhow;
 use chrono::{Duration, TimeZone, Utc};
 use lazy_static::lazy_static;
+use metrics::counter;
+use std::collections::HashMap;
+use std::future::Future;
 use std::str::FromStr;
 use web3::types::{H256, U256};
 
@@ -13,10 +16,13 @@ use ya_payment_driver::{
     bus,
     db::models::{Network, PaymentEntity, TransactionEntity, TxType},
     driver::BigDecimal,
+    model::{GenericError, PaymentDetails},
     utils,
 };
 
 // Local uses
+use super::metrics::{record_outcome, Outcome};
+use super::ordering::ConfirmationQueue;
 use crate::{
     dao::Erc20Dao,
     erc20::{ethereum, wallet},
@@ -25,39 +31,101 @@ use crate::{
 use ya_payment_driver::db::models::TransactionStatus;
 
 lazy_static! {
-    static ref TX_SUMBIT_TIMEOUT: Duration = Duration::minutes(15);
-    static ref ERC20_WAIT_FOR_TRANSACTION_ON_NETWORK: Duration = match std::env::var(
-        "ERC20_WAIT_FOR_TRANSACTION_ON_NETWORK"
+    static ref ERC20_SEND_TRANSACTIONS_CONCURRENCY: usize = match std::env::var(
+        "ERC20_SEND_TRANSACTIONS_CONCURRENCY"
     )
-    .map(|str| str.parse::<i64>())
+    .map(|str| str.parse::<usize>())
     {
-        Ok(Ok(seconds)) => Duration::seconds(seconds),
-        _ => Duration::seconds(60),
+        Ok(Ok(concurrency)) if concurrency > 0 => concurrency,
+        _ => 4,
     };
-    static ref ERC20_WAIT_FOR_PENDING_ON_NETWORK: Duration =
-        match std::env::var("ERC20_WAIT_FOR_PENDING_ON_NETWORK").map(|str| str.parse::<i64>()) {
-            Ok(Ok(seconds)) => Duration::seconds(seconds),
-            _ => Duration::seconds(600),
+    static ref ERC20_FAIL_ON_TRANSFER_MISMATCH: bool =
+        match std::env::var("ERC20_FAIL_ON_TRANSFER_MISMATCH").map(|str| str.parse::<bool>()) {
+            Ok(Ok(fail)) => fail,
+            _ => false,
         };
-    static ref ERC20_WAIT_FOR_ERROR_SENT_TRANSACTION: Duration = match std::env::var(
-        "ERC20_WAIT_FOR_ERROR_SENT_TRANSACTION"
-    )
-    .map(|str| str.parse::<i64>())
-    {
+}
+
+/// Timeouts of the send-out and confirmation jobs.
+#[derive(Clone, Debug)]
+pub struct CronConfig {
+    /// Failed submissions are retried until this long after the payment due date
+    pub submit_timeout: Duration,
+    /// Transaction not found on chain is resent after this time
+    pub wait_for_transaction_on_network: Duration,
+    /// Pending transaction is resent with higher gas after this time
+    pub wait_for_pending_on_network: Duration,
+    /// Transaction, which failed to send, is resent after this time
+    pub wait_for_error_sent_transaction: Duration,
+}
+
+impl Default for CronConfig {
+    fn default() -> Self {
+        CronConfig {
+            submit_timeout: Duration::minutes(15),
+            wait_for_transaction_on_network: Duration::seconds(60),
+            wait_for_pending_on_network: Duration::seconds(600),
+            wait_for_error_sent_transaction: Duration::seconds(200),
+        }
+    }
+}
+
+impl CronConfig {
+    /// Reads timeouts (in seconds) from the environment, falling back to defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        CronConfig {
+            submit_timeout: env_seconds("ERC20_TX_SUBMIT_TIMEOUT", default.submit_timeout),
+            wait_for_transaction_on_network: env_seconds(
+                "ERC20_WAIT_FOR_TRANSACTION_ON_NETWORK",
+                default.wait_for_transaction_on_network,
+            ),
+            wait_for_pending_on_network: env_seconds(
+                "ERC20_WAIT_FOR_PENDING_ON_NETWORK",
+                default.wait_for_pending_on_network,
+            ),
+            wait_for_error_sent_transaction: env_seconds(
+                "ERC20_WAIT_FOR_ERROR_SENT_TRANSACTION",
+                default.wait_for_error_sent_transaction,
+            ),
+        }
+    }
+}
+
+fn env_seconds(name: &str, default: Duration) -> Duration {
+    match std::env::var(name).map(|str| str.parse::<i64>()) {
         Ok(Ok(seconds)) => Duration::seconds(seconds),
-        _ => Duration::seconds(200),
-    };
+        _ => default,
+    }
 }
 
-pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
+/// Maximum number of payments sent in a single batched transfer.
+const MAX_BATCH_SIZE: usize = 50;
+
+/// Transaction, which was confirmed and succeeded, along with its on-chain hash and gas price.
+pub(super) type ConfirmedTx = (TransactionEntity, String, Option<String>);
+
+/// Checks unconfirmed transactions. When `ordering` is given, payments are reported
+/// in order of creation of their transactions, instead of the order of confirmation.
+/// Transactions held back by `ordering` stay unconfirmed in the db until reported,
+/// so they are checked again after a restart.
+/// Transactions are checked only when a new block was mined since `last_block`.
+pub async fn confirm_payments(
+    dao: &Erc20Dao,
+    name: &str,
+    network_key: &str,
+    config: &CronConfig,
+    last_block: &mut Option<u64>,
+    mut ordering: Option<&mut ConfirmationQueue<ConfirmedTx>>,
+) {
     let network = Network::from_str(&network_key).unwrap();
     let txs = dao.get_unconfirmed_txs(network).await;
     //log::debug!("confirm_payments {:?}", txs);
     let current_time = Utc::now().naive_utc();
 
-    if !txs.is_empty() {
-        // TODO: Store block number and continue only on new block
-        let block_number = match wallet::get_block_number(network).await {
+    let block_number = match txs.is_empty() {
+        true => None,
+        false => match wallet::get_block_number(network).await {
             Ok(block_number) => Some(block_number.as_u64()),
             Err(err) => {
                 log::error!(
@@ -66,9 +134,16 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
                 );
                 None
             }
-        };
+        },
+    };
+    let new_block = !txs.is_empty() && advance_block(last_block, block_number, network);
 
+    if new_block {
         'main_tx_loop: for tx in txs {
+            if matches!(&ordering, Some(queue) if queue.contains(&tx.tx_id)) {
+                log::trace!("tx {} confirmed, waiting to be reported in order", tx.tx_id);
+                continue;
+            }
             log::debug!("checking tx {:?}", &tx);
 
             let time_elapsed_from_sent = tx.time_sent;
@@ -81,10 +156,15 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
             };
 
             let mut tmp_onchain_txs_vec: Vec<&str> = vec![];
-            for str in tmp_onchain_txs.split(";") {
-                if str.len() > 2 {
-                    //todo make proper validation of transaction hash
+            for str in tmp_onchain_txs.split(";").filter(|str| !str.is_empty()) {
+                if is_valid_tx_hash(str) {
                     tmp_onchain_txs_vec.push(str);
+                } else {
+                    log::error!(
+                        "Skipping malformed transaction hash {:?} of tx {}",
+                        str,
+                        tx.tx_id
+                    );
                 }
             }
 
@@ -120,7 +200,7 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
                 }
             }
             if tx.status == TransactionStatus::ErrorSent as i32 {
-                if time_elapsed_from_last_action > *ERC20_WAIT_FOR_ERROR_SENT_TRANSACTION {
+                if time_elapsed_from_last_action > config.wait_for_error_sent_transaction {
                     log::info!("Transaction not sent, retrying");
                     log::warn!(
                         "Transaction not found on chain for {:?}",
@@ -172,7 +252,7 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
 
             if !s.exists_on_chain {
                 log::info!("Transaction not found on chain");
-                if time_elapsed_from_last_action > *ERC20_WAIT_FOR_TRANSACTION_ON_NETWORK {
+                if time_elapsed_from_last_action > config.wait_for_transaction_on_network {
                     log::warn!(
                         "Transaction not found on chain for {:?}",
                         time_elapsed_from_sent
@@ -183,7 +263,7 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
 
                 continue;
             } else if s.pending {
-                if time_elapsed_from_last_action > *ERC20_WAIT_FOR_PENDING_ON_NETWORK {
+                if time_elapsed_from_last_action > config.wait_for_pending_on_network {
                     let cur_gas_price = tx
                         .current_gas_price
                         .and_then(|str| U256::from_dec_str(&str).ok())
@@ -202,7 +282,9 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
                         );
                         continue;
                     }
-                    if cur_gas_price >= max_gas_price {
+                    if cur_gas_price >= max_gas_price
+                        || wallet::bump_gas_price(cur_gas_price) > max_gas_price
+                    {
                         log::debug!("Cannot bump gas more: Current gas price current_gas_price: {} max_gas_price: {}", cur_gas_price, max_gas_price);
                         continue;
                     }
@@ -222,67 +304,16 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
             } else if s.succeeded {
                 log::info!("Transaction confirmed and succeeded");
 
-                dao.transaction_confirmed(&tx.tx_id, newest_tx, final_gas_price)
-                    .await;
-                // Faucet can stop here IF the tx was a success.
-                if tx.tx_type == TxType::Faucet as i32 {
-                    log::debug!("Faucet tx confirmed, exit early. hash={}", &newest_tx);
-                    continue;
-                }
-
-                let payments = dao.get_payments_based_on_tx(&tx.tx_id).await;
-
-                // CLI Transfer ( no related payments ) can stop here IF the tx was a success.
-                if tx.tx_type == TxType::Transfer as i32 && payments.is_empty() {
-                    log::debug!("Transfer confirmed, exit early. hash={}", &newest_tx);
-                    continue;
-                }
-                let order_ids: Vec<String> = payments
-                    .iter()
-                    .map(|payment| payment.order_id.clone())
-                    .collect();
-
-                let platform = match network::network_token_to_platform(Some(network), None) {
-                    Ok(platform) => platform,
-                    Err(e) => {
-                        log::error!(
-                            "Error when converting network_token_to_platform. hash={}. Err={:?}",
-                            &newest_tx,
-                            e
-                        );
-                        continue;
-                    }
-                };
-                let details = match wallet::verify_tx(&newest_tx, network).await {
-                    Ok(a) => a,
-                    Err(e) => {
-                        log::warn!("Failed to get transaction details from erc20, creating bespoke details. Error={}", e);
-
-                        let first_payment: PaymentEntity =
-                            match dao.get_first_payment(&newest_tx).await {
-                                Some(p) => p,
-                                None => continue,
-                            };
-
-                        //Create bespoke payment details:
-                        // - Sender + receiver are the same
-                        // - Date is always now
-                        // - Amount needs to be updated to total of all PaymentEntity's
-                        let mut details = utils::db_to_payment_details(&first_payment);
-                        details.amount = payments
-                            .into_iter()
-                            .map(|payment| utils::db_amount_to_big_dec(payment.amount.clone()))
-                            .sum::<BigDecimal>();
-                        details
+                match ordering.as_deref_mut() {
+                    Some(queue) => queue.push(
+                        tx.time_created,
+                        tx.tx_id.clone(),
+                        (tx.clone(), newest_tx.to_string(), final_gas_price),
+                    ),
+                    None => {
+                        report_confirmed(dao, name, network, &tx, newest_tx, final_gas_price).await
                     }
-                };
-
-                let newest_tx = hex::decode(&newest_tx[2..]).unwrap();
-                if let Err(e) =
-                    bus::notify_payment(name, &platform, order_ids, &details, newest_tx).await
-                {
-                    log::error!("{}", e)
-                };
+                }
             } else {
                 log::info!("Transaction confirmed, but resulted in error");
 
@@ -293,6 +324,7 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
                     "Failure on chain during execution",
                 )
                 .await;
+                record_outcome(network, Outcome::FailedOnChain, 1);
 
                 let payments = dao.get_payments_based_on_tx(&tx.tx_id).await;
 
@@ -303,16 +335,229 @@ pub async fn confirm_payments(dao: &Erc20Dao, name: &str, network_key: &str) {
                 for order_id in order_ids.iter() {
                     dao.payment_failed(order_id).await;
                 }
+                record_outcome(network, Outcome::Failed, order_ids.len() as u64);
+                notify_failed(
+                    name,
+                    network,
+                    order_ids,
+                    "Failure on chain during execution",
+                )
+                .await;
                 continue;
             }
         }
     }
+
+    if let Some(queue) = ordering {
+        if queue.len() == 0 {
+            return;
+        }
+        let unconfirmed = dao.get_unconfirmed_txs(network).await;
+        let first_pending = unconfirmed
+            .iter()
+            .filter(|tx| !queue.contains(&tx.tx_id))
+            .map(|tx| tx.time_created)
+            .min();
+        let confirmed = queue.release(first_pending);
+        log::debug!(
+            "Reporting {} confirmed transactions in order, holding back {}.",
+            confirmed.len(),
+            queue.len()
+        );
+        for (tx, newest_tx, final_gas_price) in confirmed {
+            // Skip transactions already confirmed in the meantime, e.g. by reconciliation
+            if unconfirmed.iter().any(|pending| pending.tx_id == tx.tx_id) {
+                report_confirmed(dao, name, network, &tx, &newest_tx, final_gas_price).await;
+            }
+        }
+    }
+}
+
+/// Checks whether `s` is a `0x` prefixed, 32 bytes long hex string.
+fn is_valid_tx_hash(s: &str) -> bool {
+    s.len() == 66 && s.starts_with("0x") && s[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Records `current` block as the last checked one. Returns `false` when no new block
+/// was mined since the previous check. An unknown block number is always checked.
+fn advance_block(last_block: &mut Option<u64>, current: Option<u64>, network: Network) -> bool {
+    match (*last_block, current) {
+        (_, None) => true,
+        (Some(last), Some(current)) if current == last => {
+            log::trace!(
+                "No new block since the last check. network={}, block={}",
+                network,
+                current
+            );
+            false
+        }
+        (Some(last), Some(current)) => {
+            if current < last {
+                log::warn!(
+                    "Block number decreased from {} to {}, chain reorganization? network={}",
+                    last,
+                    current,
+                    network
+                );
+            }
+            *last_block = Some(current);
+            true
+        }
+        (None, Some(current)) => {
+            *last_block = Some(current);
+            true
+        }
+    }
+}
+
+/// Reports all buffered confirmations regardless of transactions still pending.
+pub(super) async fn flush_confirmations(
+    dao: &Erc20Dao,
+    name: &str,
+    network_key: &str,
+    queue: &mut ConfirmationQueue<ConfirmedTx>,
+) {
+    let network = Network::from_str(&network_key).unwrap();
+    for (tx, newest_tx, final_gas_price) in queue.release(None) {
+        report_confirmed(dao, name, network, &tx, &newest_tx, final_gas_price).await;
+    }
+}
+
+/// Marks a transaction, which succeeded, as confirmed and reports its payments.
+async fn report_confirmed(
+    dao: &Erc20Dao,
+    name: &str,
+    network: Network,
+    tx: &TransactionEntity,
+    newest_tx: &str,
+    final_gas_price: Option<String>,
+) {
+    dao.transaction_confirmed(&tx.tx_id, newest_tx, final_gas_price)
+        .await;
+    record_outcome(network, Outcome::Succeeded, 1);
+    notify_confirmed(dao, name, network, tx, newest_tx).await;
+}
+
+/// Reports payments of a transaction, which was confirmed and succeeded, to the payment service.
+pub(super) async fn notify_confirmed(
+    dao: &Erc20Dao,
+    name: &str,
+    network: Network,
+    tx: &TransactionEntity,
+    newest_tx: &str,
+) {
+    // Faucet can stop here IF the tx was a success.
+    if tx.tx_type == TxType::Faucet as i32 {
+        log::debug!("Faucet tx confirmed, exit early. hash={}", &newest_tx);
+        return;
+    }
+
+    let payments = dao.get_payments_based_on_tx(&tx.tx_id).await;
+
+    // CLI Transfer ( no related payments ) can stop here IF the tx was a success.
+    if tx.tx_type == TxType::Transfer as i32 && payments.is_empty() {
+        log::debug!("Transfer confirmed, exit early. hash={}", &newest_tx);
+        return;
+    }
+
+    if let Err(e) = wallet::verify_encoded_transfer(tx, &payments) {
+        counter!("payment.erc20.transfer.mismatch", 1);
+        log::error!(
+            "Confirmed transaction doesn't match its payments. hash={}. Err={}",
+            &newest_tx,
+            e
+        );
+        if *ERC20_FAIL_ON_TRANSFER_MISMATCH {
+            let mut order_ids = Vec::with_capacity(payments.len());
+            for payment in payments {
+                dao.payment_failed(&payment.order_id).await;
+                order_ids.push(payment.order_id);
+            }
+            let reason = format!("Transaction doesn't match its payments: {}", e);
+            notify_failed(name, network, order_ids, &reason).await;
+            return;
+        }
+    }
+    let order_ids: Vec<String> = payments
+        .iter()
+        .map(|payment| payment.order_id.clone())
+        .collect();
+
+    let platform = match network::network_token_to_platform(Some(network), None) {
+        Ok(platform) => platform,
+        Err(e) => {
+            log::error!(
+                "Error when converting network_token_to_platform. hash={}. Err={:?}",
+                &newest_tx,
+                e
+            );
+            return;
+        }
+    };
+    let details = match wallet::verify_tx(&newest_tx, network).await {
+        Ok(a) => a,
+        Err(e) => {
+            log::warn!(
+                "Failed to get transaction details from erc20, creating bespoke details. Error={}",
+                e
+            );
+
+            let first_payment: PaymentEntity = match dao.get_first_payment(&newest_tx).await {
+                Some(p) => p,
+                None => return,
+            };
+
+            //Create bespoke payment details:
+            // - Sender + receiver are the same
+            // - Date is always now
+            // - Amount needs to be updated to total of all PaymentEntity's
+            let mut details = utils::db_to_payment_details(&first_payment);
+            details.amount = payments
+                .into_iter()
+                .map(|payment| utils::db_amount_to_big_dec(payment.amount.clone()))
+                .sum::<BigDecimal>();
+            details
+        }
+    };
+
+    let newest_tx = hex::decode(&newest_tx[2..]).unwrap();
+    if let Err(e) = bus::notify_payment(name, &platform, order_ids, &details, newest_tx).await {
+        log::error!("{}", e)
+    };
+}
+
+/// Lets the payment service know, that `order_ids` won't be paid.
+pub(super) async fn notify_failed(
+    name: &str,
+    network: Network,
+    order_ids: Vec<String>,
+    reason: &str,
+) {
+    if order_ids.is_empty() {
+        return;
+    }
+    let platform = match network::network_token_to_platform(Some(network), None) {
+        Ok(platform) => platform,
+        Err(e) => {
+            log::error!(
+                "Error when converting network_token_to_platform. order_ids={:?}. Err={:?}",
+                order_ids,
+                e
+            );
+            return;
+        }
+    };
+    if let Err(e) = bus::notify_payment_failed(name, &platform, order_ids, reason).await {
+        log::error!("{}", e)
+    };
 }
 
 pub async fn process_payments_for_account(
     dao: &Erc20Dao,
+    name: &str,
     node_id: &str,
     network: Network,
+    config: &CronConfig,
 ) -> anyhow::Result<()> {
     log::trace!(
         "Processing payments for node_id={}, network={}",
@@ -327,7 +572,8 @@ pub async fn process_payments_for_account(
             network,
             node_id
         );
-        let mut nonce = wallet::get_next_nonce(
+        // Runs under send-out lock, so there are no nonce allocations in progress.
+        let mut nonce = wallet::resume_nonce(
             dao,
             crate::erc20::utils::str_to_addr(&node_id).unwrap(),
             network,
@@ -343,8 +589,8 @@ pub async fn process_payments_for_account(
         })?;
 
         log::debug!("Payments: nonce={}, details={:?}", &nonce, payments);
-        for payment in payments {
-            handle_payment(&dao, payment, &mut nonce).await;
+        for payments in batch_payments(payments, network) {
+            handle_payment(&dao, name, payments, &mut nonce, config.submit_timeout).await;
         }
     }
     Ok(())
@@ -355,34 +601,528 @@ pub async fn process_transactions(dao: &Erc20Dao, network: Network) {
 
     if !transactions.is_empty() {
         log::debug!("transactions: {:?}", transactions);
-        match wallet::send_transactions(dao, transactions, network).await {
+        match wallet::send_transactions(
+            dao,
+            transactions,
+            network,
+            *ERC20_SEND_TRANSACTIONS_CONCURRENCY,
+        )
+        .await
+        {
             Ok(()) => log::debug!("transactions sent!"),
             Err(e) => log::error!("transactions sent ERROR: {:?}", e),
         };
     }
 }
 
-async fn handle_payment(dao: &Erc20Dao, payment: PaymentEntity, nonce: &mut U256) {
-    let details = utils::db_to_payment_details(&payment);
-    let tx_nonce = nonce.to_owned();
+/// Pays `payments` of one recipient with a single transfer.
+async fn handle_payment(
+    dao: &Erc20Dao,
+    name: &str,
+    payments: Vec<PaymentEntity>,
+    nonce: &mut U256,
+    submit_timeout: Duration,
+) {
+    let fee_ceiling = ethereum::get_fee_ceiling(payments[0].network);
+    let max_gas_price = ethereum::get_max_gas_price(payments[0].network);
+    handle_payment_with(
+        dao,
+        name,
+        payments,
+        nonce,
+        fee_ceiling,
+        max_gas_price,
+        submit_timeout,
+        |details, nonce, network| async move {
+            match details.as_slice() {
+                [details] => wallet::make_transfer(details, nonce, network, None, None, None).await,
+                details => wallet::make_multi_transfer(details, nonce, network).await,
+            }
+        },
+    )
+    .await
+}
 
-    match wallet::make_transfer(&details, tx_nonce, payment.network, None, None, None).await {
-        Ok(db_tx) => {
-            let tx_id = dao.insert_raw_transaction(db_tx).await;
-            dao.transaction_saved(&tx_id, &payment.order_id).await;
-            *nonce += U256::from(1);
+/// Groups payments by recipient, if transfers can be batched on `network`.
+/// Otherwise every payment is sent separately.
+fn batch_payments(payments: Vec<PaymentEntity>, network: Network) -> Vec<Vec<PaymentEntity>> {
+    if ethereum::get_multi_transfer_contract(network).is_none() {
+        return payments.into_iter().map(|payment| vec![payment]).collect();
+    }
+    group_by_recipient(payments, MAX_BATCH_SIZE)
+}
+
+fn group_by_recipient(payments: Vec<PaymentEntity>, max_size: usize) -> Vec<Vec<PaymentEntity>> {
+    let mut groups = Vec::<Vec<PaymentEntity>>::new();
+    let mut open = HashMap::<String, usize>::new();
+    for payment in payments {
+        let recipient = payment.recipient.to_lowercase();
+        match open.get(&recipient) {
+            Some(&idx) if groups[idx].len() < max_size => groups[idx].push(payment),
+            _ => {
+                open.insert(recipient, groups.len());
+                groups.push(vec![payment]);
+            }
         }
+    }
+    groups
+}
+
+/// Returns the estimated fee of `db_tx` along with the ceiling, if the fee exceeds it.
+fn fee_over_ceiling(db_tx: &TransactionEntity, fee_ceiling: Option<U256>) -> Option<(U256, U256)> {
+    let fee_ceiling = fee_ceiling?;
+    match ethereum::get_max_gas_costs(db_tx) {
+        Ok(fee) if fee > fee_ceiling => Some((fee, fee_ceiling)),
+        Ok(_) => None,
         Err(e) => {
-            let deadline = Utc.from_utc_datetime(&payment.payment_due_date) + *TX_SUMBIT_TIMEOUT;
-            if Utc::now() > deadline {
-                log::error!("Failed to submit erc20 transaction. Retry deadline reached. details={:?} error={}", payment, e);
-                dao.payment_failed(&payment.order_id).await;
-            } else {
+            log::warn!("Unable to estimate transaction fee: {}", e);
+            None
+        }
+    }
+}
+
+/// Reason to hold `db_tx` back until the network fees drop.
+fn deferral_reason(
+    db_tx: &TransactionEntity,
+    fee_ceiling: Option<U256>,
+    max_gas_price: Option<U256>,
+) -> Option<String> {
+    if let Some((fee, ceiling)) = fee_over_ceiling(db_tx, fee_ceiling) {
+        return Some(format!(
+            "Estimated transaction fee {} wei exceeds the ceiling {} wei",
+            fee, ceiling
+        ));
+    }
+    let max_gas_price = max_gas_price?;
+    match ethereum::get_gas_price_from_db_tx(db_tx) {
+        Ok(gas_price) if gas_price > max_gas_price => Some(format!(
+            "Gas price {} wei exceeds the maximum {} wei",
+            gas_price, max_gas_price
+        )),
+        Ok(_) => None,
+        Err(e) => {
+            log::warn!("Unable to read transaction gas price: {}", e);
+            None
+        }
+    }
+}
+
+async fn handle_payment_with<F, Fut>(
+    dao: &Erc20Dao,
+    name: &str,
+    payments: Vec<PaymentEntity>,
+    nonce: &mut U256,
+    fee_ceiling: Option<U256>,
+    max_gas_price: Option<U256>,
+    submit_timeout: Duration,
+    make_transfer: F,
+) where
+    F: FnOnce(Vec<PaymentDetails>, U256, Network) -> Fut,
+    Fut: Future<Output = Result<TransactionEntity, GenericError>>,
+{
+    let payment = match payments.first() {
+        Some(payment) => payment.clone(),
+        None => return,
+    };
+    let order_ids = payments
+        .iter()
+        .map(|payment| payment.order_id.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    let details = payments
+        .iter()
+        .map(utils::db_to_payment_details)
+        .collect::<Vec<_>>();
+    let sender = match crate::erc20::utils::str_to_addr(&payment.sender) {
+        Ok(sender) => format!("0x{:x}", sender),
+        Err(e) => {
+            log::error!("Invalid payment sender. details={:?} error={}", payment, e);
+            return;
+        }
+    };
+    // Nonce is persisted before the transaction is created, so after a crash
+    // we can tell whether it was used.
+    let tx_nonce = match dao
+        .allocate_nonce(&sender, payment.network, nonce.to_owned())
+        .await
+    {
+        Ok(tx_nonce) => tx_nonce,
+        Err(e) => {
+            log::error!(
+                "Failed to allocate nonce. details={:?} error={}",
+                payments,
+                e
+            );
+            return;
+        }
+    };
+
+    match make_transfer(details, tx_nonce, payment.network).await {
+        Ok(db_tx) => {
+            // Deferred payment stays pending, so it's retried on the next cron tick.
+            if let Some(reason) = deferral_reason(&db_tx, fee_ceiling, max_gas_price) {
+                dao.release_nonce(&sender, payment.network, tx_nonce).await;
                 log::warn!(
-                    "Failed to submit erc20 transaction. Payment will be retried until {}. details={:?} error={}",
-                    deadline, payment, e
+                    "{}. Payment deferred. network={}, order_ids={}",
+                    reason,
+                    payment.network,
+                    order_ids
                 );
-            };
+                counter!("payment.erc20.transfer.deferred", payments.len() as u64);
+                for payment in payments.iter() {
+                    dao.payment_error(&payment.order_id, &reason).await;
+                }
+                return;
+            }
+
+            let tx_id = dao.insert_raw_transaction(db_tx).await;
+            // Allocation stays taken by the stored transaction, even if unbound
+            if let Err(e) = dao
+                .bind_nonce(&sender, payment.network, tx_nonce, &tx_id)
+                .await
+            {
+                log::error!(
+                    "Failed to bind nonce. tx_id={}, nonce={}, error={}",
+                    tx_id,
+                    tx_nonce,
+                    e
+                );
+            }
+            for payment in payments.iter() {
+                dao.transaction_saved(&tx_id, &payment.order_id).await;
+            }
+            record_outcome(payment.network, Outcome::Submitted, 1);
+            if payments.len() > 1 {
+                log::info!(
+                    "Batched payments into a single transfer. tx_id={}, order_ids={}",
+                    tx_id,
+                    order_ids
+                );
+                counter!("payment.erc20.transfer.batched", payments.len() as u64);
+            }
+            *nonce = tx_nonce + U256::from(1);
+        }
+        Err(e) => {
+            dao.release_nonce(&sender, payment.network, tx_nonce).await;
+            let mut failed = vec![];
+            for payment in payments {
+                dao.payment_error(&payment.order_id, &e.to_string()).await;
+                let deadline = Utc.from_utc_datetime(&payment.payment_due_date) + submit_timeout;
+                if Utc::now() > deadline {
+                    log::error!("Failed to submit erc20 transaction. Retry deadline reached. details={:?} error={}", payment, e);
+                    dao.payment_failed(&payment.order_id).await;
+                    failed.push(payment.order_id);
+                } else {
+                    log::warn!(
+                        "Failed to submit erc20 transaction. Payment will be retried until {}. details={:?} error={}",
+                        deadline, payment, e
+                    );
+                    record_outcome(payment.network, Outcome::Retried, 1);
+                };
+            }
+            record_outcome(payment.network, Outcome::Failed, failed.len() as u64);
+            notify_failed(name, payment.network, failed, &e.to_string()).await;
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::erc20::transaction::YagnaRawTransaction;
+    use crate::DRIVER_NAME;
+    use chrono::NaiveDateTime;
+    use ya_payment_driver::dao::{payment::PaymentDao, DbExecutor};
+
+    const SENDER: &str = "0xfeaed3f817169c012d040f05c6c52bce5740fc37";
+    const GAS: u64 = 100_000;
+
+    fn payment() -> PaymentEntity {
+        PaymentEntity {
+            order_id: "order-1".to_string(),
+            amount: utils::u256_to_big_endian_hex(U256::from(1000)),
+            gas: Default::default(),
+            sender: SENDER.to_string(),
+            recipient: "0xd4ea255b238e214a9a0e5656ec36fe27cd14adac".to_string(),
+            payment_due_date: NaiveDateTime::from_timestamp(0, 0),
+            status: 0,
+            tx_id: None,
+            network: Network::Rinkeby,
+        }
+    }
+
+    fn transfer_tx(nonce: U256, gas_price: u64) -> TransactionEntity {
+        let raw_tx = YagnaRawTransaction {
+            nonce,
+            gas_price: U256::from(gas_price),
+            gas: U256::from(GAS),
+            ..Default::default()
+        };
+        ethereum::create_dao_entity(
+            nonce,
+            crate::erc20::utils::str_to_addr(SENDER).unwrap(),
+            raw_tx.gas_price.to_string(),
+            None,
+            GAS as i32,
+            serde_json::to_string(&raw_tx).unwrap(),
+            Network::Rinkeby,
+            Utc::now(),
+            TxType::Transfer,
+            None,
+        )
+    }
+
+    async fn handle(
+        dao: &Erc20Dao,
+        nonce: &mut U256,
+        gas_price: u64,
+        ceiling: Option<u64>,
+        max_gas_price: Option<u64>,
+    ) {
+        handle_payment_with(
+            dao,
+            DRIVER_NAME,
+            vec![payment()],
+            nonce,
+            ceiling.map(U256::from),
+            max_gas_price.map(U256::from),
+            CronConfig::default().submit_timeout,
+            |_, nonce, _| async move { Ok(transfer_tx(nonce, gas_price)) },
+        )
+        .await
+    }
+
+    #[test]
+    fn tx_hash_validation() {
+        let hash = "0x5e5bd0d7a4c81b7dc13b4c1f52c0b3f16d36bd2ba3a7b4a0b6c5bf49bd3d8b2f";
+        assert!(is_valid_tx_hash(hash));
+        assert!(is_valid_tx_hash(
+            &hash.to_uppercase().replacen("0X", "0x", 1)
+        ));
+
+        // short
+        assert!(!is_valid_tx_hash(&hash[..65]));
+        assert!(!is_valid_tx_hash("0x"));
+        // non-hex
+        assert!(!is_valid_tx_hash(&hash.replace('5', "g")));
+        // missing prefix
+        assert!(!is_valid_tx_hash(&hash[2..]));
+        assert!(!is_valid_tx_hash(&format!("00{}", &hash[2..])));
+    }
+
+    #[test]
+    fn only_new_blocks_are_checked() {
+        let network = Network::Rinkeby;
+        let mut last_block = None;
+
+        assert!(advance_block(&mut last_block, Some(100), network));
+        assert!(!advance_block(&mut last_block, Some(100), network));
+        assert!(advance_block(&mut last_block, Some(101), network));
+        assert_eq!(last_block, Some(101));
+
+        // RPC failure: check anyway, but keep the last known block
+        assert!(advance_block(&mut last_block, None, network));
+        assert_eq!(last_block, Some(101));
+
+        // Reorganized chain
+        assert!(advance_block(&mut last_block, Some(99), network));
+        assert!(!advance_block(&mut last_block, Some(99), network));
+    }
+
+    #[actix_rt::test]
+    async fn high_fee_defers_payment_until_fee_drops() {
+        let db = DbExecutor::in_memory("erc20-fee-ceiling").unwrap();
+        ya_payment_driver::dao::init(&db).await.unwrap();
+        let dao = Erc20Dao::new(db);
+        let network = Network::Rinkeby;
+        let ceiling = 50 * GAS;
+        let mut nonce = U256::from(3);
+
+        // Fee above the ceiling: no transaction and the nonce is released
+        handle(&dao, &mut nonce, 80, Some(ceiling), None).await;
+        assert!(dao.get_unsent_txs(network).await.is_empty());
+        assert_eq!(nonce, U256::from(3));
+        assert_eq!(
+            dao.get_next_nonce(SENDER, network).await.unwrap(),
+            U256::zero()
+        );
+
+        // Fee dropped on the next tick
+        handle(&dao, &mut nonce, 40, Some(ceiling), None).await;
+        let txs = dao.get_unsent_txs(network).await;
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].nonce, 3);
+        assert_eq!(nonce, U256::from(4));
+    }
+
+    #[actix_rt::test]
+    async fn high_gas_price_defers_payment() {
+        let db = DbExecutor::in_memory("erc20-max-gas-price").unwrap();
+        ya_payment_driver::dao::init(&db).await.unwrap();
+        db.as_dao::<PaymentDao>().insert(payment()).await.unwrap();
+        let dao = Erc20Dao::new(db);
+        let network = Network::Rinkeby;
+        let mut nonce = U256::from(3);
+
+        handle(&dao, &mut nonce, 80, None, Some(50)).await;
+        assert!(dao.get_unsent_txs(network).await.is_empty());
+        assert_eq!(nonce, U256::from(3));
+        // Deferred payment stays pending
+        let unsettled = dao.get_unsettled_payments(network).await.unwrap();
+        assert_eq!(unsettled.len(), 1);
+        let error = unsettled[0]
+            .1
+            .as_ref()
+            .expect("deferral should be recorded");
+        assert_eq!(
+            error.error_msg,
+            "Gas price 80 wei exceeds the maximum 50 wei"
+        );
+
+        handle(&dao, &mut nonce, 50, None, Some(50)).await;
+        assert_eq!(dao.get_unsent_txs(network).await.len(), 1);
+        assert_eq!(nonce, U256::from(4));
+    }
+
+    #[test]
+    fn payments_are_grouped_by_recipient() {
+        let to = |order_id: &str, recipient: &str| PaymentEntity {
+            order_id: order_id.to_string(),
+            recipient: recipient.to_string(),
+            ..payment()
+        };
+        let payments = vec![
+            to("a", "0xd4ea255b238e214a9a0e5656ec36fe27cd14adac"),
+            to("b", "0x0000000000000000000000000000000000000001"),
+            to("c", "0xD4EA255B238E214A9A0E5656EC36FE27CD14ADAC"),
+            to("d", "0xd4ea255b238e214a9a0e5656ec36fe27cd14adac"),
+        ];
+
+        let order_ids = |groups: Vec<Vec<PaymentEntity>>| {
+            groups
+                .into_iter()
+                .map(|group| group.into_iter().map(|p| p.order_id).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            order_ids(group_by_recipient(payments.clone(), 50)),
+            vec![vec!["a", "c", "d"], vec!["b"]]
+        );
+        assert_eq!(
+            order_ids(group_by_recipient(payments, 2)),
+            vec![vec!["a", "c"], vec!["b"], vec!["d"]]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn batched_payments_share_transaction() {
+        let db = DbExecutor::in_memory("erc20-batch").unwrap();
+        ya_payment_driver::dao::init(&db).await.unwrap();
+        let payments = vec![
+            payment(),
+            PaymentEntity {
+                order_id: "order-2".to_string(),
+                ..payment()
+            },
+        ];
+        for payment in payments.iter() {
+            db.as_dao::<PaymentDao>()
+                .insert(payment.clone())
+                .await
+                .unwrap();
+        }
+        let dao = Erc20Dao::new(db);
+        let mut nonce = U256::from(3);
+
+        handle_payment_with(
+            &dao,
+            DRIVER_NAME,
+            payments,
+            &mut nonce,
+            None,
+            None,
+            CronConfig::default().submit_timeout,
+            |details, nonce, _| async move {
+                assert_eq!(details.len(), 2);
+                Ok(transfer_tx(nonce, 40))
+            },
+        )
+        .await;
+
+        let txs = dao.get_unsent_txs(Network::Rinkeby).await;
+        assert_eq!(txs.len(), 1);
+        let order_ids = dao
+            .get_payments_based_on_tx(&txs[0].tx_id)
+            .await
+            .into_iter()
+            .map(|payment| payment.order_id)
+            .collect::<Vec<_>>();
+        assert_eq!(order_ids.len(), 2);
+        assert!(order_ids.contains(&"order-1".to_string()));
+        assert!(order_ids.contains(&"order-2".to_string()));
+        assert_eq!(nonce, U256::from(4));
+    }
+
+    #[actix_rt::test]
+    async fn failed_transfer_records_payment_error() {
+        let db = DbExecutor::in_memory("erc20-payment-error").unwrap();
+        ya_payment_driver::dao::init(&db).await.unwrap();
+        // Within the retry deadline
+        let payment = PaymentEntity {
+            payment_due_date: Utc::now().naive_utc(),
+            ..payment()
+        };
+        db.as_dao::<PaymentDao>()
+            .insert(payment.clone())
+            .await
+            .unwrap();
+        let dao = Erc20Dao::new(db);
+        let network = Network::Rinkeby;
+        let mut nonce = U256::from(3);
+        let started = Utc::now().naive_utc();
+
+        let submit_timeout = CronConfig::default().submit_timeout;
+        handle_payment_with(
+            &dao,
+            DRIVER_NAME,
+            vec![payment.clone()],
+            &mut nonce,
+            None,
+            None,
+            submit_timeout,
+            |_, _, _| async { Err(GenericError::new("insufficient funds for gas")) },
+        )
+        .await;
+
+        let unsettled = dao.get_unsettled_payments(network).await.unwrap();
+        assert_eq!(unsettled.len(), 1);
+        let (unsettled_payment, error) = &unsettled[0];
+        assert_eq!(unsettled_payment.order_id, payment.order_id);
+        let error = error.as_ref().expect("error should be recorded");
+        assert_eq!(error.error_msg, "insufficient funds for gas");
+        assert!(error.time_occurred >= started - chrono::Duration::seconds(1));
+        assert_eq!(nonce, U256::from(3));
+
+        // Only the last error is kept
+        handle_payment_with(
+            &dao,
+            DRIVER_NAME,
+            vec![payment],
+            &mut nonce,
+            None,
+            None,
+            submit_timeout,
+            |_, _, _| async { Err(GenericError::new("connection refused")) },
+        )
+        .await;
+        let unsettled = dao.get_unsettled_payments(network).await.unwrap();
+        assert_eq!(unsettled.len(), 1);
+        assert_eq!(
+            unsettled[0]
+                .1
+                .as_ref()
+                .map(|error| error.error_msg.as_str()),
+            Some("connection refused")
+        );
+    }
+}
@@ -0,0 +1,123 @@
+/*
+    Counters of transaction outcomes, broken out by network.
+*/
+// Extrnal crates
+use lazy_static::lazy_static;
+use metrics::counter;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Workspace uses
+use ya_payment_driver::db::models::Network;
+
+lazy_static! {
+    static ref METRICS: Mutex<Erc20Metrics> = Default::default();
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetworkMetrics {
+    /// Transactions created for pending payments
+    pub submitted: u64,
+    /// Transactions confirmed on chain
+    pub succeeded: u64,
+    /// Transactions confirmed on chain, which resulted in an error
+    pub failed_on_chain: u64,
+    /// Payments, which failed to be submitted and will be retried
+    pub retried: u64,
+    /// Payments, which won't be realized
+    pub failed: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Erc20Metrics {
+    /// Keyed by network name
+    pub networks: HashMap<String, NetworkMetrics>,
+}
+
+impl Erc20Metrics {
+    pub fn network(&self, network: Network) -> NetworkMetrics {
+        self.networks
+            .get(&network.to_string())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Outcome {
+    Submitted,
+    Succeeded,
+    FailedOnChain,
+    Retried,
+    Failed,
+}
+
+pub(super) fn record_outcome(network: Network, outcome: Outcome, count: u64) {
+    if count == 0 {
+        return;
+    }
+    let name = network.to_string();
+    match outcome {
+        Outcome::Submitted => {
+            counter!("payment.erc20.transaction.submitted", count, "network" => name.clone())
+        }
+        Outcome::Succeeded => {
+            counter!("payment.erc20.transaction.succeeded", count, "network" => name.clone())
+        }
+        Outcome::FailedOnChain => {
+            counter!("payment.erc20.transaction.failed_on_chain", count, "network" => name.clone())
+        }
+        Outcome::Retried => {
+            counter!("payment.erc20.payment.retried", count, "network" => name.clone())
+        }
+        Outcome::Failed => {
+            counter!("payment.erc20.payment.failed", count, "network" => name.clone())
+        }
+    }
+
+    let mut metrics = METRICS.lock().unwrap();
+    let entry = metrics.networks.entry(name).or_default();
+    let value = match outcome {
+        Outcome::Submitted => &mut entry.submitted,
+        Outcome::Succeeded => &mut entry.succeeded,
+        Outcome::FailedOnChain => &mut entry.failed_on_chain,
+        Outcome::Retried => &mut entry.retried,
+        Outcome::Failed => &mut entry.failed,
+    };
+    *value += count;
+}
+
+/// Snapshot of transaction outcomes since the driver started.
+pub fn driver_metrics() -> Erc20Metrics {
+    METRICS.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes_are_counted_per_network() {
+        let before = driver_metrics();
+
+        record_outcome(Network::Goerli, Outcome::Submitted, 2);
+        record_outcome(Network::Goerli, Outcome::Succeeded, 1);
+        record_outcome(Network::Goerli, Outcome::Failed, 3);
+        record_outcome(Network::Mumbai, Outcome::FailedOnChain, 1);
+
+        let after = driver_metrics();
+        let goerli = (
+            before.network(Network::Goerli),
+            after.network(Network::Goerli),
+        );
+        assert_eq!(goerli.1.submitted - goerli.0.submitted, 2);
+        assert_eq!(goerli.1.succeeded - goerli.0.succeeded, 1);
+        assert_eq!(goerli.1.failed - goerli.0.failed, 3);
+        assert_eq!(goerli.1.failed_on_chain, goerli.0.failed_on_chain);
+        assert_eq!(
+            after.network(Network::Mumbai).failed_on_chain
+                - before.network(Network::Mumbai).failed_on_chain,
+            1
+        );
+    }
+}
@@ -0,0 +1,106 @@
+/*
+    Ordering of payment confirmation notifications.
+*/
+// Extrnal crates
+use chrono::NaiveDateTime;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Buffers confirmed transactions, so that payments are reported in the order,
+/// in which their transactions were created.
+///
+/// Confirmation is held back while a transaction created earlier is still
+/// unconfirmed, but no longer than `max_delay`.
+pub(super) struct ConfirmationQueue<T> {
+    max_delay: Duration,
+    buffered: BTreeMap<(NaiveDateTime, String), (T, Instant)>,
+}
+
+impl<T> ConfirmationQueue<T> {
+    pub fn new(max_delay: Duration) -> Self {
+        Self {
+            max_delay,
+            buffered: Default::default(),
+        }
+    }
+
+    pub fn push(&mut self, time_created: NaiveDateTime, tx_id: String, confirmed: T) {
+        self.buffered
+            .insert((time_created, tx_id), (confirmed, Instant::now()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffered.len()
+    }
+
+    pub fn contains(&self, tx_id: &str) -> bool {
+        self.buffered.keys().any(|(_, id)| id == tx_id)
+    }
+
+    /// Takes confirmations, which are ready to be reported, in creation order.
+    /// `first_pending` is the creation time of the oldest unconfirmed transaction.
+    pub fn release(&mut self, first_pending: Option<NaiveDateTime>) -> Vec<T> {
+        let ready = |(time_created, _): &(NaiveDateTime, String), buffered_at: &Instant| {
+            first_pending.map_or(true, |pending| *time_created < pending)
+                || buffered_at.elapsed() >= self.max_delay
+        };
+        // Confirmations waiting too long release all the ones created before them.
+        let last_ready = match self
+            .buffered
+            .iter()
+            .rev()
+            .find(|(key, (_, buffered_at))| ready(key, buffered_at))
+        {
+            Some((key, _)) => key.clone(),
+            None => return Vec::new(),
+        };
+
+        let pending = self.buffered.split_off(&last_ready);
+        let mut released = std::mem::replace(&mut self.buffered, pending);
+        if let Some(last) = self.buffered.remove(&last_ready) {
+            released.insert(last_ready, last);
+        }
+        released
+            .into_iter()
+            .map(|(_, (confirmed, _))| confirmed)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(secs: i64) -> NaiveDateTime {
+        NaiveDateTime::from_timestamp(secs, 0)
+    }
+
+    #[test]
+    fn out_of_order_confirmations_are_released_in_order() {
+        let mut queue = ConfirmationQueue::new(Duration::from_secs(60));
+
+        // Transactions 1, 2, 3 and 4 were created in order. 3 and 2 confirm first.
+        queue.push(time(3), "tx-3".to_string(), 3);
+        queue.push(time(2), "tx-2".to_string(), 2);
+        assert!(queue.release(Some(time(1))).is_empty());
+        assert_eq!(queue.len(), 2);
+
+        queue.push(time(1), "tx-1".to_string(), 1);
+        assert_eq!(queue.release(Some(time(4))), vec![1, 2, 3]);
+        assert_eq!(queue.len(), 0);
+
+        queue.push(time(4), "tx-4".to_string(), 4);
+        assert!(queue.contains("tx-4"));
+        assert_eq!(queue.release(None), vec![4]);
+        assert!(!queue.contains("tx-4"));
+    }
+
+    #[test]
+    fn confirmations_are_not_held_back_forever() {
+        let mut queue = ConfirmationQueue::new(Duration::from_millis(0));
+
+        queue.push(time(2), "tx-2".to_string(), 2);
+        queue.push(time(3), "tx-3".to_string(), 3);
+        assert_eq!(queue.release(Some(time(1))), vec![2, 3]);
+    }
+}
@@ -0,0 +1,405 @@
+/*
+    Driver helper for reconciling the database with the on-chain state of transactions.
+*/
+// Extrnal crates
+use chrono::{Duration, Utc};
+use std::future::Future;
+use std::str::FromStr;
+use web3::types::H256;
+
+// Workspace uses
+use ya_payment_driver::{
+    db::models::{Network, TransactionEntity},
+    model::{GenericError, ReconcileChange, ReconcileReport},
+};
+
+// Local uses
+use super::cron;
+use crate::{
+    dao::Erc20Dao,
+    erc20::{
+        ethereum::{self, TransactionChainStatus},
+        wallet,
+    },
+};
+
+/// Period, for which confirmed transactions are checked for being dropped from the chain.
+const RECENTLY_CONFIRMED_HOURS: i64 = 24;
+const FAILED_ON_CHAIN: &str = "Failure on chain during execution";
+
+pub async fn reconcile(
+    dao: &Erc20Dao,
+    name: &str,
+    network: Network,
+    config: &cron::CronConfig,
+) -> Result<ReconcileReport, GenericError> {
+    let block_number = wallet::get_block_number(network).await?.as_u64();
+    let reconciliation = reconcile_with(
+        dao,
+        network,
+        config.wait_for_transaction_on_network,
+        |hash| ethereum::get_tx_on_chain_status(hash, Some(block_number), network),
+    )
+    .await?;
+
+    for (tx, tx_hash) in reconciliation.confirmed.iter() {
+        cron::notify_confirmed(dao, name, network, tx, tx_hash).await;
+    }
+    cron::notify_failed(name, network, reconciliation.failed, FAILED_ON_CHAIN).await;
+
+    let report = reconciliation.report;
+    log::info!(
+        "Reconciled transactions. network={}, checked={}, changes={}, flagged={}",
+        network,
+        report.checked,
+        report.changes.len(),
+        report.flagged.len()
+    );
+    Ok(report)
+}
+
+struct Reconciliation {
+    report: ReconcileReport,
+    /// Transactions confirmed during reconciliation along with their mined hash.
+    /// Their payments are yet to be reported.
+    confirmed: Vec<(TransactionEntity, String)>,
+    /// Orders of transactions, which failed on chain. Their failure is yet to be reported.
+    failed: Vec<String>,
+}
+
+/// Only statuses, which disagree with the chain are updated, so reconciliation
+/// can be safely repeated. Transactions, which can't be checked are skipped.
+async fn reconcile_with<F, Fut>(
+    dao: &Erc20Dao,
+    network: Network,
+    resend_after: Duration,
+    chain_status: F,
+) -> Result<Reconciliation, GenericError>
+where
+    F: Fn(H256) -> Fut,
+    Fut: Future<Output = Result<TransactionChainStatus, GenericError>>,
+{
+    let now = Utc::now().naive_utc();
+    let since = now - Duration::hours(RECENTLY_CONFIRMED_HOURS);
+    // Fetched up front, so transactions confirmed below are not checked twice
+    let recently_confirmed = dao.get_confirmed_txs_since(network, since).await?;
+
+    let mut reconciliation = Reconciliation {
+        report: ReconcileReport {
+            network: network.to_string(),
+            ..Default::default()
+        },
+        confirmed: Vec::new(),
+        failed: Vec::new(),
+    };
+    let report = &mut reconciliation.report;
+
+    'txs: for tx in dao.get_unconfirmed_txs(network).await {
+        let hashes = onchain_hashes(&tx);
+        let mut mined = None;
+        // Any of the submitted transactions might have been mined, not only the newest one
+        for (tx_hash, hash) in hashes.iter().rev() {
+            match chain_status(*hash).await {
+                Ok(status) if status.exists_on_chain => {
+                    mined = Some((tx_hash.clone(), status));
+                    break;
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    log::warn!(
+                        "Unable to check transaction on chain. Skipping. tx_id={}, hash={}, error={}",
+                        tx.tx_id,
+                        tx_hash,
+                        e
+                    );
+                    continue 'txs;
+                }
+            }
+        }
+        report.checked += 1;
+
+        match mined {
+            Some((_, status)) if status.pending || !status.confirmed => (),
+            Some((tx_hash, status)) => {
+                let gas_price = status.gas_price.map(|gas_price| gas_price.to_string());
+                if status.succeeded {
+                    log::info!(
+                        "Reconcile: transaction mined. tx_id={}, hash={}",
+                        tx.tx_id,
+                        tx_hash
+                    );
+                    dao.transaction_confirmed(&tx.tx_id, &tx_hash, gas_price)
+                        .await;
+                    report.changes.push(ReconcileChange::Confirmed {
+                        tx_id: tx.tx_id.clone(),
+                        tx_hash: tx_hash.clone(),
+                    });
+                    reconciliation.confirmed.push((tx, tx_hash));
+                } else {
+                    log::warn!(
+                        "Reconcile: transaction failed on chain. tx_id={}, hash={}",
+                        tx.tx_id,
+                        tx_hash
+                    );
+                    dao.transaction_confirmed_and_failed(
+                        &tx.tx_id,
+                        &tx_hash,
+                        gas_price,
+                        FAILED_ON_CHAIN,
+                    )
+                    .await;
+                    for payment in dao.get_payments_based_on_tx(&tx.tx_id).await {
+                        dao.payment_failed(&payment.order_id).await;
+                        reconciliation.failed.push(payment.order_id);
+                    }
+                    report.changes.push(ReconcileChange::FailedOnChain {
+                        tx_id: tx.tx_id.clone(),
+                        tx_hash,
+                    });
+                }
+            }
+            None if !hashes.is_empty() && now - tx.time_last_action > resend_after => {
+                log::warn!(
+                    "Reconcile: transaction dropped from the network, resending. tx_id={}",
+                    tx.tx_id
+                );
+                dao.retry_send_transaction(&tx.tx_id, false).await;
+                report.changes.push(ReconcileChange::Requeued {
+                    tx_id: tx.tx_id.clone(),
+                });
+            }
+            None => (),
+        }
+    }
+
+    for tx in recently_confirmed {
+        let (tx_hash, hash) = match tx.final_tx.as_deref().and_then(parse_hash) {
+            Some(hash) => hash,
+            None => continue,
+        };
+        match chain_status(hash).await {
+            Ok(status) => {
+                report.checked += 1;
+                if !status.exists_on_chain {
+                    log::warn!(
+                        "Reconcile: confirmed transaction not found on chain. tx_id={}, hash={}",
+                        tx.tx_id,
+                        tx_hash
+                    );
+                    report.flagged.push(ReconcileChange::MissingOnChain {
+                        tx_id: tx.tx_id,
+                        tx_hash,
+                    });
+                }
+            }
+            Err(e) => log::warn!(
+                "Unable to check transaction on chain. Skipping. tx_id={}, hash={}, error={}",
+                tx.tx_id,
+                tx_hash,
+                e
+            ),
+        }
+    }
+
+    for payment in dao.get_orphaned_payments(network).await? {
+        log::warn!(
+            "Reconcile: payment has no transaction. order_id={}, tx_id={:?}",
+            payment.order_id,
+            payment.tx_id
+        );
+        report.flagged.push(ReconcileChange::OrphanedPayment {
+            order_id: payment.order_id,
+            tx_id: payment.tx_id,
+        });
+    }
+
+    Ok(reconciliation)
+}
+
+/// Hashes of all submitted versions of the transaction, oldest first.
+fn onchain_hashes(tx: &TransactionEntity) -> Vec<(String, H256)> {
+    tx.tmp_onchain_txs
+        .as_deref()
+        .unwrap_or_default()
+        .split(';')
+        .filter_map(parse_hash)
+        .collect()
+}
+
+fn parse_hash(tx_hash: &str) -> Option<(String, H256)> {
+    let hash = H256::from_str(tx_hash.trim_start_matches("0x")).ok()?;
+    Some((tx_hash.to_string(), hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use web3::types::U256;
+    use ya_payment_driver::dao::{payment::PaymentDao, DbExecutor};
+    use ya_payment_driver::db::models::{
+        PaymentEntity, TransactionStatus, TxType, PAYMENT_STATUS_FAILED, PAYMENT_STATUS_OK,
+    };
+    use ya_payment_driver::utils;
+
+    const SENDER: &str = "0xfeaed3f817169c012d040f05c6c52bce5740fc37";
+    const NETWORK: Network = Network::Rinkeby;
+
+    fn hash(n: u8) -> String {
+        format!("0x{}", hex::encode([n; 32]))
+    }
+
+    fn tx(id: &str, status: TransactionStatus, hashes: &[u8]) -> TransactionEntity {
+        let mut tx = ethereum::create_dao_entity(
+            Default::default(),
+            crate::erc20::utils::str_to_addr(SENDER).unwrap(),
+            "1".to_string(),
+            None,
+            100_000,
+            "encoded".to_string(),
+            NETWORK,
+            Utc::now() - Duration::hours(1),
+            TxType::Transfer,
+            None,
+        );
+        tx.tx_id = id.to_string();
+        tx.status = status as i32;
+        tx.tmp_onchain_txs = Some(
+            hashes
+                .iter()
+                .map(|n| hash(*n))
+                .collect::<Vec<_>>()
+                .join(";"),
+        );
+        tx
+    }
+
+    fn payment(order_id: &str, tx_id: Option<&str>) -> PaymentEntity {
+        PaymentEntity {
+            order_id: order_id.to_string(),
+            amount: utils::u256_to_big_endian_hex(U256::from(1000)),
+            gas: Default::default(),
+            sender: SENDER.to_string(),
+            recipient: "0xd4ea255b238e214a9a0e5656ec36fe27cd14adac".to_string(),
+            payment_due_date: Utc::now().naive_utc(),
+            status: PAYMENT_STATUS_OK,
+            tx_id: tx_id.map(ToString::to_string),
+            network: NETWORK,
+        }
+    }
+
+    fn chain_status(
+        exists_on_chain: bool,
+        pending: bool,
+        succeeded: bool,
+    ) -> TransactionChainStatus {
+        TransactionChainStatus {
+            exists_on_chain,
+            pending,
+            confirmed: exists_on_chain && !pending,
+            succeeded,
+            gas_used: None,
+            gas_price: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn reconcile_fixes_drift_and_is_idempotent() {
+        let db = DbExecutor::in_memory("erc20-reconcile").unwrap();
+        ya_payment_driver::dao::init(&db).await.unwrap();
+        let dao = Erc20Dao::new(db.clone());
+
+        // Gas was bumped, but the original transaction got mined
+        dao.insert_raw_transaction(tx("mined", TransactionStatus::ErrorSent, &[1, 2]))
+            .await;
+        dao.insert_raw_transaction(tx("reverted", TransactionStatus::Pending, &[3]))
+            .await;
+        dao.insert_raw_transaction(tx("dropped", TransactionStatus::Sent, &[4]))
+            .await;
+        dao.insert_raw_transaction(tx("in-flight", TransactionStatus::Sent, &[5]))
+            .await;
+        let mut reorged = tx("reorged", TransactionStatus::Confirmed, &[]);
+        reorged.final_tx = Some(hash(6));
+        reorged.time_confirmed = Some(Utc::now().naive_utc());
+        dao.insert_raw_transaction(reorged).await;
+
+        let payments = db.as_dao::<PaymentDao>();
+        for payment in vec![
+            payment("paid", Some("mined")),
+            payment("failed", Some("reverted")),
+            payment("orphan", None),
+        ] {
+            payments.insert(payment).await.unwrap();
+        }
+
+        let chain: HashMap<H256, TransactionChainStatus> = vec![
+            (1, chain_status(true, false, true)),
+            (3, chain_status(true, false, false)),
+            (5, chain_status(true, true, false)),
+        ]
+        .into_iter()
+        .map(|(n, status)| (H256::from([n; 32]), status))
+        .collect();
+        let lookup = |hash: H256| {
+            let status = chain
+                .get(&hash)
+                .cloned()
+                .unwrap_or_else(|| chain_status(false, false, false));
+            async move { Ok(status) }
+        };
+
+        let first = reconcile_with(&dao, NETWORK, Duration::minutes(1), &lookup)
+            .await
+            .unwrap();
+        assert_eq!(first.report.checked, 5);
+        let changes = &first.report.changes;
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&ReconcileChange::Confirmed {
+            tx_id: "mined".to_string(),
+            tx_hash: hash(1),
+        }));
+        assert!(changes.contains(&ReconcileChange::FailedOnChain {
+            tx_id: "reverted".to_string(),
+            tx_hash: hash(3),
+        }));
+        assert!(changes.contains(&ReconcileChange::Requeued {
+            tx_id: "dropped".to_string(),
+        }));
+        assert_eq!(
+            first.report.flagged,
+            vec![
+                ReconcileChange::MissingOnChain {
+                    tx_id: "reorged".to_string(),
+                    tx_hash: hash(6),
+                },
+                ReconcileChange::OrphanedPayment {
+                    order_id: "orphan".to_string(),
+                    tx_id: None,
+                },
+            ]
+        );
+        assert_eq!(first.confirmed.len(), 1);
+        assert_eq!(first.confirmed[0].0.tx_id, "mined");
+        assert_eq!(first.failed, vec!["failed".to_string()]);
+
+        let unsent = dao.get_unsent_txs(NETWORK).await;
+        assert_eq!(unsent.len(), 1);
+        assert_eq!(unsent[0].tx_id, "dropped");
+        let unconfirmed = dao.get_unconfirmed_txs(NETWORK).await;
+        assert_eq!(unconfirmed.len(), 1);
+        assert_eq!(unconfirmed[0].tx_id, "in-flight");
+        assert_eq!(
+            dao.get_payments_based_on_tx("reverted").await[0].status,
+            PAYMENT_STATUS_FAILED
+        );
+
+        // Nothing left to correct
+        let second = reconcile_with(&dao, NETWORK, Duration::minutes(1), &lookup)
+            .await
+            .unwrap();
+        assert!(second.report.changes.is_empty());
+        assert!(second.confirmed.is_empty());
+        assert!(second.failed.is_empty());
+        assert_eq!(second.report.flagged, first.report.flagged);
+    }
+}
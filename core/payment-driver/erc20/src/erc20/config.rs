@@ -1,6 +1,8 @@
+use bigdecimal::BigDecimal;
 use lazy_static::lazy_static;
 use std::env;
-use web3::types::Address;
+use std::str::FromStr;
+use web3::types::{Address, U256};
 
 use crate::erc20::utils;
 
@@ -16,7 +18,105 @@ use crate::erc20::utils;
 pub struct EnvConfiguration {
     pub glm_contract_address: Address,
     pub glm_faucet_address: Option<Address>,
+    /// Blocks, including the one with the transaction, after which it's
+    /// considered confirmed.
     pub required_confirmations: u64,
+    /// Maximum transaction fee (in the network's native token). Payments with
+    /// a higher estimated fee are deferred until the fee drops.
+    pub fee_ceiling: Option<U256>,
+    /// Maximum gas price (in wei). Payments are deferred while the network
+    /// gas price is higher.
+    pub max_gas_price: Option<U256>,
+    /// Contract sending multiple GLM transfers in one transaction. Payments
+    /// are sent one by one, when it's not deployed on the network.
+    pub multi_transfer_contract_address: Option<Address>,
+    /// How transaction fees are paid on the network.
+    pub fee_mode: FeeMode,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeeMode {
+    /// Single gas price
+    Legacy,
+    /// Base fee burned by the network and priority fee (tip) for the miner
+    Eip1559,
+}
+
+fn fee_mode(var: &str, default: FeeMode) -> FeeMode {
+    match env::var(var).map(|v| v.to_lowercase()).as_deref() {
+        Ok("legacy") => FeeMode::Legacy,
+        Ok("eip1559") => FeeMode::Eip1559,
+        Ok(value) => {
+            log::warn!("Invalid {} value: {}. Using {:?}", var, value, default);
+            default
+        }
+        Err(_) => default,
+    }
+}
+
+fn required_confirmations(var: &str, default: u64) -> u64 {
+    match env::var(var).map(|v| v.parse::<u64>()) {
+        Ok(Ok(confirmations)) if confirmations > 0 => confirmations,
+        Ok(_) => {
+            log::warn!("Invalid {} value. Using {}", var, default);
+            default
+        }
+        Err(_) => default,
+    }
+}
+
+fn fee_ceiling(var: &str) -> Option<U256> {
+    let value = env::var(var).ok()?;
+    match BigDecimal::from_str(&value)
+        .map_err(|e| e.to_string())
+        .and_then(|v| utils::big_dec_to_u256(&v).map_err(|e| e.to_string()))
+    {
+        Ok(ceiling) => Some(ceiling),
+        Err(e) => {
+            log::warn!(
+                "Invalid {} value: {}. Fee ceiling disabled. {}",
+                var,
+                value,
+                e
+            );
+            None
+        }
+    }
+}
+
+fn max_gas_price(var: &str) -> Option<U256> {
+    let value = env::var(var).ok()?;
+    match BigDecimal::from_str(&value)
+        .map_err(|e| e.to_string())
+        .and_then(|v| utils::big_dec_gwei_to_u256(v).map_err(|e| e.to_string()))
+    {
+        Ok(max) => Some(max),
+        Err(e) => {
+            log::warn!(
+                "Invalid {} value: {}. Gas price cap disabled. {}",
+                var,
+                value,
+                e
+            );
+            None
+        }
+    }
+}
+
+fn multi_transfer_contract(var: &str) -> Option<Address> {
+    let value = env::var(var).ok()?;
+    match utils::str_to_addr(&value) {
+        Ok(address) => Some(address),
+        Err(e) => {
+            log::warn!(
+                "Invalid {} value: {}. Batched transfers disabled. {}",
+                var,
+                value,
+                e
+            );
+            None
+        }
+    }
 }
 
 lazy_static! {
@@ -33,12 +133,13 @@ lazy_static! {
             )
             .unwrap()
         ),
-        required_confirmations: {
-            match env::var("ERC20_RINKEBY_REQUIRED_CONFIRMATIONS").map(|s| s.parse()) {
-                Ok(Ok(x)) => x,
-                _ => 3,
-            }
-        }
+        required_confirmations: required_confirmations("ERC20_RINKEBY_REQUIRED_CONFIRMATIONS", 3),
+        fee_ceiling: fee_ceiling("ERC20_RINKEBY_FEE_CEILING"),
+        max_gas_price: max_gas_price("ERC20_RINKEBY_MAX_GAS_PRICE"),
+        multi_transfer_contract_address: multi_transfer_contract(
+            "RINKEBY_MULTI_TRANSFER_CONTRACT_ADDRESS"
+        ),
+        fee_mode: fee_mode("ERC20_RINKEBY_FEE_MODE", FeeMode::Eip1559),
     };
     pub static ref MAINNET_CONFIG: EnvConfiguration = EnvConfiguration {
         glm_contract_address: utils::str_to_addr(
@@ -47,12 +148,13 @@ lazy_static! {
         )
         .unwrap(),
         glm_faucet_address: None,
-        required_confirmations: {
-            match env::var("ERC20_MAINNET_REQUIRED_CONFIRMATIONS").map(|s| s.parse()) {
-                Ok(Ok(x)) => x,
-                _ => 5,
-            }
-        }
+        required_confirmations: required_confirmations("ERC20_MAINNET_REQUIRED_CONFIRMATIONS", 5),
+        fee_ceiling: fee_ceiling("ERC20_MAINNET_FEE_CEILING"),
+        max_gas_price: max_gas_price("ERC20_MAINNET_MAX_GAS_PRICE"),
+        multi_transfer_contract_address: multi_transfer_contract(
+            "MAINNET_MULTI_TRANSFER_CONTRACT_ADDRESS"
+        ),
+        fee_mode: fee_mode("ERC20_MAINNET_FEE_MODE", FeeMode::Eip1559),
     };
     pub static ref GOERLI_CONFIG: EnvConfiguration = EnvConfiguration {
         glm_contract_address: utils::str_to_addr(
@@ -61,12 +163,13 @@ lazy_static! {
         )
         .unwrap(),
         glm_faucet_address: None,
-        required_confirmations: {
-            match env::var("ERC20_GOERLI_REQUIRED_CONFIRMATIONS").map(|s| s.parse()) {
-                Ok(Ok(x)) => x,
-                _ => 3,
-            }
-        }
+        required_confirmations: required_confirmations("ERC20_GOERLI_REQUIRED_CONFIRMATIONS", 3),
+        fee_ceiling: fee_ceiling("ERC20_GOERLI_FEE_CEILING"),
+        max_gas_price: max_gas_price("ERC20_GOERLI_MAX_GAS_PRICE"),
+        multi_transfer_contract_address: multi_transfer_contract(
+            "GOERLI_MULTI_TRANSFER_CONTRACT_ADDRESS"
+        ),
+        fee_mode: fee_mode("ERC20_GOERLI_FEE_MODE", FeeMode::Eip1559),
     };
     pub static ref MUMBAI_CONFIG: EnvConfiguration = EnvConfiguration {
         glm_contract_address: utils::str_to_addr(
@@ -75,12 +178,13 @@ lazy_static! {
         )
         .unwrap(),
         glm_faucet_address: None,
-        required_confirmations: {
-            match env::var("ERC20_MUMBAI_REQUIRED_CONFIRMATIONS").map(|s| s.parse()) {
-                Ok(Ok(x)) => x,
-                _ => 3,
-            }
-        }
+        required_confirmations: required_confirmations("ERC20_MUMBAI_REQUIRED_CONFIRMATIONS", 3),
+        fee_ceiling: fee_ceiling("ERC20_MUMBAI_FEE_CEILING"),
+        max_gas_price: max_gas_price("ERC20_MUMBAI_MAX_GAS_PRICE"),
+        multi_transfer_contract_address: multi_transfer_contract(
+            "MUMBAI_MULTI_TRANSFER_CONTRACT_ADDRESS"
+        ),
+        fee_mode: fee_mode("ERC20_MUMBAI_FEE_MODE", FeeMode::Legacy),
     };
     pub static ref POLYGON_MAINNET_CONFIG: EnvConfiguration = EnvConfiguration {
         glm_contract_address: utils::str_to_addr(
@@ -89,11 +193,12 @@ lazy_static! {
         )
         .unwrap(),
         glm_faucet_address: None,
-        required_confirmations: {
-            match env::var("ERC20_POLYGON_REQUIRED_CONFIRMATIONS").map(|s| s.parse()) {
-                Ok(Ok(x)) => x,
-                _ => 5,
-            }
-        }
+        required_confirmations: required_confirmations("ERC20_POLYGON_REQUIRED_CONFIRMATIONS", 5),
+        fee_ceiling: fee_ceiling("ERC20_POLYGON_FEE_CEILING"),
+        max_gas_price: max_gas_price("ERC20_POLYGON_MAX_GAS_PRICE"),
+        multi_transfer_contract_address: multi_transfer_contract(
+            "POLYGON_MULTI_TRANSFER_CONTRACT_ADDRESS"
+        ),
+        fee_mode: fee_mode("ERC20_POLYGON_FEE_MODE", FeeMode::Legacy),
     };
 }
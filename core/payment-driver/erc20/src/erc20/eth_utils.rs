@@ -6,7 +6,18 @@ use tiny_keccak::{Hasher, Keccak};
 
 use crate::erc20::transaction::YagnaRawTransaction;
 
+/// EIP-2718 type of EIP-1559 transactions
+const EIP1559_TX_TYPE: u8 = 0x02;
+
 pub fn get_tx_hash(tx: &YagnaRawTransaction, chain_id: u64) -> Vec<u8> {
+    if tx.is_eip1559() {
+        let mut hash = RlpStream::new();
+        hash.begin_unbounded_list();
+        eip1559_tx_encode(tx, chain_id, &mut hash);
+        hash.finalize_unbounded_list();
+        return keccak256_hash(&[&[EIP1559_TX_TYPE][..], &hash.out()[..]].concat());
+    }
+
     let mut hash = RlpStream::new();
     hash.begin_unbounded_list();
     tx_encode(tx, &mut hash);
@@ -38,6 +49,23 @@ fn tx_encode(tx: &YagnaRawTransaction, s: &mut RlpStream) {
     s.append(&tx.data);
 }
 
+fn eip1559_tx_encode(tx: &YagnaRawTransaction, chain_id: u64, s: &mut RlpStream) {
+    s.append(&chain_id);
+    s.append(&tx.nonce);
+    s.append(&tx.max_priority_fee_per_gas.unwrap_or_default());
+    s.append(&tx.max_fee_per_gas.unwrap_or_default());
+    s.append(&tx.gas);
+    if let Some(ref t) = tx.to {
+        s.append(t);
+    } else {
+        s.append(&vec![]);
+    }
+    s.append(&tx.value);
+    s.append(&tx.data);
+    // empty access list
+    s.begin_list(0);
+}
+
 // MISSING RawTransaction.encode_signed_tx()
 
 pub fn encode_signed_tx(
@@ -45,6 +73,10 @@ pub fn encode_signed_tx(
     signature: Vec<u8>,
     chain_id: u64,
 ) -> Vec<u8> {
+    if raw_tx.is_eip1559() {
+        return encode_signed_eip1559_tx(raw_tx, signature, chain_id);
+    }
+
     let (sig_v, sig_r, sig_s) = prepare_signature(signature, chain_id);
 
     let mut tx = RlpStream::new();
@@ -61,6 +93,30 @@ pub fn encode_signed_tx(
     tx.out().to_vec()
 }
 
+fn encode_signed_eip1559_tx(
+    raw_tx: &YagnaRawTransaction,
+    signature: Vec<u8>,
+    chain_id: u64,
+) -> Vec<u8> {
+    assert_eq!(signature.len(), 65);
+    // Signature recovery id is used as the y parity
+    let y_parity = signature[0] as u64;
+    let mut sig_r = signature.to_owned().split_off(1);
+    let mut sig_s = sig_r.split_off(32);
+    prepare_signature_part(&mut sig_r);
+    prepare_signature_part(&mut sig_s);
+
+    let mut tx = RlpStream::new();
+    tx.begin_unbounded_list();
+    eip1559_tx_encode(raw_tx, chain_id, &mut tx);
+    tx.append(&y_parity);
+    tx.append(&sig_r);
+    tx.append(&sig_s);
+    tx.finalize_unbounded_list();
+
+    [&[EIP1559_TX_TYPE][..], &tx.out()[..]].concat()
+}
+
 fn prepare_signature(signature: Vec<u8>, chain_id: u64) -> (u64, Vec<u8>, Vec<u8>) {
     // TODO ugly solution
     assert_eq!(signature.len(), 65);
@@ -21,7 +21,7 @@ use ya_payment_driver::{bus, model::GenericError};
 
 use crate::erc20::eth_utils::keccak256_hash;
 use crate::erc20::transaction::YagnaRawTransaction;
-use crate::erc20::{config, eth_utils};
+use crate::erc20::{config, eth_utils, utils};
 
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum ClientError {
@@ -76,6 +76,7 @@ lazy_static! {
 const CREATE_FAUCET_FUNCTION: &str = "create";
 const BALANCE_ERC20_FUNCTION: &str = "balanceOf";
 const TRANSFER_ERC20_FUNCTION: &str = "transfer";
+const MULTI_TRANSFER_FUNCTION: &str = "golemTransferDirect";
 const GET_DOMAIN_SEPARATOR_FUNCTION: &str = "getDomainSeperator";
 const GET_NONCE_FUNCTION: &str = "getNonce";
 
@@ -124,6 +125,34 @@ pub fn get_polygon_gas_price_method() -> PolygonGasPriceMethod {
     }
 }
 
+/// Minimum gas price increase accepted by nodes for a replacement transaction
+pub const MIN_GAS_BUMP_PERCENT: u64 = 10;
+
+/// Gas price increase (in percent) of resent pending transactions
+pub fn get_gas_bump_percent() -> u64 {
+    match std::env::var("ERC20_GAS_BUMP_PERCENT").map(|v| v.parse::<u64>()) {
+        Ok(Ok(percent)) if percent >= MIN_GAS_BUMP_PERCENT => percent,
+        Ok(Ok(percent)) => {
+            log::warn!(
+                "ERC20_GAS_BUMP_PERCENT={} is too low for transaction replacement, using {}",
+                percent,
+                MIN_GAS_BUMP_PERCENT
+            );
+            MIN_GAS_BUMP_PERCENT
+        }
+        _ => 11,
+    }
+}
+
+/// Priority fee (tip) per gas of EIP-1559 transactions
+pub fn get_max_priority_fee() -> U256 {
+    let gwei = match std::env::var("ERC20_MAX_PRIORITY_FEE").map(|v| v.parse::<f64>()) {
+        Ok(Ok(gwei)) if gwei >= 0.0 => gwei,
+        _ => 1.5,
+    };
+    utils::convert_float_gas_to_u256(gwei)
+}
+
 pub fn get_polygon_priority() -> PolygonPriority {
     match std::env::var("POLYGON_PRIORITY")
         .unwrap_or("default".to_string())
@@ -265,6 +294,7 @@ async fn sign_faucet_tx_with(
         gas_price,
         gas: *GLM_FAUCET_GAS,
         data,
+        ..Default::default()
     };
     //let chain_id = network as u64;
     //let node_id = NodeId::from(address.as_ref());
@@ -341,6 +371,107 @@ async fn prepare_raw_transaction_with(
     let data = eth_utils::contract_encode(&contract, TRANSFER_ERC20_FUNCTION, (recipient, amount))
         .map_err(GenericError::new)?;
 
+    let gas_price = gas_price_or_network(&client, gas_price_override).await?;
+
+    let gas_limit = match network {
+        Network::Polygon => gas_limit_override.map_or(*GLM_POLYGON_GAS_LIMIT, |v| U256::from(v)),
+        _ => gas_limit_override.map_or(*GLM_TRANSFER_GAS, |v| U256::from(v)),
+    };
+
+    let mut tx = YagnaRawTransaction {
+        nonce,
+        to: Some(contract.address()),
+        value: U256::from(0),
+        gas_price,
+        gas: gas_limit,
+        data,
+        ..Default::default()
+    };
+    apply_fee_mode(&mut tx, &env);
+
+    Ok(tx)
+}
+
+/// Address of the contract batching GLM transfers, if it's available on `network`.
+pub fn get_multi_transfer_contract(network: Network) -> Option<H160> {
+    get_env(network).multi_transfer_contract_address
+}
+
+/// Prepares a single transaction with GLM transfers to all `recipients`.
+pub async fn prepare_raw_multi_transaction(
+    recipients: Vec<H160>,
+    amounts: Vec<U256>,
+    network: Network,
+    nonce: U256,
+    gas_price_override: Option<U256>,
+) -> Result<YagnaRawTransaction, GenericError> {
+    with_clients(network, |client| {
+        prepare_raw_multi_transaction_with(
+            client,
+            recipients.clone(),
+            amounts.clone(),
+            network,
+            nonce,
+            gas_price_override,
+        )
+    })
+    .await
+}
+
+async fn prepare_raw_multi_transaction_with(
+    client: Web3<Http>,
+    recipients: Vec<H160>,
+    amounts: Vec<U256>,
+    network: Network,
+    nonce: U256,
+    gas_price_override: Option<U256>,
+) -> Result<YagnaRawTransaction, ClientError> {
+    let address = get_multi_transfer_contract(network).ok_or_else(|| {
+        ClientError::new(format!("No multi transfer contract on network {}", network))
+    })?;
+    let contract = prepare_contract(
+        &client,
+        address,
+        include_bytes!("../contracts/multi_transfer.json"),
+    )?;
+    let transfers = U256::from(recipients.len());
+    let data =
+        eth_utils::contract_encode(&contract, MULTI_TRANSFER_FUNCTION, (recipients, amounts))
+            .map_err(GenericError::new)?;
+
+    let gas_price = gas_price_or_network(&client, gas_price_override).await?;
+    // Upper bound, batched transfers use less gas than separate ones
+    let gas_limit = match network {
+        Network::Polygon => *GLM_POLYGON_GAS_LIMIT * transfers,
+        _ => *GLM_TRANSFER_GAS * transfers,
+    };
+
+    let mut tx = YagnaRawTransaction {
+        nonce,
+        to: Some(contract.address()),
+        value: U256::from(0),
+        gas_price,
+        gas: gas_limit,
+        data,
+        ..Default::default()
+    };
+    apply_fee_mode(&mut tx, &get_env(network));
+
+    Ok(tx)
+}
+
+/// Gas price is used as the maximum fee per gas of EIP-1559 transactions.
+fn apply_fee_mode(tx: &mut YagnaRawTransaction, env: &config::EnvConfiguration) {
+    if env.fee_mode == config::FeeMode::Eip1559 {
+        tx.use_eip1559(get_max_priority_fee());
+    }
+}
+
+/// Returns `gas_price_override` or the current gas price of the network.
+async fn gas_price_or_network(
+    client: &Web3<Http>,
+    gas_price_override: Option<U256>,
+) -> Result<U256, ClientError> {
     //get gas price from network in not provided
     let gas_price = match gas_price_override {
         Some(gas_price_new) => gas_price_new,
@@ -356,22 +487,12 @@ async fn prepare_raw_transaction_with(
             gas_price_from_network
         }
     };
+    Ok(gas_price)
+}
 
-    let gas_limit = match network {
-        Network::Polygon => gas_limit_override.map_or(*GLM_POLYGON_GAS_LIMIT, |v| U256::from(v)),
-        _ => gas_limit_override.map_or(*GLM_TRANSFER_GAS, |v| U256::from(v)),
-    };
-
-    let tx = YagnaRawTransaction {
-        nonce,
-        to: Some(contract.address()),
-        value: U256::from(0),
-        gas_price,
-        gas: gas_limit,
-        data,
-    };
-
-    Ok(tx)
+/// Block of the transaction is its first confirmation.
+fn is_confirmed(tx_block: u64, required_confirmations: u64, current_block: u64) -> bool {
+    tx_block + required_confirmations.max(1) - 1 <= current_block
 }
 
 pub async fn send_tx(signed_tx: Vec<u8>, network: Network) -> Result<H256, GenericError> {
@@ -386,6 +507,7 @@ async fn send_tx_with(client: Web3<Http>, signed_tx: Vec<u8>) -> Result<H256, Cl
         .map_err(Into::into)
 }
 
+#[derive(Clone, Debug)]
 pub struct TransactionChainStatus {
     pub exists_on_chain: bool,
     pub pending: bool,
@@ -425,14 +547,13 @@ pub async fn get_tx_on_chain_status(
                 env.required_confirmations,
                 current_block.unwrap_or(0)
             );
-            // tx.block_number is the first confirmation, so we need to - 1
             if let Some(current_block) = current_block {
-                if tx_bn.as_u64() + env.required_confirmations - 1 <= current_block {
-                    res.confirmed = true;
-                }
+                res.confirmed =
+                    is_confirmed(tx_bn.as_u64(), env.required_confirmations, current_block);
             }
             let transaction = get_tx_from_network(tx_hash, network).await?;
             if let Some(t) = transaction {
+                // Effective price of mined EIP-1559 transactions (base fee + tip)
                 res.gas_price = Some(t.gas_price);
             }
         } else {
@@ -447,28 +568,60 @@ pub async fn get_tx_on_chain_status(
     Ok(res)
 }
 
-//unused but tested that it is working for transfers
-pub async fn decode_encoded_transaction_data(
-    network: Network,
+/// Decodes recipients and amounts of GLM transfers from a stored (encoded) transaction,
+/// which is either a single ERC20 transfer or a batch of transfers.
+pub fn decode_encoded_transfers(
     encoded: &str,
-) -> Result<(ethereum_types::Address, ethereum_types::U256), GenericError> {
-    with_clients(network, |client| {
-        decode_encoded_transaction_data_with(client, network, encoded)
-    })
-    .await
+) -> Result<Vec<(ethereum_types::Address, ethereum_types::U256)>, GenericError> {
+    let raw_tx: YagnaRawTransaction = serde_json::from_str(encoded).map_err(GenericError::new)?;
+    let contract = ethabi::Contract::load(&include_bytes!("../contracts/multi_transfer.json")[..])
+        .map_err(GenericError::new)?;
+    let function = contract
+        .function(MULTI_TRANSFER_FUNCTION)
+        .map_err(GenericError::new)?;
+
+    let selector = function.short_signature();
+    if raw_tx.data.len() < selector.len() || raw_tx.data[..selector.len()] != selector[..] {
+        return decode_encoded_transaction_data(encoded).map(|transfer| vec![transfer]);
+    }
+    let tokens = function
+        .decode_input(&raw_tx.data[selector.len()..])
+        .map_err(GenericError::new)?;
+
+    match tokens.as_slice() {
+        [Token::Array(recipients), Token::Array(amounts)] if recipients.len() == amounts.len() => {
+            recipients
+                .iter()
+                .zip(amounts.iter())
+                .map(|pair| match pair {
+                    (Token::Address(recipient), Token::Uint(amount)) => Ok((*recipient, *amount)),
+                    _ => Err(GenericError::new("Failed to parse tokens")),
+                })
+                .collect()
+        }
+        _ => Err(GenericError::new("Failed to parse tokens")),
+    }
 }
 
-async fn decode_encoded_transaction_data_with(
-    client: Web3<Http>,
-    network: Network,
+/// Decodes recipient and amount of ERC20 transfer from a stored (encoded) transaction.
+pub fn decode_encoded_transaction_data(
     encoded: &str,
-) -> Result<(ethereum_types::Address, ethereum_types::U256), ClientError> {
-    let env = get_env(network);
-    let contract = prepare_erc20_contract(&client, &env)?;
+) -> Result<(ethereum_types::Address, ethereum_types::U256), GenericError> {
     let raw_tx: YagnaRawTransaction = serde_json::from_str(encoded).map_err(GenericError::new)?;
+    let contract = ethabi::Contract::load(&include_bytes!("../contracts/ierc20.json")[..])
+        .map_err(GenericError::new)?;
+    let function = contract
+        .function(TRANSFER_ERC20_FUNCTION)
+        .map_err(GenericError::new)?;
 
-    let tokens = eth_utils::contract_decode(&contract, TRANSFER_ERC20_FUNCTION, raw_tx.data)
+    let selector = function.short_signature();
+    if raw_tx.data.len() < selector.len() || raw_tx.data[..selector.len()] != selector[..] {
+        return Err(GenericError::new("Transaction is not an ERC20 transfer"));
+    }
+    let tokens = function
+        .decode_input(&raw_tx.data[selector.len()..])
         .map_err(GenericError::new)?;
+
     let mut address: Option<H160> = None;
     let mut amount: Option<U256> = None;
     for token in tokens {
@@ -483,7 +636,7 @@ async fn decode_encoded_transaction_data_with(
             return Ok((add, am));
         }
     }
-    Err(GenericError::new("Failed to parse tokens").into())
+    Err(GenericError::new("Failed to parse tokens"))
 }
 
 pub async fn get_tx_from_network(
@@ -693,6 +846,15 @@ pub fn create_dao_entity(
     }
 }
 
+/// Configured maximum fee of a single transaction on the `network`.
+pub fn get_fee_ceiling(network: Network) -> Option<U256> {
+    get_env(network).fee_ceiling
+}
+
+pub fn get_max_gas_price(network: Network) -> Option<U256> {
+    get_env(network).max_gas_price
+}
+
 pub fn get_max_gas_costs(db_tx: &TransactionEntity) -> Result<U256, GenericError> {
     let raw_tx: YagnaRawTransaction =
         serde_json::from_str(&db_tx.encoded).map_err(GenericError::new)?;
@@ -806,6 +968,15 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_confirmation_depth() {
+        assert!(is_confirmed(100, 1, 100));
+        assert!(!is_confirmed(100, 3, 101));
+        assert!(is_confirmed(100, 3, 102));
+        // At least the block with the transaction is required
+        assert!(!is_confirmed(100, 0, 99));
+    }
+
     #[tokio::test]
     async fn test_create_gasless_message() {
         let sender = H160::from_str("0xfeaed3f817169c012d040f05c6c52bce5740fc37").unwrap();
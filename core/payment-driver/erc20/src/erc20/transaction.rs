@@ -9,11 +9,112 @@ pub struct YagnaRawTransaction {
     pub to: Option<H160>,
     /// Transferred value
     pub value: U256,
-    /// Gas price
+    /// Gas price. Maximum fee per gas of EIP-1559 transactions
     #[serde(rename = "gasPrice")]
     pub gas_price: U256,
     /// Gas amount
     pub gas: U256,
     /// Transaction data
     pub data: Vec<u8>,
+    /// Maximum fee per gas, set for EIP-1559 transactions only
+    #[serde(
+        rename = "maxFeePerGas",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_fee_per_gas: Option<U256>,
+    /// Maximum priority fee (tip) per gas, set for EIP-1559 transactions only
+    #[serde(
+        rename = "maxPriorityFeePerGas",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+impl YagnaRawTransaction {
+    pub fn is_eip1559(&self) -> bool {
+        self.max_fee_per_gas.is_some()
+    }
+
+    /// Turns the transaction into an EIP-1559 one, paying at most its gas price.
+    pub fn use_eip1559(&mut self, max_priority_fee: U256) {
+        self.max_fee_per_gas = Some(self.gas_price);
+        self.max_priority_fee_per_gas = Some(max_priority_fee.min(self.gas_price));
+    }
+
+    /// Sets the maximum price paid for gas. Priority fee of EIP-1559 transactions
+    /// is scaled along, so bumped transactions can replace pending ones.
+    pub fn set_gas_price(&mut self, gas_price: U256) {
+        if let (Some(max_fee), Some(priority_fee)) =
+            (self.max_fee_per_gas, self.max_priority_fee_per_gas)
+        {
+            let priority_fee = match max_fee.is_zero() {
+                true => priority_fee,
+                false => priority_fee * gas_price / max_fee,
+            };
+            self.max_fee_per_gas = Some(gas_price);
+            self.max_priority_fee_per_gas = Some(priority_fee.min(gas_price));
+        }
+        self.gas_price = gas_price;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_transactions_deserialize() {
+        let json =
+            r#"{"nonce":"0x1","to":null,"value":"0x0","gasPrice":"0x64","gas":"0x5208","data":[]}"#;
+        let tx: YagnaRawTransaction = serde_json::from_str(json).unwrap();
+        assert!(!tx.is_eip1559());
+        assert_eq!(serde_json::to_string(&tx).unwrap(), json);
+    }
+
+    #[test]
+    fn eip1559_fees_follow_gas_price() {
+        let mut tx = YagnaRawTransaction {
+            gas_price: U256::from(100),
+            ..Default::default()
+        };
+        tx.set_gas_price(U256::from(90));
+        assert_eq!(tx.max_fee_per_gas, None);
+
+        tx.use_eip1559(U256::from(20));
+        assert_eq!(tx.max_fee_per_gas, Some(U256::from(90)));
+        assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(20)));
+
+        tx.set_gas_price(U256::from(180));
+        assert_eq!(tx.gas_price, U256::from(180));
+        assert_eq!(tx.max_fee_per_gas, Some(U256::from(180)));
+        assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(40)));
+
+        let json = serde_json::to_string(&tx).unwrap();
+        assert_eq!(
+            serde_json::from_str::<YagnaRawTransaction>(&json).unwrap(),
+            tx
+        );
+    }
+
+    #[test]
+    fn eip1559_transactions_are_typed() {
+        use crate::erc20::eth_utils::{encode_signed_tx, get_tx_hash};
+
+        let legacy = YagnaRawTransaction {
+            gas_price: U256::from(100),
+            gas: U256::from(21000),
+            ..Default::default()
+        };
+        let mut typed = legacy.clone();
+        typed.use_eip1559(U256::from(2));
+        assert_ne!(get_tx_hash(&legacy, 1), get_tx_hash(&typed, 1));
+
+        let mut signature = vec![1u8; 65];
+        signature[0] = 1;
+        let encoded = encode_signed_tx(&typed, signature.clone(), 1);
+        assert_eq!(encoded[0], 0x02);
+        assert!(encode_signed_tx(&legacy, signature, 1)[0] >= 0xc0);
+    }
 }
@@ -5,8 +5,8 @@
 // External crates
 use crate::erc20::{
     ethereum::{
-        get_polygon_gas_price_method, get_polygon_maximum_price, get_polygon_priority,
-        get_polygon_starting_price, PolygonGasPriceMethod, PolygonPriority,
+        get_gas_bump_percent, get_polygon_gas_price_method, get_polygon_maximum_price,
+        get_polygon_priority, get_polygon_starting_price, PolygonGasPriceMethod, PolygonPriority,
         POLYGON_PREFERRED_GAS_PRICES_EXPRESS, POLYGON_PREFERRED_GAS_PRICES_FAST,
         POLYGON_PREFERRED_GAS_PRICES_SLOW,
     },
@@ -14,13 +14,15 @@ use crate::erc20::{
 };
 use bigdecimal::BigDecimal;
 use chrono::Utc;
+use futures::{stream, Future, StreamExt};
 use num_bigint::BigUint;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use web3::types::{H160, H256, U256, U64};
 
 // Workspace uses
 use ya_payment_driver::{
-    db::models::{Network, TransactionEntity, TxType},
+    db::models::{Network, PaymentEntity, TransactionEntity, TxType},
     model::{AccountMode, GenericError, Init, PaymentDetails},
 };
 
@@ -105,8 +107,31 @@ pub async fn get_next_nonce(
     dao: &Erc20Dao,
     address: H160,
     network: Network,
+) -> Result<U256, GenericError> {
+    let network_nonce = ethereum::get_next_nonce_pending(address, network).await?;
+    next_nonce(dao, address, network, network_nonce).await
+}
+
+/// Like `get_next_nonce`, but first drops nonce allocations left unused
+/// by an interrupted run. Must not be called concurrently with allocating nonces.
+pub async fn resume_nonce(
+    dao: &Erc20Dao,
+    address: H160,
+    network: Network,
 ) -> Result<U256, GenericError> {
     let network_nonce = ethereum::get_next_nonce_pending(address, network).await?;
+    let str_addr = format!("0x{:x}", &address);
+    dao.reconcile_nonces(&str_addr, network, network_nonce)
+        .await?;
+    next_nonce(dao, address, network, network_nonce).await
+}
+
+async fn next_nonce(
+    dao: &Erc20Dao,
+    address: H160,
+    network: Network,
+    network_nonce: U256,
+) -> Result<U256, GenericError> {
     let str_addr = format!("0x{:x}", &address);
     let db_nonce = dao.get_next_nonce(&str_addr, network).await?;
 
@@ -164,8 +189,110 @@ pub async fn make_transfer(
     );
     let amount_big_dec = details.amount.clone();
     let amount = big_dec_to_u256(&amount_big_dec)?;
+    let (gas_price, max_gas_price) = gas_prices(network, gas_price, max_gas_price)?;
+
+    let address = str_to_addr(&details.sender)?;
+    let recipient = str_to_addr(&details.recipient)?;
+    // TODO: Implement token
+    //let token = get_network_token(network, None);
+    let mut raw_tx = ethereum::prepare_raw_transaction(
+        address, recipient, amount, network, nonce, gas_price, gas_limit,
+    )
+    .await?;
+
+    if let Some(max_gas_price) = max_gas_price {
+        if raw_tx.gas_price > max_gas_price {
+            raw_tx.set_gas_price(max_gas_price);
+        }
+    }
+
+    Ok(ethereum::create_dao_entity(
+        nonce,
+        address,
+        raw_tx.gas_price.to_string(),
+        bump_limit(network, max_gas_price).map(|v| v.to_string()),
+        raw_tx.gas.as_u32() as i32,
+        serde_json::to_string(&raw_tx).map_err(GenericError::new)?,
+        network,
+        Utc::now(),
+        TxType::Transfer,
+        Some(amount_big_dec),
+    ))
+}
+
+/// Creates a single transaction paying all `payments` of one sender, using the
+/// multi transfer contract of the network.
+pub async fn make_multi_transfer(
+    payments: &[PaymentDetails],
+    nonce: U256,
+    network: Network,
+) -> Result<TransactionEntity, GenericError> {
+    log::debug!(
+        "make_multi_transfer(). network={}, nonce={}, payments={:?}",
+        &network,
+        &nonce,
+        &payments
+    );
+    let sender = match payments {
+        [first, rest @ ..] if rest.iter().all(|p| p.sender == first.sender) => &first.sender,
+        [] => return Err(GenericError::new("No payments to transfer")),
+        _ => return Err(GenericError::new("Batched payments of different senders")),
+    };
+    let address = str_to_addr(sender)?;
+    let recipients = payments
+        .iter()
+        .map(|details| str_to_addr(&details.recipient))
+        .collect::<Result<Vec<_>, _>>()?;
+    let amounts = payments
+        .iter()
+        .map(|details| big_dec_to_u256(&details.amount))
+        .collect::<Result<Vec<_>, _>>()?;
+    let amount_big_dec = payments
+        .iter()
+        .map(|details| details.amount.clone())
+        .sum::<BigDecimal>();
+    let (gas_price, max_gas_price) = gas_prices(network, None, None)?;
+
+    let mut raw_tx =
+        ethereum::prepare_raw_multi_transaction(recipients, amounts, network, nonce, gas_price)
+            .await?;
 
-    let (gas_price, max_gas_price) = match network {
+    if let Some(max_gas_price) = max_gas_price {
+        if raw_tx.gas_price > max_gas_price {
+            raw_tx.set_gas_price(max_gas_price);
+        }
+    }
+
+    Ok(ethereum::create_dao_entity(
+        nonce,
+        address,
+        raw_tx.gas_price.to_string(),
+        bump_limit(network, max_gas_price).map(|v| v.to_string()),
+        raw_tx.gas.as_u32() as i32,
+        serde_json::to_string(&raw_tx).map_err(GenericError::new)?,
+        network,
+        Utc::now(),
+        TxType::Transfer,
+        Some(amount_big_dec),
+    ))
+}
+
+/// Gas price, up to which resent transactions can be bumped. Never above
+/// the gas price cap of the network.
+fn bump_limit(network: Network, max_gas_price: Option<U256>) -> Option<U256> {
+    match (max_gas_price, ethereum::get_max_gas_price(network)) {
+        (Some(max_gas_price), Some(cap)) => Some(max_gas_price.min(cap)),
+        (max_gas_price, cap) => max_gas_price.or(cap),
+    }
+}
+
+/// Starting and maximum gas price of a transfer.
+fn gas_prices(
+    network: Network,
+    gas_price: Option<BigDecimal>,
+    max_gas_price: Option<BigDecimal>,
+) -> Result<(Option<U256>, Option<U256>), GenericError> {
+    Ok(match network {
         Network::Polygon => match get_polygon_gas_price_method() {
             PolygonGasPriceMethod::PolygonGasPriceStatic => (
                 Some(match gas_price {
@@ -198,35 +325,7 @@ pub async fn make_transfer(
                 Some(v) => Some(big_dec_gwei_to_u256(v)?),
             },
         ),
-    };
-
-    let address = str_to_addr(&details.sender)?;
-    let recipient = str_to_addr(&details.recipient)?;
-    // TODO: Implement token
-    //let token = get_network_token(network, None);
-    let mut raw_tx = ethereum::prepare_raw_transaction(
-        address, recipient, amount, network, nonce, gas_price, gas_limit,
-    )
-    .await?;
-
-    if let Some(max_gas_price) = max_gas_price {
-        if raw_tx.gas_price > max_gas_price {
-            raw_tx.gas_price = max_gas_price;
-        }
-    }
-
-    Ok(ethereum::create_dao_entity(
-        nonce,
-        address,
-        raw_tx.gas_price.to_string(),
-        max_gas_price.map(|v| v.to_string()),
-        raw_tx.gas.as_u32() as i32,
-        serde_json::to_string(&raw_tx).map_err(GenericError::new)?,
-        network,
-        Utc::now(),
-        TxType::Transfer,
-        Some(amount_big_dec),
-    ))
+    })
 }
 
 pub async fn make_gasless_transfer(
@@ -248,10 +347,16 @@ pub async fn make_gasless_transfer(
     gasless_transfer::send_gasless_transfer(details, network).await
 }
 
-fn bump_gas_price(gas_in_gwei: U256) -> U256 {
-    let min_bump_num: U256 = U256::from(111u64);
-    let min_bump_den: U256 = U256::from(100u64);
-    let min_gas = gas_in_gwei * min_bump_num / min_bump_den;
+/// Increases `gas` by `percent`, rounding up, so it grows by at least 1 wei.
+fn bump_by_percent(gas: U256, percent: u64) -> U256 {
+    let hundred = U256::from(100u64);
+    let bumped = (gas * (hundred + U256::from(percent)) + hundred - 1) / hundred;
+    bumped.max(gas + 1)
+}
+
+/// Gas price of a resent transaction, high enough to replace the pending one.
+pub fn bump_gas_price(gas_in_gwei: U256) -> U256 {
+    let min_gas = bump_by_percent(gas_in_gwei, get_gas_bump_percent());
 
     match get_polygon_gas_price_method() {
         PolygonGasPriceMethod::PolygonGasPriceDynamic => {
@@ -282,122 +387,174 @@ pub async fn send_transactions(
     dao: &Erc20Dao,
     txs: Vec<TransactionEntity>,
     network: Network,
+    concurrency: usize,
 ) -> Result<(), GenericError> {
-    // TODO: Use batch sending?
+    send_ordered(txs, concurrency, |tx| send_transaction(dao, tx, network)).await;
+    Ok(())
+}
+
+/// Sends up to `concurrency` transactions at a time, also several of one sender,
+/// since their nonces are already allocated. Sends are started in nonce order
+/// per sender, alternating between senders, so a node queues any nonce that
+/// arrives before its predecessor. A failed send doesn't stop later transactions
+/// of the sender; the nonce gap is filled once the failed transaction is resent.
+async fn send_ordered<S, F>(mut txs: Vec<TransactionEntity>, concurrency: usize, send: S)
+where
+    S: Fn(TransactionEntity) -> F,
+    F: Future<Output = Result<(), GenericError>>,
+{
+    txs.sort_by(|a, b| (&a.sender, a.nonce).cmp(&(&b.sender, b.nonce)));
+
+    // Position of the transaction among the ones of its sender
+    let mut ranked: Vec<(usize, TransactionEntity)> = Vec::with_capacity(txs.len());
     for tx in txs {
-        let mut raw_tx: YagnaRawTransaction =
-            match serde_json::from_str::<YagnaRawTransaction>(&tx.encoded) {
-                Ok(raw_tx) => raw_tx,
-                Err(err) => {
-                    log::error!(
-                        "send_transactions - YagnaRawTransaction serialization failed: {:?}",
-                        err
+        let rank = match ranked.last() {
+            Some((rank, prev)) if prev.sender == tx.sender => rank + 1,
+            _ => 0,
+        };
+        ranked.push((rank, tx));
+    }
+    ranked.sort_by(|(a_rank, a), (b_rank, b)| (a_rank, &a.sender).cmp(&(b_rank, &b.sender)));
+
+    let send = &send;
+    stream::iter(ranked)
+        .for_each_concurrent(concurrency.max(1), |(_, tx)| async move {
+            let tx_id = tx.tx_id.clone();
+            if let Err(e) = send(tx).await {
+                log::error!("Failed to send transaction. id={}, error={:?}", tx_id, e);
+            }
+        })
+        .await;
+}
+
+async fn send_transaction(
+    dao: &Erc20Dao,
+    tx: TransactionEntity,
+    network: Network,
+) -> Result<(), GenericError> {
+    let mut raw_tx: YagnaRawTransaction =
+        match serde_json::from_str::<YagnaRawTransaction>(&tx.encoded) {
+            Ok(raw_tx) => raw_tx,
+            Err(err) => {
+                log::error!(
+                    "send_transactions - YagnaRawTransaction serialization failed: {:?}",
+                    err
+                );
+                //handle problem when deserializing transaction
+                dao.transaction_confirmed_and_failed(
+                    &tx.tx_id,
+                    "",
+                    None,
+                    "Json parse failed, unrecoverable error",
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+    let address = str_to_addr(&tx.sender)?;
+
+    let new_gas_price = if let Some(current_gas_price) = tx.current_gas_price {
+        if tx.status == TransactionStatus::ResendAndBumpGas as i32 {
+            let gas_u256 = U256::from_dec_str(&current_gas_price).map_err(GenericError::new)?;
+
+            let max_gas_u256 = match tx.max_gas_price {
+                Some(max_gas_price) => {
+                    Some(U256::from_dec_str(&max_gas_price).map_err(GenericError::new)?)
+                }
+                None => None,
+            };
+            let new_gas = bump_gas_price(gas_u256);
+            if let Some(max_gas_u256) = max_gas_u256 {
+                // Nodes reject replacements bumped less than required, so the cap can't be used
+                if new_gas > max_gas_u256 {
+                    log::warn!(
+                        "Bumped gas price ({}) larger than max gas price ({}), leaving transaction pending. id={}",
+                        new_gas,
+                        max_gas_u256,
+                        tx.tx_id
                     );
-                    //handle problem when deserializing transaction
-                    dao.transaction_confirmed_and_failed(
+                    dao.overwrite_tmp_onchain_txs_and_status_back_to_pending(
                         &tx.tx_id,
-                        "",
-                        None,
-                        "Json parse failed, unrecoverable error",
+                        tx.tmp_onchain_txs.as_deref().unwrap_or_default(),
                     )
                     .await;
-                    continue;
+                    return Ok(());
                 }
-            };
-
-        let address = str_to_addr(&tx.sender)?;
-
-        let new_gas_price = if let Some(current_gas_price) = tx.current_gas_price {
-            if tx.status == TransactionStatus::ResendAndBumpGas as i32 {
-                let gas_u256 = U256::from_dec_str(&current_gas_price).map_err(GenericError::new)?;
-
-                let max_gas_u256 = match tx.max_gas_price {
-                    Some(max_gas_price) => {
-                        Some(U256::from_dec_str(&max_gas_price).map_err(GenericError::new)?)
-                    }
-                    None => None,
-                };
-                let new_gas = bump_gas_price(gas_u256);
-                if let Some(max_gas_u256) = max_gas_u256 {
-                    if gas_u256 > max_gas_u256 {
-                        log::warn!(
-                            "bump gas ({}) larger than max gas ({}) price",
-                            gas_u256,
-                            max_gas_u256
-                        )
-                    }
-                }
-                new_gas
-            } else {
-                U256::from_dec_str(&current_gas_price).map_err(GenericError::new)?
             }
-        } else if let Some(starting_gas_price) = tx.starting_gas_price {
-            U256::from_dec_str(&starting_gas_price).map_err(GenericError::new)?
+            log::info!(
+                "Bumping gas price of pending transaction. id={}, nonce={}, gas_price={} -> {}",
+                tx.tx_id,
+                tx.nonce,
+                gas_u256,
+                new_gas
+            );
+            new_gas
         } else {
-            convert_float_gas_to_u256(get_polygon_starting_price())
-        };
-        raw_tx.gas_price = new_gas_price;
-
-        let encoded = serde_json::to_string(&raw_tx).map_err(GenericError::new)?;
-        let signature = ethereum::sign_raw_transfer_transaction(address, network, &raw_tx).await?;
-
-        //save new parameters to db before proceeding. Maybe we should change status to sending
-        dao.update_tx_fields(
-            &tx.tx_id,
-            encoded,
-            hex::encode(&signature),
-            Some(new_gas_price.to_string()),
-        )
-        .await;
+            U256::from_dec_str(&current_gas_price).map_err(GenericError::new)?
+        }
+    } else if let Some(starting_gas_price) = tx.starting_gas_price {
+        U256::from_dec_str(&starting_gas_price).map_err(GenericError::new)?
+    } else {
+        convert_float_gas_to_u256(get_polygon_starting_price())
+    };
+    raw_tx.set_gas_price(new_gas_price);
 
-        let signed = eth_utils::encode_signed_tx(&raw_tx, signature, network as u64);
+    let encoded = serde_json::to_string(&raw_tx).map_err(GenericError::new)?;
+    let signature = ethereum::sign_raw_transfer_transaction(address, network, &raw_tx).await?;
 
-        match ethereum::send_tx(signed, network).await {
-            Ok(tx_hash) => {
-                let str_tx_hash = format!("0x{:x}", &tx_hash);
-                let str_tx_hash = if let Some(tmp_onchain_txs) = tx.tmp_onchain_txs {
-                    tmp_onchain_txs + ";" + str_tx_hash.as_str()
-                } else {
-                    str_tx_hash
-                };
-                dao.transaction_sent(&tx.tx_id, &str_tx_hash, Some(raw_tx.gas_price.to_string()))
+    //save new parameters to db before proceeding. Maybe we should change status to sending
+    dao.update_tx_fields(
+        &tx.tx_id,
+        encoded,
+        hex::encode(&signature),
+        Some(new_gas_price.to_string()),
+    )
+    .await;
+
+    let signed = eth_utils::encode_signed_tx(&raw_tx, signature, network as u64);
+
+    match ethereum::send_tx(signed, network).await {
+        Ok(tx_hash) => {
+            let str_tx_hash = format!("0x{:x}", &tx_hash);
+            let str_tx_hash = if let Some(tmp_onchain_txs) = tx.tmp_onchain_txs {
+                tmp_onchain_txs + ";" + str_tx_hash.as_str()
+            } else {
+                str_tx_hash
+            };
+            dao.transaction_sent(&tx.tx_id, &str_tx_hash, Some(raw_tx.gas_price.to_string()))
+                .await;
+            log::info!("Send transaction. hash={}", &str_tx_hash);
+            log::debug!("id={}", &tx.tx_id);
+        }
+        Err(e) => {
+            log::error!("Error sending transaction: {:?}", e);
+            if e.to_string().contains("nonce too low") {
+                if tx.tmp_onchain_txs.filter(|v| !v.is_empty()).is_some() && tx.resent_times < 5 {
+                    //if tmp on-chain tx transactions exist give it a chance but marking it as failed sent
+                    dao.transaction_failed_send(
+                        &tx.tx_id,
+                        tx.resent_times + 1,
+                        e.to_string().as_str(),
+                    )
                     .await;
-                log::info!("Send transaction. hash={}", &str_tx_hash);
-                log::debug!("id={}", &tx.tx_id);
-            }
-            Err(e) => {
-                log::error!("Error sending transaction: {:?}", e);
-                if e.to_string().contains("nonce too low") {
-                    if tx.tmp_onchain_txs.filter(|v| !v.is_empty()).is_some() && tx.resent_times < 5
-                    {
-                        //if tmp on-chain tx transactions exist give it a chance but marking it as failed sent
-                        dao.transaction_failed_send(
-                            &tx.tx_id,
-                            tx.resent_times + 1,
-                            e.to_string().as_str(),
-                        )
-                        .await;
-                        continue;
-                    } else {
-                        //if trying to sent transaction too much times just end with unrecoverable error
-                        log::error!("Nonce too low: {:?}", e);
-                        dao.transaction_failed_with_nonce_too_low(
-                            &tx.tx_id,
-                            e.to_string().as_str(),
-                        )
+                    return Ok(());
+                } else {
+                    //if trying to sent transaction too much times just end with unrecoverable error
+                    log::error!("Nonce too low: {:?}", e);
+                    dao.transaction_failed_with_nonce_too_low(&tx.tx_id, e.to_string().as_str())
                         .await;
-                        continue;
-                    }
+                    return Ok(());
                 }
-                if e.to_string().contains("already known") {
-                    log::error!("Already known: {:?}. Send transaction with higher gas to get from this error loop. (resent won't fix anything)", e);
-                    dao.retry_send_transaction(&tx.tx_id, true).await;
-                    continue;
-                }
-
-                dao.transaction_failed_send(&tx.tx_id, tx.resent_times, e.to_string().as_str())
-                    .await;
             }
+            if e.to_string().contains("already known") {
+                log::error!("Already known: {:?}. Send transaction with higher gas to get from this error loop. (resent won't fix anything)", e);
+                dao.retry_send_transaction(&tx.tx_id, true).await;
+                return Ok(());
+            }
+
+            dao.transaction_failed_send(&tx.tx_id, tx.resent_times, e.to_string().as_str())
+                .await;
         }
     }
     Ok(())
@@ -420,6 +577,55 @@ pub async fn send_transactions(
 //     todo!();
 // }
 
+/// Checks, that recipient and amount encoded in the transaction match the payments it settles.
+pub fn verify_encoded_transfer(
+    tx: &TransactionEntity,
+    payments: &[PaymentEntity],
+) -> Result<(), GenericError> {
+    // Amounts per recipient, transfers of a batch may repeat recipients
+    let mut encoded = BTreeMap::<H160, U256>::new();
+    for (recipient, amount) in ethereum::decode_encoded_transfers(&tx.encoded)? {
+        let total = encoded.entry(recipient).or_default();
+        *total = total
+            .checked_add(amount)
+            .ok_or_else(|| GenericError::new("Transfers amount overflow"))?;
+    }
+
+    let mut expected = BTreeMap::<H160, U256>::new();
+    for payment in payments {
+        let payment_recipient = str_to_addr(&payment.recipient)?;
+        if !encoded.contains_key(&payment_recipient) {
+            let encoded = encoded
+                .keys()
+                .map(|recipient| format!("{:#x}", recipient))
+                .collect::<Vec<_>>()
+                .join(",");
+            return Err(GenericError::new(format!(
+                "Recipient mismatch. tx_id={}, order_id={}, expected={:#x}, encoded={}",
+                tx.tx_id, payment.order_id, payment_recipient, encoded
+            )));
+        }
+        let payment_amount = hex::decode(&payment.amount)
+            .map(|bytes| U256::from_big_endian(&bytes))
+            .map_err(GenericError::new)?;
+        let total = expected.entry(payment_recipient).or_default();
+        *total = total
+            .checked_add(payment_amount)
+            .ok_or_else(|| GenericError::new("Payments amount overflow"))?;
+    }
+
+    for (recipient, amount) in encoded {
+        let expected = expected.get(&recipient).cloned().unwrap_or_default();
+        if expected != amount {
+            return Err(GenericError::new(format!(
+                "Amount mismatch. tx_id={}, recipient={:#x}, expected={}, encoded={}",
+                tx.tx_id, recipient, expected, amount
+            )));
+        }
+    }
+    Ok(())
+}
+
 pub async fn verify_tx(tx_hash: &str, network: Network) -> Result<PaymentDetails, GenericError> {
     log::debug!("verify_tx. hash={}", tx_hash);
     let hex_hash = H256::from_str(&tx_hash[2..]).map_err(|err| {
@@ -456,7 +662,13 @@ pub async fn verify_tx(tx_hash: &str, network: Network) -> Result<PaymentDetails
         let sender = topic_to_str_address(topic1);
         let recipient = topic_to_str_address(topic2);
 
-        let amount = big_uint_to_big_dec(BigUint::from_bytes_be(&tx_log.data.0));
+        // Batched transfers emit a log per payment
+        let amount = tx
+            .logs
+            .iter()
+            .filter(|log| log.topics.len() == 3 && log.topics[1..] == tx_log.topics[1..])
+            .map(|log| big_uint_to_big_dec(BigUint::from_bytes_be(&log.data.0)))
+            .sum::<BigDecimal>();
 
         if let Some(_block_number) = tx_log.block_number {
             // TODO: Get date from block
@@ -479,3 +691,205 @@ pub async fn verify_tx(tx_hash: &str, network: Network) -> Result<PaymentDetails
         )))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use ethabi::Token;
+    use std::cell::{Cell, RefCell};
+    use std::time::Duration;
+
+    const RECIPIENT: &str = "0xd4ea255b238e214a9a0e5656ec36fe27cd14adac";
+
+    fn transfer_tx(recipient: &str, amount: u64) -> TransactionEntity {
+        let contract =
+            ethabi::Contract::load(&include_bytes!("../contracts/ierc20.json")[..]).unwrap();
+        let data = contract
+            .function("transfer")
+            .unwrap()
+            .encode_input(&[
+                Token::Address(str_to_addr(recipient).unwrap()),
+                Token::Uint(U256::from(amount)),
+            ])
+            .unwrap();
+        let raw_tx = YagnaRawTransaction {
+            data,
+            ..Default::default()
+        };
+
+        let now = Utc::now().naive_utc();
+        TransactionEntity {
+            tx_id: "tx-1".to_string(),
+            sender: Default::default(),
+            nonce: 0,
+            status: 0,
+            tx_type: TxType::Transfer as i32,
+            tmp_onchain_txs: None,
+            final_tx: None,
+            network: Network::Rinkeby,
+            starting_gas_price: None,
+            current_gas_price: None,
+            max_gas_price: None,
+            final_gas_used: None,
+            amount_base: None,
+            amount_erc20: None,
+            gas_limit: None,
+            time_created: now,
+            time_last_action: now,
+            time_sent: None,
+            time_confirmed: None,
+            last_error_msg: None,
+            resent_times: 0,
+            signature: None,
+            encoded: serde_json::to_string(&raw_tx).unwrap(),
+        }
+    }
+
+    fn payment(order_id: &str, recipient: &str, amount: u64) -> PaymentEntity {
+        let mut bytes = [0u8; 32];
+        U256::from(amount).to_big_endian(&mut bytes);
+        PaymentEntity {
+            order_id: order_id.to_string(),
+            amount: hex::encode(&bytes),
+            gas: Default::default(),
+            sender: Default::default(),
+            recipient: recipient.to_string(),
+            payment_due_date: NaiveDateTime::from_timestamp(0, 0),
+            status: 0,
+            tx_id: Some("tx-1".to_string()),
+            network: Network::Rinkeby,
+        }
+    }
+
+    #[test]
+    fn verify_encoded_transfer_matching_payments() {
+        let tx = transfer_tx(RECIPIENT, 1500);
+        let payments = vec![payment("a", RECIPIENT, 1000), payment("b", RECIPIENT, 500)];
+        verify_encoded_transfer(&tx, &payments).unwrap();
+    }
+
+    #[test]
+    fn verify_encoded_multi_transfer() {
+        let other = "0x0000000000000000000000000000000000000001";
+        let contract =
+            ethabi::Contract::load(&include_bytes!("../contracts/multi_transfer.json")[..])
+                .unwrap();
+        let data = contract
+            .function("golemTransferDirect")
+            .unwrap()
+            .encode_input(&[
+                Token::Array(vec![
+                    Token::Address(str_to_addr(RECIPIENT).unwrap()),
+                    Token::Address(str_to_addr(other).unwrap()),
+                    Token::Address(str_to_addr(RECIPIENT).unwrap()),
+                ]),
+                Token::Array(vec![
+                    Token::Uint(U256::from(1000)),
+                    Token::Uint(U256::from(700)),
+                    Token::Uint(U256::from(500)),
+                ]),
+            ])
+            .unwrap();
+        let tx = TransactionEntity {
+            encoded: serde_json::to_string(&YagnaRawTransaction {
+                data,
+                ..Default::default()
+            })
+            .unwrap(),
+            ..transfer_tx(RECIPIENT, 0)
+        };
+
+        let payments = vec![
+            payment("a", RECIPIENT, 1000),
+            payment("b", other, 700),
+            payment("c", RECIPIENT, 500),
+        ];
+        verify_encoded_transfer(&tx, &payments).unwrap();
+
+        let err = verify_encoded_transfer(&tx, &payments[..2]).unwrap_err();
+        assert!(err.to_string().contains("Amount mismatch"));
+    }
+
+    #[test]
+    fn verify_encoded_transfer_mismatch() {
+        let payments = vec![payment("a", RECIPIENT, 1000)];
+
+        let tx = transfer_tx(RECIPIENT, 1001);
+        let err = verify_encoded_transfer(&tx, &payments).unwrap_err();
+        assert!(err.to_string().contains("Amount mismatch"));
+
+        let tx = transfer_tx("0xfeaed3f817169c012d040f05c6c52bce5740fc37", 1000);
+        let err = verify_encoded_transfer(&tx, &payments).unwrap_err();
+        assert!(err.to_string().contains("Recipient mismatch"));
+    }
+
+    #[actix_rt::test]
+    async fn send_transactions_in_nonce_order() {
+        let tx = |sender: &str, nonce: i32| TransactionEntity {
+            tx_id: format!("{}-{}", sender, nonce),
+            sender: sender.to_string(),
+            nonce,
+            ..transfer_tx(RECIPIENT, 0)
+        };
+        let txs = vec![
+            tx("b", 1),
+            tx("a", 2),
+            tx("a", 0),
+            tx("b", 0),
+            tx("a", 1),
+            tx("a", 3),
+        ];
+
+        let started = RefCell::new(Vec::new());
+        let sent = RefCell::new(Vec::new());
+        let in_flight = Cell::new(0usize);
+        let max_in_flight = Cell::new(0usize);
+
+        send_ordered(txs, 4, |tx| {
+            let (started, sent) = (&started, &sent);
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+                started.borrow_mut().push(tx.tx_id.clone());
+                in_flight.set(in_flight.get() + 1);
+                max_in_flight.set(max_in_flight.get().max(in_flight.get()));
+                // Earlier nonces take longer to broadcast
+                tokio::time::sleep(Duration::from_millis(10 - 2 * tx.nonce as u64)).await;
+                in_flight.set(in_flight.get() - 1);
+                sent.borrow_mut().push(tx.tx_id.clone());
+                match tx.tx_id.as_str() {
+                    "a-1" => Err(GenericError::new("rejected")),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await;
+
+        let started = started.into_inner();
+        assert_eq!(sent.into_inner().len(), 6);
+        let order = |sender: &str| {
+            started
+                .iter()
+                .filter(|id| id.starts_with(sender))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        // Rejected broadcast doesn't block the following ones
+        assert_eq!(order("a-"), vec!["a-0", "a-1", "a-2", "a-3"]);
+        assert_eq!(order("b-"), vec!["b-0", "b-1"]);
+        // Senders alternate, so one sender doesn't hold up the others
+        assert_eq!(started[..2], ["a-0", "b-0"]);
+        // Transactions of a sender are sent concurrently too
+        assert_eq!(max_in_flight.get(), 4);
+    }
+
+    #[test]
+    fn gas_bump_satisfies_replacement_rules() {
+        let gwei = U256::from(1_000_000_000u64);
+        assert_eq!(bump_by_percent(gwei * 30, 10), gwei * 33);
+        assert_eq!(bump_by_percent(U256::from(101u64), 10), U256::from(112u64));
+        // Price always grows, also for tiny amounts
+        assert_eq!(bump_by_percent(U256::from(1u64), 10), U256::from(2u64));
+        assert_eq!(bump_by_percent(U256::zero(), 10), U256::from(1u64));
+    }
+}
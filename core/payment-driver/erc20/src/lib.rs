@@ -37,6 +37,7 @@ pub const POLYGON_MAINNET_PLATFORM: &'static str = "erc20-polygon-glm";
 pub const POLYGON_MAINNET_CURRENCY_SHORT: &'static str = "MATIC";
 pub const POLYGON_MAINNET_CURRENCY_LONG: &'static str = "Polygon";
 
+pub use driver::{driver_metrics, Erc20Metrics, NetworkMetrics};
 pub use service::Erc20Service as PaymentDriverService;
 
 // Private
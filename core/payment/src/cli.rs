@@ -105,6 +105,18 @@ pub enum PaymentCli {
 
     /// Clear all existing allocations
     ReleaseAllocations,
+
+    /// Reconcile driver's database with the on-chain state of transactions
+    Reconcile {
+        #[structopt(flatten)]
+        network: pay::NetworkCli,
+    },
+
+    /// List payments, which are not settled yet, along with their last errors
+    Unsettled {
+        #[structopt(flatten)]
+        network: pay::NetworkCli,
+    },
 }
 
 #[derive(StructOpt, Debug)]
@@ -378,6 +390,43 @@ impl PaymentCli {
                     .await;
                 Ok(CommandOutput::NoOutput)
             }
+            PaymentCli::Reconcile { network } => CommandOutput::object(
+                wallet::reconcile(network.driver(), Some(network.network())).await?,
+            ),
+            PaymentCli::Unsettled { network } => {
+                let payments = wallet::unsettled(network.driver(), Some(network.network())).await?;
+                if ctx.json_output {
+                    return CommandOutput::object(payments);
+                }
+
+                Ok(ResponseTable {
+                    columns: vec![
+                        "order id".to_owned(),
+                        "recipient".to_owned(),
+                        "amount".to_owned(),
+                        "due date".to_owned(),
+                        "last error".to_owned(),
+                        "occurred".to_owned(),
+                    ],
+                    values: payments
+                        .into_iter()
+                        .map(|payment| {
+                            serde_json::json! {[
+                                payment.order_id,
+                                payment.recipient,
+                                payment.amount.to_string(),
+                                payment.due_date.to_rfc3339(),
+                                payment.last_error.unwrap_or_default(),
+                                payment
+                                    .last_error_time
+                                    .map(|time| time.to_rfc3339())
+                                    .unwrap_or_default(),
+                            ]}
+                        })
+                        .collect(),
+                }
+                .into())
+            }
         }
     }
 }
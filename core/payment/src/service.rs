@@ -37,6 +37,7 @@ mod local {
             .bind_with_processor(register_account)
             .bind_with_processor(unregister_account)
             .bind_with_processor(notify_payment)
+            .bind_with_processor(notify_payment_failed)
             .bind_with_processor(get_status)
             .bind_with_processor(get_invoice_stats)
             .bind_with_processor(get_accounts)
@@ -153,6 +154,23 @@ mod local {
         Ok(())
     }
 
+    async fn notify_payment_failed(
+        db: DbExecutor,
+        processor: Arc<Mutex<PaymentProcessor>>,
+        sender: String,
+        msg: NotifyPaymentFailed,
+    ) -> Result<(), GenericError> {
+        log::warn!(
+            "Payment failed. driver={}, platform={}, order_ids={:?}, reason={}",
+            msg.driver,
+            msg.platform,
+            msg.order_ids,
+            msg.reason
+        );
+        counter!("payment.orders.failed", msg.order_ids.len() as u64);
+        Ok(())
+    }
+
     async fn get_status(
         db: DbExecutor,
         processor: Arc<Mutex<PaymentProcessor>>,
@@ -2,7 +2,10 @@
 use bigdecimal::BigDecimal;
 
 // Workspace uses
-use ya_core_model::driver::{driver_bus_id, Enter, Exit, Fund, Transfer};
+use ya_core_model::driver::{
+    driver_bus_id, Enter, Exit, Fund, GetUnsettledPayments, Reconcile, ReconcileReport, Transfer,
+    UnsettledPayment,
+};
 use ya_service_bus::typed as bus;
 
 pub async fn fund(
@@ -71,3 +74,20 @@ pub async fn transfer(
     let tx_id = bus::service(driver_id).call(message).await??;
     Ok(tx_id)
 }
+
+pub async fn reconcile(driver: String, network: Option<String>) -> anyhow::Result<ReconcileReport> {
+    let driver_id = driver_bus_id(driver);
+    let message = Reconcile::new(network);
+    let report = bus::service(driver_id).call(message).await??;
+    Ok(report)
+}
+
+pub async fn unsettled(
+    driver: String,
+    network: Option<String>,
+) -> anyhow::Result<Vec<UnsettledPayment>> {
+    let driver_id = driver_bus_id(driver);
+    let message = GetUnsettledPayments::new(network);
+    let payments = bus::service(driver_id).call(message).await??;
+    Ok(payments)
+}
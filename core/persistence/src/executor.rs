@@ -250,6 +250,11 @@ where
     do_with_rw_connection(pool, move |conn| conn.immediate_transaction(|| f(conn))).await
 }
 
+/// Rebuilds the database, releasing unused space. Runs outside of a transaction.
+pub async fn vacuum(pool: &PoolType) -> Result<(), Error> {
+    do_with_rw_connection(pool, |conn| Ok(conn.batch_execute("VACUUM;")?)).await
+}
+
 pub async fn readonly_transaction<R: Send + 'static, Error, F>(
     pool: &PoolType,
     f: F,
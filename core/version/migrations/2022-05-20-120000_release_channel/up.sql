@@ -0,0 +1,6 @@
+CREATE TABLE version_settings (
+	id INTEGER PRIMARY KEY CHECK (id = 1),
+	release_channel TEXT NOT NULL DEFAULT 'stable'
+);
+
+INSERT INTO version_settings (id) VALUES (1);
@@ -0,0 +1,11 @@
+CREATE TABLE version_settings_migrate (
+	id INTEGER PRIMARY KEY CHECK (id = 1),
+	release_channel TEXT NOT NULL DEFAULT 'stable'
+);
+
+INSERT INTO version_settings_migrate(id, release_channel)
+SELECT id, release_channel
+FROM version_settings;
+
+DROP TABLE version_settings;
+ALTER TABLE version_settings_migrate RENAME TO version_settings;
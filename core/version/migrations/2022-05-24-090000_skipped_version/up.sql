@@ -0,0 +1 @@
+ALTER TABLE version_settings ADD COLUMN skipped_version TEXT;
@@ -0,0 +1,36 @@
+use ya_core_model::version::ReleaseChannel;
+
+/// Channel of a release with given semver. Pre-releases belong to `Beta` only.
+pub(crate) fn channel_of(version: &str) -> ReleaseChannel {
+    let version = version.split('+').next().unwrap_or_default();
+    match version.contains('-') {
+        true => ReleaseChannel::Beta,
+        false => ReleaseChannel::Stable,
+    }
+}
+
+/// Whether release with given semver is reported to users of `channel`.
+pub(crate) fn is_reported(channel: ReleaseChannel, version: &str) -> bool {
+    match channel {
+        ReleaseChannel::Stable => channel_of(version) == ReleaseChannel::Stable,
+        ReleaseChannel::Beta => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pre_releases_are_beta_only() {
+        assert_eq!(channel_of("0.10.0"), ReleaseChannel::Stable);
+        assert_eq!(channel_of("0.10.0+build.5"), ReleaseChannel::Stable);
+        assert_eq!(channel_of("0.10.0-rc3"), ReleaseChannel::Beta);
+        assert_eq!(channel_of("0.10.0-beta.1+build-5"), ReleaseChannel::Beta);
+
+        assert!(is_reported(ReleaseChannel::Stable, "0.10.0"));
+        assert!(!is_reported(ReleaseChannel::Stable, "0.10.0-rc3"));
+        assert!(is_reported(ReleaseChannel::Beta, "0.10.0"));
+        assert!(is_reported(ReleaseChannel::Beta, "0.10.0-rc3"));
+    }
+}
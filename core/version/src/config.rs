@@ -0,0 +1,22 @@
+use std::time::Duration;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct Config {
+    /// Interval of checking for new Yagna releases. Checking is disabled with `0s`
+    #[structopt(env = "YA_VERSION_CHECK_INTERVAL", parse(try_from_str = humantime::parse_duration), default_value = "1d")]
+    pub check_interval: Duration,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Config, structopt::clap::Error> {
+        // Empty command line arguments, because we want to use ENV fallback
+        // or default values if ENV variables are not set.
+        Config::from_iter_safe(&[""])
+    }
+
+    /// `None` when checking for new releases is disabled.
+    pub fn check_interval(&self) -> Option<Duration> {
+        Some(self.check_interval).filter(|interval| !interval.is_zero())
+    }
+}
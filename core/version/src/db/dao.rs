@@ -1,13 +1,16 @@
 use diesel::prelude::*;
 
-use ya_core_model::version::{Release, VersionInfo};
+use ya_core_model::version::{Release, ReleaseChannel, VersionInfo};
 use ya_persistence::executor::{
     do_with_transaction, readonly_transaction, AsDao, ConnType, PoolType,
 };
 
+use crate::channel::is_reported;
 use crate::db::model::DBRelease;
 use crate::db::schema::version_release::dsl as release;
 use crate::db::schema::version_release::dsl::version_release;
+use crate::db::schema::version_settings::dsl as settings;
+use crate::db::schema::version_settings::dsl::version_settings;
 use self_update::version::bump_is_greater;
 
 pub struct ReleaseDAO<'c> {
@@ -50,11 +53,37 @@ impl<'c> ReleaseDAO<'c> {
                 current: get_current_release(conn)?
                     .unwrap_or_else(|| DBRelease::current().unwrap().into()),
                 pending: get_pending_release(conn, true)?,
+                channel: get_release_channel(conn)?,
             })
         })
         .await
     }
 
+    pub async fn release_channel(&self) -> anyhow::Result<ReleaseChannel> {
+        readonly_transaction(self.pool, move |conn| get_release_channel(conn)).await
+    }
+
+    pub async fn set_release_channel(&self, channel: ReleaseChannel) -> anyhow::Result<()> {
+        log::debug!("Setting Yagna release channel to {}", channel);
+        do_with_transaction(self.pool, move |conn| {
+            diesel::update(version_settings)
+                .set(settings::release_channel.eq(channel.to_string()))
+                .execute(conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn skipped_version(&self) -> anyhow::Result<Option<String>> {
+        readonly_transaction(self.pool, move |conn| get_skipped_version(conn)).await
+    }
+
+    /// Stops notifying about `version` and older releases.
+    pub async fn skip_version(&self, version: String) -> anyhow::Result<()> {
+        log::debug!("Skipping Yagna releases up to {}", version);
+        do_with_transaction(self.pool, move |conn| set_skipped_version(conn, &version)).await
+    }
+
     pub async fn skip_pending_release(&self) -> anyhow::Result<Option<Release>> {
         log::debug!("Skipping latest pending Yagna release");
         do_with_transaction(self.pool, move |conn| {
@@ -65,6 +94,7 @@ impl<'c> ReleaseDAO<'c> {
             let num_updated = diesel::update(version_release.find(&pending_rel.version))
                 .set(release::seen.eq(true))
                 .execute(conn)?;
+            set_skipped_version(conn, &pending_rel.version)?;
             pending_rel.seen = true;
             match num_updated {
                 0 => anyhow::bail!("Release not skipped: {}", pending_rel),
@@ -88,17 +118,64 @@ fn get_release(conn: &ConnType, ver: &str) -> anyhow::Result<Option<Release>> {
         .map(|db_rel| db_rel.into()))
 }
 
+fn get_release_channel(conn: &ConnType) -> anyhow::Result<ReleaseChannel> {
+    let channel = version_settings
+        .select(settings::release_channel)
+        .first::<String>(conn)
+        .optional()?;
+    Ok(match channel {
+        Some(channel) => channel.parse()?,
+        None => ReleaseChannel::default(),
+    })
+}
+
+fn get_skipped_version(conn: &ConnType) -> anyhow::Result<Option<String>> {
+    Ok(version_settings
+        .select(settings::skipped_version)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten())
+}
+
+/// Keeps the newest of skipped versions.
+fn set_skipped_version(conn: &ConnType, version: &str) -> anyhow::Result<()> {
+    if is_skipped(get_skipped_version(conn)?.as_deref(), version) {
+        return Ok(());
+    }
+    diesel::update(version_settings)
+        .set(settings::skipped_version.eq(version))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Whether `version` isn't newer than the `skipped` one.
+pub(crate) fn is_skipped(skipped: Option<&str>, version: &str) -> bool {
+    match skipped {
+        Some(skipped) => bump_is_greater(skipped, version)
+            .map(|greater| !greater)
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
 fn get_pending_release(conn: &ConnType, include_seen: bool) -> anyhow::Result<Option<Release>> {
+    let channel = get_release_channel(conn)?;
+    let skipped = match include_seen {
+        true => None,
+        false => get_skipped_version(conn)?,
+    };
     let mut query = version_release
         // insertion_ts is to distinguish among fake-entries of `DBRelease::current`
-        .filter(release::version.not_like("%rc%"))
         .order((release::release_ts.desc(), release::insertion_ts.desc()))
         .into_boxed();
     if !include_seen {
         query = query.filter(release::seen.eq(false));
     }
 
-    match query.first::<DBRelease>(conn).optional()? {
+    let latest = query.load::<DBRelease>(conn)?.into_iter().find(|db_rel| {
+        is_reported(channel, &db_rel.version) && !is_skipped(skipped.as_deref(), &db_rel.version)
+    });
+    match latest {
         Some(db_rel) => {
             let running_ver = ya_compile_time_utils::semver_str!();
             if !bump_is_greater(running_ver, &db_rel.version)
@@ -119,3 +196,18 @@ fn get_pending_release(conn: &ConnType, include_seen: bool) -> anyhow::Result<Op
         None => Ok(None),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_skipped_versions() {
+        assert!(!is_skipped(None, "0.10.0"));
+        assert!(is_skipped(Some("0.10.0"), "0.9.1"));
+        assert!(is_skipped(Some("0.10.0"), "0.10.0"));
+        // Notifications resume, once a newer version appears
+        assert!(!is_skipped(Some("0.10.0"), "0.10.1"));
+        assert!(!is_skipped(Some("0.10.0"), "0.11.0"));
+    }
+}
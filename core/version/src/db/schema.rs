@@ -8,3 +8,11 @@ table! {
         update_ts -> Nullable<Timestamp>,
     }
 }
+
+table! {
+    version_settings(id) {
+        id -> Integer,
+        release_channel -> Text,
+        skipped_version -> Nullable<Text>,
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::Mutex;
+
+use ya_core_model::version::{event::VersionUpgradeAvailable, Release};
+use ya_service_bus::{typed as bus, RpcEndpoint};
+
+lazy_static::lazy_static! {
+    static ref SUBSCRIPTIONS: Mutex<Subscriptions> = Default::default();
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    endpoints: Vec<String>,
+    announced: Option<String>,
+}
+
+impl Subscriptions {
+    /// Returns `false` if `endpoint` is already subscribed.
+    fn add(&mut self, endpoint: String) -> bool {
+        if self.endpoints.contains(&endpoint) {
+            return false;
+        }
+        self.endpoints.push(endpoint);
+        true
+    }
+
+    /// Returns `false` if `version` was already announced.
+    fn announce(&mut self, version: &str) -> bool {
+        if self.announced.as_deref() == Some(version) {
+            return false;
+        }
+        self.announced = Some(version.to_string());
+        true
+    }
+}
+
+pub(crate) fn subscribe(endpoint: String) {
+    log::debug!("Subscribing {} to version events", endpoint);
+    SUBSCRIPTIONS.lock().unwrap().add(endpoint);
+}
+
+/// Notifies subscribers about new `release`, unless already done.
+/// Subscribers, which missed the event, can query the pending release with `Get`.
+pub(crate) async fn upgrade_available(release: &Release, release_notes_url: String) {
+    let endpoints = {
+        let mut subscriptions = SUBSCRIPTIONS.lock().unwrap();
+        if !subscriptions.announce(&release.version) {
+            return;
+        }
+        subscriptions.endpoints.clone()
+    };
+
+    let event = VersionUpgradeAvailable {
+        current: ya_compile_time_utils::semver_str!().into(),
+        available: release.clone(),
+        release_notes_url,
+    };
+    for endpoint in endpoints {
+        match bus::service(&endpoint).send(event.clone()).await {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => log::debug!("Version event rejected by {}: {}", endpoint, e),
+            Err(e) => log::debug!("Failed to send version event to {}: {}", endpoint, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_versions_are_announced_once() {
+        let mut subscriptions = Subscriptions::default();
+        assert!(subscriptions.announce("0.10.0"));
+        assert!(!subscriptions.announce("0.10.0"));
+        assert!(subscriptions.announce("0.10.1"));
+        assert!(!subscriptions.announce("0.10.1"));
+    }
+
+    #[test]
+    fn test_endpoints_are_subscribed_once() {
+        let mut subscriptions = Subscriptions::default();
+        assert!(subscriptions.add("/local/a".into()));
+        assert!(!subscriptions.add("/local/a".into()));
+        assert!(subscriptions.add("/local/b".into()));
+        assert_eq!(subscriptions.endpoints, vec!["/local/a", "/local/b"]);
+    }
+}
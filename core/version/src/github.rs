@@ -1,12 +1,13 @@
 use anyhow::anyhow;
 use metrics::counter;
-use self_update::backends::github::UpdateBuilder;
+use self_update::backends::github::{ReleaseList, UpdateBuilder};
 use std::convert::TryFrom;
 
-use ya_core_model::version::Release;
+use ya_core_model::version::{Release, ReleaseChannel};
 use ya_persistence::executor::DbExecutor;
 
-use crate::db::dao::ReleaseDAO;
+use crate::channel::is_reported;
+use crate::db::dao::{is_skipped, ReleaseDAO};
 use crate::db::model::DBRelease;
 use crate::service::cli::ReleaseMessage;
 
@@ -14,18 +15,9 @@ const REPO_OWNER: &'static str = "golemfactory";
 const REPO_NAME: &'static str = "yagna";
 
 pub async fn check_latest_release(db: &DbExecutor) -> anyhow::Result<Release> {
-    log::debug!("Checking latest Yagna release");
-    let gh_rel = tokio::task::spawn_blocking(|| -> anyhow::Result<self_update::update::Release> {
-        Ok(UpdateBuilder::new()
-            .repo_owner(REPO_OWNER)
-            .repo_name(REPO_NAME)
-            .bin_name("") // seems required by builder but unused
-            .current_version("") // similar as above
-            .target_version_tag("latest")
-            .build()?
-            .get_latest_release()?)
-    })
-    .await??;
+    let channel = db.as_dao::<ReleaseDAO>().release_channel().await?;
+    log::debug!("Checking latest Yagna release on {} channel", channel);
+    let gh_rel = tokio::task::spawn_blocking(move || latest_release(channel)).await??;
 
     log::trace!("Got latest Yagna release {:?}", gh_rel);
 
@@ -39,21 +31,54 @@ pub async fn check_latest_release(db: &DbExecutor) -> anyhow::Result<Release> {
         Ok(r) => r,
     };
 
-    if self_update::version::bump_is_greater(ya_compile_time_utils::semver_str!(), &rel.version)
-        .map_err(|e| {
-            anyhow!(
-                "Github release version `{}` parse error: {}",
-                rel.version,
-                e
-            )
-        })?
+    let skipped = db.as_dao::<ReleaseDAO>().skipped_version().await?;
+    if is_reported(channel, &rel.version)
+        && !is_skipped(skipped.as_deref(), &rel.version)
+        && self_update::version::bump_is_greater(ya_compile_time_utils::semver_str!(), &rel.version)
+            .map_err(|e| {
+                anyhow!(
+                    "Github release version `{}` parse error: {}",
+                    rel.version,
+                    e
+                )
+            })?
     {
         counter!("version.new", 1);
         log::warn!("{}", ReleaseMessage::Available(&rel));
+        crate::event::upgrade_available(&rel, release_notes_url(&rel.version)).await;
     };
     Ok(rel)
 }
 
+fn release_notes_url(version: &str) -> String {
+    format!(
+        "https://github.com/{}/{}/releases/tag/v{}",
+        REPO_OWNER, REPO_NAME, version
+    )
+}
+
+fn latest_release(channel: ReleaseChannel) -> anyhow::Result<self_update::update::Release> {
+    match channel {
+        ReleaseChannel::Stable => Ok(UpdateBuilder::new()
+            .repo_owner(REPO_OWNER)
+            .repo_name(REPO_NAME)
+            .bin_name("") // seems required by builder but unused
+            .current_version("") // similar as above
+            .target_version_tag("latest")
+            .build()?
+            .get_latest_release()?),
+        // Github's `latest` release is never a pre-release
+        ReleaseChannel::Beta => ReleaseList::configure()
+            .repo_owner(REPO_OWNER)
+            .repo_name(REPO_NAME)
+            .build()?
+            .fetch()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No Yagna releases found")),
+    }
+}
+
 pub(crate) async fn check_running_release(db: &DbExecutor) -> anyhow::Result<Release> {
     if let Some(release) = db.as_dao::<ReleaseDAO>().current_release().await? {
         return Ok(release);
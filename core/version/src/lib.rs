@@ -3,9 +3,13 @@ extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 
+mod channel;
+mod config;
 mod db;
+mod event;
 mod github;
 mod notifier;
 mod service;
 
+pub use config::Config;
 pub use service::VersionService;
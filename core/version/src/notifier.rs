@@ -1,5 +1,8 @@
+use std::future::Future;
 use std::time::Duration;
+use tokio::task::JoinHandle;
 
+use ya_core_model::version::Release;
 use ya_persistence::executor::DbExecutor;
 
 use crate::db::dao::ReleaseDAO;
@@ -7,27 +10,47 @@ use crate::github;
 use crate::github::check_running_release;
 use crate::service::cli::ReleaseMessage;
 
-pub async fn on_start(db: &DbExecutor) -> anyhow::Result<()> {
+pub async fn on_start(db: &DbExecutor, check_interval: Option<Duration>) -> anyhow::Result<()> {
     check_running_release(&db).await?;
 
-    if let Err(e) = github::check_latest_release(&db).await {
-        log::error!("Failed to check for new Yagna release: {}", e);
-    };
+    match check_interval {
+        Some(_) => {
+            if let Err(e) = github::check_latest_release(&db).await {
+                log::error!("Failed to check for new Yagna release: {}", e);
+            };
+        }
+        None => log::info!("Checking for new Yagna releases is disabled"),
+    }
 
     let worker_db = db.clone();
-    tokio::task::spawn_local(async move { crate::notifier::worker(worker_db).await });
+    spawn_worker(check_interval, move || {
+        let db = worker_db.clone();
+        async move { github::check_latest_release(&db).await }
+    });
     let pinger_db = db.clone();
     tokio::task::spawn_local(async move { crate::notifier::pinger(pinger_db).await });
 
     Ok(())
 }
 
-pub(crate) async fn worker(db: DbExecutor) {
-    // TODO: make interval configurable
-    let interval = Duration::from_secs(3600 * 24);
+/// Spawns periodic checks for new releases, unless checking is disabled.
+fn spawn_worker<F, Fut>(interval: Option<Duration>, check: F) -> Option<JoinHandle<()>>
+where
+    F: Fn() -> Fut + 'static,
+    Fut: Future<Output = anyhow::Result<Release>> + 'static,
+{
+    let interval = interval?;
+    Some(tokio::task::spawn_local(worker(interval, check)))
+}
+
+pub(crate) async fn worker<F, Fut>(interval: Duration, check: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<Release>>,
+{
     loop {
         tokio::time::sleep(interval).await;
-        if let Err(e) = github::check_latest_release(&db).await {
+        if let Err(e) = check().await {
             log::error!("Failed to check for new Yagna release: {}", e);
         };
     }
@@ -53,7 +76,10 @@ pub(crate) async fn pinger(db: DbExecutor) -> ! {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use chrono::NaiveDateTime;
+    use std::cell::Cell;
+    use std::rc::Rc;
 
     #[test]
     fn test_default_release_ts() {
@@ -63,4 +89,21 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_disabled_checks() {
+        let checks = Rc::new(Cell::new(0));
+        let checks_clone = checks.clone();
+        let worker = spawn_worker(None, move || {
+            checks_clone.set(checks_clone.get() + 1);
+            async { Err(anyhow::anyhow!("unexpected check")) }
+        });
+        assert!(worker.is_none());
+        assert_eq!(checks.get(), 0);
+
+        let config = crate::Config {
+            check_interval: Duration::from_secs(0),
+        };
+        assert_eq!(config.check_interval(), None);
+    }
 }
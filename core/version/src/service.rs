@@ -1,6 +1,9 @@
+use ya_core_model::version;
 use ya_persistence::executor::DbExecutor;
 use ya_service_api_interfaces::{Provider, Service};
+use ya_service_bus::{typed as bus, RpcEndpoint};
 
+use crate::config::Config;
 use crate::db::migrations;
 
 pub(crate) mod cli;
@@ -17,12 +20,23 @@ impl VersionService {
     pub async fn gsb<C: Provider<Self, DbExecutor>>(ctx: &C) -> anyhow::Result<()> {
         let db = ctx.component();
         db.apply_migration(migrations::run_with_output)?;
-        crate::notifier::on_start(&db).await?;
+        let config = Config::from_env()?;
+        crate::notifier::on_start(&db, config.check_interval()).await?;
         gsb::bind_gsb(&db);
 
         Ok(())
     }
 
+    /// Stops notifying about `version` and all the older releases.
+    pub async fn skip_version(version: &str) -> anyhow::Result<()> {
+        bus::service(version::BUS_ID)
+            .send(version::SkipVersion {
+                version: version.to_string(),
+            })
+            .await??;
+        Ok(())
+    }
+
     pub fn rest<C: Provider<Self, DbExecutor>>(ctx: &C) -> actix_web::Scope {
         rest::web_scope(ctx.component())
     }
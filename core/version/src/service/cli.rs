@@ -31,6 +31,11 @@ pub enum VersionCLI {
     /// Stop logging warnings about latest Yagna release availability.
     #[structopt(setting = AppSettings::Hidden)]
     Skip,
+    /// Show or set the channel of releases to be notified about.
+    Channel {
+        #[structopt(possible_values = &["stable", "beta"])]
+        channel: Option<version::ReleaseChannel>,
+    },
 }
 
 impl VersionCLI {
@@ -47,6 +52,17 @@ impl VersionCLI {
                     None => ReleaseMessage::NotSkipped.to_string(),
                 },
             ),
+            VersionCLI::Channel { channel } => {
+                if let Some(channel) = channel {
+                    bus::service(version::BUS_ID)
+                        .send(version::SetReleaseChannel { channel })
+                        .await??;
+                }
+                let version_info = bus::service(version::BUS_ID)
+                    .send(version::Get::show_only())
+                    .await??;
+                CommandOutput::object(format!("Release channel: {}", version_info.channel))
+            }
         }
     }
 }
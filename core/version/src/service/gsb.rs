@@ -12,7 +12,17 @@ pub type RpcMessageResult<T> = Result<<T as RpcMessage>::Item, <T as RpcMessage>
 pub fn bind_gsb(db: &DbExecutor) {
     bus::ServiceBinder::new(version::BUS_ID, db, ())
         .bind(skip_version_gsb)
-        .bind(get_version_gsb);
+        .bind(get_version_gsb)
+        .bind(set_release_channel_gsb)
+        .bind(skip_given_version_gsb);
+    let _ = bus::bind(version::BUS_ID, |msg: version::Subscribe| async move {
+        crate::event::subscribe(msg.endpoint);
+        Ok(())
+    });
+    let _ = bus::bind(
+        version::PUBLIC_BUS_ID,
+        |_: version::GetBuildInfo| async move { Ok(build_info()) },
+    );
 
     // Initialize counters to 0 value. Otherwise they won't appear on metrics endpoint
     // until first change to value will be made.
@@ -36,6 +46,19 @@ async fn skip_version_gsb(
     }
 }
 
+async fn skip_given_version_gsb(
+    db: DbExecutor,
+    _caller: String,
+    msg: version::SkipVersion,
+) -> RpcMessageResult<version::SkipVersion> {
+    db.as_dao::<ReleaseDAO>()
+        .skip_version(msg.version)
+        .await
+        .map_err(|e| e.to_string().into())?;
+    counter!("version.skip", 1);
+    Ok(())
+}
+
 async fn get_version_gsb(
     db: DbExecutor,
     _caller: String,
@@ -52,3 +75,23 @@ async fn get_version_gsb(
         .await
         .map_err(|e| e.to_string().into())
 }
+
+async fn set_release_channel_gsb(
+    db: DbExecutor,
+    _caller: String,
+    msg: version::SetReleaseChannel,
+) -> RpcMessageResult<version::SetReleaseChannel> {
+    db.as_dao::<ReleaseDAO>()
+        .set_release_channel(msg.channel)
+        .await
+        .map_err(|e| e.to_string().into())
+}
+
+fn build_info() -> version::BuildInfo {
+    version::BuildInfo {
+        version: ya_compile_time_utils::semver_str!().into(),
+        git_commit: ya_compile_time_utils::git_rev().into(),
+        build_date: ya_compile_time_utils::build_date().into(),
+        build_number: ya_compile_time_utils::build_number_str().map(Into::into),
+    }
+}
@@ -171,10 +171,12 @@ async fn main() -> anyhow::Result<()> {
         acl: Default::default(),
         report_url: None,
         credentials: None,
+        enforcement: None,
         agreement,
         work_dir,
         cache_dir,
         runtime_args: Default::default(),
+        transfer_usage: Default::default(),
         #[cfg(feature = "sgx")]
         crypto: init_crypto()?,
     };
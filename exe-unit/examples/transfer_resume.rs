@@ -92,6 +92,10 @@ impl TransferProvider<TransferData, Error> for UnreliableHttpProvider {
     ) -> LocalBoxFuture<'a, Result<(), Error>> {
         self.inner.prepare_destination(url, ctx)
     }
+
+    fn cleanup_destination(&self, url: &Url, ctx: &TransferContext) {
+        self.inner.cleanup_destination(url, ctx)
+    }
 }
 
 fn hash_file<P: AsRef<Path>>(path: P) -> GenericArray<u8, <sha3::Sha3_224 as Digest>::OutputSize> {
@@ -1,4 +1,6 @@
+use std::convert::TryFrom;
 use std::io::Cursor;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
@@ -24,20 +26,50 @@ pub struct ContainerVolume {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ContainerEndpoint {
     Socket(PathBuf),
+    Tls(TlsEndpoint),
+    /// Plain TCP endpoint, e.g. a loopback port exposed by the runtime
+    Tcp(SocketAddr),
 }
 
+/// TCP endpoint wrapped in a TLS session
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsEndpoint {
+    pub addr: SocketAddr,
+    /// Name verified against the certificate presented by the runtime
+    pub server_name: String,
+    /// PEM file with CA certificates trusted in addition to the system ones
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file with the client certificate chain
+    #[serde(default)]
+    pub cert: Option<PathBuf>,
+    /// PEM file with the client private key
+    #[serde(default)]
+    pub key: Option<PathBuf>,
+}
+
+/// Network endpoints are converted to their `host:port` address
 impl From<ContainerEndpoint> for PathBuf {
     fn from(e: ContainerEndpoint) -> Self {
         match e {
             ContainerEndpoint::Socket(p) => p,
+            ContainerEndpoint::Tls(tls) => PathBuf::from(tls.addr.to_string()),
+            ContainerEndpoint::Tcp(addr) => PathBuf::from(addr.to_string()),
         }
     }
 }
 
-impl From<crate::server::NetworkEndpoint> for ContainerEndpoint {
-    fn from(endpoint: crate::server::NetworkEndpoint) -> Self {
+impl TryFrom<crate::server::NetworkEndpoint> for ContainerEndpoint {
+    type Error = anyhow::Error;
+
+    fn try_from(endpoint: crate::server::NetworkEndpoint) -> Result<Self, Self::Error> {
         match endpoint {
-            crate::server::NetworkEndpoint::Socket(s) => Self::Socket(PathBuf::from(s)),
+            crate::server::NetworkEndpoint::Socket(s) => Ok(Self::Socket(PathBuf::from(s))),
+            crate::server::NetworkEndpoint::Tcp(addr) => match addr.parse() {
+                Ok(addr) => Ok(Self::Tcp(addr)),
+                Err(e) => anyhow::bail!("invalid tcp endpoint address '{}': {}", addr, e),
+            },
         }
     }
 }
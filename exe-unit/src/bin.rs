@@ -9,7 +9,9 @@ use ya_client_model::activity::ExeScriptCommand;
 use ya_service_bus::RpcEnvelope;
 
 use ya_core_model::activity;
+use ya_exe_unit::acl::AccessRole;
 use ya_exe_unit::agreement::Agreement;
+use ya_exe_unit::enforcement::{disk_quota, Allocation, Enforcement, LimitAction};
 use ya_exe_unit::logger::*;
 use ya_exe_unit::manifest::ManifestContext;
 use ya_exe_unit::message::{GetState, GetStateResponse, Register};
@@ -17,7 +19,7 @@ use ya_exe_unit::runtime::process::RuntimeProcess;
 use ya_exe_unit::service::metrics::MetricsService;
 use ya_exe_unit::service::signal::SignalMonitor;
 use ya_exe_unit::service::transfer::TransferService;
-use ya_exe_unit::state::Supervision;
+use ya_exe_unit::state::{EndpointTls, Supervision};
 use ya_exe_unit::{ExeUnit, ExeUnitContext};
 use ya_utils_path::normalize_path;
 
@@ -77,6 +79,42 @@ struct SuperviseCli {
         set = clap::ArgSettings::Global,
     )]
     image: bool,
+    /// Action taken when the activity exceeds its CPU or memory allocation (terminate, throttle)
+    #[structopt(
+        long,
+        env = "EXE_UNIT_LIMIT_ACTION",
+        default_value = "terminate",
+        set = clap::ArgSettings::Global,
+    )]
+    limit_action: LimitAction,
+    /// Default disk quota of an activity in GiB, if not specified in the agreement
+    #[structopt(long, env = "EXE_UNIT_DISK_QUOTA_GIB", set = clap::ArgSettings::Global)]
+    disk_quota_gib: Option<f64>,
+    /// Wrap TCP network endpoints of the runtime in TLS, verifying this server name
+    #[structopt(long, env = "EXE_UNIT_ENDPOINT_TLS_NAME", set = clap::ArgSettings::Global)]
+    endpoint_tls_name: Option<String>,
+    /// PEM file with additional CA certificates trusted for TLS network endpoints
+    #[structopt(long, env = "EXE_UNIT_ENDPOINT_TLS_CA_CERT", set = clap::ArgSettings::Global)]
+    endpoint_tls_ca_cert: Option<PathBuf>,
+    /// PEM file with the client certificate chain for TLS network endpoints
+    #[structopt(long, env = "EXE_UNIT_ENDPOINT_TLS_CERT", set = clap::ArgSettings::Global)]
+    endpoint_tls_cert: Option<PathBuf>,
+    /// PEM file with the client private key for TLS network endpoints
+    #[structopt(long, env = "EXE_UNIT_ENDPOINT_TLS_KEY", set = clap::ArgSettings::Global)]
+    endpoint_tls_key: Option<PathBuf>,
+}
+
+impl SuperviseCli {
+    fn endpoint_tls(&self) -> Option<EndpointTls> {
+        self.endpoint_tls_name
+            .clone()
+            .map(|server_name| EndpointTls {
+                server_name,
+                ca_cert: self.endpoint_tls_ca_cert.clone(),
+                cert: self.endpoint_tls_cert.clone(),
+                key: self.endpoint_tls_key.clone(),
+            })
+    }
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -270,11 +308,33 @@ async fn run() -> anyhow::Result<()> {
     log::info!("Manifest-enabled features: {:?}", manifest_ctx.features());
     log::info!("User-provided payload: {:?}", agreement.task_package);
 
+    let enforcement = match (cli.supervise.hardware, cli.supervise.limit_action) {
+        (true, LimitAction::Throttle) => {
+            let name = format!("activity-{}", std::process::id());
+            match Enforcement::apply(&name, Allocation::from_agreement(&agreement)) {
+                Ok(enforcement) => Some(enforcement),
+                Err(e) => {
+                    log::warn!("Unable to enforce allocation, falling back to termination: {e}");
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let limit_action = match enforcement {
+        Some(_) => LimitAction::Throttle,
+        None => LimitAction::Terminate,
+    };
+    let activity_group = enforcement.clone();
+
     let ctx = ExeUnitContext {
         supervise: Supervision {
             hardware: cli.supervise.hardware,
             image: cli.supervise.image,
             manifest: manifest_ctx,
+            limit_action,
+            disk_quota: disk_quota(&agreement, cli.supervise.disk_quota_gib),
+            endpoint_tls: cli.supervise.endpoint_tls(),
         },
         activity_id: ctx_activity_id.clone(),
         report_url: ctx_report_url,
@@ -284,6 +344,8 @@ async fn run() -> anyhow::Result<()> {
         runtime_args: cli.runtime_arg.clone(),
         acl: Default::default(),
         credentials: None,
+        enforcement,
+        transfer_usage: Default::default(),
         #[cfg(feature = "sgx")]
         crypto: init_crypto(
             cli.sec_key.replace("<hidden>".into()),
@@ -291,6 +353,16 @@ async fn run() -> anyhow::Result<()> {
         )?,
     };
 
+    // only the requestor is allowed to control the activity's networks
+    match ctx
+        .agreement
+        .inner
+        .pointer_typed::<String>("/demand/requestorId")
+    {
+        Ok(requestor_id) => ctx.acl.grant(requestor_id, AccessRole::Control),
+        Err(e) => log::warn!("Unable to read requestor id from the agreement: {}", e),
+    }
+
     log::debug!("CLI args: {:?}", cli);
     log::debug!("ExeUnitContext args: {:?}", ctx);
 
@@ -307,7 +379,11 @@ async fn run() -> anyhow::Result<()> {
         tokio::task::spawn(send_script(exe_unit, ctx_activity_id, exe_script));
     }
 
-    rx.await??;
+    let result = rx.await;
+    if let Some(enforcement) = activity_group {
+        enforcement.release();
+    }
+    result??;
     Ok(())
 }
 
@@ -0,0 +1,355 @@
+//! Enforcement of CPU and memory allocation agreed for the activity.
+#[cfg(target_os = "linux")]
+pub mod cgroup;
+
+use std::str::FromStr;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::agreement::Agreement;
+use crate::metrics::MemMetric;
+
+const CPU_THREADS_INF: &str = "cpu.threads";
+const STORAGE_GIB_INF: &str = "storage.gib";
+
+/// Action taken when the activity exceeds its agreed allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitAction {
+    /// Terminate the activity (default).
+    Terminate,
+    /// Constrain the activity to its allocation. Processes exceeding
+    /// the memory allocation are killed by the OS.
+    Throttle,
+}
+
+impl Default for LimitAction {
+    fn default() -> Self {
+        LimitAction::Terminate
+    }
+}
+
+impl FromStr for LimitAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "terminate" => Ok(LimitAction::Terminate),
+            "throttle" => Ok(LimitAction::Throttle),
+            _ => Err(format!(
+                "unknown limit action: {}. Expected 'terminate' or 'throttle'",
+                s
+            )),
+        }
+    }
+}
+
+/// Agreed CPU and memory allocation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Allocation {
+    pub cpu_threads: Option<f64>,
+    pub mem_gib: Option<f64>,
+}
+
+impl Allocation {
+    pub fn from_agreement(agreement: &Agreement) -> Self {
+        let positive = |key: &str| {
+            agreement
+                .infrastructure
+                .get(key)
+                .cloned()
+                .filter(|v| *v > 0.)
+        };
+        Allocation {
+            cpu_threads: positive(CPU_THREADS_INF),
+            mem_gib: positive(MemMetric::INF),
+        }
+    }
+
+    pub fn mem_bytes(&self) -> Option<u64> {
+        self.mem_gib.map(|gib| (gib * 1024. * 1024. * 1024.) as u64)
+    }
+}
+
+/// Disk quota of the activity in bytes. The storage declared in the agreement
+/// takes precedence over the provider default.
+pub fn disk_quota(agreement: &Agreement, default_gib: Option<f64>) -> Option<u64> {
+    agreement
+        .infrastructure
+        .get(STORAGE_GIB_INF)
+        .cloned()
+        .or(default_gib)
+        .filter(|gib| *gib > 0.)
+        .map(|gib| (gib * 1024. * 1024. * 1024.) as u64)
+}
+
+/// Constrains the ExeUnit process tree to the agreed allocation
+/// and reports limit events.
+#[derive(Clone, derivative::Derivative)]
+#[derivative(Debug)]
+pub struct Enforcement {
+    pub allocation: Allocation,
+    #[cfg(target_os = "linux")]
+    group: Arc<ActivityGroup>,
+    #[derivative(Debug = "ignore")]
+    counters: Arc<Mutex<Counters>>,
+}
+
+/// Control group of the activity, removed when released or dropped.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+struct ActivityGroup {
+    parent: cgroup::Cgroup,
+    group: cgroup::Cgroup,
+    /// Controllers enabled in the parent group by the ExeUnit
+    controllers: Vec<&'static str>,
+    released: AtomicBool,
+}
+
+#[cfg(target_os = "linux")]
+impl ActivityGroup {
+    fn release(&self) {
+        if self.released.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // Parent group can't contain processes while controllers are enabled for its children
+        if let Err(e) = self.parent.disable_controllers(&self.controllers) {
+            log::debug!("Unable to disable cgroup controllers: {}", e);
+        }
+        let result = self
+            .parent
+            .attach(std::process::id())
+            .and_then(|_| self.group.remove());
+        match result {
+            Ok(_) => log::debug!("Removed cgroup {}", self.group.path().display()),
+            Err(e) => log::warn!(
+                "Unable to remove cgroup {}: {}",
+                self.group.path().display(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for ActivityGroup {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    cpu_throttled: u64,
+    mem_high: u64,
+    oom_kill: u64,
+}
+
+impl Enforcement {
+    /// Moves the current process to a dedicated control group and applies
+    /// the allocation. Runtime processes spawned later on are constrained as well.
+    #[cfg(target_os = "linux")]
+    pub fn apply(name: &str, allocation: Allocation) -> anyhow::Result<Self> {
+        Self::apply_in(cgroup::Cgroup::current()?, name, allocation)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn apply_in(
+        parent: cgroup::Cgroup,
+        name: &str,
+        allocation: Allocation,
+    ) -> anyhow::Result<Self> {
+        let enabled = parent.subtree_control().unwrap_or_default();
+        let controllers = [
+            ("cpu", allocation.cpu_threads.is_some()),
+            ("memory", allocation.mem_gib.is_some()),
+        ]
+        .iter()
+        .filter(|(c, required)| *required && !enabled.iter().any(|e| e.as_str() == *c))
+        .map(|(c, _)| *c)
+        .collect();
+
+        // Removed on error
+        let activity = ActivityGroup {
+            group: parent.create_child(name)?,
+            parent,
+            controllers,
+            released: AtomicBool::new(false),
+        };
+        let group = &activity.group;
+        group.attach(std::process::id())?;
+        // Controllers are enabled after the process has left the parent group
+        activity.parent.enable_controllers(&activity.controllers)?;
+
+        if let Some(threads) = allocation.cpu_threads {
+            group.set_cpu_max(threads)?;
+        }
+        if let Some(bytes) = allocation.mem_bytes() {
+            // Throttle slightly below the hard limit, to reclaim memory before OOM
+            group.set_memory_high(bytes - bytes / 20)?;
+            group.set_memory_max(bytes)?;
+        }
+
+        log::info!(
+            "Enforcing {:?} allocation in {}",
+            allocation,
+            group.path().display()
+        );
+        Ok(Enforcement {
+            allocation,
+            group: Arc::new(activity),
+            counters: Default::default(),
+        })
+    }
+
+    /// Moves the current process out of the activity control group and removes the group.
+    /// Runtime processes should be terminated beforehand.
+    #[cfg(target_os = "linux")]
+    pub fn release(&self) {
+        self.group.release();
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn release(&self) {}
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(_name: &str, _allocation: Allocation) -> anyhow::Result<Self> {
+        anyhow::bail!("allocation enforcement is not supported on this platform")
+    }
+
+    /// Returns descriptions of limit events, which occurred since the last call.
+    #[cfg(target_os = "linux")]
+    pub fn events(&self) -> Vec<String> {
+        let mut counters = self.counters.lock().unwrap();
+        let mut events = Vec::new();
+
+        if let Ok(throttled) = self.group.group.cpu_throttled() {
+            if throttled > counters.cpu_throttled {
+                events.push(format!(
+                    "CPU throttled to {} thread(s) in {} period(s)",
+                    self.allocation.cpu_threads.unwrap_or_default(),
+                    throttled - counters.cpu_throttled
+                ));
+                counters.cpu_throttled = throttled;
+            }
+        }
+        if let Ok(mem) = self.group.group.memory_events() {
+            let high = mem.get("high").cloned().unwrap_or(0);
+            let oom_kill = mem.get("oom_kill").cloned().unwrap_or(0);
+            if high > counters.mem_high {
+                events.push(format!(
+                    "Memory throttled at {} GiB allocation",
+                    self.allocation.mem_gib.unwrap_or_default()
+                ));
+                counters.mem_high = high;
+            }
+            if oom_kill > counters.oom_kill {
+                events.push(format!(
+                    "{} process(es) killed after exceeding {} GiB memory allocation",
+                    oom_kill - counters.oom_kill,
+                    self.allocation.mem_gib.unwrap_or_default()
+                ));
+                counters.oom_kill = oom_kill;
+            }
+        }
+        events
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn events(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn parse_limit_action() {
+        assert_eq!("terminate".parse(), Ok(LimitAction::Terminate));
+        assert_eq!("Throttle".parse(), Ok(LimitAction::Throttle));
+        assert!("kill".parse::<LimitAction>().is_err());
+    }
+
+    #[test]
+    fn allocation_from_agreement() {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("examples/agreement.json");
+        let mut agreement = Agreement::try_from(&path).unwrap();
+        agreement
+            .infrastructure
+            .insert(CPU_THREADS_INF.to_string(), 2.);
+        agreement
+            .infrastructure
+            .insert(MemMetric::INF.to_string(), 0.5);
+
+        let allocation = Allocation::from_agreement(&agreement);
+        assert_eq!(allocation.cpu_threads, Some(2.));
+        assert_eq!(allocation.mem_bytes(), Some(512 * 1024 * 1024));
+
+        agreement
+            .infrastructure
+            .insert(CPU_THREADS_INF.to_string(), 0.);
+        assert_eq!(Allocation::from_agreement(&agreement).cpu_threads, None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn allocation_written_to_cgroup() {
+        let root = tempdir::TempDir::new("cgroup").unwrap();
+        let allocation = Allocation {
+            cpu_threads: Some(1.5),
+            mem_gib: Some(1.),
+        };
+        let parent = cgroup::Cgroup::new(root.path());
+        let enforcement = Enforcement::apply_in(parent, "activity-test", allocation).unwrap();
+
+        let group = root.path().join("activity-test");
+        let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap();
+        let gib = 1024 * 1024 * 1024u64;
+        let pid = std::process::id().to_string();
+
+        assert_eq!(
+            read(root.path().join("cgroup.subtree_control")),
+            "+cpu +memory"
+        );
+        assert_eq!(read(group.join("cgroup.procs")), pid);
+        assert_eq!(read(group.join("cpu.max")), "150000 100000");
+        assert_eq!(
+            read(group.join("memory.high")),
+            (gib - gib / 20).to_string()
+        );
+        assert_eq!(read(group.join("memory.max")), gib.to_string());
+
+        // Interface files are virtual on cgroupfs and don't prevent removal
+        for entry in std::fs::read_dir(&group).unwrap() {
+            std::fs::remove_file(entry.unwrap().path()).unwrap();
+        }
+        enforcement.release();
+
+        assert!(!group.exists());
+        assert_eq!(
+            read(root.path().join("cgroup.subtree_control")),
+            "-cpu -memory"
+        );
+        assert_eq!(read(root.path().join("cgroup.procs")), pid);
+    }
+
+    #[test]
+    fn disk_quota_from_agreement() {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("examples/agreement.json");
+        let mut agreement = Agreement::try_from(&path).unwrap();
+        agreement.infrastructure.remove(STORAGE_GIB_INF);
+
+        assert_eq!(disk_quota(&agreement, None), None);
+        assert_eq!(disk_quota(&agreement, Some(1.)), Some(1024 * 1024 * 1024));
+
+        agreement
+            .infrastructure
+            .insert(STORAGE_GIB_INF.to_string(), 0.5);
+        assert_eq!(disk_quota(&agreement, Some(1.)), Some(512 * 1024 * 1024));
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Unified (v2) control group.
+#[derive(Clone, Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Cgroup { path: path.into() }
+    }
+
+    /// Control group of the current process.
+    pub fn current() -> io::Result<Self> {
+        let content = std::fs::read_to_string("/proc/self/cgroup")?;
+        let relative = parse_proc_cgroup(&content)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "cgroup v2 hierarchy not found"))?;
+        Ok(Cgroup::new(
+            Path::new(CGROUP_ROOT).join(relative.trim_start_matches('/')),
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates a child group. Requires the current group to be delegated.
+    pub fn create_child(&self, name: &str) -> io::Result<Self> {
+        let child = Cgroup::new(self.path.join(name));
+        match std::fs::create_dir(&child.path) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+            _ => (),
+        }
+        Ok(child)
+    }
+
+    /// Removes the group. The group must not contain any processes.
+    pub fn remove(&self) -> io::Result<()> {
+        std::fs::remove_dir(&self.path)
+    }
+
+    /// Moves a process into this group. Processes spawned later on inherit the group.
+    pub fn attach(&self, pid: u32) -> io::Result<()> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    /// Controllers enabled for child groups.
+    pub fn subtree_control(&self) -> io::Result<Vec<String>> {
+        let content = std::fs::read_to_string(self.path.join("cgroup.subtree_control"))?;
+        Ok(content
+            .split_whitespace()
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    /// Enables controllers for child groups. Fails when the group contains processes.
+    pub fn enable_controllers(&self, controllers: &[&str]) -> io::Result<()> {
+        self.write_subtree_control('+', controllers)
+    }
+
+    pub fn disable_controllers(&self, controllers: &[&str]) -> io::Result<()> {
+        self.write_subtree_control('-', controllers)
+    }
+
+    fn write_subtree_control(&self, op: char, controllers: &[&str]) -> io::Result<()> {
+        if controllers.is_empty() {
+            return Ok(());
+        }
+        let value = controllers
+            .iter()
+            .map(|c| format!("{}{}", op, c))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.write("cgroup.subtree_control", &value)
+    }
+
+    pub fn set_cpu_max(&self, cores: f64) -> io::Result<()> {
+        self.write("cpu.max", &cpu_max(cores, CPU_PERIOD_US))
+    }
+
+    /// Soft memory limit. Processes exceeding it are throttled and put under reclaim pressure.
+    pub fn set_memory_high(&self, bytes: u64) -> io::Result<()> {
+        self.write("memory.high", &bytes.to_string())
+    }
+
+    /// Hard memory limit. Processes exceeding it are killed by the OOM killer.
+    pub fn set_memory_max(&self, bytes: u64) -> io::Result<()> {
+        self.write("memory.max", &bytes.to_string())
+    }
+
+    /// Number of periods in which the group was throttled due to CPU limit.
+    pub fn cpu_throttled(&self) -> io::Result<u64> {
+        Ok(self
+            .read_keyed("cpu.stat")?
+            .remove("nr_throttled")
+            .unwrap_or(0))
+    }
+
+    /// Counters of memory limit events (e.g. `high`, `max`, `oom_kill`).
+    pub fn memory_events(&self) -> io::Result<HashMap<String, u64>> {
+        self.read_keyed("memory.events")
+    }
+
+    fn write(&self, file: &str, value: &str) -> io::Result<()> {
+        std::fs::write(self.path.join(file), value)
+    }
+
+    fn read_keyed(&self, file: &str) -> io::Result<HashMap<String, u64>> {
+        Ok(parse_keyed(&std::fs::read_to_string(self.path.join(file))?))
+    }
+}
+
+/// Returns the path of the cgroup v2 entry (`0::<path>`) from `/proc/<pid>/cgroup`.
+fn parse_proc_cgroup(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(str::trim)
+}
+
+fn parse_keyed(content: &str) -> HashMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut split = line.split_whitespace();
+            let key = split.next()?;
+            let value = split.next()?.parse().ok()?;
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+fn cpu_max(cores: f64, period_us: u64) -> String {
+    let quota = (cores * period_us as f64).round() as u64;
+    // Kernel rejects quotas below 1ms
+    format!("{} {}", quota.max(1000), period_us)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_cgroup() {
+        let content = "0::/user.slice/user-1000.slice/session-2.scope\n";
+        assert_eq!(
+            parse_proc_cgroup(content),
+            Some("/user.slice/user-1000.slice/session-2.scope")
+        );
+        // cgroup v1 only
+        let content = "12:memory:/user.slice\n1:name=systemd:/user.slice\n";
+        assert_eq!(parse_proc_cgroup(content), None);
+    }
+
+    #[test]
+    fn keyed_values() {
+        let events = parse_keyed("low 0\nhigh 12\nmax 3\noom 1\noom_kill 1\n");
+        assert_eq!(events["high"], 12);
+        assert_eq!(events["oom_kill"], 1);
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn cpu_quota() {
+        assert_eq!(cpu_max(2.0, 100_000), "200000 100000");
+        assert_eq!(cpu_max(0.5, 100_000), "50000 100000");
+        assert_eq!(cpu_max(0.0, 100_000), "1000 100000");
+    }
+
+    /// Requires a delegated cgroup v2 hierarchy, e.g.
+    /// `systemd-run --user --scope -p Delegate=yes cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn workload_constrained_to_cpu_cap() {
+        let group = Cgroup::current()
+            .unwrap()
+            .create_child(&format!("ya-test-{}", std::process::id()))
+            .unwrap();
+        group.set_cpu_max(0.1).unwrap();
+
+        let mut child = std::process::Command::new("sh")
+            .args(&["-c", "while :; do :; done"])
+            .spawn()
+            .unwrap();
+        group.attach(child.id()).unwrap();
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        let throttled = group.cpu_throttled().unwrap();
+        let usage = group.read_keyed("cpu.stat").unwrap()["usage_usec"];
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let _ = std::fs::remove_dir(group.path());
+
+        assert!(throttled > 0);
+        // 10% of a single core within ~2s, with margin
+        assert!(usage < 500_000, "cpu usage: {} us", usage);
+    }
+}
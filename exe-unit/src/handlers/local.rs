@@ -73,7 +73,7 @@ impl<R: Runtime> Handler<SetState> for ExeUnit<R> {
                 activity::ActivityState {
                     state: update.state,
                     reason: update.reason,
-                    error_message: None,
+                    error_message: update.error_message,
                 },
                 credentials,
             ),
@@ -88,6 +88,38 @@ impl<R: Runtime> Handler<SetState> for ExeUnit<R> {
     }
 }
 
+impl<R: Runtime> Handler<RecordEnforcement> for ExeUnit<R> {
+    type Result = ActorResponse<Self, ()>;
+
+    fn handle(&mut self, msg: RecordEnforcement, _ctx: &mut Context<Self>) -> Self::Result {
+        log::warn!("Allocation enforced: {}", msg.0);
+
+        if self.ctx.activity_id.is_none() || self.ctx.report_url.is_none() {
+            return ActorResponse::reply(());
+        }
+
+        let fut = report(
+            self.ctx.report_url.clone().unwrap(),
+            SetActivityState::new(
+                self.ctx.activity_id.clone().unwrap(),
+                activity::ActivityState {
+                    state: self.state.inner.clone(),
+                    reason: Some(msg.0),
+                    error_message: None,
+                },
+                None,
+            ),
+        );
+
+        ActorResponse::r#async(
+            async move {
+                fut.await;
+            }
+            .into_actor(self),
+        )
+    }
+}
+
 impl<R: Runtime> Handler<GetStdOut> for ExeUnit<R> {
     type Result = <GetStdOut as Message>::Result;
 
@@ -282,6 +314,7 @@ impl<R: Runtime> Handler<Shutdown> for ExeUnit<R> {
         let address = ctx.address();
         let services = std::mem::replace(&mut self.services, Vec::new());
         let state = self.state.inner.to_pending(State::Terminated);
+        let termination_reason = msg.0.termination_reason();
         let reason = format!("{}: {}", msg.0, self.state.report());
 
         let fut = async move {
@@ -293,7 +326,7 @@ impl<R: Runtime> Handler<Shutdown> for ExeUnit<R> {
                 service.stop().await;
             }
 
-            let set_state = SetState::new(State::Terminated.into(), reason);
+            let set_state = SetState::terminated(termination_reason, reason);
             let _ = address.send(set_state).await;
 
             log::info!("Shutdown process complete");
@@ -14,7 +14,7 @@ use ya_service_bus::{Error as RpcError, RpcEnvelope, RpcStreamCall};
 
 use crate::error::Error;
 use crate::manifest::{ManifestValidatorExt, ScriptValidator};
-use crate::message::{GetBatchResults, GetMetrics};
+use crate::message::{GetBatchResults, GetMetrics, SetVpnEnabled as SetRuntimeVpnEnabled};
 use crate::runtime::Runtime;
 use crate::{ExeUnit, RuntimeRef};
 
@@ -128,6 +128,25 @@ impl<R: Runtime> Handler<RpcEnvelope<GetRunningCommand>> for ExeUnit<R> {
     }
 }
 
+impl<R: Runtime> Handler<RpcEnvelope<SetVpnEnabled>> for ExeUnit<R> {
+    type Result = ActorResponse<Self, Result<(), RpcMessageError>>;
+
+    fn handle(&mut self, msg: RpcEnvelope<SetVpnEnabled>, _: &mut Self::Context) -> Self::Result {
+        if let Err(err) = self.ctx.verify_activity_id(&msg.activity_id) {
+            return ActorResponse::reply(Err(err.into()));
+        }
+
+        let runtime = self.runtime.clone();
+        let fut = async move {
+            match runtime.send(SetRuntimeVpnEnabled(msg.enabled)).await {
+                Ok(result) => result.map_err(Into::into),
+                Err(e) => Err(Error::from(e).into()),
+            }
+        };
+        ActorResponse::r#async(fut.into_actor(self))
+    }
+}
+
 impl<R: Runtime> Handler<RpcEnvelope<GetExecBatchResults>> for ExeUnit<R> {
     type Result = ActorResponse<Self, Result<Vec<ExeScriptCommandResult>, RpcMessageError>>;
 
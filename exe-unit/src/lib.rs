@@ -20,7 +20,7 @@ use ya_service_bus::{actix_rpc, RpcEndpoint, RpcMessage};
 
 use crate::acl::Acl;
 use crate::agreement::Agreement;
-use crate::error::Error;
+use crate::error::{Error, LocalServiceError, TransferError};
 use crate::message::*;
 use crate::runtime::*;
 use crate::service::metrics::MetricsService;
@@ -28,10 +28,11 @@ use crate::service::transfer::{AddVolumes, DeployImage, TransferResource, Transf
 use crate::service::{ServiceAddr, ServiceControl};
 use crate::state::{ExeUnitState, StateError, Supervision};
 
-mod acl;
+pub mod acl;
 pub mod agreement;
 #[cfg(feature = "sgx")]
 pub mod crypto;
+pub mod enforcement;
 pub mod error;
 mod handlers;
 pub mod logger;
@@ -109,6 +110,7 @@ impl<R: Runtime> ExeUnit<R> {
             self.ctx.activity_id.clone().unwrap(),
             context.address(),
             self.metrics.clone(),
+            self.ctx.enforcement.clone(),
         );
         context.spawn(fut.into_actor(self));
     }
@@ -297,7 +299,17 @@ impl<R: Runtime> RuntimeRef<R> {
                     to: to.clone(),
                     args: args.clone(),
                 };
-                transfer_service.send(msg).await??;
+                let result = transfer_service.send(msg).await?;
+                if let Err(Error::LocalServiceError(LocalServiceError::TransferError(
+                    TransferError::QuotaExceeded { limit },
+                ))) = &result
+                {
+                    self.do_send(RecordEnforcement(format!(
+                        "Disk quota of {} B exceeded",
+                        limit
+                    )));
+                }
+                result?;
             }
             ExeScriptCommand::Deploy { net, hosts } => {
                 let task_package = transfer_service.send(DeployImage {}).await??;
@@ -376,6 +388,7 @@ impl<R: Runtime> Actor for ExeUnit<R> {
                 actix_rpc::bind::<activity::Exec>(&srv_id, addr.clone().recipient());
                 actix_rpc::bind::<activity::GetExecBatchResults>(&srv_id, addr.clone().recipient());
                 actix_rpc::bind::<activity::GetRunningCommand>(&srv_id, addr.clone().recipient());
+                actix_rpc::bind::<activity::SetVpnEnabled>(&srv_id, addr.clone().recipient());
                 actix_rpc::binds::<activity::StreamExecBatchResults>(
                     &srv_id,
                     addr.clone().recipient(),
@@ -452,6 +465,9 @@ pub struct ExeUnitContext {
     pub runtime_args: Vec<String>,
     pub acl: Acl,
     pub credentials: Option<Credentials>,
+    pub enforcement: Option<enforcement::Enforcement>,
+    /// Bytes transferred by `TransferService`, keyed by agreement id
+    pub transfer_usage: ya_transfer::BandwidthRegistry,
     #[cfg(feature = "sgx")]
     #[derivative(Debug = "ignore")]
     pub crypto: crypto::Crypto,
@@ -512,7 +528,14 @@ async fn report_usage<R: Runtime>(
     activity_id: String,
     exe_unit: Addr<ExeUnit<R>>,
     metrics: Addr<MetricsService>,
+    enforcement: Option<enforcement::Enforcement>,
 ) {
+    if let Some(enforcement) = enforcement {
+        for event in enforcement.events() {
+            exe_unit.do_send(RecordEnforcement(event));
+        }
+    }
+
     match metrics.send(GetMetrics).await {
         Ok(resp) => match resp {
             Ok(data) => {
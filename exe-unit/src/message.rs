@@ -11,6 +11,7 @@ use ya_client_model::activity;
 use ya_client_model::activity::activity_state::{State, StatePair};
 use ya_client_model::activity::exe_script_command::Network;
 use ya_client_model::activity::{CommandOutput, ExeScriptCommand, ExeScriptCommandResult};
+use ya_core_model::activity::TerminationReason;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Message)]
 #[rtype(result = "Result<Vec<f64>>")]
@@ -23,6 +24,11 @@ pub struct SetMetric {
     pub value: f64,
 }
 
+/// Records an event of enforcing the allocation in the activity state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Message)]
+#[rtype(result = "()")]
+pub struct RecordEnforcement(pub String);
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Message)]
 #[rtype(result = "GetStateResponse")]
 pub struct GetState;
@@ -52,6 +58,7 @@ pub struct GetStdOut {
 pub struct SetState {
     pub state: StatePair,
     pub reason: Option<String>,
+    pub error_message: Option<String>,
 }
 
 impl SetState {
@@ -59,6 +66,15 @@ impl SetState {
         SetState {
             state,
             reason: Some(reason),
+            error_message: None,
+        }
+    }
+
+    pub fn terminated(reason: TerminationReason, message: String) -> Self {
+        SetState {
+            state: State::Terminated.into(),
+            reason: Some(reason.to_string()),
+            error_message: Some(message),
         }
     }
 }
@@ -76,6 +92,7 @@ impl From<StatePair> for SetState {
         SetState {
             state,
             reason: None,
+            error_message: None,
         }
     }
 }
@@ -174,6 +191,13 @@ pub struct UpdateDeployment {
 #[rtype(result = "Result<()>")]
 pub struct Initialize;
 
+/// Turns VPN traffic forwarding off and on without stopping the activity.
+/// Disabling unbinds the VPN GSB endpoints and closes the runtime socket;
+/// enabling re-connects to the runtime endpoint and binds them again.
+#[derive(Clone, Copy, Debug, Message)]
+#[rtype(result = "Result<()>")]
+pub struct SetVpnEnabled(pub bool);
+
 #[derive(Clone, Debug, PartialEq, Message)]
 #[rtype(result = "()")]
 pub struct Register<Svc>(pub Addr<Svc>)
@@ -221,6 +245,17 @@ pub enum ShutdownReason {
     Error(#[from] Error),
 }
 
+impl ShutdownReason {
+    pub fn termination_reason(&self) -> TerminationReason {
+        match self {
+            ShutdownReason::Finished => TerminationReason::Finished,
+            ShutdownReason::Interrupted(_) => TerminationReason::Interrupted,
+            ShutdownReason::UsageLimitExceeded(_) => TerminationReason::UsageLimitExceeded,
+            ShutdownReason::Error(_) => TerminationReason::ExeUnitCrashed,
+        }
+    }
+}
+
 impl Default for ShutdownReason {
     fn default() -> Self {
         ShutdownReason::Finished
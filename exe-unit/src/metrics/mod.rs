@@ -110,6 +110,36 @@ impl Metric for TimeMetric {
     }
 }
 
+/// Data transferred by the ExeUnit within a single agreement
+pub struct TransferMetric {
+    accounting: ya_transfer::TransferAccounting,
+}
+
+impl TransferMetric {
+    pub const ID: &'static str = "golem.usage.transfer_gib";
+
+    pub fn new(accounting: ya_transfer::TransferAccounting) -> Self {
+        TransferMetric { accounting }
+    }
+}
+
+impl Metric for TransferMetric {
+    fn frame(&mut self) -> Result<MetricData> {
+        let accounting = &self.accounting;
+        let bytes = accounting
+            .registry
+            .usage(&accounting.context_id)
+            .map(|usage| usage.bytes)
+            .unwrap_or(0);
+        Ok(bytes as MetricData / (1024. * 1024. * 1024.))
+    }
+
+    #[inline]
+    fn peak(&mut self) -> Result<MetricData> {
+        self.frame()
+    }
+}
+
 pub struct StorageMetric {
     path: PathBuf,
     peak: MetricData,
@@ -212,3 +242,23 @@ impl Drop for StorageMetric {
         self.running.store(false, Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ya_transfer::{BandwidthRegistry, TransferAccounting};
+
+    #[test]
+    fn transfer_metric_reads_agreement_usage() {
+        let registry = BandwidthRegistry::default();
+        let mut metric =
+            TransferMetric::new(TransferAccounting::new("agreement", registry.clone()));
+        assert_eq!(metric.frame().unwrap(), 0.);
+
+        registry.record("agreement", 512 * 1024 * 1024);
+        registry.record("agreement", 512 * 1024 * 1024);
+        registry.record("other", 1024);
+        assert_eq!(metric.frame().unwrap(), 1.);
+        assert_eq!(metric.peak().unwrap(), 1.);
+    }
+}
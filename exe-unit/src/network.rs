@@ -1,44 +1,109 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
 
 use futures::channel::mpsc;
-use futures::Stream;
+use futures::{Sink, Stream};
+use ipnet::IpNet;
 
-use ya_runtime_api::deploy::ContainerEndpoint;
+use ya_runtime_api::deploy::{ContainerEndpoint, TlsEndpoint};
 use ya_runtime_api::server::Network;
 use ya_service_bus::{typed, typed::Endpoint as GsbEndpoint};
 use ya_utils_networking::vpn::common::DEFAULT_MAX_FRAME_SIZE;
 use ya_utils_networking::vpn::{network::DuoEndpoint, Error as NetError};
 
 use crate::error::Error;
-use crate::state::DeploymentNetwork;
+use crate::state::{DeploymentNetwork, EndpointTls};
 use crate::Result;
 
 pub(crate) mod inet;
+#[cfg(feature = "tls")]
+mod tls;
 pub(crate) mod vpn;
 
+const COALESCE_BYTES_ENV_VAR: &str = "YA_VPN_COALESCE_BYTES";
+const COALESCE_DELAY_MS_ENV_VAR: &str = "YA_VPN_COALESCE_DELAY_MS";
+const DEFAULT_COALESCE_DELAY_MS: u64 = 1;
+const MAX_IN_FLIGHT_ENV_VAR: &str = "YA_VPN_MAX_IN_FLIGHT_FRAMES";
+const DEFAULT_MAX_IN_FLIGHT: usize = 1024;
+
+/// Wraps a TCP endpoint exposed by the runtime in TLS, if configured
+pub(crate) fn secure_endpoint(
+    endpoint: ContainerEndpoint,
+    tls: Option<&EndpointTls>,
+) -> ContainerEndpoint {
+    match (endpoint, tls) {
+        (ContainerEndpoint::Tcp(addr), Some(tls)) => ContainerEndpoint::Tls(TlsEndpoint {
+            addr,
+            server_name: tls.server_name.clone(),
+            ca_cert: tls.ca_cert.clone(),
+            cert: tls.cert.clone(),
+            key: tls.key.clone(),
+        }),
+        (endpoint, _) => endpoint,
+    }
+}
+
 pub(crate) struct Endpoint {
     tx: mpsc::Sender<Result<Vec<u8>>>,
     rx: Option<Box<dyn Stream<Item = Result<Vec<u8>>> + Unpin>>,
 }
 
 impl Endpoint {
-    pub async fn connect(endpoint: impl Into<ContainerEndpoint>) -> Result<Self> {
-        match endpoint.into() {
+    pub async fn connect(endpoint: ContainerEndpoint) -> Result<Self> {
+        match endpoint {
             ContainerEndpoint::Socket(path) => Self::connect_to_socket(path).await,
+            ContainerEndpoint::Tls(tls) => Self::connect_tls(tls).await,
+            ContainerEndpoint::Tcp(addr) => Self::connect_tcp(addr).await,
             ep => Err(Error::Other(format!("Unsupported endpoint type: {:?}", ep))),
         }
     }
 
     #[cfg(unix)]
     async fn connect_to_socket<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let socket = tokio::net::UnixStream::connect(path.as_ref()).await?;
+        Ok(Self::framed(socket))
+    }
+
+    #[cfg(not(unix))]
+    async fn connect_to_socket<P: AsRef<Path>>(_path: P) -> Result<Self> {
+        Err(Error::Other("OS not supported".into()))
+    }
+
+    async fn connect_tcp(addr: SocketAddr) -> Result<Self> {
+        let socket = tokio::net::TcpStream::connect(addr).await?;
+        socket.set_nodelay(true)?;
+        Ok(Self::framed(socket))
+    }
+
+    #[cfg(feature = "tls")]
+    async fn connect_tls(endpoint: TlsEndpoint) -> Result<Self> {
+        let stream = tls::connect(&endpoint).await?;
+        Ok(Self::framed(stream))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    async fn connect_tls(endpoint: TlsEndpoint) -> Result<Self> {
+        Err(Error::Other(format!(
+            "TLS endpoint {} is not supported: exe-unit built without the `tls` feature",
+            endpoint.addr
+        )))
+    }
+
+    /// Exchanges raw (prefixed) frames over a byte stream
+    fn framed<S>(io: S) -> Self
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + 'static,
+    {
         use bytes::Bytes;
         use futures::{future, SinkExt, StreamExt, TryStreamExt};
-        use tokio::io;
         use tokio_util::codec::{BytesCodec, FramedRead, FramedWrite};
 
-        let socket = tokio::net::UnixStream::connect(path.as_ref()).await?;
-        let (read, write) = io::split(socket);
+        let (read, write) = tokio::io::split(io);
 
         let sink = FramedWrite::new(write, BytesCodec::new()).with(|v| future::ok(Bytes::from(v)));
         let stream = FramedRead::with_capacity(read, BytesCodec::new(), DEFAULT_MAX_FRAME_SIZE)
@@ -48,20 +113,152 @@ impl Endpoint {
 
         let (tx_si, rx_si) = mpsc::channel(1);
         tokio::task::spawn_local(async move {
-            if let Err(e) = rx_si.forward(sink).await {
+            let result = match Coalescing::from_env() {
+                Some(coalescing) => coalesce(rx_si, sink, coalescing).await,
+                None => rx_si.forward(sink).await,
+            };
+            if let Err(e) = result {
                 log::error!("Socket endpoint error: {}", e);
             }
         });
 
-        Ok(Self {
+        Self {
             tx: tx_si,
             rx: Some(Box::new(stream)),
+        }
+    }
+}
+
+/// Batching of ingress frames into fewer socket writes
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Coalescing {
+    /// Batch size, which triggers a write
+    max_bytes: usize,
+    /// Maximum time a frame waits for a batch to fill up
+    max_delay: Duration,
+}
+
+impl Coalescing {
+    /// Coalescing is disabled unless `YA_VPN_COALESCE_BYTES` is set to a non-zero value
+    fn from_env() -> Option<Self> {
+        let max_bytes = std::env::var(COALESCE_BYTES_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)?;
+        let max_delay_ms = std::env::var(COALESCE_DELAY_MS_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_COALESCE_DELAY_MS);
+
+        Some(Self {
+            max_bytes,
+            max_delay: Duration::from_millis(max_delay_ms),
         })
     }
+}
 
-    #[cfg(not(unix))]
-    async fn connect_to_socket<P: AsRef<Path>>(_path: P) -> Result<Self> {
-        Err(Error::Other("OS not supported".into()))
+/// Concatenates prefixed frames, which are ready before the batch fills up or its delay
+/// elapses. Frames retain their prefixes, so the receiver is able to split them apart.
+async fn coalesce<St, Si>(mut frames: St, mut sink: Si, coalescing: Coalescing) -> Result<()>
+where
+    St: Stream<Item = Result<Vec<u8>>> + Unpin,
+    Si: Sink<Vec<u8>, Error = Error> + Unpin,
+{
+    use futures::future::{self, Either};
+    use futures::{SinkExt, StreamExt};
+
+    let mut closed = false;
+    while !closed {
+        let mut batch = match frames.next().await {
+            Some(frame) => frame?,
+            None => break,
+        };
+
+        let mut deadline = Box::pin(tokio::time::sleep(coalescing.max_delay));
+        while batch.len() < coalescing.max_bytes {
+            match future::select(frames.next(), &mut deadline).await {
+                Either::Left((Some(frame), _)) => batch.extend(frame?),
+                Either::Left((None, _)) => {
+                    closed = true;
+                    break;
+                }
+                Either::Right(_) => break,
+            }
+        }
+
+        sink.send(batch).await?;
+    }
+    sink.close().await
+}
+
+/// Bounds the number of egress frames being forwarded to each node, so that
+/// a slow node doesn't accumulate an unbounded number of pending calls
+pub(crate) struct InFlightLimit {
+    max: usize,
+    in_flight: Rc<RefCell<HashMap<String, usize>>>,
+    dropped: u64,
+}
+
+impl InFlightLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            in_flight: Default::default(),
+            dropped: 0,
+        }
+    }
+
+    /// Limit is read from `YA_VPN_MAX_IN_FLIGHT_FRAMES`
+    pub fn from_env() -> Self {
+        let max = std::env::var(MAX_IN_FLIGHT_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_MAX_IN_FLIGHT);
+        Self::new(max)
+    }
+
+    /// Reserves a slot for a frame sent to `node`. The slot is released when
+    /// the permit is dropped. Frames exceeding the limit are counted as dropped.
+    pub fn try_acquire(&mut self, node: &str) -> Option<InFlightPermit> {
+        {
+            let mut in_flight = self.in_flight.borrow_mut();
+            let count = in_flight.entry(node.to_string()).or_insert(0);
+            if *count >= self.max {
+                self.dropped += 1;
+                return None;
+            }
+            *count += 1;
+        }
+        Some(InFlightPermit {
+            node: node.to_string(),
+            in_flight: self.in_flight.clone(),
+        })
+    }
+
+    pub fn in_flight(&self, node: &str) -> usize {
+        self.in_flight.borrow().get(node).copied().unwrap_or(0)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+pub(crate) struct InFlightPermit {
+    node: String,
+    in_flight: Rc<RefCell<HashMap<String, usize>>>,
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.borrow_mut();
+        if let Some(count) = in_flight.get_mut(&self.node) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.node);
+            }
+        }
     }
 }
 
@@ -71,11 +268,7 @@ impl<'a> TryFrom<&'a DeploymentNetwork> for Network {
     fn try_from(net: &'a DeploymentNetwork) -> Result<Self> {
         let ip = net.network.addr();
         let mask = net.network.netmask();
-        let gateway = net
-            .network
-            .hosts()
-            .find(|ip_| ip_ != &ip)
-            .ok_or(NetError::NetAddrTaken(ip))?;
+        let gateway = net.gateway().ok_or(NetError::NetAddrTaken(ip))?;
 
         Ok(Network {
             addr: ip.to_string(),
@@ -86,8 +279,57 @@ impl<'a> TryFrom<&'a DeploymentNetwork> for Network {
     }
 }
 
+/// Destination class of an egress IP packet
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum IpDestination {
+    /// 255.255.255.255, delivered to nodes in all networks
+    LimitedBroadcast,
+    /// Directed broadcast address of a VPN network, delivered to nodes of that network
+    SubnetBroadcast(IpNet),
+    /// Multicast group address, delivered to nodes in all networks
+    Multicast,
+    /// IPv6 solicited-node multicast address (neighbor discovery), delivered
+    /// to nodes with IPv6 addresses ending with given 24 bits
+    SolicitedNode([u8; 3]),
+    Unicast(IpAddr),
+}
+
+impl IpDestination {
+    pub fn classify<'a>(dst: IpAddr, networks: impl IntoIterator<Item = &'a IpNet>) -> Self {
+        match dst {
+            IpAddr::V4(Ipv4Addr::BROADCAST) => return Self::LimitedBroadcast,
+            // IPv6 has no broadcast, all-nodes multicast group is the equivalent
+            IpAddr::V6(ip) if ip == Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1) => {
+                return Self::LimitedBroadcast
+            }
+            IpAddr::V6(ip) if ip.segments()[..6] == [0xff02, 0, 0, 0, 0, 1] => {
+                let octets = ip.octets();
+                if octets[12] == 0xff {
+                    return Self::SolicitedNode([octets[13], octets[14], octets[15]]);
+                }
+            }
+            _ => (),
+        }
+        if dst.is_multicast() {
+            return Self::Multicast;
+        }
+        if dst.is_ipv4() {
+            let net = networks
+                .into_iter()
+                .find(|net| net.prefix_len() < net.max_prefix_len() && net.broadcast() == dst);
+            if let Some(net) = net {
+                return Self::SubnetBroadcast(net.trunc());
+            }
+        }
+        Self::Unicast(dst)
+    }
+}
+
 type Prefix = u16;
 const PREFIX_SIZE: usize = std::mem::size_of::<Prefix>();
+const ETHERNET_HEADER_SIZE: usize = 14;
+/// Largest frame accepted from the container: MTU and the Ethernet header
+const MAX_FRAME_SIZE: usize = DEFAULT_MAX_FRAME_SIZE + ETHERNET_HEADER_SIZE;
 
 pub(self) struct RxBuffer {
     expected: usize,
@@ -127,6 +369,9 @@ impl<'a> Iterator for RxIterator<'a> {
         }
 
         if let Some(len) = read_prefix(&self.buffer.inner) {
+            if len as usize > MAX_FRAME_SIZE {
+                return self.discard(len);
+            }
             if let Some(item) = take_next(&mut self.buffer.inner, len) {
                 self.buffer.expected = read_prefix(&self.buffer.inner).unwrap_or(0) as usize;
                 return Some(item);
@@ -134,6 +379,9 @@ impl<'a> Iterator for RxIterator<'a> {
         }
 
         if let Some(len) = read_prefix(&self.received) {
+            if len as usize > MAX_FRAME_SIZE {
+                return self.discard(len);
+            }
             if let Some(item) = take_next(&mut self.received, len) {
                 return Some(item);
             }
@@ -148,6 +396,22 @@ impl<'a> Iterator for RxIterator<'a> {
     }
 }
 
+impl<'a> RxIterator<'a> {
+    /// Drops all buffered data after reading a corrupt length prefix.
+    fn discard(&mut self, len: Prefix) -> Option<Vec<u8>> {
+        log::error!(
+            "VPN: invalid frame length {} (max {}), dropping {} B of received data",
+            len,
+            MAX_FRAME_SIZE,
+            self.buffer.inner.len() + self.received.len()
+        );
+        self.buffer.inner.clear();
+        self.buffer.expected = 0;
+        self.received.clear();
+        None
+    }
+}
+
 fn take_next(src: &mut Vec<u8>, len: Prefix) -> Option<Vec<u8>> {
     let p_len = PREFIX_SIZE + len as usize;
     if src.len() >= p_len {
@@ -171,6 +435,120 @@ fn write_prefix(dst: &mut Vec<u8>) {
     dst.splice(0..0, u16::to_ne_bytes(len_u16).to_vec());
 }
 
+/// Builds a reply to the ICMP (v4 or v6) echo request carried by the Ethernet
+/// `frame`. Returns `None` for any other frame.
+pub(crate) fn icmp_echo_reply(frame: &[u8]) -> Option<Vec<u8>> {
+    if frame.len() < ETHERNET_HEADER_SIZE {
+        return None;
+    }
+
+    let mut reply = frame.to_vec();
+    let (eth, ip) = reply.split_at_mut(ETHERNET_HEADER_SIZE);
+    let len = match [eth[12], eth[13]] {
+        [0x08, 0x00] => icmpv4_echo_reply(ip)?,
+        [0x86, 0xdd] => icmpv6_echo_reply(ip)?,
+        _ => return None,
+    };
+
+    let mut dst_mac = [0u8; 6];
+    dst_mac.copy_from_slice(&eth[..6]);
+    eth.copy_within(6..12, 0);
+    eth[6..12].copy_from_slice(&dst_mac);
+
+    // drop the Ethernet padding
+    reply.truncate(ETHERNET_HEADER_SIZE + len);
+    Some(reply)
+}
+
+/// Turns an IPv4 echo request into a reply in place; returns the packet length
+fn icmpv4_echo_reply(ip: &mut [u8]) -> Option<usize> {
+    const HEADER_SIZE: usize = 20;
+    const ICMP: u8 = 1;
+
+    if ip.len() < HEADER_SIZE || ip[0] >> 4 != 4 || ip[9] != ICMP {
+        return None;
+    }
+    // fragmented requests are not reassembled
+    if u16::from_be_bytes([ip[6], ip[7]]) & 0x3fff != 0 {
+        return None;
+    }
+    let header_len = (ip[0] & 0x0f) as usize * 4;
+    let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    if header_len < HEADER_SIZE || total_len < header_len + 8 || total_len > ip.len() {
+        return None;
+    }
+
+    let (header, icmp) = ip[..total_len].split_at_mut(header_len);
+    if icmp[0] != 8 {
+        return None;
+    }
+    icmp[0] = 0;
+    icmp[2..4].copy_from_slice(&[0, 0]);
+    let sum = checksum(&[&icmp[..]]);
+    icmp[2..4].copy_from_slice(&sum.to_be_bytes());
+
+    swap(header, 12..16, 16..20);
+    header[8] = 64;
+    header[10..12].copy_from_slice(&[0, 0]);
+    let sum = checksum(&[&header[..]]);
+    header[10..12].copy_from_slice(&sum.to_be_bytes());
+
+    Some(total_len)
+}
+
+/// Turns an IPv6 echo request into a reply in place; returns the packet length
+fn icmpv6_echo_reply(ip: &mut [u8]) -> Option<usize> {
+    const HEADER_SIZE: usize = 40;
+    const ICMPV6: u8 = 58;
+
+    // extension headers are not supported
+    if ip.len() < HEADER_SIZE || ip[0] >> 4 != 6 || ip[6] != ICMPV6 {
+        return None;
+    }
+    let payload_len = u16::from_be_bytes([ip[4], ip[5]]) as usize;
+    if payload_len < 8 || HEADER_SIZE + payload_len > ip.len() {
+        return None;
+    }
+
+    let (header, icmp) = ip[..HEADER_SIZE + payload_len].split_at_mut(HEADER_SIZE);
+    if icmp[0] != 128 {
+        return None;
+    }
+    swap(header, 8..24, 24..40);
+    header[7] = 64;
+
+    icmp[0] = 129;
+    icmp[2..4].copy_from_slice(&[0, 0]);
+    let len = (payload_len as u32).to_be_bytes();
+    let sum = checksum(&[&header[8..40], &len[..], &[0, 0, 0, ICMPV6][..], &icmp[..]]);
+    icmp[2..4].copy_from_slice(&sum.to_be_bytes());
+
+    Some(HEADER_SIZE + payload_len)
+}
+
+fn swap(buf: &mut [u8], a: std::ops::Range<usize>, b: std::ops::Range<usize>) {
+    let tmp = buf[a.clone()].to_vec();
+    buf.copy_within(b.clone(), a.start);
+    buf[b].copy_from_slice(&tmp);
+}
+
+/// Internet checksum (RFC 1071). All chunks but the last one must be of even length.
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum = chunks
+        .iter()
+        .flat_map(|chunk| chunk.chunks(2))
+        .map(|word| match word {
+            [hi, lo] => u16::from_be_bytes([*hi, *lo]) as u32,
+            [hi] => u16::from_be_bytes([*hi, 0]) as u32,
+            _ => 0,
+        })
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
 fn gsb_endpoint(node_id: &str, net_id: &str) -> DuoEndpoint<GsbEndpoint> {
     DuoEndpoint {
         tcp: typed::service(format!("/net/{}/vpn/{}", node_id, net_id)),
@@ -181,8 +559,21 @@ fn gsb_endpoint(node_id: &str, net_id: &str) -> DuoEndpoint<GsbEndpoint> {
 #[cfg(test)]
 mod test {
     use std::iter::FromIterator;
+    use std::net::{IpAddr, Ipv6Addr};
+
+    use futures::{SinkExt, StreamExt};
+    use ipnet::IpNet;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use ya_runtime_api::deploy::ContainerEndpoint;
+    use ya_utils_networking::vpn::common::ntoh;
+    use ya_utils_networking::vpn::IpPacket;
 
-    use super::{write_prefix, RxBuffer};
+    use super::{
+        checksum, coalesce, icmp_echo_reply, secure_endpoint, write_prefix, Coalescing, Endpoint,
+        EndpointTls, InFlightLimit, IpDestination, Prefix, RxBuffer, ETHERNET_HEADER_SIZE,
+        PREFIX_SIZE,
+    };
 
     enum TxMode {
         Full,
@@ -223,4 +614,418 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn rx_buffer_oversized_prefix() {
+        let mut buf = RxBuffer::default();
+
+        let mut bogus = Prefix::MAX.to_ne_bytes().to_vec();
+        bogus.extend_from_slice(&[0xaa; 64]);
+        assert_eq!(buf.process(bogus).count(), 0);
+        assert!(buf.inner.is_empty());
+
+        // corrupt prefix split across reads
+        assert_eq!(buf.process(vec![0xff]).count(), 0);
+        assert_eq!(buf.process(vec![0xff]).count(), 0);
+        assert_eq!(buf.process(vec![0xaa; 64]).count(), 0);
+        assert!(buf.inner.is_empty());
+        assert_eq!(buf.expected, 0);
+
+        let frame = vec![0x42; 32];
+        let mut prefixed = frame.clone();
+        write_prefix(&mut prefixed);
+        assert_eq!(buf.process(prefixed).collect::<Vec<_>>(), vec![frame]);
+    }
+
+    fn ethernet_frame(ether_type: [u8; 2], payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 0x01]; // destination mac
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]); // source mac
+        frame.extend_from_slice(&ether_type);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn icmpv4_echo_request_is_answered() {
+        let mut pkt = vec![
+            0x45, 0x00, 0x00, 0x20, // version, ihl, dscp, total length
+            0x12, 0x34, 0x40, 0x00, // identification, flags (df), fragment offset
+            0x40, 0x01, 0x00, 0x00, // ttl, protocol (icmp), checksum
+            10, 0, 0, 3, // source
+            10, 0, 0, 1, // destination
+            0x08, 0x00, 0x00, 0x00, // echo request, code, checksum
+            0x00, 0x01, 0x00, 0x07, // identifier, sequence number
+            0xde, 0xad, 0xbe, 0xef, // data
+        ];
+        let sum = checksum(&[&pkt[..20]]);
+        pkt[10..12].copy_from_slice(&sum.to_be_bytes());
+        let sum = checksum(&[&pkt[20..]]);
+        pkt[22..24].copy_from_slice(&sum.to_be_bytes());
+
+        let mut request = ethernet_frame([0x08, 0x00], &pkt);
+        request.extend_from_slice(&[0; 14]); // padding
+        let reply = icmp_echo_reply(&request).unwrap();
+
+        assert_eq!(reply.len(), ETHERNET_HEADER_SIZE + pkt.len());
+        assert_eq!(&reply[..6], &request[6..12]);
+        assert_eq!(&reply[6..12], &request[..6]);
+
+        let ip = &reply[ETHERNET_HEADER_SIZE..];
+        assert_eq!(&ip[12..16], &[10, 0, 0, 1]);
+        assert_eq!(&ip[16..20], &[10, 0, 0, 3]);
+        assert_eq!(checksum(&[&ip[..20]]), 0);
+        assert_eq!(ip[20], 0);
+        assert_eq!(checksum(&[&ip[20..]]), 0);
+        assert_eq!(&ip[24..], &pkt[24..]);
+
+        // not an echo request
+        assert!(icmp_echo_reply(&reply).is_none());
+        assert!(
+            icmp_echo_reply(&ethernet_frame([0x08, 0x00], &ipv4_packet([10, 0, 0, 1]))).is_none()
+        );
+    }
+
+    #[test]
+    fn icmpv6_echo_request_is_answered() {
+        let src = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 3).octets();
+        let dst = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1).octets();
+        let icmp = [
+            0x80, 0x00, 0x00, 0x00, // echo request, code, checksum
+            0x00, 0x01, 0x00, 0x07, // identifier, sequence number
+            0xde, 0xad, 0xbe, 0xef, // data
+        ];
+        let mut pkt = vec![
+            0x60, 0x00, 0x00, 0x00, // version, traffic class, flow label
+            0x00, 0x0c, 0x3a, 0x40, // payload length, next header (icmpv6), hop limit
+        ];
+        pkt.extend_from_slice(&src);
+        pkt.extend_from_slice(&dst);
+        pkt.extend_from_slice(&icmp);
+
+        let reply = icmp_echo_reply(&ethernet_frame([0x86, 0xdd], &pkt)).unwrap();
+        let ip = &reply[ETHERNET_HEADER_SIZE..];
+        assert_eq!(&ip[8..24], &dst);
+        assert_eq!(&ip[24..40], &src);
+        assert_eq!(ip[40], 129);
+        let sum = checksum(&[
+            &ip[8..40],
+            &[0, 0, 0, 12][..],
+            &[0, 0, 0, 58][..],
+            &ip[40..],
+        ]);
+        assert_eq!(sum, 0);
+        assert_eq!(&ip[44..], &icmp[4..]);
+    }
+
+    #[actix_rt::test]
+    async fn coalesced_frames_decode() {
+        let src = (1..=64u8)
+            .map(|e| Vec::from_iter(std::iter::repeat(e).take(e as usize)))
+            .collect::<Vec<_>>();
+        let total = src.iter().map(|v| PREFIX_SIZE + v.len()).sum::<usize>();
+
+        let (mut tx, rx) = futures::channel::mpsc::channel(src.len());
+        for mut frame in src.clone() {
+            write_prefix(&mut frame);
+            tx.send(Ok(frame)).await.unwrap();
+        }
+        drop(tx);
+
+        let (sink, writes) = futures::channel::mpsc::unbounded::<Vec<u8>>();
+        let sink = sink.sink_map_err(|e| super::Error::Other(e.to_string()));
+        let coalescing = Coalescing {
+            max_bytes: 512,
+            max_delay: std::time::Duration::from_millis(10),
+        };
+        coalesce(rx, sink, coalescing).await.unwrap();
+
+        let writes = writes.collect::<Vec<_>>().await;
+        // every batch but the last one fills up to the threshold
+        assert!(writes.len() <= total / 512 + 1);
+
+        let mut buf = RxBuffer::default();
+        let dst = writes
+            .into_iter()
+            .flat_map(|w| buf.process(w).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(src, dst);
+    }
+
+    fn ipv4_packet(dst: [u8; 4]) -> Vec<u8> {
+        let mut pkt = vec![
+            0x45, 0x00, 0x00, 0x14, // version, ihl, dscp, total length
+            0x00, 0x00, 0x00, 0x00, // identification, flags, fragment offset
+            0x40, 0x11, 0x00, 0x00, // ttl, protocol (udp), checksum
+            10, 0, 0, 2, // source
+        ];
+        pkt.extend_from_slice(&dst);
+        pkt
+    }
+
+    fn ipv6_packet(dst: [u8; 16]) -> Vec<u8> {
+        let mut pkt = vec![
+            0x60, 0x00, 0x00, 0x00, // version, traffic class, flow label
+            0x00, 0x00, 0x3a, 0xff, // payload length, next header (icmpv6), hop limit
+        ];
+        pkt.extend_from_slice(&Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2).octets());
+        pkt.extend_from_slice(&dst);
+        pkt
+    }
+
+    fn classify(dst: [u8; 4], networks: &[IpNet]) -> IpDestination {
+        let data = ipv4_packet(dst);
+        let pkt = IpPacket::packet(&data[..]);
+        let ip = ntoh(pkt.dst_address()).unwrap();
+        IpDestination::classify(ip, networks)
+    }
+
+    fn classify_v6(dst: &str, networks: &[IpNet]) -> IpDestination {
+        let data = ipv6_packet(dst.parse::<Ipv6Addr>().unwrap().octets());
+        let pkt = IpPacket::packet(&data[..]);
+        let ip = ntoh(pkt.dst_address()).unwrap();
+        IpDestination::classify(ip, networks)
+    }
+
+    #[test]
+    fn classify_ip_destination() {
+        let networks: Vec<IpNet> = vec!["10.0.0.1/24".parse().unwrap()];
+        let net: IpNet = "10.0.0.0/24".parse().unwrap();
+
+        assert_eq!(
+            classify([255, 255, 255, 255], &networks),
+            IpDestination::LimitedBroadcast
+        );
+        assert_eq!(
+            classify([10, 0, 0, 255], &networks),
+            IpDestination::SubnetBroadcast(net)
+        );
+        assert_eq!(
+            classify([224, 0, 0, 251], &networks),
+            IpDestination::Multicast
+        );
+        assert_eq!(
+            classify([10, 0, 0, 3], &networks),
+            IpDestination::Unicast(IpAddr::from([10, 0, 0, 3]))
+        );
+        // broadcast address of a network we're not a part of
+        assert_eq!(
+            classify([10, 0, 1, 255], &networks),
+            IpDestination::Unicast(IpAddr::from([10, 0, 1, 255]))
+        );
+    }
+
+    #[test]
+    fn classify_ipv6_destination() {
+        let networks: Vec<IpNet> = vec!["fd00::1/64".parse().unwrap()];
+
+        assert_eq!(
+            classify_v6("ff02::1", &networks),
+            IpDestination::LimitedBroadcast
+        );
+        assert_eq!(
+            classify_v6("ff02::1:ff00:3", &networks),
+            IpDestination::SolicitedNode([0x00, 0x00, 0x03])
+        );
+        assert_eq!(classify_v6("ff02::fb", &networks), IpDestination::Multicast);
+        assert_eq!(
+            classify_v6("fd00::3", &networks),
+            IpDestination::Unicast("fd00::3".parse().unwrap())
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    fn tls_acceptor(name: &str) -> (tokio_rustls::TlsAcceptor, String) {
+        use std::sync::Arc;
+        use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(cert.serialize_der().unwrap())],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+
+        (Arc::new(config).into(), cert.serialize_pem().unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn tcp_endpoint_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // echo a single prefixed frame back to the client
+        let server = tokio::task::spawn_local(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut prefix = [0u8; PREFIX_SIZE];
+            stream.read_exact(&mut prefix).await.unwrap();
+            let mut data = vec![0u8; u16::from_ne_bytes(prefix) as usize];
+            stream.read_exact(&mut data).await.unwrap();
+
+            stream.write_all(&prefix).await.unwrap();
+            stream.write_all(&data).await.unwrap();
+            stream.flush().await.unwrap();
+            data
+        });
+
+        let mut endpoint = Endpoint::connect(ContainerEndpoint::Tcp(addr))
+            .await
+            .unwrap();
+
+        let payload = (0..=255u8).collect::<Vec<_>>();
+        let mut frame = payload.clone();
+        write_prefix(&mut frame);
+        endpoint.tx.send(Ok(frame)).await.unwrap();
+
+        let mut rx = endpoint.rx.take().unwrap();
+        let mut buf = RxBuffer::default();
+        let received = loop {
+            let chunk = rx.next().await.unwrap().unwrap();
+            if let Some(item) = buf.process(chunk).next() {
+                break item;
+            }
+        };
+
+        assert_eq!(server.await.unwrap(), payload);
+        assert_eq!(received, payload);
+    }
+
+    #[test]
+    fn tcp_endpoints_are_wrapped_in_tls() {
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let tls = EndpointTls {
+            server_name: "runtime".to_string(),
+            ca_cert: Some("ca.pem".into()),
+            cert: None,
+            key: None,
+        };
+
+        match secure_endpoint(ContainerEndpoint::Tcp(addr), Some(&tls)) {
+            ContainerEndpoint::Tls(endpoint) => {
+                assert_eq!(endpoint.addr, addr);
+                assert_eq!(endpoint.server_name, "runtime");
+                assert_eq!(endpoint.ca_cert, tls.ca_cert);
+            }
+            endpoint => panic!("expected a TLS endpoint, got {:?}", endpoint),
+        }
+        match secure_endpoint(ContainerEndpoint::Tcp(addr), None) {
+            ContainerEndpoint::Tcp(a) => assert_eq!(a, addr),
+            endpoint => panic!("expected a TCP endpoint, got {:?}", endpoint),
+        }
+        match secure_endpoint(ContainerEndpoint::Socket("vpn.sock".into()), Some(&tls)) {
+            ContainerEndpoint::Socket(_) => (),
+            endpoint => panic!("expected a socket endpoint, got {:?}", endpoint),
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    #[actix_rt::test]
+    async fn tls_endpoint_round_trip() {
+        let dir = tempdir::TempDir::new("vpn-tls").unwrap();
+        let (acceptor, cert) = tls_acceptor("localhost");
+        let ca_cert = dir.path().join("ca.pem");
+        std::fs::write(&ca_cert, cert).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // echo a single prefixed frame back to the client
+        let server = tokio::task::spawn_local(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(socket).await.unwrap();
+
+            let mut prefix = [0u8; PREFIX_SIZE];
+            stream.read_exact(&mut prefix).await.unwrap();
+            let mut data = vec![0u8; u16::from_ne_bytes(prefix) as usize];
+            stream.read_exact(&mut data).await.unwrap();
+
+            stream.write_all(&prefix).await.unwrap();
+            stream.write_all(&data).await.unwrap();
+            stream.flush().await.unwrap();
+            data
+        });
+
+        let tls = EndpointTls {
+            server_name: "localhost".to_string(),
+            ca_cert: Some(ca_cert),
+            cert: None,
+            key: None,
+        };
+        let endpoint = secure_endpoint(ContainerEndpoint::Tcp(addr), Some(&tls));
+        let mut endpoint = Endpoint::connect(endpoint).await.unwrap();
+
+        let payload = (0..=255u8).collect::<Vec<_>>();
+        let mut frame = payload.clone();
+        write_prefix(&mut frame);
+        endpoint.tx.send(Ok(frame)).await.unwrap();
+
+        let mut rx = endpoint.rx.take().unwrap();
+        let mut buf = RxBuffer::default();
+        let received = loop {
+            let chunk = rx.next().await.unwrap().unwrap();
+            if let Some(item) = buf.process(chunk).next() {
+                break item;
+            }
+        };
+
+        assert_eq!(server.await.unwrap(), payload);
+        assert_eq!(received, payload);
+    }
+
+    #[cfg(feature = "tls")]
+    #[actix_rt::test]
+    async fn tls_endpoint_rejects_untrusted_cert() {
+        let (acceptor, _) = tls_acceptor("localhost");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::task::spawn_local(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let _ = acceptor.accept(socket).await;
+        });
+
+        let tls = EndpointTls {
+            server_name: "localhost".to_string(),
+            ca_cert: None,
+            cert: None,
+            key: None,
+        };
+        let endpoint = secure_endpoint(ContainerEndpoint::Tcp(addr), Some(&tls));
+        assert!(Endpoint::connect(endpoint).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn in_flight_frames_are_bounded() {
+        let mut limit = InFlightLimit::new(4);
+        let mut max_in_flight = 0;
+
+        // Slow endpoint: frames are not delivered before the next ones arrive
+        for _ in 0..10 {
+            if let Some(permit) = limit.try_acquire("slow") {
+                tokio::task::spawn_local(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    drop(permit);
+                });
+            }
+            max_in_flight = max_in_flight.max(limit.in_flight("slow"));
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(max_in_flight, 4);
+        assert_eq!(limit.dropped(), 6);
+
+        // Other nodes are not affected
+        let permit = limit.try_acquire("fast").unwrap();
+        assert_eq!(limit.in_flight("fast"), 1);
+        drop(permit);
+        assert_eq!(limit.in_flight("fast"), 0);
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(limit.in_flight("slow"), 0);
+        assert!(limit.try_acquire("slow").is_some());
+        assert_eq!(limit.dropped(), 6);
+    }
 }
@@ -27,6 +27,7 @@ use net::socket::SocketDesc;
 use net::{EgressReceiver, IngressEvent, IngressReceiver};
 use net::{Error as NetError, Protocol};
 
+use ya_runtime_api::deploy::ContainerEndpoint;
 use ya_runtime_api::server::{CreateNetwork, NetworkInterface, RuntimeService};
 use ya_utils_networking::vpn::common::{ntoh, DEFAULT_MAX_FRAME_SIZE};
 use ya_utils_networking::vpn::stack as net;
@@ -40,6 +41,7 @@ use crate::manifest::UrlValidator;
 use crate::message::Shutdown;
 use crate::network;
 use crate::network::{Endpoint, RxBuffer};
+use crate::state::EndpointTls;
 use crate::{Error, Result};
 
 const IP4_ADDRESS: std::net::Ipv4Addr = std::net::Ipv4Addr::new(9, 0, 0x0d, 0x01);
@@ -62,6 +64,7 @@ type TransportKey = (
 pub(crate) async fn start_inet<R: RuntimeService>(
     service: &R,
     filter: Option<UrlValidator>,
+    tls: Option<&EndpointTls>,
 ) -> Result<Addr<Inet>> {
     use ya_runtime_api::server::Network;
 
@@ -99,7 +102,10 @@ pub(crate) async fn start_inet<R: RuntimeService>(
         .map_err(|e| Error::Other(format!("initialization error: {:?}", e)))?;
 
     let endpoint = match response.endpoint {
-        Some(endpoint) => Endpoint::connect(endpoint).await?,
+        Some(endpoint) => {
+            let endpoint = ContainerEndpoint::try_from(endpoint).map_err(Error::other)?;
+            Endpoint::connect(network::secure_endpoint(endpoint, tls)).await?
+        }
         None => return Err(Error::Other("endpoint already connected".into())),
     };
 
@@ -0,0 +1,95 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+
+use ya_runtime_api::deploy::TlsEndpoint;
+
+use crate::error::Error;
+use crate::Result;
+
+pub(super) async fn connect(endpoint: &TlsEndpoint) -> Result<TlsStream<TcpStream>> {
+    let server_name = ServerName::try_from(endpoint.server_name.as_str()).map_err(|e| {
+        Error::Other(format!(
+            "invalid TLS server name '{}': {}",
+            endpoint.server_name, e
+        ))
+    })?;
+    let connector = TlsConnector::from(Arc::new(client_config(endpoint)?));
+
+    let socket = TcpStream::connect(endpoint.addr).await?;
+    socket.set_nodelay(true)?;
+    connector.connect(server_name, socket).await.map_err(|e| {
+        Error::Other(format!(
+            "TLS handshake with {} failed: {}",
+            endpoint.addr, e
+        ))
+    })
+}
+
+fn client_config(endpoint: &TlsEndpoint) -> Result<ClientConfig> {
+    let tls_err = |e: tokio_rustls::rustls::Error| Error::Other(format!("TLS error: {}", e));
+
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        // invalid system certificates are not fatal
+        let _ = roots.add(&Certificate(cert.0));
+    }
+    if let Some(ca_cert) = endpoint.ca_cert.as_ref() {
+        for cert in read_certs(ca_cert)? {
+            roots.add(&cert).map_err(|e| {
+                Error::Other(format!(
+                    "invalid CA certificate {}: {}",
+                    ca_cert.display(),
+                    e
+                ))
+            })?;
+        }
+    }
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+
+    match (endpoint.cert.as_ref(), endpoint.key.as_ref()) {
+        (Some(cert), Some(key)) => builder
+            .with_single_cert(read_certs(cert)?, read_key(key)?)
+            .map_err(tls_err),
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(Error::Other(
+            "TLS client certificate and key must be provided together".into(),
+        )),
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey> {
+    use rustls_pemfile::Item;
+
+    let mut reader = BufReader::new(File::open(path)?);
+    loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(Item::RSAKey(key)) | Some(Item::PKCS8Key(key)) | Some(Item::ECKey(key)) => {
+                return Ok(PrivateKey(key))
+            }
+            Some(_) => continue,
+            None => {
+                return Err(Error::Other(format!(
+                    "no private key found in {}",
+                    path.display()
+                )))
+            }
+        }
+    }
+}
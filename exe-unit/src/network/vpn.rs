@@ -1,29 +1,35 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::net::IpAddr;
 use std::ops::Not;
 
 use actix::prelude::*;
-use futures::{future, FutureExt, SinkExt, TryFutureExt};
+use futures::{future, Future, FutureExt, SinkExt, TryFutureExt};
+use ipnet::IpNet;
 
 use ya_core_model::activity;
 use ya_core_model::activity::{RpcMessageError, VpnControl, VpnPacket};
+use ya_runtime_api::deploy::ContainerEndpoint;
 use ya_runtime_api::server::{CreateNetwork, NetworkInterface, RuntimeService};
 use ya_service_bus::typed::Endpoint as GsbEndpoint;
 use ya_service_bus::{actix_rpc, typed, RpcEnvelope};
+use ya_utils_networking::vpn::common::{hton, ntoh, to_ip};
 use ya_utils_networking::vpn::network::DuoEndpoint;
-use ya_utils_networking::vpn::{common::ntoh, Error as NetError, PeekPacket};
 use ya_utils_networking::vpn::{ArpField, ArpPacket, EtherFrame, EtherType, IpPacket, Networks};
+use ya_utils_networking::vpn::{Error as NetError, PeekPacket};
 
-use crate::acl::Acl;
+use crate::acl::{AccessRole, Acl, Error as AclError};
 use crate::error::Error;
-use crate::message::Shutdown;
+use crate::message::{SetVpnEnabled, Shutdown};
 use crate::network;
-use crate::network::{Endpoint, RxBuffer};
-use crate::state::Deployment;
+use crate::network::{Endpoint, InFlightLimit, IpDestination, RxBuffer};
+use crate::state::{Deployment, EndpointTls};
 
 pub(crate) async fn start_vpn<R: RuntimeService>(
     acl: Acl,
     service: &R,
     deployment: &Deployment,
+    tls: Option<&EndpointTls>,
 ) -> crate::Result<Option<Addr<Vpn>>> {
     if !deployment.networking() {
         return Ok(None);
@@ -46,27 +52,43 @@ pub(crate) async fn start_vpn<R: RuntimeService>(
         .await
         .map_err(|e| Error::Other(format!("[vpn] initialization error: {:?}", e)))?;
 
-    let endpoint = match response.endpoint {
-        Some(endpoint) => Endpoint::connect(endpoint).await?,
+    let container_endpoint = match response.endpoint {
+        Some(endpoint) => ContainerEndpoint::try_from(endpoint).map_err(Error::other)?,
         None => return Err(Error::Other("[vpn] endpoint already connected".into())),
     };
+    let container_endpoint = network::secure_endpoint(container_endpoint, tls);
+    let endpoint = Endpoint::connect(container_endpoint.clone()).await?;
 
-    let vpn = Vpn::try_new(acl, endpoint, deployment.clone())?;
+    let vpn = Vpn::try_new(acl, endpoint, container_endpoint, deployment.clone())?;
     Ok(Some(vpn.start()))
 }
 
 pub(crate) struct Vpn {
-    // TODO: Populate & use ACL
-    #[allow(unused)]
     acl: Acl,
     networks: Networks<DuoEndpoint<GsbEndpoint>>,
-    endpoint: Endpoint,
+    /// Gateway and interface addresses of the runtime, answering pings locally
+    local_addresses: HashSet<IpAddr>,
+    endpoint: Option<Endpoint>,
+    container_endpoint: ContainerEndpoint,
     rx_buf: Option<RxBuffer>,
+    rx_handle: Option<SpawnHandle>,
+    in_flight: InFlightLimit,
+    stats: HashMap<IpAddr, NodeStats>,
 }
 
 impl Vpn {
-    fn try_new(acl: Acl, endpoint: Endpoint, deployment: Deployment) -> crate::Result<Self> {
+    pub(crate) fn try_new(
+        acl: Acl,
+        endpoint: Endpoint,
+        container_endpoint: ContainerEndpoint,
+        deployment: Deployment,
+    ) -> crate::Result<Self> {
         let mut networks = Networks::default();
+        let local_addresses = deployment
+            .networks
+            .values()
+            .flat_map(|net| net.gateway().into_iter().chain(Some(net.node_ip)))
+            .collect();
 
         deployment
             .networks
@@ -84,35 +106,135 @@ impl Vpn {
         Ok(Self {
             acl,
             networks,
-            endpoint,
+            local_addresses,
+            endpoint: Some(endpoint),
+            container_endpoint,
             rx_buf: Some(Default::default()),
+            rx_handle: None,
+            in_flight: InFlightLimit::from_env(),
+            stats: Default::default(),
         })
     }
 
+    fn attach(&mut self, mut endpoint: Endpoint, ctx: &mut Context<Self>) -> crate::Result<()> {
+        let rx = endpoint
+            .rx
+            .take()
+            .ok_or_else(|| Error::Other("[vpn] local endpoint missing".into()))?;
+
+        self.rx_handle = Some(Self::add_stream(rx, ctx));
+        self.rx_buf = Some(Default::default());
+        self.endpoint = Some(endpoint);
+        Ok(())
+    }
+
+    fn detach(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.rx_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        // dropping the sender closes the runtime socket
+        self.endpoint = None;
+    }
+
+    fn bind_gsb(&self, ctx: &mut Context<Self>) {
+        self.networks.as_ref().keys().for_each(|net| {
+            let actor = ctx.address();
+            let net_id = net.clone();
+            let vpn_id = activity::exeunit::network_id(&net_id);
+
+            actix_rpc::bind::<VpnControl>(&vpn_id, ctx.address().recipient());
+            typed::bind_with_caller::<VpnPacket, _, _>(&vpn_id, move |caller, pkt| {
+                actor
+                    .send(Packet {
+                        network_id: net_id.clone(),
+                        caller,
+                        data: pkt.0,
+                    })
+                    .then(|sent| match sent {
+                        Ok(result) => future::ready(result),
+                        Err(err) => future::err(RpcMessageError::Service(err.to_string())),
+                    })
+            });
+        });
+    }
+
+    fn unbind_gsb(&self) -> impl Future<Output = ()> + 'static {
+        let networks = self.networks.as_ref().keys().cloned().collect::<Vec<_>>();
+        async move {
+            for net in networks {
+                let vpn_id = activity::exeunit::network_id(&net);
+                let _ = typed::unbind(&vpn_id).await;
+            }
+        }
+    }
+
     fn handle_ip(&mut self, frame: EtherFrame, ctx: &mut Context<Self>) {
         let ip_pkt = IpPacket::packet(frame.payload());
         log::trace!("[vpn] egress packet to {:?}", ip_pkt.dst_address());
 
-        if ip_pkt.is_broadcast() {
-            let futs = self
-                .networks
-                .endpoints()
-                .into_iter()
-                .map(|e| e.udp.call(VpnPacket(frame.as_ref().to_vec())))
-                .collect::<Vec<_>>();
-            futs.is_empty().not().then(|| {
-                let fut = future::join_all(futs).then(|_| future::ready(()));
-                ctx.spawn(fut.into_actor(self))
-            });
-        } else {
-            let ip = ip_pkt.dst_address();
-            match self.networks.endpoint(ip) {
-                Some(endpoint) => self.forward_frame(endpoint, frame, ctx),
-                None => log::debug!("[vpn] no endpoint for {ip:?}"),
+        let dst = match ntoh(ip_pkt.dst_address()) {
+            Some(ip) => ip,
+            None => return log::debug!("[vpn] invalid destination address"),
+        };
+
+        if self.local_addresses.contains(&dst) && self.networks.endpoint(hton(dst)).is_none() {
+            if let Some(reply) = network::icmp_echo_reply(frame.as_ref()) {
+                log::trace!("[vpn] replying to ICMP echo request to {dst}");
+                return self.ingress(reply, ctx);
+            }
+        }
+
+        let networks = self.networks.as_ref().values().map(|n| n.as_ref());
+
+        match IpDestination::classify(dst, networks) {
+            IpDestination::LimitedBroadcast | IpDestination::Multicast => {
+                let endpoints = self.networks.endpoints();
+                self.broadcast_frame(endpoints, frame, ctx);
+            }
+            IpDestination::SubnetBroadcast(net) => {
+                let endpoints = self
+                    .networks
+                    .as_ref()
+                    .values()
+                    .filter(|n| n.as_ref().trunc() == net)
+                    .flat_map(|n| n.endpoints().values().cloned())
+                    .collect();
+                self.broadcast_frame(endpoints, frame, ctx);
             }
+            IpDestination::SolicitedNode(suffix) => {
+                let endpoints = self
+                    .networks
+                    .as_ref()
+                    .values()
+                    .flat_map(|n| n.endpoints().iter())
+                    .filter(|(ip, _)| ip.len() == 16 && ip[13..] == suffix)
+                    .map(|(_, endpoint)| endpoint.clone())
+                    .collect();
+                self.broadcast_frame(endpoints, frame, ctx);
+            }
+            IpDestination::Unicast(ip) => match self.networks.endpoint(hton(ip)) {
+                Some(endpoint) => self.forward_frame(ip, endpoint, frame, ctx),
+                None => log::debug!("[vpn] no endpoint for {ip:?}"),
+            },
         }
     }
 
+    fn broadcast_frame(
+        &mut self,
+        endpoints: Vec<DuoEndpoint<GsbEndpoint>>,
+        frame: EtherFrame,
+        ctx: &mut Context<Self>,
+    ) {
+        let futs = endpoints
+            .into_iter()
+            .map(|e| e.udp.call(VpnPacket(frame.as_ref().to_vec())))
+            .collect::<Vec<_>>();
+        futs.is_empty().not().then(|| {
+            let fut = future::join_all(futs).then(|_| future::ready(()));
+            ctx.spawn(fut.into_actor(self))
+        });
+    }
+
     fn handle_arp(&mut self, frame: EtherFrame, ctx: &mut Context<Self>) {
         let arp = ArpPacket::packet(frame.payload());
         // forward only IP ARP packets
@@ -121,59 +243,77 @@ impl Vpn {
         }
 
         let ip = arp.get_field(ArpField::TPA);
-        match self.networks.endpoint(ip) {
-            Some(endpoint) => self.forward_frame(endpoint, frame, ctx),
-            None => log::debug!("[vpn] no endpoint for {ip:?}"),
+        match (ntoh(ip), self.networks.endpoint(ip)) {
+            (Some(ip), Some(endpoint)) => self.forward_frame(ip, endpoint, frame, ctx),
+            _ => log::debug!("[vpn] no endpoint for {ip:?}"),
         }
     }
 
     fn forward_frame(
         &mut self,
+        ip: IpAddr,
         endpoint: DuoEndpoint<GsbEndpoint>,
         frame: EtherFrame,
         ctx: &mut Context<Self>,
     ) {
+        let permit = match self.in_flight.try_acquire(endpoint.udp.addr()) {
+            Some(permit) => permit,
+            None => {
+                return log::debug!(
+                    "[vpn] too many frames in flight to {}, dropping frame (total dropped: {})",
+                    endpoint.udp.addr(),
+                    self.in_flight.dropped()
+                )
+            }
+        };
+
         let pkt: Vec<_> = frame.into();
         log::trace!("[vpn] egress {} b", pkt.len());
+        self.stats.entry(ip).or_default().egress(pkt.len());
 
         endpoint
             .udp
             .call(VpnPacket(pkt))
             .map_err(|err| log::debug!("[vpn] call error: {err}"))
-            .then(|_| future::ready(()))
+            .then(move |_| {
+                drop(permit);
+                future::ready(())
+            })
             .into_actor(self)
             .spawn(ctx);
     }
+
+    /// Sends a frame to the runtime
+    fn ingress(&mut self, mut data: Vec<u8>, ctx: &mut Context<Self>) {
+        let mut tx = match self.endpoint.as_ref() {
+            Some(endpoint) => endpoint.tx.clone(),
+            None => return log::debug!("[vpn] service disabled, dropping ingress packet"),
+        };
+
+        network::write_prefix(&mut data);
+
+        async move {
+            if let Err(e) = tx.send(Ok(data)).await {
+                log::debug!("[vpn] ingress error: {}", e);
+            }
+        }
+        .into_actor(self)
+        .spawn(ctx);
+    }
 }
 
 impl Actor for Vpn {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        self.networks.as_ref().keys().for_each(|net| {
-            let actor = ctx.address();
-            let net_id = net.clone();
-            let vpn_id = activity::exeunit::network_id(&net_id);
-
-            actix_rpc::bind::<VpnControl>(&vpn_id, ctx.address().recipient());
-            typed::bind_with_caller::<VpnPacket, _, _>(&vpn_id, move |caller, pkt| {
-                actor
-                    .send(Packet {
-                        network_id: net_id.clone(),
-                        caller,
-                        data: pkt.0,
-                    })
-                    .then(|sent| match sent {
-                        Ok(result) => future::ready(result),
-                        Err(err) => future::err(RpcMessageError::Service(err.to_string())),
-                    })
-            });
-        });
+        self.bind_gsb(ctx);
 
-        match self.endpoint.rx.take() {
-            Some(rx) => {
-                Self::add_stream(rx, ctx);
-                log::info!("[vpn] service started")
+        let endpoint = self.endpoint.take();
+        match endpoint.map(|endpoint| self.attach(endpoint, ctx)) {
+            Some(Ok(_)) => log::info!("[vpn] service started"),
+            Some(Err(err)) => {
+                log::error!("{}", err);
+                ctx.stop();
             }
             None => {
                 log::error!("[vpn] local endpoint missing");
@@ -185,15 +325,7 @@ impl Actor for Vpn {
     fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
         log::info!("[vpn] stopping service");
 
-        let networks = self.networks.as_ref().keys().cloned().collect::<Vec<_>>();
-        async move {
-            for net in networks {
-                let vpn_id = activity::exeunit::network_id(&net);
-                let _ = typed::unbind(&vpn_id).await;
-            }
-        }
-        .into_actor(self)
-        .wait(ctx);
+        self.unbind_gsb().into_actor(self).wait(ctx);
 
         Running::Stop
     }
@@ -243,6 +375,21 @@ impl Handler<Packet> for Vpn {
         let node_id = packet.caller;
         let data = packet.data.into_boxed_slice();
 
+        // Only nodes of the network and the requestor may send packets
+        let is_member = self
+            .networks
+            .as_ref()
+            .get(&network_id)
+            .map(|network| network.nodes().contains_key(&node_id))
+            .unwrap_or(false);
+        if !is_member && !self.acl.has_access(&node_id, AccessRole::Control) {
+            log::debug!("[vpn] dropping packet from {node_id}: not a member of {network_id}");
+            return Err(RpcMessageError::Forbidden(format!(
+                "{} is not a member of network {}",
+                node_id, network_id
+            )));
+        }
+
         // fixme: should requestor be queried for unknown IP addresses instead?
         // read and add unknown node id -> ip if it doesn't exist
         if let Ok(ether_type) = EtherFrame::peek_type(&data) {
@@ -266,21 +413,11 @@ impl Handler<Packet> for Vpn {
                         let _ = network.add_node(ip, &node_id, network::gsb_endpoint);
                     }
                 });
+                self.stats.entry(ip).or_default().ingress(data.len());
             }
         }
 
-        let mut data = data.into();
-        network::write_prefix(&mut data);
-
-        let mut tx = self.endpoint.tx.clone();
-        async move {
-            if let Err(e) = tx.send(Ok(data)).await {
-                log::debug!("[vpn] ingress error: {}", e);
-            }
-        }
-        .into_actor(self)
-        .spawn(ctx);
-
+        self.ingress(data.into(), ctx);
         Ok(())
     }
 }
@@ -289,17 +426,29 @@ impl Handler<RpcEnvelope<VpnControl>> for Vpn {
     type Result = <RpcEnvelope<VpnControl> as Message>::Result;
 
     fn handle(&mut self, msg: RpcEnvelope<VpnControl>, _: &mut Context<Self>) -> Self::Result {
-        // if !self.acl.has_access(msg.caller(), AccessRole::Control) {
-        //     return Err(AclError::Forbidden(msg.caller().to_string(), AccessRole::Control).into());
-        // }
+        if !self.acl.has_access(msg.caller(), AccessRole::Control) {
+            let caller = msg.caller().to_string();
+            return Err(Error::from(AclError::Forbidden(caller, AccessRole::Control)).into());
+        }
 
         match msg.into_inner() {
             VpnControl::AddNodes { network_id, nodes } => {
                 let network = self.networks.get_mut(&network_id).map_err(Error::from)?;
-                for (ip, id) in Deployment::map_nodes(nodes).map_err(Error::from)? {
-                    network
-                        .add_node(ip, &id, network::gsb_endpoint)
-                        .map_err(Error::from)?;
+                // Add all valid nodes, report the ones that failed
+                let errors = nodes
+                    .into_iter()
+                    .filter_map(|(ip, id)| {
+                        to_ip(ip.as_ref())
+                            .and_then(|addr| network.add_node(addr, &id, network::gsb_endpoint))
+                            .err()
+                            .map(|e| format!("{} ({}): {}", id, ip, e))
+                    })
+                    .collect::<Vec<_>>();
+
+                if !errors.is_empty() {
+                    let msg = format!("unable to add nodes: {}", errors.join(", "));
+                    log::warn!("[vpn] {}", msg);
+                    return Err(Error::Other(msg).into());
                 }
             }
             VpnControl::RemoveNodes {
@@ -314,6 +463,81 @@ impl Handler<RpcEnvelope<VpnControl>> for Vpn {
     }
 }
 
+impl Handler<SetVpnEnabled> for Vpn {
+    type Result = AtomicResponse<Self, crate::Result<()>>;
+
+    fn handle(&mut self, msg: SetVpnEnabled, ctx: &mut Context<Self>) -> Self::Result {
+        match msg.0 {
+            false => {
+                if self.endpoint.is_none() {
+                    return AtomicResponse::new(Box::pin(actix::fut::ready(Ok(()))));
+                }
+
+                log::info!("[vpn] disabling service");
+                self.detach(ctx);
+
+                let fut = self.unbind_gsb().map(|_| Ok(())).into_actor(self);
+                AtomicResponse::new(Box::pin(fut))
+            }
+            true => {
+                if self.endpoint.is_some() {
+                    return AtomicResponse::new(Box::pin(actix::fut::ready(Ok(()))));
+                }
+
+                log::info!("[vpn] enabling service");
+                let fut = Endpoint::connect(self.container_endpoint.clone())
+                    .into_actor(self)
+                    .map(|result, act, ctx| {
+                        act.attach(result?, ctx)?;
+                        act.bind_gsb(ctx);
+                        Ok(())
+                    });
+                AtomicResponse::new(Box::pin(fut))
+            }
+        }
+    }
+}
+
+impl Handler<GetVpnStats> for Vpn {
+    type Result = MessageResult<GetVpnStats>;
+
+    fn handle(&mut self, _: GetVpnStats, _: &mut Context<Self>) -> Self::Result {
+        let mut stats = HashMap::<String, NodeStats>::new();
+        for network in self.networks.as_ref().values() {
+            for (node_id, ips) in network.nodes() {
+                let node_stats = stats.entry(node_id.clone()).or_default();
+                ips.iter()
+                    .filter_map(|ip| self.stats.get(ip))
+                    .for_each(|s| node_stats.add(s));
+            }
+        }
+        MessageResult(stats)
+    }
+}
+
+impl Handler<GetNetworks> for Vpn {
+    type Result = MessageResult<GetNetworks>;
+
+    fn handle(&mut self, _: GetNetworks, _: &mut Context<Self>) -> Self::Result {
+        let mut networks = self
+            .networks
+            .as_ref()
+            .iter()
+            .map(|(id, network)| VpnNetwork {
+                id: id.clone(),
+                network: *network.as_ref(),
+                nodes: network
+                    .nodes()
+                    .iter()
+                    .flat_map(|(node_id, ips)| ips.iter().map(move |ip| (*ip, node_id.clone())))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        networks.sort_by(|a, b| a.id.cmp(&b.id));
+        MessageResult(networks)
+    }
+}
+
 impl Handler<Shutdown> for Vpn {
     type Result = <Shutdown as Message>::Result;
 
@@ -324,6 +548,53 @@ impl Handler<Shutdown> for Vpn {
     }
 }
 
+/// Current VPN topology, ordered by network id
+#[derive(Clone, Copy, Debug, Message)]
+#[rtype(result = "Vec<VpnNetwork>")]
+pub(crate) struct GetNetworks;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct VpnNetwork {
+    pub id: String,
+    pub network: IpNet,
+    /// Node ids by IP address
+    pub nodes: HashMap<IpAddr, String>,
+}
+
+/// Traffic exchanged with VPN nodes, by node id
+#[derive(Clone, Copy, Debug, Message)]
+#[rtype(result = "HashMap<String, NodeStats>")]
+pub(crate) struct GetVpnStats;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct NodeStats {
+    /// Received from the node
+    pub ingress_bytes: u64,
+    pub ingress_packets: u64,
+    /// Sent to the node
+    pub egress_bytes: u64,
+    pub egress_packets: u64,
+}
+
+impl NodeStats {
+    fn ingress(&mut self, bytes: usize) {
+        self.ingress_bytes += bytes as u64;
+        self.ingress_packets += 1;
+    }
+
+    fn egress(&mut self, bytes: usize) {
+        self.egress_bytes += bytes as u64;
+        self.egress_packets += 1;
+    }
+
+    fn add(&mut self, other: &NodeStats) {
+        self.ingress_bytes += other.ingress_bytes;
+        self.ingress_packets += other.ingress_packets;
+        self.egress_bytes += other.egress_bytes;
+        self.egress_packets += other.egress_packets;
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "<RpcEnvelope<VpnPacket> as Message>::Result")]
 pub(crate) struct Packet {
@@ -331,3 +602,229 @@ pub(crate) struct Packet {
     pub caller: String,
     pub data: Vec<u8>,
 }
+
+#[cfg(all(test, unix))]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+
+    use super::*;
+    use crate::state::DeploymentNetwork;
+
+    const CALLER: &str = "0x0000000000000000000000000000000000000000";
+
+    fn packet(data: Vec<u8>) -> Packet {
+        Packet {
+            network_id: "net".to_string(),
+            caller: CALLER.to_string(),
+            data,
+        }
+    }
+
+    fn requestor_acl() -> Acl {
+        let acl = Acl::default();
+        acl.grant(CALLER.to_string(), AccessRole::Control);
+        acl
+    }
+
+    fn deployment() -> Deployment {
+        let mut deployment = Deployment::default();
+        deployment.networks.insert(
+            "net".to_string(),
+            DeploymentNetwork {
+                network: "10.0.0.0/24".parse().unwrap(),
+                node_ip: "10.0.0.1".parse().unwrap(),
+                nodes: vec![("10.0.0.2".parse().unwrap(), CALLER.to_string())]
+                    .into_iter()
+                    .collect(),
+            },
+        );
+        deployment
+    }
+
+    async fn read_frame(socket: &mut UnixStream) -> Vec<u8> {
+        let mut prefix = [0u8; 2];
+        socket.read_exact(&mut prefix).await.unwrap();
+        let mut data = vec![0u8; u16::from_ne_bytes(prefix) as usize];
+        socket.read_exact(&mut data).await.unwrap();
+        data
+    }
+
+    #[actix_rt::test]
+    async fn disable_and_enable() {
+        let dir = tempdir::TempDir::new("vpn").unwrap();
+        let path = dir.path().join("vpn.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let container_endpoint = ContainerEndpoint::Socket(path);
+
+        let endpoint = Endpoint::connect(container_endpoint.clone()).await.unwrap();
+        let (mut socket, _) = listener.accept().await.unwrap();
+
+        let vpn = Vpn::try_new(
+            requestor_acl(),
+            endpoint,
+            container_endpoint,
+            Deployment::default(),
+        )
+        .unwrap()
+        .start();
+
+        vpn.send(packet(vec![1, 2, 3])).await.unwrap().unwrap();
+        assert_eq!(read_frame(&mut socket).await, vec![1, 2, 3]);
+
+        vpn.send(SetVpnEnabled(false)).await.unwrap().unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(socket.read(&mut buf).await.unwrap(), 0);
+        vpn.send(packet(vec![4, 5, 6])).await.unwrap().unwrap();
+
+        vpn.send(SetVpnEnabled(true)).await.unwrap().unwrap();
+        let (mut socket, _) = listener.accept().await.unwrap();
+
+        vpn.send(packet(vec![7, 8, 9])).await.unwrap().unwrap();
+        assert_eq!(read_frame(&mut socket).await, vec![7, 8, 9]);
+    }
+
+    #[actix_rt::test]
+    async fn ingress_traffic_is_counted() {
+        let dir = tempdir::TempDir::new("vpn").unwrap();
+        let path = dir.path().join("vpn.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let container_endpoint = ContainerEndpoint::Socket(path);
+        let endpoint = Endpoint::connect(container_endpoint.clone()).await.unwrap();
+        let (mut socket, _) = listener.accept().await.unwrap();
+
+        let vpn = Vpn::try_new(Acl::default(), endpoint, container_endpoint, deployment())
+            .unwrap()
+            .start();
+
+        let mut frame = vec![0xff; 6]; // destination mac
+        frame.extend_from_slice(&[0x02; 6]); // source mac
+        frame.extend_from_slice(&[0x08, 0x00]); // ipv4
+        frame.extend_from_slice(&[
+            0x45, 0x00, 0x00, 0x14, // version, ihl, dscp, total length
+            0x00, 0x00, 0x00, 0x00, // identification, flags, fragment offset
+            0x40, 0x11, 0x00, 0x00, // ttl, protocol (udp), checksum
+            10, 0, 0, 2, // source
+            10, 0, 0, 1, // destination
+        ]);
+
+        for _ in 0..2 {
+            vpn.send(packet(frame.clone())).await.unwrap().unwrap();
+            assert_eq!(read_frame(&mut socket).await, frame);
+        }
+
+        let stats = vpn.send(GetVpnStats).await.unwrap();
+        assert_eq!(
+            stats.get(CALLER),
+            Some(&NodeStats {
+                ingress_bytes: 2 * frame.len() as u64,
+                ingress_packets: 2,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn gateway_answers_ping() {
+        let dir = tempdir::TempDir::new("vpn").unwrap();
+        let path = dir.path().join("vpn.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let container_endpoint = ContainerEndpoint::Socket(path);
+        let endpoint = Endpoint::connect(container_endpoint.clone()).await.unwrap();
+        let (mut socket, _) = listener.accept().await.unwrap();
+
+        let _vpn = Vpn::try_new(Acl::default(), endpoint, container_endpoint, deployment())
+            .unwrap()
+            .start();
+
+        let mut request = vec![0xff; 6]; // destination mac
+        request.extend_from_slice(&[0x02; 6]); // source mac
+        request.extend_from_slice(&[0x08, 0x00]); // ipv4
+        request.extend_from_slice(&[
+            0x45, 0x00, 0x00, 0x1c, // version, ihl, dscp, total length
+            0x00, 0x00, 0x00, 0x00, // identification, flags, fragment offset
+            0x40, 0x01, 0x00, 0x00, // ttl, protocol (icmp), checksum
+            10, 0, 0, 3, // source
+            10, 0, 0, 1, // destination (gateway)
+            0x08, 0x00, 0xf7, 0xfe, // echo request, code, checksum
+            0x00, 0x01, 0x00, 0x00, // identifier, sequence number
+        ]);
+        let mut prefixed = request.clone();
+        network::write_prefix(&mut prefixed);
+        socket.write_all(&prefixed).await.unwrap();
+
+        let reply = read_frame(&mut socket).await;
+        assert_eq!(reply, network::icmp_echo_reply(&request).unwrap());
+        assert_eq!(&reply[26..30], &[10, 0, 0, 1]);
+        assert_eq!(reply[34], 0);
+    }
+
+    #[actix_rt::test]
+    async fn get_networks() {
+        let dir = tempdir::TempDir::new("vpn").unwrap();
+        let path = dir.path().join("vpn.sock");
+        let _listener = UnixListener::bind(&path).unwrap();
+        let container_endpoint = ContainerEndpoint::Socket(path);
+        let endpoint = Endpoint::connect(container_endpoint.clone()).await.unwrap();
+
+        let vpn = Vpn::try_new(Acl::default(), endpoint, container_endpoint, deployment())
+            .unwrap()
+            .start();
+
+        let networks = vpn.send(GetNetworks).await.unwrap();
+        assert_eq!(
+            networks,
+            vec![VpnNetwork {
+                id: "net".to_string(),
+                network: "10.0.0.0/24".parse().unwrap(),
+                nodes: vec![("10.0.0.2".parse().unwrap(), CALLER.to_string())]
+                    .into_iter()
+                    .collect(),
+            }]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn unauthorized_callers_are_rejected() {
+        let dir = tempdir::TempDir::new("vpn").unwrap();
+        let path = dir.path().join("vpn.sock");
+        let _listener = UnixListener::bind(&path).unwrap();
+        let container_endpoint = ContainerEndpoint::Socket(path);
+        let endpoint = Endpoint::connect(container_endpoint.clone()).await.unwrap();
+
+        let acl = Acl::default();
+        let vpn = Vpn::try_new(
+            acl.clone(),
+            endpoint,
+            container_endpoint,
+            Deployment::default(),
+        )
+        .unwrap()
+        .start();
+
+        let control = || {
+            RpcEnvelope::with_caller(
+                CALLER,
+                VpnControl::RemoveNodes {
+                    network_id: "net".to_string(),
+                    node_ids: Default::default(),
+                },
+            )
+        };
+        match vpn.send(control()).await.unwrap() {
+            Err(RpcMessageError::Forbidden(_)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match vpn.send(packet(vec![1, 2, 3])).await.unwrap() {
+            Err(RpcMessageError::Forbidden(_)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // requestor passes ACL checks; the network is unknown though
+        acl.grant(CALLER.to_string(), AccessRole::Control);
+        match vpn.send(control()).await.unwrap() {
+            Err(RpcMessageError::Service(_)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -10,6 +10,7 @@ pub trait Runtime:
     + Handler<Shutdown>
     + Handler<ExecuteCommand>
     + Handler<UpdateDeployment>
+    + Handler<SetVpnEnabled>
 {
 }
 
@@ -20,7 +20,8 @@ use crate::acl::Acl;
 use crate::error::Error;
 use crate::manifest::UrlValidator;
 use crate::message::{
-    CommandContext, ExecuteCommand, RuntimeEvent, Shutdown, ShutdownReason, UpdateDeployment,
+    CommandContext, ExecuteCommand, RuntimeEvent, SetVpnEnabled, Shutdown, ShutdownReason,
+    UpdateDeployment,
 };
 use crate::network::inet::start_inet;
 use crate::network::inet::Inet;
@@ -29,7 +30,7 @@ use crate::output::{forward_output, vec_to_string};
 use crate::process::{kill, ProcessTree, SystemError};
 use crate::runtime::event::EventMonitor;
 use crate::runtime::{Runtime, RuntimeMode};
-use crate::state::Deployment;
+use crate::state::{Deployment, EndpointTls};
 use crate::ExeUnitContext;
 
 const PROCESS_KILL_TIMEOUT_SECONDS_ENV_VAR: &str = "PROCESS_KILL_TIMEOUT_SECONDS";
@@ -278,12 +279,20 @@ impl RuntimeProcess {
             let service_ = service.clone();
             let net = async {
                 if proc_ctx.feature_inet {
-                    let inet = start_inet(&service_, proc_ctx.feature_inet_filter).await?;
+                    let inet = start_inet(
+                        &service_,
+                        proc_ctx.feature_inet_filter,
+                        proc_ctx.endpoint_tls.as_ref(),
+                    )
+                    .await?;
                     address.send(SetInetService(inet)).await?;
                 }
 
                 if proc_ctx.feature_vpn {
-                    if let Some(vpn) = start_vpn(acl, &service_, &deployment).await? {
+                    if let Some(vpn) =
+                        start_vpn(acl, &service_, &deployment, proc_ctx.endpoint_tls.as_ref())
+                            .await?
+                    {
                         address.send(SetVpnService(vpn)).await?;
                     }
                 }
@@ -400,6 +409,18 @@ impl Handler<UpdateDeployment> for RuntimeProcess {
     }
 }
 
+impl Handler<SetVpnEnabled> for RuntimeProcess {
+    type Result = ResponseFuture<<SetVpnEnabled as Message>::Result>;
+
+    fn handle(&mut self, msg: SetVpnEnabled, _: &mut Self::Context) -> Self::Result {
+        let vpn = match self.vpn.clone() {
+            Some(vpn) => vpn,
+            None => return future::err(Error::Other("VPN is not running".into())).boxed_local(),
+        };
+        async move { vpn.send(msg).await? }.boxed_local()
+    }
+}
+
 impl Handler<SetProcessService> for RuntimeProcess {
     type Result = <SetProcessService as Message>::Result;
 
@@ -485,6 +506,7 @@ struct RuntimeProcessContext {
     feature_vpn: bool,
     feature_inet: bool,
     feature_inet_filter: Option<UrlValidator>,
+    endpoint_tls: Option<EndpointTls>,
 }
 
 impl<'a> From<&'a ExeUnitContext> for RuntimeProcessContext {
@@ -499,6 +521,7 @@ impl<'a> From<&'a ExeUnitContext> for RuntimeProcessContext {
             feature_vpn: manifest.features().contains(&Feature::Vpn),
             feature_inet: manifest.features().contains(&Feature::Inet),
             feature_inet_filter: manifest.validator::<UrlValidator>(),
+            endpoint_tls: ctx.supervise.endpoint_tls.clone(),
         }
     }
 }
@@ -651,3 +674,67 @@ struct AddChildProcess(ChildProcess);
 #[derive(Message)]
 #[rtype("()")]
 struct RemoveChildProcess(ChildProcess);
+
+#[cfg(all(test, unix))]
+mod test {
+    use tokio::io::AsyncReadExt;
+    use tokio::net::UnixListener;
+    use ya_runtime_api::deploy::ContainerEndpoint;
+
+    use super::*;
+    use crate::network::Endpoint;
+
+    fn runtime() -> RuntimeProcess {
+        RuntimeProcess {
+            ctx: RuntimeProcessContext {
+                work_dir: Default::default(),
+                runtime_args: Default::default(),
+                supervise_image: false,
+                supervise_hardware: false,
+                infrastructure: Default::default(),
+                feature_vpn: true,
+                feature_inet: false,
+                feature_inet_filter: None,
+                endpoint_tls: None,
+            },
+            binary: Default::default(),
+            deployment: Default::default(),
+            children: Default::default(),
+            service: None,
+            monitor: None,
+            acl: Default::default(),
+            vpn: None,
+            inet: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn set_vpn_enabled_toggles_vpn() {
+        let dir = tempdir::TempDir::new("runtime").unwrap();
+        let path = dir.path().join("vpn.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let container_endpoint = ContainerEndpoint::Socket(path);
+
+        let runtime = runtime().start();
+        assert!(runtime.send(SetVpnEnabled(false)).await.unwrap().is_err());
+
+        let endpoint = Endpoint::connect(container_endpoint.clone()).await.unwrap();
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let vpn = Vpn::try_new(
+            Default::default(),
+            endpoint,
+            container_endpoint,
+            Default::default(),
+        )
+        .unwrap()
+        .start();
+        runtime.send(SetVpnService(vpn)).await.unwrap();
+
+        runtime.send(SetVpnEnabled(false)).await.unwrap().unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(socket.read(&mut buf).await.unwrap(), 0);
+
+        runtime.send(SetVpnEnabled(true)).await.unwrap().unwrap();
+        listener.accept().await.unwrap();
+    }
+}
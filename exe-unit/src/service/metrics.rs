@@ -5,6 +5,7 @@ use crate::message::{GetMetrics, SetMetric, Shutdown};
 use crate::metrics::error::MetricError;
 use crate::metrics::{
     CpuMetric, MemMetric, Metric, MetricData, MetricReport, StorageMetric, TimeMetric,
+    TransferMetric,
 };
 use crate::ExeUnitContext;
 use actix::prelude::*;
@@ -25,6 +26,8 @@ impl MetricsService {
         supervise_caps: bool,
     ) -> Result<Self, MetricError> {
         let caps = move |ctx: &ExeUnitContext, id: &str| match supervise_caps {
+            // Memory allocation is enforced by the OS instead
+            true if id == MemMetric::ID && ctx.enforcement.is_some() => None,
             true => ctx.agreement.usage_limits.get(id).cloned(),
             _ => None,
         };
@@ -117,6 +120,17 @@ impl MetricsService {
                 TimeMetric::ID.to_string(),
                 MetricProvider::new(TimeMetric::default(), Some(1), caps(ctx, TimeMetric::ID)),
             ),
+            (
+                TransferMetric::ID.to_string(),
+                MetricProvider::new(
+                    TransferMetric::new(ya_transfer::TransferAccounting::new(
+                        &ctx.agreement.inner.agreement_id,
+                        ctx.transfer_usage.clone(),
+                    )),
+                    backlog_limit,
+                    caps(ctx, TransferMetric::ID),
+                ),
+            ),
         ]
         .into_iter()
         .collect()
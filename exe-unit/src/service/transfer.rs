@@ -142,6 +142,15 @@ impl TransferProvider<TransferData, TransferError> for ContainerTransferProvider
         }
         self.file_tp.destination(&file_url, ctx)
     }
+
+    fn cleanup_destination(&self, url: &Url, ctx: &TransferContext) {
+        if ctx.args.format.is_some() {
+            return;
+        }
+        if let Ok(file_url) = self.resolve_url(url.path_decoded().as_str()) {
+            self.file_tp.cleanup_destination(&file_url, ctx);
+        }
+    }
 }
 
 /// Handles resources transfers.
@@ -150,6 +159,8 @@ pub struct TransferService {
     cache: Cache,
     work_dir: PathBuf,
     task_package: Option<String>,
+    disk_quota: Option<DiskQuota>,
+    accounting: TransferAccounting,
     abort_handles: Rc<RefCell<HashSet<Abort>>>,
 }
 
@@ -160,6 +171,11 @@ impl TransferService {
             cache: Cache::new(ctx.cache_dir.clone()),
             work_dir: ctx.work_dir.clone(),
             task_package: ctx.agreement.task_package.clone(),
+            disk_quota: ctx.supervise.disk_quota.map(DiskQuota::new),
+            accounting: TransferAccounting::new(
+                &ctx.agreement.inner.agreement_id,
+                ctx.transfer_usage.clone(),
+            ),
             abort_handles: Default::default(),
         }
     }
@@ -258,6 +274,7 @@ impl Handler<DeployImage> for TransferService {
             };
 
             let handles = self.abort_handles.clone();
+            let accounting = self.accounting.clone();
             let fut = async move {
                 if path.exists() {
                     log::info!("Deploying cached image: {:?}", path);
@@ -266,7 +283,7 @@ impl Handler<DeployImage> for TransferService {
 
                 let (abort, reg) = Abort::new_pair();
                 {
-                    let ctx = Default::default();
+                    let ctx = TransferContext::default().with_accounting(accounting);
                     let retry = transfer_with(src, &src_url, dst, &dst_url, &ctx);
 
                     let _guard = AbortHandleGuard::register(handles, abort);
@@ -321,10 +338,15 @@ impl Handler<TransferResource> for TransferService {
         let (abort, reg) = Abort::new_pair();
 
         let handles = self.abort_handles.clone();
+        let disk_quota = self.disk_quota.clone();
+        let accounting = self.accounting.clone();
         let fut = async move {
             log::info!("Transferring {:?} to {:?}", src_url.url, dst_url.url);
             {
-                let ctx = TransferContext::from(msg.args);
+                let mut ctx = TransferContext::from(msg.args).with_accounting(accounting);
+                if let Some(quota) = disk_quota {
+                    ctx = ctx.with_quota(quota);
+                }
                 let retry = transfer_with(src, &src_url, dst, &dst_url, &ctx);
 
                 let _guard = AbortHandleGuard::register(handles, abort);
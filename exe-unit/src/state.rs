@@ -18,6 +18,7 @@ use ya_core_model::activity::Exec;
 use ya_utils_networking::vpn::common::{to_ip, to_net};
 use ya_utils_networking::vpn::Error as NetError;
 
+use crate::enforcement::LimitAction;
 use crate::error::Error;
 use crate::manifest::ManifestContext;
 use crate::notify::Notify;
@@ -58,6 +59,23 @@ pub struct Supervision {
     pub hardware: bool,
     pub image: bool,
     pub manifest: ManifestContext,
+    pub limit_action: LimitAction,
+    /// Limit of bytes written by transfers of the activity
+    pub disk_quota: Option<u64>,
+    /// TLS applied to TCP network endpoints exposed by the runtime
+    pub endpoint_tls: Option<EndpointTls>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EndpointTls {
+    /// Name verified against the certificate presented by the runtime
+    pub server_name: String,
+    /// PEM file with CA certificates trusted in addition to the system ones
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file with the client certificate chain
+    pub cert: Option<PathBuf>,
+    /// PEM file with the client private key
+    pub key: Option<PathBuf>,
 }
 
 pub(crate) struct ExeUnitState {
@@ -403,6 +421,14 @@ pub(crate) struct DeploymentNetwork {
     pub nodes: HashMap<IpAddr, String>,
 }
 
+impl DeploymentNetwork {
+    /// Gateway address announced to the runtime: the first host of the network
+    pub fn gateway(&self) -> Option<IpAddr> {
+        let ip = self.network.addr();
+        self.network.hosts().find(|ip_| ip_ != &ip)
+    }
+}
+
 impl Deployment {
     pub fn networking(&self) -> bool {
         !self.networks.is_empty()
@@ -33,7 +33,8 @@ impl OfferDefinition {
 #[derive(Clone)]
 pub struct NodeInfo {
     pub name: Option<String>,
-    pub subnet: Option<String>,
+    /// Subnets the node participates in. Empty if none.
+    pub subnet: Vec<String>,
     pub geo_country_code: Option<String>,
 }
 
@@ -42,12 +43,12 @@ impl NodeInfo {
         NodeInfo {
             name: Some(name.into()),
             geo_country_code: None,
-            subnet: None,
+            subnet: Vec::new(),
         }
     }
 
     pub fn with_subnet(&mut self, subnet: String) -> &mut Self {
-        self.subnet = Some(subnet);
+        self.subnet.push(subnet);
         self
     }
 
@@ -59,7 +60,13 @@ impl NodeInfo {
         if let Some(cc) = self.geo_country_code {
             let _ = node.insert("geo".into(), serde_json::json!({ "country_code": cc }));
         }
-        if let Some(subnet) = self.subnet {
+        // Single subnet is kept as a plain string for compatibility with older nodes.
+        let subnet = match self.subnet.len() {
+            0 => None,
+            1 => self.subnet.into_iter().next().map(Value::String),
+            _ => Some(serde_json::json!(self.subnet)),
+        };
+        if let Some(subnet) = subnet {
             let _ = node.insert("debug".into(), serde_json::json!({ "subnet": subnet }));
         }
         map.insert("node".into(), node.into());
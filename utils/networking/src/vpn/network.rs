@@ -120,6 +120,8 @@ impl<E> Network<E> {
         Ok(())
     }
 
+    /// Assigns `ip_addr` to node `id`. Adding an already present address
+    /// of the same node is a no-op, while assigning it to a different node fails.
     pub fn add_node<F>(&mut self, ip_addr: IpAddr, id: &str, endpoint_fn: F) -> Result<(), Error>
     where
         F: Fn(&str, &str) -> E,
@@ -132,7 +134,10 @@ impl<E> Network<E> {
         let ip: Box<[u8]> = hton(ip_addr).into();
 
         if self.endpoints.contains_key(&ip) {
-            return Err(Error::IpAddrTaken(ip_addr));
+            return match self.nodes.get(&node_id) {
+                Some(addrs) if addrs.contains(&ip_addr) => Ok(()),
+                _ => Err(Error::IpAddrTaken(ip_addr)),
+            };
         }
 
         self.endpoints.insert(ip, endpoint_fn(&node_id, &self.id));
@@ -164,3 +169,47 @@ impl<E> AsRef<IpNet> for Network<E> {
         &self.network
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE_A: &str = "0x0000000000000000000000000000000000000001";
+    const NODE_B: &str = "0x0000000000000000000000000000000000000002";
+
+    fn network() -> Network<String> {
+        Network::new("net", "10.0.0.0/24".parse().unwrap())
+    }
+
+    fn endpoint(node_id: &str, _: &str) -> String {
+        node_id.to_string()
+    }
+
+    #[test]
+    fn add_same_node_twice() {
+        let mut network = network();
+        let ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        network.add_node(ip, NODE_A, endpoint).unwrap();
+        network.add_node(ip, NODE_A, endpoint).unwrap();
+
+        assert_eq!(network.endpoints().len(), 1);
+        assert_eq!(network.nodes()[NODE_A].len(), 1);
+        assert_eq!(network.endpoint(hton(ip)), Some(NODE_A.to_string()));
+    }
+
+    #[test]
+    fn add_conflicting_node() {
+        let mut network = network();
+        let ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        network.add_node(ip, NODE_A, endpoint).unwrap();
+        match network.add_node(ip, NODE_B, endpoint) {
+            Err(Error::IpAddrTaken(addr)) => assert_eq!(addr, ip),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        assert!(network.nodes().get(NODE_B).is_none());
+        assert_eq!(network.endpoint(hton(ip)), Some(NODE_A.to_string()));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Aggregated amount of data moved within a single transfer context
+/// (e.g. an activity or an agreement).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BandwidthUsage {
+    /// Total number of bytes read from transfer sources
+    pub bytes: u64,
+    /// Number of successfully finished transfers
+    pub transfers: u64,
+    /// Time of the last update
+    pub updated: Instant,
+}
+
+impl Default for BandwidthUsage {
+    fn default() -> Self {
+        BandwidthUsage {
+            bytes: 0,
+            transfers: 0,
+            updated: Instant::now(),
+        }
+    }
+}
+
+/// Registry of bytes transferred per context id.
+///
+/// Entries are accumulated until explicitly removed:
+/// - `take` returns and resets usage of a single context (e.g. when it was billed),
+/// - `take_all` returns and resets usage of all contexts,
+/// - `prune` drops contexts not updated within the given retention period.
+#[derive(Clone, Debug, Default)]
+pub struct BandwidthRegistry {
+    inner: Arc<Mutex<HashMap<String, BandwidthUsage>>>,
+}
+
+impl BandwidthRegistry {
+    pub fn record(&self, context_id: &str, bytes: u64) {
+        self.update(context_id, |usage| usage.bytes += bytes);
+    }
+
+    pub fn record_finished(&self, context_id: &str) {
+        self.update(context_id, |usage| usage.transfers += 1);
+    }
+
+    pub fn usage(&self, context_id: &str) -> Option<BandwidthUsage> {
+        self.inner.lock().unwrap().get(context_id).cloned()
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, BandwidthUsage> {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub fn take(&self, context_id: &str) -> Option<BandwidthUsage> {
+        self.inner.lock().unwrap().remove(context_id)
+    }
+
+    pub fn take_all(&self) -> HashMap<String, BandwidthUsage> {
+        std::mem::take(&mut *self.inner.lock().unwrap())
+    }
+
+    /// Removes contexts idle for longer than `retention`. Returns the number of removed entries.
+    pub fn prune(&self, retention: Duration) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let len = inner.len();
+        inner.retain(|_, usage| usage.updated.elapsed() <= retention);
+        len - inner.len()
+    }
+
+    fn update(&self, context_id: &str, f: impl FnOnce(&mut BandwidthUsage)) {
+        let mut inner = self.inner.lock().unwrap();
+        let usage = inner.entry(context_id.to_string()).or_default();
+        f(usage);
+        usage.updated = Instant::now();
+    }
+}
+
+/// Tags transfers within `TransferContext` with a context id.
+#[derive(Clone)]
+pub struct TransferAccounting {
+    pub context_id: String,
+    pub registry: BandwidthRegistry,
+}
+
+impl TransferAccounting {
+    pub fn new(context_id: impl ToString, registry: BandwidthRegistry) -> Self {
+        TransferAccounting {
+            context_id: context_id.to_string(),
+            registry,
+        }
+    }
+
+    pub fn record(&self, bytes: u64) {
+        self.registry.record(&self.context_id, bytes);
+    }
+
+    pub fn record_finished(&self) {
+        self.registry.record_finished(&self.context_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{transfer_with, FileTransferProvider, TransferContext, TransferUrl};
+    use std::io::Write;
+    use std::path::Path;
+    use std::rc::Rc;
+    use url::Url;
+
+    fn create_file(path: &Path, size: usize) -> TransferUrl {
+        let mut file = std::fs::File::create(path).unwrap();
+        file.write_all(&vec![7u8; size]).unwrap();
+        file_url(path)
+    }
+
+    fn file_url(path: &Path) -> TransferUrl {
+        TransferUrl {
+            hash: None,
+            url: Url::from_file_path(path).unwrap(),
+        }
+    }
+
+    async fn copy(src: &TransferUrl, dst: &TransferUrl, ctx: &TransferContext) {
+        let provider = Rc::new(FileTransferProvider::default());
+        transfer_with(&provider, src, &provider, dst, ctx)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn tagged_transfers_are_accounted_per_context() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let small = create_file(&dir.path().join("small"), 1000);
+        let large = create_file(&dir.path().join("large"), 300_000);
+        let dst = file_url(&dir.path().join("dst"));
+
+        let registry = BandwidthRegistry::default();
+        let ctx_a = TransferContext::default()
+            .with_accounting(TransferAccounting::new("agreement-a", registry.clone()));
+        let ctx_b = TransferContext::default()
+            .with_accounting(TransferAccounting::new("agreement-b", registry.clone()));
+
+        copy(&small, &dst, &ctx_a).await;
+        copy(&large, &dst, &ctx_a).await;
+        copy(&large, &dst, &ctx_b).await;
+        copy(&small, &dst, &TransferContext::default()).await;
+
+        let usage = registry.snapshot();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage["agreement-a"].bytes, 301_000);
+        assert_eq!(usage["agreement-a"].transfers, 2);
+        assert_eq!(usage["agreement-b"].bytes, 300_000);
+        assert_eq!(usage["agreement-b"].transfers, 1);
+
+        assert_eq!(registry.take("agreement-a").unwrap().bytes, 301_000);
+        assert!(registry.usage("agreement-a").is_none());
+
+        copy(&small, &dst, &ctx_a).await;
+        assert_eq!(registry.usage("agreement-a").unwrap().bytes, 1000);
+
+        assert_eq!(registry.prune(Duration::from_secs(3600)), 0);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(registry.prune(Duration::from_secs(0)), 2);
+        assert!(registry.snapshot().is_empty());
+    }
+}
@@ -15,7 +15,7 @@ use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str::FromStr;
 use tokio::fs::OpenOptions;
-use tokio::io::{copy, AsyncWriteExt, ReadBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadBuf};
 use ya_client_model::activity::TransferArgs;
 use ya_utils_path::normalize_path;
 use zip::tokio::read::read_zipfile_from_stream;
@@ -328,17 +328,22 @@ where
     }
 }
 
-pub async fn extract<B, S, E, P>(
+/// Extracts the archive `stream` into directory `path`. `reserve` is called
+/// with the number of bytes before they are written to disk; an error returned
+/// by it stops the extraction.
+pub async fn extract<B, S, E, P, R>(
     stream: S,
     path: P,
     format: ArchiveFormat,
     evt_sender: Sender<FileEvent>,
+    reserve: R,
 ) -> Result<(), E>
 where
     B: Into<Bytes>,
     S: Stream<Item = Result<B, E>> + Unpin + Send + Sync + 'static,
     E: Into<io::Error> + From<io::Error> + 'static,
     P: AsRef<Path> + 'static,
+    R: FnMut(u64) -> io::Result<()>,
 {
     std::fs::create_dir_all(&path)?;
     let path = normalize_path(path.as_ref())?;
@@ -346,13 +351,14 @@ where
     let stream = stream.map(|r| r.map(Into::into).map_err(Into::into));
     match format {
         ArchiveFormat::Tar => {
-            extract_tar(stream, path, evt_sender).await?;
+            extract_tar(stream, path, evt_sender, reserve).await?;
         }
         ArchiveFormat::TarBz2 => {
             extract_tar(
                 codec_stream(BzDecoder::new(stream.into_async_read())),
                 path,
                 evt_sender,
+                reserve,
             )
             .await?;
         }
@@ -361,6 +367,7 @@ where
                 codec_stream(GzipDecoder::new(stream.into_async_read())),
                 path,
                 evt_sender,
+                reserve,
             )
             .await?;
         }
@@ -369,24 +376,27 @@ where
                 codec_stream(XzDecoder::new(stream.into_async_read())),
                 path,
                 evt_sender,
+                reserve,
             )
             .await?;
         }
         ArchiveFormat::Zip | ArchiveFormat::ZipStored => {
-            extract_zip(stream, path, evt_sender).await?;
+            extract_zip(stream, path, evt_sender, reserve).await?;
         }
     }
     Ok(())
 }
 
-async fn extract_zip<'a, S, P>(
+async fn extract_zip<'a, S, P, R>(
     stream: S,
     path: P,
     mut evt_sender: Sender<FileEvent>,
+    mut reserve: R,
 ) -> Result<(), io::Error>
 where
     S: Stream<Item = Result<Bytes, io::Error>> + Unpin + Send + Sync + 'a,
     P: AsRef<Path>,
+    R: FnMut(u64) -> io::Result<()>,
 {
     let path = path.as_ref();
     let mut reader = TokioAsyncRead(stream.into_async_read());
@@ -420,7 +430,16 @@ where
                     .truncate(true)
                     .open(file_path)
                     .await?;
-                copy(&mut result, &mut file).await?;
+                // Declared size of an entry may be missing, count the data instead
+                let mut buf = vec![0u8; 64 * 1024];
+                loop {
+                    let count = result.read(&mut buf).await?;
+                    if count == 0 {
+                        break;
+                    }
+                    reserve(count as u64)?;
+                    file.write_all(&buf[..count]).await?;
+                }
                 file.flush().await?;
             }
 
@@ -434,14 +453,16 @@ where
     Ok(())
 }
 
-async fn extract_tar<'a, S, P>(
+async fn extract_tar<'a, S, P, R>(
     stream: S,
     path: P,
     mut evt_sender: Sender<FileEvent>,
+    mut reserve: R,
 ) -> Result<(), io::Error>
 where
     S: Stream<Item = Result<Bytes, io::Error>> + Send + Sync + Unpin + 'a,
     P: AsRef<Path>,
+    R: FnMut(u64) -> io::Result<()>,
 {
     let path = path.as_ref();
     let stream = TokioAsyncRead(stream.into_async_read());
@@ -452,17 +473,24 @@ where
         let mut file = file?;
         let header = file.header();
         let name = file.path()?.to_path_buf();
+        let size = header.size().ok().unwrap_or(0);
+        let is_dir = match header.entry_type() {
+            tokio_tar::EntryType::Directory => true,
+            _ => false,
+        };
 
         let evt = FileEvent::Processing {
             name: name.clone(),
-            size: header.size().ok().unwrap_or(0) as usize,
-            is_dir: match header.entry_type() {
-                tokio_tar::EntryType::Directory => true,
-                _ => false,
-            },
+            size: size as usize,
+            is_dir,
         };
         let _ = evt_sender.send(evt).await;
 
+        // Unpacked entry is exactly as long as declared in its header
+        if !is_dir {
+            reserve(size)?;
+        }
+
         file.unpack_in(path).await?;
 
         let _ = evt_sender.send(FileEvent::Finished { name }).await;
@@ -0,0 +1,37 @@
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// Sha256 digests of the bytes streamed by the source and written by the
+/// destination of a file transfer. Both cover data moved by the last attempt
+/// only, i.e. starting from the resumed offset.
+///
+/// Destinations return the digest from `TransferSink::finish`, sources
+/// through `TransferStream::take_digest`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransferChecksum {
+    pub source: Option<Vec<u8>>,
+    pub destination: Option<Vec<u8>>,
+}
+
+impl TransferChecksum {
+    /// Checks whether the destination received what the source has sent
+    pub fn verify(&self) -> Result<(), Error> {
+        match (&self.source, &self.destination) {
+            (Some(src), Some(dst)) if src == dst => Ok(()),
+            (Some(src), Some(dst)) => Err(Error::InvalidHashError {
+                hash: hex::encode(dst),
+                expected: hex::encode(src),
+            }),
+            _ => Err(Error::Other("transfer checksum not computed".to_string())),
+        }
+    }
+
+    pub(crate) fn hasher() -> Sha256 {
+        Sha256::default()
+    }
+
+    pub(crate) fn digest(hasher: Sha256) -> Vec<u8> {
+        hasher.result().to_vec()
+    }
+}
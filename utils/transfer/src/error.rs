@@ -104,14 +104,20 @@ pub enum Error {
     InvalidUrlError(String),
     #[error("Unsupported scheme: {0}")]
     UnsupportedSchemeError(String),
+    #[error("Invalid byte range: {0}")]
+    InvalidRangeError(String),
     #[error("Unsupported digest: {0}")]
     UnsupportedDigestError(String),
     #[error("Downloaded VM image is corrupted: calculated hash {hash} differs from the expected one {expected}")]
     InvalidHashError { hash: String, expected: String },
+    #[error("Invalid file size: {len} B, expected {expected} B")]
+    InvalidSizeError { len: u64, expected: u64 },
     #[error("Hex error: {0}")]
     HexError(#[from] hex::FromHexError),
     #[error("Net API error: {0}")]
     NetApiError(#[from] ya_core_model::net::NetApiError),
+    #[error("Disk quota of {limit} B exceeded")]
+    QuotaExceeded { limit: u64 },
     #[error("Cancelled")]
     Cancelled,
     #[error("{0}")]
@@ -1,13 +1,18 @@
 use crate::archive::ArchiveFormat;
 use crate::archive::{archive, extract};
 use crate::error::Error;
+use crate::location::TransferHash;
 use crate::traverse::PathTraverse;
-use crate::{abortable_sink, abortable_stream};
-use crate::{TransferContext, TransferData, TransferProvider, TransferSink, TransferStream};
+use crate::{abortable_sink, abortable_stream, hasher};
+use crate::{TransferChecksum, TransferContext, TransferData, TransferProvider};
+use crate::{TransferSink, TransferStream};
 use futures::future::{ready, LocalBoxFuture};
 use futures::{FutureExt, SinkExt, StreamExt, TryFutureExt};
+use sha2::Digest;
+use std::cell::Cell;
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, SeekFrom};
 use tokio::task::spawn_local;
@@ -30,20 +35,31 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
     }
 
     fn source(&self, url: &Url, ctx: &TransferContext) -> TransferStream<TransferData, Error> {
-        let (stream, tx, abort_reg) = TransferStream::<TransferData, Error>::create(1);
+        let (mut stream, tx, abort_reg) = TransferStream::<TransferData, Error>::create(1);
         let mut txc = tx.clone();
         let url = url.clone();
         let offset = ctx.state.offset();
+        let digest_tx = match ctx.checksum {
+            true => Some(stream.digest_channel()),
+            false => None,
+        };
 
         spawn_local(async move {
             let fut = async move {
+                let range = ByteRange::from_url(&url)?;
                 let mut file = File::open(extract_file_url(&url)).await?;
-                file.seek(SeekFrom::Start(offset)).await?;
                 let meta = file.metadata().await?;
+                let (start, end) = match range {
+                    Some(range) => range.bounds(meta.len())?,
+                    None => (0, meta.len()),
+                };
+                let start = start + offset;
+                file.seek(SeekFrom::Start(start)).await?;
 
                 let mut reader = BufReader::with_capacity(DEFAULT_CHUNK_SIZE, file);
                 let mut buf: [u8; DEFAULT_CHUNK_SIZE] = [0; DEFAULT_CHUNK_SIZE];
-                let mut remaining = meta.len() - offset;
+                let mut remaining = end.saturating_sub(start);
+                let mut hasher = digest_tx.as_ref().map(|_| TransferChecksum::hasher());
 
                 loop {
                     // read_exact returns EOF if there are less than DEFAULT_CHUNK_SIZE bytes to read
@@ -51,8 +67,8 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
                         let count = reader.read_exact(&mut buf).await?;
                         buf[..count].to_vec()
                     } else {
-                        let mut vec = Vec::with_capacity(remaining as usize);
-                        reader.read_to_end(&mut vec).await?;
+                        let mut vec = vec![0u8; remaining as usize];
+                        reader.read_exact(&mut vec).await?;
                         vec
                     };
                     if vec.len() == 0 {
@@ -60,9 +76,15 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
                     }
 
                     remaining -= vec.len() as u64;
+                    if let Some(ref mut hasher) = hasher {
+                        hasher.input(&vec);
+                    }
                     txc.send(Ok(TransferData::from(vec))).await?;
                 }
 
+                if let (Some(digest_tx), Some(hasher)) = (digest_tx, hasher) {
+                    let _ = digest_tx.send(TransferChecksum::digest(hasher));
+                }
                 Ok(())
             };
 
@@ -76,7 +98,20 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
         let (sink, mut rx, res_tx) = TransferSink::<TransferData, Error>::create(1);
         let path = PathBuf::from(extract_file_url(&url));
         let path_c = path.clone();
+        // Content ranges are assembled in place by subsequent transfers
+        let part = match ctx.content_range {
+            Some(_) => None,
+            None => Some(part_path(&path)),
+        };
+        let part_c = part.clone();
         let state = ctx.state.clone();
+        let range = ctx.content_range.clone();
+        let quota = ctx.quota.clone();
+        let quota_c = quota.clone();
+        let checksum = ctx.checksum;
+        // Source has failed, the transfer may be resumed
+        let interrupted = Rc::new(Cell::new(false));
+        let interrupted_c = interrupted.clone();
 
         spawn_local(async move {
             if let Some(parent) = path.parent() {
@@ -86,38 +121,94 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
             let fut = async move {
                 log::debug!("Transferring to file: {}", path.display());
 
+                let target = part.as_ref().unwrap_or(&path);
                 let offset = state.offset();
-                let mut file = if offset == 0 {
+                let mut file = if let Some(ref range) = range {
+                    range.validate()?;
+                    let mut file = OpenOptions::new()
+                        .create(true)
+                        .write(true)
+                        .open(target)
+                        .await?;
+                    file.seek(SeekFrom::Start(range.offset + offset)).await?;
+                    file
+                } else if offset == 0 {
                     OpenOptions::new()
                         .create(true)
                         .write(true)
                         .truncate(true)
-                        .open(&path)
+                        .open(target)
                         .await?
                 } else {
-                    let mut file = OpenOptions::new().write(true).open(&path).await?;
+                    let mut file = OpenOptions::new().write(true).open(target).await?;
                     file.seek(SeekFrom::Start(offset)).await?;
                     file
                 };
-
-                while let Some(result) = rx.next().await {
-                    let data = result?;
-                    let bytes = data.as_ref();
-                    if bytes.len() == 0 {
-                        break;
+                let mut position = match range {
+                    Some(ref range) => range.offset + offset,
+                    None => {
+                        // Temporary file is truncated to the resumed offset
+                        if let Some(ref quota) = quota {
+                            quota.truncate(target, offset);
+                        }
+                        offset
                     }
+                };
+                let mut hasher = match checksum {
+                    true => Some(TransferChecksum::hasher()),
+                    false => None,
+                };
 
-                    file.write_all(bytes).await?;
-                    state.set_offset(state.offset() + bytes.len() as u64);
+                let written = async {
+                    while let Some(result) = rx.next().await {
+                        let data = result.map_err(|e| {
+                            interrupted.set(true);
+                            e
+                        })?;
+                        let bytes = data.as_ref();
+                        if bytes.len() == 0 {
+                            break;
+                        }
+
+                        position += bytes.len() as u64;
+                        if let Some(ref quota) = quota {
+                            quota.reserve(target, position)?;
+                        }
+                        file.write_all(bytes).await?;
+                        if let Some(ref mut hasher) = hasher {
+                            hasher.input(bytes);
+                        }
+                        state.set_offset(state.offset() + bytes.len() as u64);
+                    }
+                    Ok::<(), Error>(())
                 }
-                file.flush().await?;
-                file.sync_all().await?;
+                .await;
+                // Data written so far is persisted even if the transfer fails
+                close_file(file, target).await?;
+                written?;
 
-                Ok::<(), Error>(())
+                if let Some(range) = range {
+                    range.verify(&path).await?;
+                }
+                if let Some(ref part) = part {
+                    tokio::fs::rename(part, &path).await?;
+                    if let Some(ref quota) = quota {
+                        quota.rename(part, &path);
+                    }
+                }
+                Ok::<_, Error>(hasher.map(TransferChecksum::digest))
             }
-            .map_err(|error| {
+            .or_else(|error| async move {
                 log::error!("Error writing to file [{}]: {}", path_c.display(), error);
-                Error::from(error)
+                // Temporary file of an interrupted transfer is kept for a retry,
+                // see `cleanup_destination`
+                if let (Some(part), false) = (part_c, interrupted_c.get()) {
+                    let _ = tokio::fs::remove_file(&part).await;
+                    if let Some(quota) = quota_c {
+                        quota.release(&part);
+                    }
+                }
+                Err(error)
             });
 
             abortable_sink(fut, res_tx).await
@@ -126,16 +217,38 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
         sink
     }
 
+    fn cleanup_destination(&self, url: &Url, ctx: &TransferContext) {
+        if ctx.content_range.is_some() {
+            return;
+        }
+        let part = part_path(Path::new(&extract_file_url(url)));
+        match std::fs::remove_file(&part) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                log::warn!("Unable to remove [{}]: {}", part.display(), e)
+            }
+            _ => (),
+        }
+        if let Some(ref quota) = ctx.quota {
+            quota.release(&part);
+        }
+    }
+
     fn prepare_destination<'a>(
         &self,
         url: &Url,
         ctx: &TransferContext,
     ) -> LocalBoxFuture<'a, Result<(), Error>> {
         let path = PathBuf::from(extract_file_url(&url));
+        let path = match ctx.content_range {
+            Some(_) => path,
+            None => part_path(&path),
+        };
         let state = ctx.state.clone();
+        let range_offset = ctx.content_range.as_ref().map(|r| r.offset).unwrap_or(0);
         async move {
+            // Within a content range, offset is relative to the start of the range
             state.set_offset(match tokio::fs::metadata(path).await {
-                Ok(meta) => meta.len(),
+                Ok(meta) => meta.len().saturating_sub(range_offset),
                 _ => 0,
             });
 
@@ -145,6 +258,23 @@ impl TransferProvider<TransferData, Error> for FileTransferProvider {
     }
 }
 
+/// Sibling file written to until the transfer completes. Renamed onto `path`
+/// afterwards, so that the destination is never observed half-written.
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Flushes buffered data and waits until it's persisted on disk.
+/// The completion of a transfer is signalled only afterwards.
+async fn close_file(mut file: File, path: &Path) -> Result<(), Error> {
+    file.flush().await?;
+    file.sync_all().await?;
+    log::debug!("File synced to disk: {}", path.display());
+    Ok(())
+}
+
 impl Default for DirTransferProvider {
     fn default() -> Self {
         DirTransferProvider {}
@@ -194,9 +324,22 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
     fn destination(&self, url: &Url, ctx: &TransferContext) -> TransferSink<TransferData, Error> {
         let dir = Path::new(&extract_file_url(url)).to_owned();
         let args = ctx.args.clone();
+        let quota = ctx.quota.clone();
         log::debug!("Transfer destination directory: {}", dir.display());
 
         let (sink, rx, res_tx) = TransferSink::<TransferData, Error>::create(1);
+        // Extracted bytes are accounted, before they are written to disk
+        let quota_dir = dir.clone();
+        let mut extracted = 0u64;
+        let reserve = move |len: u64| -> std::io::Result<()> {
+            extracted += len;
+            match quota {
+                Some(ref quota) => quota
+                    .reserve(&quota_dir, extracted)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)),
+                None => Ok(()),
+            }
+        };
 
         spawn_local(async move {
             let fut = async move {
@@ -209,8 +352,10 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
                     }
                 });
 
-                extract(rx, dir, format, evt_tx).await?;
-                Ok::<(), Error>(())
+                extract(rx, dir, format, evt_tx, reserve)
+                    .await
+                    .map_err(unwrap_io_error)?;
+                Ok::<_, Error>(None)
             };
 
             abortable_sink(fut, res_tx).await
@@ -220,6 +365,150 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
     }
 }
 
+/// Errors of the transferred stream are wrapped in `io::Error` while extracting.
+fn unwrap_io_error(error: Error) -> Error {
+    match error {
+        Error::IoError(e) if e.get_ref().map(|inner| inner.is::<Error>()) == Some(true) => {
+            match e.into_inner().map(|inner| inner.downcast::<Error>()) {
+                Some(Ok(inner)) => *inner,
+                _ => Error::Other("unable to unwrap io error".to_string()),
+            }
+        }
+        error => error,
+    }
+}
+
+/// Part of the destination file written by a single transfer.
+/// Once the transfer completes, the whole file is expected to be
+/// `total` bytes long and match the `hash` (if provided).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentRange {
+    pub offset: u64,
+    pub total: u64,
+    pub hash: Option<TransferHash>,
+}
+
+impl ContentRange {
+    pub fn new(offset: u64, total: u64) -> Self {
+        ContentRange {
+            offset,
+            total,
+            hash: None,
+        }
+    }
+
+    pub fn with_hash(mut self, hash: TransferHash) -> Self {
+        self.hash = Some(hash);
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.offset > self.total {
+            return Err(Error::InvalidRangeError(format!(
+                "offset {} exceeds total size of {} B",
+                self.offset, self.total
+            )));
+        }
+        if let Some(ref hash) = self.hash {
+            hasher(&hash.alg, &hash.val)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the length and hash of the assembled file
+    async fn verify(&self, path: &Path) -> Result<(), Error> {
+        let len = tokio::fs::metadata(path).await?.len();
+        if len != self.total {
+            return Err(Error::InvalidSizeError {
+                len,
+                expected: self.total,
+            });
+        }
+
+        let expected = match self.hash {
+            Some(ref hash) => hash,
+            None => return Ok(()),
+        };
+        let mut hasher = hasher(&expected.alg, &expected.val)?;
+        let mut reader = BufReader::with_capacity(DEFAULT_CHUNK_SIZE, File::open(path).await?);
+        let mut buf = vec![0u8; DEFAULT_CHUNK_SIZE];
+        loop {
+            let count = reader.read(&mut buf).await?;
+            if count == 0 {
+                break;
+            }
+            hasher.input(&buf[..count]);
+        }
+
+        let hash = hasher.result_reset();
+        if hash.as_ref() != expected.val.as_slice() {
+            return Err(Error::InvalidHashError {
+                hash: hex::encode(&hash),
+                expected: hex::encode(&expected.val),
+            });
+        }
+        log::debug!("Content of {} verified", path.display());
+        Ok(())
+    }
+}
+
+/// Inclusive byte range read from a `#bytes=<start>-[<end>]` URL fragment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ByteRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl ByteRange {
+    const PREFIX: &'static str = "bytes=";
+
+    /// Fragments other than `bytes=` ranges are ignored
+    pub fn from_url(url: &Url) -> Result<Option<Self>, Error> {
+        match url.fragment() {
+            Some(fragment) if fragment.starts_with(Self::PREFIX) => Self::parse(fragment).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidRangeError(s.to_string());
+
+        let range = s.strip_prefix(Self::PREFIX).ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start = start.trim().parse::<u64>().map_err(|_| invalid())?;
+        let end = match end.trim() {
+            "" => None,
+            end => Some(end.parse::<u64>().map_err(|_| invalid())?),
+        };
+
+        if end.map(|end| end < start).unwrap_or(false) {
+            return Err(invalid());
+        }
+        Ok(ByteRange { start, end })
+    }
+
+    /// Returns the `[start, end)` bounds of the range within a file of `len` bytes
+    pub fn bounds(&self, len: u64) -> Result<(u64, u64), Error> {
+        let end = self.end.unwrap_or_else(|| len.saturating_sub(1));
+        if self.start >= len || end >= len {
+            return Err(Error::InvalidRangeError(format!(
+                "{} exceeds file size of {} B",
+                self, len
+            )));
+        }
+        Ok((self.start, end + 1))
+    }
+}
+
+impl std::fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "bytes={}-{}", self.start, end),
+            None => write!(f, "bytes={}-", self.start),
+        }
+    }
+}
+
 pub(crate) fn extract_file_url(url: &Url) -> String {
     // On Windows, Rust implementation of Url::parse() adds a third '/' after the 'file://' indicator,
     // thus making .path() method unusable for the purposes of file creation (because File::create() will not accept that),
@@ -234,3 +523,326 @@ pub(crate) fn extract_file_url(url: &Url) -> String {
         url.path_decoded()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn create_file(dir: &Path) -> PathBuf {
+        let path = dir.join("file");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(&(0..=255u8).collect::<Vec<_>>()).unwrap();
+        path
+    }
+
+    async fn read(path: &Path, fragment: &str) -> Result<Vec<u8>, Error> {
+        let mut url = Url::from_file_path(path).unwrap();
+        url.set_fragment(Some(fragment));
+
+        let ctx = TransferContext::default();
+        let mut stream = FileTransferProvider::default().source(&url, &ctx);
+        let mut bytes = Vec::new();
+        while let Some(result) = stream.next().await {
+            bytes.extend_from_slice(result?.as_ref());
+        }
+        Ok(bytes)
+    }
+
+    async fn write(path: &Path, data: &[u8], range: ContentRange) -> Result<(), Error> {
+        let url = Url::from_file_path(path).unwrap();
+        let ctx = TransferContext::default().with_content_range(range);
+        let sink = FileTransferProvider::default().destination(&url, &ctx);
+        let stream = futures::stream::iter(vec![
+            Ok(TransferData::from(data.to_vec())),
+            Ok(TransferData::from(Vec::new())),
+        ]);
+        crate::transfer(stream, sink).await
+    }
+
+    fn sha3(data: &[u8]) -> TransferHash {
+        use sha3::Digest;
+        TransferHash {
+            alg: "sha3".to_string(),
+            val: sha3::Sha3_256::digest(data).to_vec(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn destination_content_range_verified() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = dir.path().join("dst");
+        let data = (0..=255u8).cycle().take(100_000).collect::<Vec<_>>();
+        let (head, tail) = data.split_at(60_000);
+        std::fs::write(&path, head).unwrap();
+
+        let range = ContentRange::new(head.len() as u64, data.len() as u64).with_hash(sha3(&data));
+        write(&path, tail, range).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        // Assembled file doesn't match the expected hash
+        std::fs::write(&path, head).unwrap();
+        let range = ContentRange::new(head.len() as u64, data.len() as u64).with_hash(sha3(head));
+        match write(&path, tail, range).await {
+            Err(Error::InvalidHashError { .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // Assembled file is shorter than expected
+        std::fs::write(&path, head).unwrap();
+        let range = ContentRange::new(head.len() as u64, data.len() as u64 + 1);
+        match write(&path, tail, range).await {
+            Err(Error::InvalidSizeError { len, expected }) => {
+                assert_eq!((len, expected), (data.len() as u64, data.len() as u64 + 1));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn destination_finish_awaits_sync() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = dir.path().join("dst");
+        let url = Url::from_file_path(&path).unwrap();
+        let data = (0..=255u8)
+            .cycle()
+            .take(3 * DEFAULT_CHUNK_SIZE)
+            .collect::<Vec<_>>();
+
+        let ctx = TransferContext::default();
+        let mut sink = FileTransferProvider::default().destination(&url, &ctx);
+        for chunk in data.chunks(DEFAULT_CHUNK_SIZE / 2) {
+            sink.send(TransferData::from(chunk.to_vec())).await.unwrap();
+        }
+        sink.finish().await.unwrap();
+
+        // File handle was synced and closed before the completion signal
+        let file = std::fs::File::open(&path).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), data.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert_eq!(ctx.state.offset(), data.len() as u64);
+
+        // Completion signal carries write errors
+        let url = Url::from_file_path(dir.path()).unwrap();
+        let mut sink = FileTransferProvider::default().destination(&url, &ctx);
+        let _ = sink.send(TransferData::from(data.clone())).await;
+        assert!(sink.finish().await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn destination_written_atomically() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = dir.path().join("dst");
+        let url = Url::from_file_path(&path).unwrap();
+        std::fs::write(&path, b"previous").unwrap();
+
+        // Interrupted transfer leaves the destination intact and keeps
+        // the temporary file for a retry
+        let ctx = TransferContext::default();
+        let sink = FileTransferProvider::default().destination(&url, &ctx);
+        let stream = futures::stream::iter(vec![
+            Ok(TransferData::from(vec![1u8; 1000])),
+            Err(Error::Other("interrupted".to_string())),
+        ]);
+        match crate::transfer(stream, sink).await {
+            Err(Error::Other(_)) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"previous");
+        assert_eq!(std::fs::read(part_path(&path)).unwrap(), vec![1u8; 1000]);
+
+        let ctx = TransferContext::default();
+        let sink = FileTransferProvider::default().destination(&url, &ctx);
+        let stream = futures::stream::iter(vec![Ok(TransferData::from(vec![1u8; 1000]))]);
+        crate::transfer(stream, sink).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), vec![1u8; 1000]);
+        assert!(!part_path(&path).exists());
+    }
+
+    /// Streams `data` from the current offset and fails with a retryable error
+    /// after `fail_at` bytes on the first attempt
+    struct FlakyProvider {
+        data: Vec<u8>,
+        fail_at: usize,
+        offsets: Rc<std::cell::RefCell<Vec<u64>>>,
+    }
+
+    impl TransferProvider<TransferData, Error> for FlakyProvider {
+        fn schemes(&self) -> Vec<&'static str> {
+            vec!["flaky"]
+        }
+
+        fn source(&self, _url: &Url, ctx: &TransferContext) -> TransferStream<TransferData, Error> {
+            let (stream, mut tx, _) = TransferStream::<TransferData, Error>::create(1);
+            let offset = ctx.state.offset() as usize;
+            let first = self.offsets.borrow().is_empty();
+            self.offsets.borrow_mut().push(offset as u64);
+
+            let end = if first { self.fail_at } else { self.data.len() };
+            let data = self.data[offset..end].to_vec();
+            spawn_local(async move {
+                for chunk in data.chunks(1000) {
+                    let _ = tx.send(Ok(TransferData::from(chunk.to_vec()))).await;
+                }
+                if first {
+                    let err = ya_service_bus::error::Error::Closed("flaky".to_string());
+                    let _ = tx.send(Err(Error::Gsb(err))).await;
+                }
+            });
+            stream
+        }
+
+        fn destination(&self, _: &Url, _: &TransferContext) -> TransferSink<TransferData, Error> {
+            unimplemented!()
+        }
+
+        fn prepare_source<'a>(
+            &self,
+            _url: &Url,
+            _ctx: &TransferContext,
+        ) -> LocalBoxFuture<'a, Result<(), Error>> {
+            // keep the offset set by the destination
+            futures::future::ok(()).boxed_local()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn destination_resumed_on_retry() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = dir.path().join("dst");
+        let data = (0..=255u8).cycle().take(100_000).collect::<Vec<_>>();
+
+        let src = Rc::new(FlakyProvider {
+            data: data.clone(),
+            fail_at: 30_000,
+            offsets: Default::default(),
+        });
+        let dst = Rc::new(FileTransferProvider::default());
+        let src_url = crate::TransferUrl {
+            hash: None,
+            url: Url::parse("flaky://data").unwrap(),
+        };
+        let dst_url = crate::TransferUrl {
+            hash: None,
+            url: Url::from_file_path(&path).unwrap(),
+        };
+
+        let ctx = TransferContext::default();
+        ctx.state
+            .retry_with(crate::Retry::new(1).backoff(0.01, 1.).clone());
+        crate::transfer_with(&src, &src_url, &dst, &dst_url, &ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(*src.offsets.borrow(), vec![0, 30_000]);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert!(!part_path(&path).exists());
+    }
+
+    #[actix_rt::test]
+    async fn destination_removed_on_final_failure() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = dir.path().join("dst");
+
+        let src = Rc::new(FlakyProvider {
+            data: vec![1u8; 10_000],
+            fail_at: 5_000,
+            offsets: Default::default(),
+        });
+        let dst = Rc::new(FileTransferProvider::default());
+        let src_url = crate::TransferUrl {
+            hash: None,
+            url: Url::parse("flaky://data").unwrap(),
+        };
+        let dst_url = crate::TransferUrl {
+            hash: None,
+            url: Url::from_file_path(&path).unwrap(),
+        };
+
+        let ctx = TransferContext::default();
+        ctx.state.retry(0);
+        match crate::transfer_with(&src, &src_url, &dst, &dst_url, &ctx).await {
+            Err(Error::Gsb(_)) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(!path.exists());
+        assert!(!part_path(&path).exists());
+    }
+
+    #[actix_rt::test]
+    async fn checksum_of_streamed_data() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let src = Url::from_file_path(create_file(dir.path())).unwrap();
+        let dst = Url::from_file_path(dir.path().join("dst")).unwrap();
+        let expected = sha2::Sha256::digest(&(0..=255u8).collect::<Vec<_>>()).to_vec();
+
+        let ctx = TransferContext::default().with_checksum();
+        let mut stream = FileTransferProvider::default().source(&src, &ctx);
+        let source = stream.take_digest().unwrap();
+        let sink = FileTransferProvider::default().destination(&dst, &ctx);
+        let destination = crate::transfer_digest(stream, sink).await.unwrap();
+
+        let checksum = TransferChecksum {
+            source: source.await.ok(),
+            destination,
+        };
+        assert_eq!(checksum.source, Some(expected.clone()));
+        assert_eq!(checksum.destination, Some(expected));
+        checksum.verify().unwrap();
+
+        // Digests are not computed unless requested
+        let ctx = TransferContext::default();
+        let mut stream = FileTransferProvider::default().source(&src, &ctx);
+        assert!(stream.take_digest().is_none());
+        let sink = FileTransferProvider::default().destination(&dst, &ctx);
+        assert_eq!(crate::transfer_digest(stream, sink).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn source_mid_file_range() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = create_file(dir.path());
+
+        let bytes = read(&path, "bytes=100-199").await.unwrap();
+        assert_eq!(bytes, (100..=199u8).collect::<Vec<_>>());
+    }
+
+    #[actix_rt::test]
+    async fn source_range_to_eof() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = create_file(dir.path());
+
+        let bytes = read(&path, "bytes=200-").await.unwrap();
+        assert_eq!(bytes, (200..=255u8).collect::<Vec<_>>());
+    }
+
+    #[actix_rt::test]
+    async fn source_ignores_other_fragments() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = create_file(dir.path());
+
+        for fragment in ["100-199", "section", ""] {
+            let bytes = read(&path, fragment).await.unwrap();
+            assert_eq!(bytes, (0..=255u8).collect::<Vec<_>>(), "{}", fragment);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn source_invalid_range() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let path = create_file(dir.path());
+
+        for fragment in [
+            "bytes=200-100",
+            "bytes=100-256",
+            "bytes=256-",
+            "bytes=a-b",
+            "bytes=100",
+        ] {
+            match read(&path, fragment).await {
+                Err(Error::InvalidRangeError(_)) => (),
+                result => panic!("{}: unexpected result {:?}", fragment, result),
+            }
+        }
+    }
+}
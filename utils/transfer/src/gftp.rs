@@ -132,7 +132,7 @@ impl TransferProvider<TransferData, Error> for GftpTransferProvider {
 
                 let hash = Some(format!("{:x}", digest));
                 remote.call(model::UploadFinished { hash }).await??;
-                Result::<(), Error>::Ok(())
+                Result::<_, Error>::Ok(None)
             }
             .map_err(Error::from);
 
@@ -105,7 +105,7 @@ impl TransferProvider<TransferData, Error> for HttpTransferProvider {
                 .send_stream(rx.map(|res| res.map(Bytes::from)))
                 .http_err()?
                 .await
-                .map(|_| ())
+                .map(|_| None)
             };
 
             abortable_sink(fut, res_tx).await
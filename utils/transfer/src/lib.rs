@@ -1,9 +1,12 @@
+mod accounting;
 mod archive;
+mod checksum;
 pub mod error;
 mod file;
 mod gftp;
 mod http;
 mod location;
+mod quota;
 mod retry;
 mod traverse;
 
@@ -24,24 +27,45 @@ use url::Url;
 
 use crate::error::Error;
 
+pub use crate::accounting::{BandwidthRegistry, BandwidthUsage, TransferAccounting};
 pub use crate::archive::{archive, extract, ArchiveFormat};
-pub use crate::file::{DirTransferProvider, FileTransferProvider};
+pub use crate::checksum::TransferChecksum;
+pub use crate::file::{ContentRange, DirTransferProvider, FileTransferProvider};
 pub use crate::gftp::GftpTransferProvider;
 pub use crate::http::HttpTransferProvider;
-pub use crate::location::{TransferUrl, UrlExt};
+pub use crate::location::{TransferHash, TransferUrl, UrlExt};
+pub use crate::quota::DiskQuota;
 pub use crate::retry::Retry;
 pub use crate::traverse::PathTraverse;
 
 use ya_client_model::activity::TransferArgs;
 
 /// Transfers data from `stream` to a `TransferSink`
-pub async fn transfer<S, T>(stream: S, mut sink: TransferSink<T, Error>) -> Result<(), Error>
+pub async fn transfer<S, T>(stream: S, sink: TransferSink<T, Error>) -> Result<(), Error>
 where
     S: Stream<Item = Result<T, Error>>,
 {
-    let rx = sink.res_rx.take().unwrap();
-    stream.forward(sink).await?;
-    Ok(rx.await??)
+    transfer_digest(stream, sink).await.map(|_| ())
+}
+
+/// Returns the digest of data written to `sink`, if computed by the destination
+async fn transfer_digest<S, T>(
+    stream: S,
+    mut sink: TransferSink<T, Error>,
+) -> Result<Option<Vec<u8>>, Error>
+where
+    S: Stream<Item = Result<T, Error>>,
+{
+    if let Err(error) = stream.forward(&mut sink).await {
+        // Failing destination closes the channel. Report the cause of the failure.
+        if sink.abort().await.is_err() {
+            sink.finish().await?;
+        } else {
+            let _ = sink.finish().await;
+        }
+        return Err(error);
+    }
+    sink.finish().await
 }
 
 /// Transfers data between `TransferProvider`s within current context
@@ -58,6 +82,12 @@ where
 {
     let src = src.as_ref();
     let dst = dst.as_ref();
+    let mut guard = DestinationGuard {
+        dst,
+        url: &dst_url.url,
+        ctx,
+        done: false,
+    };
 
     loop {
         let fut = async {
@@ -66,15 +96,30 @@ where
 
             log::debug!("Transferring from offset: {}", ctx.state.offset());
 
-            let stream = wrap_stream(src.source(&src_url.url, ctx), &src_url)?;
+            let mut stream = src.source(&src_url.url, ctx);
+            let source = stream.take_digest();
+            let stream = wrap_stream(stream, &src_url, ctx)?;
             let sink = dst.destination(&dst_url.url, ctx);
 
-            transfer(stream, sink).await?;
+            let destination = transfer_digest(stream, sink).await?;
+            if let (Some(source), Some(destination)) = (source, destination) {
+                TransferChecksum {
+                    source: source.await.ok(),
+                    destination: Some(destination),
+                }
+                .verify()?;
+            }
+            if let Some(accounting) = &ctx.accounting {
+                accounting.record_finished();
+            }
             Ok::<_, Error>(())
         };
 
         match fut.await {
-            Ok(val) => return Ok(val),
+            Ok(val) => {
+                guard.done = true;
+                return Ok(val);
+            }
             Err(err) => match ctx.state.delay(&err) {
                 Some(delay) => {
                     log::warn!("Retrying in {}s because: {}", delay.as_secs_f32(), err);
@@ -86,13 +131,38 @@ where
     }
 }
 
+/// Cleans up the destination of a transfer, which has failed for good or was aborted
+struct DestinationGuard<'a, D: TransferProvider<TransferData, Error> + ?Sized> {
+    dst: &'a D,
+    url: &'a Url,
+    ctx: &'a TransferContext,
+    done: bool,
+}
+
+impl<'a, D: TransferProvider<TransferData, Error> + ?Sized> Drop for DestinationGuard<'a, D> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.dst.cleanup_destination(self.url, self.ctx);
+        }
+    }
+}
+
 fn wrap_stream(
     stream: TransferStream<TransferData, Error>,
     url: &TransferUrl,
+    ctx: &TransferContext,
 ) -> Result<Box<dyn Stream<Item = Result<TransferData, Error>> + Unpin>, Error> {
-    Ok(match url.hash {
+    let stream: Box<dyn Stream<Item = Result<TransferData, Error>> + Unpin> = match url.hash {
         Some(ref h) => Box::new(HashStream::try_new(stream, &h.alg, h.val.clone())?),
         None => Box::new(stream),
+    };
+    Ok(match ctx.accounting.clone() {
+        Some(accounting) => Box::new(stream.inspect(move |result| {
+            if let Ok(data) = result {
+                accounting.record(data.as_ref().len() as u64);
+            }
+        })),
+        None => stream,
     })
 }
 
@@ -127,6 +197,10 @@ pub trait TransferProvider<T, E> {
         ctx.state.set_offset(0);
         futures::future::ok(()).boxed_local()
     }
+
+    /// Removes data kept by the sink for resuming the transfer.
+    /// Executed when the transfer has failed for good or was aborted
+    fn cleanup_destination(&self, _url: &Url, _ctx: &TransferContext) {}
 }
 
 type InnerStream<'a, I> = Pin<Box<dyn Stream<Item = I> + Send + Sync + Unpin + 'a>>;
@@ -134,6 +208,7 @@ type InnerStream<'a, I> = Pin<Box<dyn Stream<Item = I> + Send + Sync + Unpin + '
 pub struct TransferStream<T, E> {
     rx: Option<InnerStream<'static, Result<T, E>>>,
     abort_handle: AbortHandle,
+    digest_rx: Option<oneshot::Receiver<Vec<u8>>>,
 }
 
 impl<T, E> TransferStream<T, E>
@@ -145,7 +220,24 @@ where
         let (tx, rx) = channel(channel_size);
         let rx: Option<InnerStream<Result<T, E>>> = Some(Box::pin(rx));
         let (abort_handle, abort_reg) = AbortHandle::new_pair();
-        (TransferStream { rx, abort_handle }, tx, abort_reg)
+        let stream = TransferStream {
+            rx,
+            abort_handle,
+            digest_rx: None,
+        };
+        (stream, tx, abort_reg)
+    }
+
+    /// Digest of the streamed data, sent by the source once the stream has ended.
+    /// Available for sources computing a checksum
+    pub fn take_digest(&mut self) -> Option<oneshot::Receiver<Vec<u8>>> {
+        self.digest_rx.take()
+    }
+
+    pub(crate) fn digest_channel(&mut self) -> oneshot::Sender<Vec<u8>> {
+        let (tx, rx) = oneshot::channel();
+        self.digest_rx = Some(rx);
+        tx
     }
 
     pub fn map_inner<F>(&mut self, f: F)
@@ -188,13 +280,17 @@ impl<T, E> Drop for TransferStream<T, E> {
 
 pub struct TransferSink<T, E> {
     tx: Sender<Result<T, E>>,
-    res_rx: Option<oneshot::Receiver<Result<(), E>>>,
+    res_rx: Option<oneshot::Receiver<Result<Option<Vec<u8>>, E>>>,
 }
 
 impl<T, E> TransferSink<T, E> {
     pub fn create(
         channel_size: usize,
-    ) -> (Self, Receiver<Result<T, E>>, oneshot::Sender<Result<(), E>>) {
+    ) -> (
+        Self,
+        Receiver<Result<T, E>>,
+        oneshot::Sender<Result<Option<Vec<u8>>, E>>,
+    ) {
         let (tx, rx) = channel(channel_size);
         let (res_tx, res_rx) = oneshot::channel();
         (
@@ -214,6 +310,26 @@ impl<T, E> TransferSink<T, E> {
     }
 }
 
+impl<T> TransferSink<T, Error> {
+    /// Closes the sink and waits for the destination to confirm,
+    /// that all written data has been flushed and persisted.
+    /// Returns the digest of written data, if computed by the destination.
+    pub async fn finish(mut self) -> Result<Option<Vec<u8>>, Error> {
+        self.tx.close_channel();
+        let rx = self
+            .res_rx
+            .take()
+            .ok_or_else(|| Error::Other("transfer sink already finished".to_string()))?;
+        rx.await?
+    }
+
+    /// Notifies the destination, that the data sent so far is incomplete
+    async fn abort(&mut self) -> Result<(), Error> {
+        self.tx.send(Err(Error::from(Aborted))).await?;
+        Ok(())
+    }
+}
+
 impl<T> Sink<T> for TransferSink<T, Error> {
     type Error = Error;
 
@@ -286,6 +402,10 @@ impl From<Box<[u8]>> for TransferData {
 pub struct TransferContext {
     pub state: TransferState,
     pub args: TransferArgs,
+    pub accounting: Option<TransferAccounting>,
+    pub content_range: Option<ContentRange>,
+    pub quota: Option<DiskQuota>,
+    pub checksum: bool,
 }
 
 impl TransferContext {
@@ -294,7 +414,38 @@ impl TransferContext {
         let state = TransferState::default();
         state.set_offset(offset);
 
-        Self { args, state }
+        Self {
+            args,
+            state,
+            accounting: None,
+            content_range: None,
+            quota: None,
+            checksum: false,
+        }
+    }
+
+    /// Accounts bytes moved within this context to `accounting.context_id`
+    pub fn with_accounting(mut self, accounting: TransferAccounting) -> Self {
+        self.accounting = Some(accounting);
+        self
+    }
+
+    /// Writes received data at `range.offset` and verifies the assembled destination
+    pub fn with_content_range(mut self, range: ContentRange) -> Self {
+        self.content_range = Some(range);
+        self
+    }
+
+    /// Fails writes to disk, which would exceed the `quota`
+    pub fn with_quota(mut self, quota: DiskQuota) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Computes digests of data moved by file sources and destinations
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = true;
+        self
     }
 }
 
@@ -372,6 +523,28 @@ impl Default for TransferStateInner {
     }
 }
 
+/// Creates a digest matching `alg` and the length of the expected `hash`
+pub(crate) fn hasher(alg: &str, hash: &[u8]) -> Result<Box<dyn DynDigest>, Error> {
+    match alg {
+        "sha3" => match hash.len() * 8 {
+            224 => Ok(Box::new(Sha3_224::default())),
+            256 => Ok(Box::new(Sha3_256::default())),
+            384 => Ok(Box::new(Sha3_384::default())),
+            512 => Ok(Box::new(Sha3_512::default())),
+            len => Err(Error::UnsupportedDigestError(format!(
+                "Unsupported digest {} of length {}: {}",
+                alg,
+                len,
+                hex::encode(hash),
+            ))),
+        },
+        _ => Err(Error::UnsupportedDigestError(format!(
+            "Unsupported digest: {}",
+            alg
+        ))),
+    }
+}
+
 struct HashStream<T, E, S>
 where
     S: Stream<Item = Result<T, E>>,
@@ -387,28 +560,7 @@ where
     S: Stream<Item = Result<T, Error>> + Unpin,
 {
     pub fn try_new(stream: S, alg: &str, hash: Vec<u8>) -> Result<Self, Error> {
-        let hasher: Box<dyn DynDigest> = match alg {
-            "sha3" => match hash.len() * 8 {
-                224 => Box::new(Sha3_224::default()),
-                256 => Box::new(Sha3_256::default()),
-                384 => Box::new(Sha3_384::default()),
-                512 => Box::new(Sha3_512::default()),
-                len => {
-                    return Err(Error::UnsupportedDigestError(format!(
-                        "Unsupported digest {} of length {}: {}",
-                        alg,
-                        len,
-                        hex::encode(&hash),
-                    )))
-                }
-            },
-            _ => {
-                return Err(Error::UnsupportedDigestError(format!(
-                    "Unsupported digest: {}",
-                    alg
-                )))
-            }
-        };
+        let hasher = hasher(alg, &hash)?;
 
         Ok(HashStream {
             inner: stream,
@@ -487,17 +639,14 @@ where
 
 fn abortable_sink<'f, E, F>(
     fut: F,
-    res_tx: oneshot::Sender<Result<(), E>>,
+    res_tx: oneshot::Sender<Result<Option<Vec<u8>>, E>>,
 ) -> Pin<Box<dyn Future<Output = Result<(), E>> + 'f>>
 where
-    F: Future<Output = Result<(), E>> + 'f,
+    F: Future<Output = Result<Option<Vec<u8>>, E>> + 'f,
     E: From<Aborted> + 'f,
 {
-    fut.then(|r: Result<(), E>| async move {
-        let _ = match r {
-            Err(e) => res_tx.send(Err(e)),
-            _ => res_tx.send(Ok(())),
-        };
+    fut.then(|r: Result<Option<Vec<u8>>, E>| async move {
+        let _ = res_tx.send(r);
 
         Result::<(), E>::Ok(())
     })
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::error::Error;
+
+/// Limit of bytes written to disk by transfers sharing the quota
+/// (e.g. all transfers of an activity). Usage is tracked per destination,
+/// so that overwritten, resumed and removed files are accounted only once.
+#[derive(Clone, Debug)]
+pub struct DiskQuota {
+    limit: u64,
+    usage: Arc<Mutex<Usage>>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    total: u64,
+    destinations: HashMap<PathBuf, u64>,
+}
+
+impl Usage {
+    fn set(&mut self, path: &Path, len: u64) {
+        let previous = match len {
+            0 => self.destinations.remove(path),
+            len => self.destinations.insert(path.to_path_buf(), len),
+        };
+        self.total = self.total - previous.unwrap_or(0) + len;
+    }
+}
+
+impl DiskQuota {
+    pub fn new(limit: u64) -> Self {
+        DiskQuota {
+            limit,
+            usage: Default::default(),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.usage.lock().unwrap().total
+    }
+
+    /// Accounts destination `path` growing to `len` bytes. Fails without
+    /// accounting them, if the growth would exceed the limit.
+    pub fn reserve(&self, path: &Path, len: u64) -> Result<(), Error> {
+        let mut usage = self.usage.lock().unwrap();
+        let current = usage.destinations.get(path).cloned().unwrap_or(0);
+        if len <= current {
+            return Ok(());
+        }
+        match usage.total.checked_add(len - current) {
+            Some(total) if total <= self.limit => {
+                usage.set(path, len);
+                Ok(())
+            }
+            _ => Err(Error::QuotaExceeded { limit: self.limit }),
+        }
+    }
+
+    /// Sets usage of destination `path` to `len` bytes, once it's truncated
+    pub fn truncate(&self, path: &Path, len: u64) {
+        self.usage.lock().unwrap().set(path, len);
+    }
+
+    /// Releases usage of removed destination `path`
+    pub fn release(&self, path: &Path) {
+        self.truncate(path, 0);
+    }
+
+    /// Moves usage of `from` onto `to`, replacing the previous usage of `to`
+    pub fn rename(&self, from: &Path, to: &Path) {
+        let mut usage = self.usage.lock().unwrap();
+        let len = usage.destinations.get(from).cloned().unwrap_or(0);
+        usage.set(from, 0);
+        usage.set(to, len);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ContentRange, DirTransferProvider, FileTransferProvider, TransferContext, TransferData,
+        TransferProvider,
+    };
+    use url::Url;
+    use ya_client_model::activity::TransferArgs;
+
+    async fn write(ctx: &TransferContext, url: &Url, chunks: &[usize]) -> Result<(), Error> {
+        let provider = FileTransferProvider::default();
+        provider.prepare_destination(url, ctx).await?;
+        let sink = provider.destination(url, ctx);
+        let mut data = chunks
+            .iter()
+            .map(|size| Ok(TransferData::from(vec![1u8; *size])))
+            .collect::<Vec<_>>();
+        data.push(Ok(TransferData::from(Vec::new())));
+        crate::transfer(futures::stream::iter(data), sink).await
+    }
+
+    #[actix_rt::test]
+    async fn writes_past_quota_fail() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let first = Url::from_file_path(dir.path().join("first")).unwrap();
+        let second = Url::from_file_path(dir.path().join("second")).unwrap();
+
+        let quota = DiskQuota::new(10_000);
+        let ctx = TransferContext::default().with_quota(quota.clone());
+
+        write(&ctx, &first, &[4000, 4000]).await.unwrap();
+        assert_eq!(quota.used(), 8000);
+
+        // Quota is shared between transfers. Content range is written in place,
+        // so the data written before exceeding the quota is kept.
+        let range_ctx = ctx.clone().with_content_range(ContentRange::new(0, 5000));
+        match write(&range_ctx, &second, &[1000, 4000]).await {
+            Err(Error::QuotaExceeded { limit }) => assert_eq!(limit, 10_000),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(quota.used(), 9000);
+        assert_eq!(
+            std::fs::metadata(dir.path().join("second")).unwrap().len(),
+            1000
+        );
+
+        // Fits, as the overwritten file is released afterwards
+        write(&ctx, &second, &[1000]).await.unwrap();
+        assert_eq!(quota.used(), 9000);
+    }
+
+    #[actix_rt::test]
+    async fn rewrites_and_failures_release_quota() {
+        let dir = tempdir::TempDir::new("transfer").unwrap();
+        let first = Url::from_file_path(dir.path().join("first")).unwrap();
+        let second = Url::from_file_path(dir.path().join("second")).unwrap();
+
+        let quota = DiskQuota::new(10_000);
+        let ctx = TransferContext::default().with_quota(quota.clone());
+
+        // Overwritten file is accounted once
+        write(&ctx, &first, &[4000, 2000]).await.unwrap();
+        write(&ctx, &first, &[3000]).await.unwrap();
+        assert_eq!(quota.used(), 3000);
+
+        // Failed transfer releases its temporary file
+        match write(&ctx, &second, &[4000, 4000]).await {
+            Err(Error::QuotaExceeded { .. }) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(quota.used(), 3000);
+
+        write(&ctx, &second, &[7000]).await.unwrap();
+        assert_eq!(quota.used(), 10_000);
+    }
+
+    #[actix_rt::test]
+    async fn extracted_size_is_accounted() {
+        let src = tempdir::TempDir::new("transfer").unwrap();
+        let dst = tempdir::TempDir::new("transfer").unwrap();
+        std::fs::write(src.path().join("zeros"), vec![0u8; 20_000]).unwrap();
+
+        let src_url = Url::from_file_path(src.path()).unwrap();
+        let dst_url = Url::from_file_path(dst.path()).unwrap();
+        let extract = |quota: DiskQuota| {
+            let ctx = TransferContext::from(TransferArgs {
+                format: Some("tar.gz".to_string()),
+                ..Default::default()
+            });
+            let provider = DirTransferProvider::default();
+            let stream = provider.source(&src_url, &ctx);
+            let sink = provider.destination(&dst_url, &ctx.with_quota(quota));
+            crate::transfer(stream, sink)
+        };
+
+        // Compressed archive is much smaller than the extracted file
+        match extract(DiskQuota::new(10_000)).await {
+            Err(Error::QuotaExceeded { limit }) => assert_eq!(limit, 10_000),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let quota = DiskQuota::new(30_000);
+        extract(quota.clone()).await.unwrap();
+        assert_eq!(quota.used(), 20_000);
+    }
+
+    #[test]
+    fn usage_is_tracked_per_destination() {
+        let quota = DiskQuota::new(100);
+        let (a, b) = (Path::new("a"), Path::new("b"));
+
+        quota.reserve(a, 60).unwrap();
+        quota.reserve(a, 40).unwrap();
+        assert_eq!(quota.used(), 60);
+        assert!(quota.reserve(b, 41).is_err());
+        assert_eq!(quota.used(), 60);
+
+        quota.truncate(a, 20);
+        quota.reserve(b, 80).unwrap();
+        quota.rename(b, a);
+        assert_eq!(quota.used(), 80);
+        quota.release(a);
+        assert_eq!(quota.used(), 0);
+    }
+}